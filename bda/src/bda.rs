@@ -3,27 +3,41 @@
 
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
-    sync::mpsc::{channel, Receiver, Sender},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
     thread::{self, JoinHandle},
     time::Instant,
 };
 
 use binding::{
-    log_rizin, log_rz, rz_notify_begin, rz_notify_done, rz_notify_error, GRzCore, LOG_WARN,
+    log_rizin, log_rz, rz_notify_begin, rz_notify_done, rz_notify_error, GRzCore, LOG_INFO,
+    LOG_WARN,
+};
+use helper::{
+    rng::{fork_rng, new_rng},
+    spinner::Spinner,
+    user::ask_yes_no,
 };
-use helper::{spinner::Spinner, user::ask_yes_no};
 use log::{debug, trace, warn};
-use rand::{thread_rng, Rng};
-use rzil_abstr::interpreter::{interpret, CodeXrefType, ConcreteCodeXref, IntrpProducts};
+use rand::{rngs::StdRng, Rng};
+use rzil_abstr::{
+    interpreter::{interpret, CodeXrefType, ConcreteCodeXref, IntrpProducts},
+    summary::{load_summaries, merge_summaries, save_summaries},
+};
+use sha2::{Digest, Sha256};
 
 use crate::{
-    bda_binding::{get_bin_entries, setup_procedure_at_addr},
+    bda_binding::{get_bin_entries, setup_procedure_at_addr, ProcedurePatterns},
     cfg::Procedure,
     flow_graphs::{Address, FlowGraphOperations, NodeId},
+    graph_export::{icfg_to_export_graph, ExportFormat},
     icfg::ICFG,
     path_sampler::{sample_path, testing_addresses_to_path, Path},
     post_analysis::posterior_dependency_analysis,
     state::{run_condition_fulfilled, BDAState, StatisticID},
+    taint::TaintQuery,
 };
 
 fn get_bda_status(state: &BDAState, num_bda_products: usize) -> String {
@@ -58,7 +72,11 @@ fn get_bda_status(state: &BDAState, num_bda_products: usize) -> String {
     )
 }
 
-fn get_entry_point_list(core: &GRzCore, icfg: &ICFG) -> Option<Vec<Address>> {
+fn get_entry_point_list(
+    core: &GRzCore,
+    icfg: &ICFG,
+    library_files: &BTreeSet<u32>,
+) -> Option<Vec<Address>> {
     let user_defined_entries = core
         .lock()
         .expect("Should not be locked")
@@ -80,7 +98,7 @@ fn get_entry_point_list(core: &GRzCore, icfg: &ICFG) -> Option<Vec<Address>> {
         }
         return Some(user_defined_entries);
     }
-    let bin_entries = get_bin_entries(core.clone());
+    let bin_entries = get_bin_entries(core.clone(), library_files);
     if bin_entries.len() == 0 {
         log_rz!(
             LOG_WARN,
@@ -93,20 +111,60 @@ fn get_entry_point_list(core: &GRzCore, icfg: &ICFG) -> Option<Vec<Address>> {
     Some(bin_entries)
 }
 
+/// Restricts the analysis to the procedures at [functions] and their callees.
+/// The [functions] are the entry points and the sampling ranges cover all procedures
+/// called by them, up to the configured call depth.
+fn apply_scope(
+    core: &GRzCore,
+    icfg: &ICFG,
+    state: &mut BDAState,
+    functions: Vec<Address>,
+) -> Option<Vec<Address>> {
+    for func in functions.iter() {
+        if !icfg.has_procedure(&NodeId::new_original(*func)) {
+            log_rz!(
+                LOG_WARN,
+                Some("BDA"),
+                format!("{:#x} doesn't point to a procedure.", func)
+            );
+            return None;
+        }
+    }
+    let depth = core
+        .lock()
+        .expect("Should not be locked")
+        .get_bda_scope_depth();
+    let scope = icfg.get_scope(&functions, depth);
+    log_rz!(
+        LOG_INFO,
+        Some("BDA"),
+        format!("Analyse {} procedures (call depth {}).", scope.len(), depth)
+    );
+    state.set_ranges(icfg.get_scope_ranges(&scope));
+    Some(functions)
+}
+
 fn move_products_to_state(state: &mut BDAState, products: &mut Vec<IntrpProducts>) {
     for _ in 0..products.len() {
         let p = products.pop().unwrap();
         state.update_iword_info(p.iword_info);
+        state.hits.add(p.iword_hits, p.edge_hits);
+        merge_summaries(&mut state.summaries, &p.summaries);
         state.update_calls(p.concrete_calls);
         state.update_jumps(p.concrete_jumps);
         state.update_mem_xrefs(p.mem_xrefs);
         state.update_stack_xrefs(p.stack_xrefs);
+        state.update_access_sizes(p.access_sizes);
+        state.update_type_hints(p.type_hints);
         state.update_mos(p.mos);
+        state.update_heap_findings(p.heap_findings);
+        state.update_data_flows(p.data_flows);
     }
 }
 
 /// Updates the iCFG with newly discovered calls.
-fn update_icfg(core: GRzCore, state: &mut BDAState, icfg: &mut ICFG) {
+/// New procedures are classified by the name [patterns].
+fn update_icfg(core: GRzCore, state: &mut BDAState, icfg: &mut ICFG, patterns: &ProcedurePatterns) {
     let mut cxref_added = false;
     let mut edited_procs = Vec::<NodeId>::new();
     let mut xrefs_to_handle: BTreeSet<ConcreteCodeXref> = BTreeSet::new();
@@ -123,11 +181,24 @@ fn update_icfg(core: GRzCore, state: &mut BDAState, icfg: &mut ICFG) {
         let mut from_edited = false;
         match code_xref.get_xtype() {
             CodeXrefType::IndirectCall => {
+                if code_xref.is_candidate() {
+                    // Candidates are only plausible. They are kept out of the sampled iCFG,
+                    // until a path observes the call.
+                    // Calls which were observed already are no candidates anymore.
+                    if !state.calls.contains(&code_xref.as_observed()) {
+                        state.calls.insert(code_xref);
+                    }
+                    continue;
+                }
                 if !icfg.has_edge(from_proc_addr, xref_to_addr) {
                     let procedure_from: Option<Procedure> = if icfg.has_procedure(&from_proc_addr) {
                         None
                     } else {
-                        setup_procedure_at_addr(&core.lock().unwrap(), from_proc_addr.address)
+                        setup_procedure_at_addr(
+                            &core.lock().unwrap(),
+                            from_proc_addr.address,
+                            patterns,
+                        )
                     };
                     if procedure_from.is_none() && !icfg.has_procedure(&from_proc_addr) {
                         panic!("Could not initialize procedure at {}", from_proc_addr);
@@ -135,7 +206,11 @@ fn update_icfg(core: GRzCore, state: &mut BDAState, icfg: &mut ICFG) {
                     let procedure_to: Option<Procedure> = if icfg.has_procedure(&xref_to_addr) {
                         None
                     } else {
-                        setup_procedure_at_addr(&core.lock().unwrap(), xref_to_addr.address)
+                        setup_procedure_at_addr(
+                            &core.lock().unwrap(),
+                            xref_to_addr.address,
+                            patterns,
+                        )
                     };
                     if procedure_to.is_none() && !icfg.has_procedure(&xref_to_addr) {
                         panic!("Could not initialize procedure at {}", xref_to_addr);
@@ -146,9 +221,14 @@ fn update_icfg(core: GRzCore, state: &mut BDAState, icfg: &mut ICFG) {
                         Some(xref_insn_addr),
                     );
                 }
+                state.calls.remove(&code_xref.as_candidate());
                 state.calls.insert(code_xref);
             }
             CodeXrefType::IndirectJump => {
+                if code_xref.is_candidate() && state.jumps.contains(&code_xref.as_observed()) {
+                    // The jump was already observed. It is no candidate anymore.
+                    continue;
+                }
                 if icfg.has_procedure(&from_proc_addr)
                     && !icfg.cfg_contains_edge(&from_proc_addr, &xref_insn_addr, &xref_to_addr)
                 {
@@ -169,6 +249,9 @@ fn update_icfg(core: GRzCore, state: &mut BDAState, icfg: &mut ICFG) {
                             .insert_jump_target(&xref_insn_addr, &xref_to_addr);
                     }
                 }
+                if !code_xref.is_candidate() {
+                    state.jumps.remove(&code_xref.as_candidate());
+                }
                 state.jumps.insert(code_xref);
             }
         }
@@ -205,12 +288,35 @@ pub fn run_bda(
             .get_bda_analysis_range()
             .expect("Failed to get analysis ranges."),
     );
-    let entry_points = match get_entry_point_list(&core, icfg) {
-        Some(ep) => ep,
-        None => {
-            rz_notify_error(core.clone(), "BDA analysis failed with an error".to_owned());
-            return None;
-        }
+    state.set_call_string_len(
+        core.lock()
+            .expect("Should not be locked")
+            .get_bda_call_string_len(),
+    );
+    // Each sampled path gets its own generator, forked from the one of the run.
+    // So a seeded run is reproducible, as long as the paths are sampled in the same order.
+    let mut rng = new_rng(core.lock().expect("Should not be locked").get_bda_seed());
+    state.set_min_confidence(
+        core.lock()
+            .expect("Should not be locked")
+            .get_bda_min_confidence() as f64
+            / 100.0,
+    );
+    state.get_weight_map().write().unwrap().set_approximate(
+        core.lock()
+            .expect("Should not be locked")
+            .get_bda_approx_weights(),
+    );
+    let patterns = state
+        .take_procedure_patterns()
+        .unwrap_or_else(|| ProcedurePatterns::new(&core.lock().expect("Should not be locked")));
+    let entry_points = match state.take_scope() {
+        Some(functions) => apply_scope(&core, icfg, state, functions),
+        None => get_entry_point_list(&core, icfg, state.get_library_files()),
+    };
+    let Some(entry_points) = entry_points else {
+        rz_notify_error(core.clone(), "BDA analysis failed with an error".to_owned());
+        return None;
     };
     icfg.set_entries(&entry_points);
 
@@ -235,6 +341,26 @@ pub fn run_bda(
     }
 
     icfg.resolve_loops(state.num_threads);
+    let weight_cache = core
+        .lock()
+        .expect("Should not be locked")
+        .get_bda_weight_cache_path();
+    if let Some(path) = &weight_cache {
+        load_weight_cache(state, icfg, path);
+    }
+    let summaries_path = core
+        .lock()
+        .expect("Should not be locked")
+        .get_bda_summaries_path();
+    // Summaries refer to functions by address. So they are bound to the binary.
+    let binary_fingerprint = summaries_path
+        .as_ref()
+        .and_then(|_| get_binary_fingerprint(&core));
+    if let Some((path, fp)) = summaries_path.as_ref().zip(binary_fingerprint.as_ref()) {
+        load_summary_file(state, path, fp);
+    }
+    // Snapshot of the summaries for the interpreters. Renewed with every iCFG update.
+    let mut summaries = Arc::new(state.summaries.clone());
 
     let mut nothing_happened = 0;
     let mut handled_thread = 0;
@@ -242,8 +368,7 @@ pub fn run_bda(
     let mut spinner = Spinner::new("".to_string());
     let mut paths_walked = 0;
     let path_buf_limit = core.lock().unwrap().get_bda_path_buf_limit();
-    let mut path_buffer = VecDeque::<Path>::new();
-    let mut rng = thread_rng();
+    let mut path_buffer = VecDeque::<(Path, StdRng)>::new();
     let mut products: Vec<IntrpProducts> = Vec::new();
     let mut threads: BTreeMap<usize, JoinHandle<_>> = BTreeMap::new();
     let mut threads_stats: BTreeMap<usize, Instant> = BTreeMap::new();
@@ -265,19 +390,27 @@ pub fn run_bda(
                 // Busy
                 continue;
             }
-            let next_path = path_buffer
+            let (next_path, path_rng) = path_buffer
                 .pop_front()
                 .expect("Path generation before failed.");
             debug_assert!(next_path.validate_for_interpretation());
 
             if threads.get(&tid).is_none() {
                 let core_ref = core.clone();
+                let thread_summaries = summaries.clone();
                 let thread_tx = tx.clone();
                 threads_stats.insert(tid, Instant::now());
                 threads.insert(
                     tid,
                     thread::spawn(move || {
-                        interpret(tid, core_ref, next_path.to_addr_path(), thread_tx)
+                        interpret(
+                            tid,
+                            core_ref,
+                            next_path.to_addr_path(),
+                            thread_summaries,
+                            thread_tx,
+                            path_rng,
+                        )
                     }),
                 );
             }
@@ -309,12 +442,13 @@ pub fn run_bda(
         }
         move_products_to_state(state, &mut products);
         if state.update_icfg_check() {
-            update_icfg(core.clone(), state, icfg);
+            update_icfg(core.clone(), state, icfg, &patterns);
             // Get rid of old paths.
             path_buffer.clear();
+            summaries = Arc::new(state.summaries.clone());
         }
 
-        if !run_condition_fulfilled(&state) {
+        if !run_condition_fulfilled(&state, paths_walked) {
             // End of run. Collect the rest of all products.
             while !threads.is_empty() {
                 let (_, thread) = threads.pop_first().unwrap();
@@ -322,14 +456,41 @@ pub fn run_bda(
                     panic!("Thread failed.");
                 }
             }
-            if let Ok(prods) = rx.try_recv() {
+            while let Ok(prods) = rx.try_recv() {
                 products.push(prods);
-                move_products_to_state(state, &mut products);
             }
+            move_products_to_state(state, &mut products);
             break;
         }
     }
     spinner.done(get_bda_status(state, paths_walked));
+    if let Some(path) = &weight_cache {
+        save_weight_cache(state, icfg, path);
+    }
+    if let Some((path, fp)) = summaries_path.as_ref().zip(binary_fingerprint.as_ref()) {
+        save_summary_file(state, path, fp);
+    }
+    if let Some(path) = core
+        .lock()
+        .expect("Should not be locked")
+        .get_bda_export_graph_path()
+    {
+        export_sampled_icfg(state, icfg, &path);
+    }
+    let coverage_file = core
+        .lock()
+        .expect("Should not be locked")
+        .get_bda_coverage_file();
+    if let Some(path) = coverage_file {
+        write_coverage_file(core.clone(), state, &path);
+    }
+    if core
+        .lock()
+        .expect("Should not be locked")
+        .get_bda_coverage_annotate()
+    {
+        annotate_coverage(core.clone(), state, icfg);
+    }
     debug!(target: "BDA",
         "Lazy factor (nothing/thread_handled): {}/{} = {}",
         nothing_happened,
@@ -337,6 +498,7 @@ pub fn run_bda(
         nothing_happened as f64 / handled_thread as f64
     );
     log_state_products(state);
+    report_heap_findings(state);
 
     let mut term_reason = "not reason specified";
     if state.bda_timed_out() {
@@ -350,11 +512,141 @@ pub fn run_bda(
         return None;
     }
     rz_notify_begin(core.clone(), format!("BDA post-analysis"));
+    let input_sites = state.get_input_call_sites();
     let dip = posterior_dependency_analysis(state, icfg);
+    report_taint_flows(state, icfg, &input_sites);
+    report_context_dependencies(state);
     rz_notify_done(core.clone(), format!("Finished BDA post-analysis"));
     Some(dip)
 }
 
+/// Loads the persisted weights of all procedures, which didn't change since the last run.
+fn load_weight_cache(state: &BDAState, icfg: &ICFG, path: &str) {
+    let path = std::path::Path::new(path);
+    if !path.exists() {
+        return;
+    }
+    match state
+        .get_weight_map()
+        .write()
+        .unwrap()
+        .load_cache(path, &icfg.get_fingerprints())
+    {
+        Ok(n) => debug!(target: "BDA", "Loaded cached weights of {} procedures.", n),
+        Err(e) => log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Failed to load weight cache {}: {}", path.display(), e)
+        ),
+    }
+}
+
+/// Persists the weights of all procedures with up to date weights.
+fn save_weight_cache(state: &BDAState, icfg: &ICFG, path: &str) {
+    let path = std::path::Path::new(path);
+    match state
+        .get_weight_map()
+        .write()
+        .unwrap()
+        .save_cache(path, icfg)
+    {
+        Ok(n) => debug!(target: "BDA", "Saved weights of {} procedures.", n),
+        Err(e) => log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Failed to save weight cache {}: {}", path.display(), e)
+        ),
+    }
+}
+
+/// Returns the fingerprint of the opened binary.
+/// It is the hex SHA-256 digest of the binary file.
+fn get_binary_fingerprint(core: &GRzCore) -> Option<String> {
+    let Some((bin_path, _)) = core
+        .lock()
+        .expect("Should not be locked")
+        .get_cur_bin_file()
+    else {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            "No binary opened. Function summaries are not loaded or saved.".to_string()
+        );
+        return None;
+    };
+    match std::fs::read(&bin_path) {
+        Ok(content) => Some(format!("{:x}", Sha256::digest(content))),
+        Err(e) => {
+            log_rz!(
+                LOG_WARN,
+                Some("BDA"),
+                format!(
+                    "Failed to read {}: {}. Function summaries are not loaded or saved.",
+                    bin_path, e
+                )
+            );
+            None
+        }
+    }
+}
+
+/// Loads the function summaries of earlier runs or given by the user.
+/// Only summaries of the binary with the [fingerprint] are loaded.
+fn load_summary_file(state: &mut BDAState, path: &str, fingerprint: &str) {
+    let path = std::path::Path::new(path);
+    if !path.exists() {
+        return;
+    }
+    match load_summaries(path, fingerprint) {
+        Ok(summaries) => {
+            debug!(target: "BDA", "Loaded summaries of {} procedures.", summaries.len());
+            merge_summaries(&mut state.summaries, &summaries);
+        }
+        Err(e) => log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Failed to load summaries {}: {}", path.display(), e)
+        ),
+    }
+}
+
+/// Persists the loaded and learned function summaries
+/// of the binary with the [fingerprint].
+fn save_summary_file(state: &BDAState, path: &str, fingerprint: &str) {
+    let path = std::path::Path::new(path);
+    match save_summaries(path, &state.summaries, fingerprint) {
+        Ok(n) => debug!(target: "BDA", "Saved summaries of {} procedures.", n),
+        Err(e) => log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Failed to save summaries {}: {}", path.display(), e)
+        ),
+    }
+}
+
+/// Writes the iCFG, as it was sampled, with the hit counts of its procedures to [path].
+fn export_sampled_icfg(state: &BDAState, icfg: &ICFG, path: &str) {
+    let Some(format) = ExportFormat::from_path(path) else {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!(
+                "Unknown graph format of {}. Use .dot, .graphml or .json",
+                path
+            )
+        );
+        return;
+    };
+    let export = icfg_to_export_graph(icfg, state.get_weight_map(), Some(&state.hits));
+    if let Err(e) = std::fs::File::create(path).and_then(|mut f| export.write(format, &mut f)) {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Failed to export the iCFG to {}: {}", path, e)
+        );
+    }
+}
+
 fn log_state_products(state: &BDAState) {
     trace!(target: "BDA", "Calls");
     for ic in state.calls.iter() {
@@ -374,16 +666,62 @@ fn log_state_products(state: &BDAState) {
     }
 }
 
+fn report_heap_findings(state: &BDAState) {
+    for finding in state.heap_findings.values() {
+        let witness = finding
+            .get_witness()
+            .iter()
+            .map(|a| format!("{:#x}", a))
+            .collect::<Vec<String>>()
+            .join(" -> ");
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("{}\n\tWitness path: [{}]", finding, witness)
+        );
+    }
+}
+
+/// Prints the dependencies with the call strings of the reads, if enabled.
+fn report_context_dependencies(state: &BDAState) {
+    let Some(context_deps) = state.context_deps.as_ref() else {
+        return;
+    };
+    for dep in context_deps.iter() {
+        log_rz!(LOG_INFO, Some("BDA"), format!("Dependency: {}", dep));
+    }
+}
+
+/// Reports the flow chains of input data reaching the arguments of sinks.
+fn report_taint_flows(state: &BDAState, icfg: &ICFG, input_sites: &BTreeSet<Address>) {
+    let sinks = icfg.get_sink_call_sites();
+    if sinks.is_empty() {
+        return;
+    }
+    let query = TaintQuery::new(&state.data_flows, &state.dep);
+    for chain in query.query(input_sites, &sinks) {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Input reaches sink: {}", chain)
+        );
+    }
+}
+
+/// Samples a path into the [path_buffer], if it isn't full.
+/// The path is buffered with its own generator, forked from [rng].
+/// It is used to sample the path and later to interpret it.
 fn sample_path_into_buffer(
-    path_buffer: &mut VecDeque<Path>,
+    path_buffer: &mut VecDeque<(Path, StdRng)>,
     path_buf_limit: usize,
     icfg: &mut ICFG,
     entry_points: &Vec<u64>,
-    rng: &mut rand::prelude::ThreadRng,
+    rng: &mut StdRng,
     state: &mut BDAState,
 ) {
     if path_buffer.len() < path_buf_limit {
         let ts_sampling_start = Instant::now();
+        let mut path_rng = fork_rng(rng);
         let path = sample_path(
             icfg,
             // Choose a random entry point.
@@ -392,13 +730,14 @@ fn sample_path_into_buffer(
                 .unwrap(),
             state.get_weight_map(),
             state.get_ranges(),
+            &mut path_rng,
         );
         state.runtime_stats.add_dp(
             StatisticID::SampleTime,
             Instant::now().duration_since(ts_sampling_start),
         );
         state.runtime_stats.add_path_len(path.len());
-        path_buffer.push_back(path);
+        path_buffer.push_back((path, path_rng));
     }
 }
 
@@ -417,7 +756,7 @@ pub fn testing_bda_on_paths(
     );
     state.bda_timer.start();
     state.icfg_update_timer.start();
-    let entry_points = match get_entry_point_list(&core, icfg) {
+    let entry_points = match get_entry_point_list(&core, icfg, state.get_library_files()) {
         Some(ep) => ep,
         None => {
             rz_notify_error(core.clone(), "BDA analysis failed with an error".to_owned());
@@ -426,6 +765,8 @@ pub fn testing_bda_on_paths(
     };
     icfg.set_entries(&entry_points);
     icfg.resolve_loops(state.num_threads);
+    let patterns = ProcedurePatterns::new(&core.lock().expect("Should not be locked"));
+    let mut rng = new_rng(core.lock().expect("Should not be locked").get_bda_seed());
 
     // Run abstract interpretation
     let mut products: Vec<IntrpProducts> = Vec::new();
@@ -435,16 +776,70 @@ pub fn testing_bda_on_paths(
         testing_addresses_to_path(icfg, &mut path_addresses, &mut path);
         debug_assert!(path.validate_for_interpretation());
         let addr_path = path.to_addr_path();
-        interpret(0, core.clone(), addr_path, tx.clone());
+        interpret(
+            0,
+            core.clone(),
+            addr_path,
+            Arc::new(state.summaries.clone()),
+            tx.clone(),
+            fork_rng(&mut rng),
+        );
 
         if let Ok(prods) = rx.try_recv() {
             products.push(prods);
         }
         move_products_to_state(state, &mut products);
         if state.update_icfg_check() {
-            update_icfg(core.clone(), state, icfg);
+            update_icfg(core.clone(), state, icfg, &patterns);
         }
     }
     let dip = posterior_dependency_analysis(state, icfg);
     Some(dip)
 }
+
+/// Writes the executed instruction words as drcov file to [path].
+fn write_coverage_file(core: GRzCore, state: &BDAState, path: &str) {
+    let Some((bin_path, base)) = core
+        .lock()
+        .expect("Should not be locked")
+        .get_cur_bin_file()
+    else {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            "No binary opened. Cannot write a coverage file.".to_string()
+        );
+        return;
+    };
+    if let Err(e) = std::fs::File::create(path)
+        .and_then(|mut f| state.hits.write_drcov(&mut f, &bin_path, base))
+    {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!("Failed to write coverage file {}: {}", path, e)
+        );
+    }
+}
+
+/// Adds a comment with the hit count to every executed instruction word
+/// and flags all procedures which were never reached.
+fn annotate_coverage(core: GRzCore, state: &BDAState, icfg: &ICFG) {
+    let core = core.lock().expect("Should not be locked");
+    for (addr, hits) in state.hits.iwords().iter() {
+        core.run_cmd(&format!("CCu BDA hits: {} @ {:#x}", hits, addr));
+    }
+    let unreached = state.hits.get_unreached_procedures(icfg);
+    for addr in unreached.iter() {
+        core.run_cmd(&format!("f bda.unreached.{:x} 1 @ {:#x}", addr, addr));
+    }
+    log_rz!(
+        LOG_INFO,
+        Some("BDA"),
+        format!(
+            "{} iwords executed. {} procedures never reached (flagged as bda.unreached.*).",
+            state.hits.iwords().len(),
+            unreached.len()
+        )
+    );
+}
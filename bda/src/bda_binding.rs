@@ -1,23 +1,37 @@
 // SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
 // SPDX-License-Identifier: LGPL-3.0-only
 
+use std::collections::BTreeSet;
 use std::ffi::CString;
+use std::path::Path;
 use std::ptr::null;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Mutex, MutexGuard};
 use std::{panic, ptr};
 
 use crate::bda::run_bda;
 use crate::cfg::{CFGNodeData, InsnNodeData, InsnNodeType, Procedure, CFG};
+use crate::code_probability::{add_likely_code, get_prob_disassembly, reject_unlikely_code};
 use crate::flow_graphs::{Address, FlowGraph, FlowGraphOperations, NodeId, MAX_ADDRESS};
+use crate::graph_export::{
+    calc_icfg_weights, cfg_to_export_graph, icfg_to_export_graph, ExportFormat,
+};
 use crate::icfg::ICFG;
+use crate::shared_libs::{link_binaries, BinSymbols, ImportLinks};
 use crate::state::BDAState;
+use crate::weight::WeightMap;
 
 use binding::{
     c_to_str, cpvec_to_vec, list_to_vec, log_rizin, log_rz, mpvec_to_vec, pderef,
     rz_analysis_create_block, rz_analysis_create_function, rz_analysis_function_add_block,
-    rz_analysis_get_block_at, rz_analysis_get_function_at, rz_bin_object_get_entries,
-    rz_cmd_status_t_RZ_CMD_STATUS_ERROR, rz_core_graph_icfg, rz_core_t, rz_graph_free,
-    rz_notify_error, str_to_c, GRzCore, RzAnalysisFcnType_RZ_ANALYSIS_FCN_TYPE_LOC, RzBinAddr,
-    RzBinFile, RzCmdStatus, RzCore, RzCoreWrapper, RzGraph, RzGraphNode,
+    rz_analysis_get_block_at, rz_analysis_get_fcn_in, rz_analysis_get_function_at,
+    rz_bin_file_set_cur_binfile, rz_bin_object_get_entries, rz_bin_object_get_libs,
+    rz_bin_object_get_relocs, rz_bin_object_get_symbols, rz_cmd_status_t_RZ_CMD_STATUS_ERROR,
+    rz_cmd_status_t_RZ_CMD_STATUS_OK, rz_cmd_status_t_RZ_CMD_STATUS_WRONG_ARGS,
+    rz_core_analysis_function_add, rz_core_graph_icfg, rz_core_t, rz_graph_free, rz_notify_error,
+    rz_num_math, str_to_c, GRzCore, RzAnalysisFcnType_RZ_ANALYSIS_FCN_TYPE_LOC,
+    RzAnalysisFcnType_RZ_ANALYSIS_FCN_TYPE_NULL, RzBinAddr, RzBinFile, RzBinReloc, RzBinSymbol,
+    RzCmdStatus, RzCore, RzCoreWrapper, RzGraph, RzGraphNode,
     RzGraphNodeCFGIWordSubType_RZ_GRAPH_NODE_SUBTYPE_CFG_IWORD_COND,
     RzGraphNodeCFGIWordSubType_RZ_GRAPH_NODE_SUBTYPE_CFG_IWORD_ENTRY,
     RzGraphNodeCFGIWordSubType_RZ_GRAPH_NODE_SUBTYPE_CFG_IWORD_EXIT,
@@ -37,16 +51,45 @@ use binding::{
 use flexi_logger::{Duplicate, FileSpec, Logger};
 use helper::progress::ProgressBar;
 use helper::spinner::Spinner;
+use helper::sysroot::find_library;
 use regex::Regex;
+use stochfuzz::disassembly::ProbDisassembly;
 
 pub struct BDAPrivateData {
-    _dummy_val: u64,
+    /// State of the last BDA run. Kept for analyses building on its products (e.g. OSPREY).
+    state: Mutex<Option<BDAState>>,
 }
 
 impl BDAPrivateData {
     pub fn new() -> BDAPrivateData {
-        BDAPrivateData { _dummy_val: 0 }
+        BDAPrivateData {
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Returns the state of the last BDA run. None if BDA didn't run yet.
+    pub fn get_state(&self) -> MutexGuard<'_, Option<BDAState>> {
+        self.state.lock().expect("Should not be locked")
+    }
+}
+
+/// The private data of the BDA core plugin.
+/// Command handlers only get the core passed. So the plugin registers it here.
+static BDA_PRIVATE_DATA: AtomicPtr<BDAPrivateData> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers the private data of the BDA core plugin.
+/// It must be unregistered (by passing null) before it is freed.
+pub fn register_bda_private_data(data: *mut BDAPrivateData) {
+    BDA_PRIVATE_DATA.store(data, Ordering::SeqCst);
+}
+
+/// Returns the private data of the BDA core plugin. None if the plugin is not loaded.
+pub fn get_bda_private_data() -> Option<&'static BDAPrivateData> {
+    let data = BDA_PRIVATE_DATA.load(Ordering::SeqCst);
+    if data.is_null() {
+        return None;
     }
+    Some(unsafe { &*data })
 }
 
 fn list_elem_to_graph_node_tuple(
@@ -77,17 +120,23 @@ macro_rules! get_node_info_address {
     };
 }
 
-pub fn get_bin_entries(rz_core: GRzCore) -> Vec<Address> {
-    let binfiles: Vec<*mut RzBinFile> = unsafe {
-        let core = rz_core.lock().unwrap();
+fn get_binfiles(core: &RzCoreWrapper) -> Vec<*mut RzBinFile> {
+    unsafe {
         list_to_vec::<*mut RzBinFile>((*(pderef!(core.get_ptr())).bin).binfiles, |elem| {
             elem as *mut RzBinFile
         })
-    };
+    }
+}
+
+/// Returns the entry points of all loaded binaries.
+/// The binary files in [library_files] are shared libraries and have no entry points.
+pub fn get_bin_entries(rz_core: GRzCore, library_files: &BTreeSet<u32>) -> Vec<Address> {
+    let binfiles = get_binfiles(&rz_core.lock().unwrap());
     let mut entries: Vec<Address> = Vec::new();
     unsafe {
         let entry_vectors = binfiles
             .into_iter()
+            .filter(|binfile| !library_files.contains(&pderef!(*binfile).id))
             .map(|binfile| rz_bin_object_get_entries(pderef!(binfile).o));
         entry_vectors.into_iter().for_each(|entry_vec| {
             cpvec_to_vec::<RzBinAddr>(entry_vec)
@@ -98,6 +147,161 @@ pub fn get_bin_entries(rz_core: GRzCore) -> Vec<Address> {
     entries
 }
 
+/// Collects the exported symbols, import stubs and import slots of [binfile].
+fn get_bin_symbols(binfile: *mut RzBinFile) -> BinSymbols {
+    let mut symbols = BinSymbols::new();
+    let obj = pderef!(binfile).o;
+    if obj.is_null() {
+        return symbols;
+    }
+    unsafe {
+        for sym in cpvec_to_vec::<RzBinSymbol>(rz_bin_object_get_symbols(obj)) {
+            let sym = pderef!(sym);
+            if sym.name.is_null() || sym.vaddr == 0 || sym.vaddr == MAX_ADDRESS {
+                continue;
+            }
+            let name = c_to_str(sym.name);
+            if sym.is_imported {
+                symbols.stubs.insert(name, sym.vaddr);
+                continue;
+            }
+            if sym.bind.is_null() || sym.type_.is_null() {
+                continue;
+            }
+            let bind = c_to_str(sym.bind);
+            let stype = c_to_str(sym.type_);
+            if (bind == "GLOBAL" || bind == "WEAK") && (stype == "FUNC" || stype == "OBJ") {
+                symbols.exports.entry(name).or_insert(sym.vaddr);
+            }
+        }
+        let relocs = rz_bin_object_get_relocs(obj);
+        if relocs.is_null() {
+            return symbols;
+        }
+        for i in 0..pderef!(relocs).relocs_count {
+            let reloc: *mut RzBinReloc = *pderef!(relocs).relocs.add(i);
+            if reloc.is_null() || pderef!(reloc).import.is_null() {
+                continue;
+            }
+            let import_name = pderef!(pderef!(reloc).import).name;
+            if import_name.is_null() {
+                continue;
+            }
+            symbols
+                .slots
+                .push((c_to_str(import_name), pderef!(reloc).vaddr));
+        }
+    }
+    symbols
+}
+
+/// Loads the shared libraries the opened binaries depend on from the
+/// configured sysroot, and links the imports of all binaries to their definitions.
+/// The import slots are patched (in the IO cache) with the addresses they link to,
+/// so loads from them resolve to the library symbols.
+/// The IO cache is enabled for this. The caller must restore `io.cache`
+/// once the patched slots are not read anymore.
+/// Returns None if no sysroot is set.
+pub fn load_shared_libraries(rz_core: GRzCore) -> Option<ImportLinks> {
+    let core = rz_core.lock().unwrap();
+    let sysroot = core.get_bda_sysroot()?;
+    let main_binfile = unsafe { (*pderef!(core.get_ptr()).bin).cur };
+
+    let mut binfiles = get_binfiles(&core);
+    // Libraries of earlier runs in this session are still open.
+    // They are neither loaded again nor analysed as entries.
+    let mut loaded: BTreeSet<String> = binfiles
+        .iter()
+        .map(|bf| c_to_str(pderef!(*bf).file))
+        .collect();
+    let mut libraries: BTreeSet<u32> = binfiles
+        .iter()
+        .filter(|bf| **bf != main_binfile)
+        .map(|bf| pderef!(*bf).id)
+        .collect();
+    let mut i = 0;
+    while i < binfiles.len() {
+        let binfile = binfiles[i];
+        i += 1;
+        let obj = pderef!(binfile).o;
+        if obj.is_null() {
+            continue;
+        }
+        let libs = unsafe { cpvec_to_vec::<std::os::raw::c_char>(rz_bin_object_get_libs(obj)) };
+        for lib in libs.into_iter().map(|l| c_to_str(l)) {
+            let Some(path) = find_library(Path::new(&sysroot), &lib) else {
+                log_rz!(
+                    LOG_WARN,
+                    Some("BDA"),
+                    format!("Shared library {} not found in {}", lib, sysroot)
+                );
+                continue;
+            };
+            let path = path.to_string_lossy().to_string();
+            if loaded.contains(&path) {
+                continue;
+            }
+            loaded.insert(path.clone());
+            // Leave space for the bss section and alike.
+            let size = std::fs::metadata(&path).map_or(0, |m| m.len()) * 2;
+            let size = (size + 0xfffff) & !0xfffff;
+            let base = core.get_free_map_addr(0x10000000, size);
+            if !core.load_bin_file(&path, base) {
+                log_rz!(
+                    LOG_WARN,
+                    Some("BDA"),
+                    format!("Failed to load shared library {}", path)
+                );
+                continue;
+            }
+            log_rz!(
+                LOG_INFO,
+                Some("BDA"),
+                format!("Loaded {} at {:#x}", path, base)
+            );
+            for bf in get_binfiles(&core) {
+                if !binfiles.contains(&bf) {
+                    libraries.insert(pderef!(bf).id);
+                    binfiles.push(bf);
+                }
+            }
+        }
+    }
+    unsafe {
+        rz_bin_file_set_cur_binfile(pderef!(core.get_ptr()).bin, main_binfile);
+    }
+
+    let symbols: Vec<BinSymbols> = binfiles.into_iter().map(get_bin_symbols).collect();
+    let mut links = link_binaries(&symbols);
+    links.libraries = libraries;
+
+    core.set_core_conf_val("io.cache", "true");
+    let ptr_size = core.get_arch_bits() / 8;
+    let big_endian = pderef!(core.get_analysis()).big_endian;
+    for (slot, addr) in links.slots.iter() {
+        let bytes = if big_endian {
+            addr.to_be_bytes()[8 - ptr_size..].to_vec()
+        } else {
+            addr.to_le_bytes()[..ptr_size].to_vec()
+        };
+        if !core.write_io_at(*slot, &bytes) {
+            log_rz!(
+                LOG_WARN,
+                Some("BDA"),
+                format!("Failed to write import slot at {:#x}", slot)
+            );
+        }
+    }
+    for (_, addr) in links.stubs.iter() {
+        if unsafe { rz_analysis_get_function_at(core.get_analysis(), *addr) }.is_null() {
+            unsafe {
+                rz_core_analysis_function_add(core.get_ptr(), null(), *addr, true);
+            }
+        }
+    }
+    Some(links)
+}
+
 /// Converts a graph from Rizin to our internal FlowGraph representation.
 pub fn get_graph(rz_graph: *mut RzGraph) -> FlowGraph {
     let nodes: Vec<(*mut RzGraphNode, *mut RzGraphNodeInfo)> =
@@ -273,9 +477,62 @@ fn guarded_rz_core_graph_icfg(core: GRzCore) -> *mut RzGraph {
     unsafe { rz_core_graph_icfg(c.get_ptr()) }
 }
 
+/// The procedure name patterns of the configuration.
+/// They are parsed and compiled once per run and matched against every set up procedure.
+pub struct ProcedurePatterns {
+    malloc: Regex,
+    input: Regex,
+    free: Regex,
+    excluded_addrs: Vec<Address>,
+    excluded: Vec<Regex>,
+    sinks: Vec<(Regex, Vec<usize>)>,
+}
+
+impl ProcedurePatterns {
+    pub fn new(core: &RzCoreWrapper) -> ProcedurePatterns {
+        let (excluded_addrs, excluded_patterns) =
+            core.get_bda_excluded_procedures().unwrap_or_default();
+        ProcedurePatterns {
+            malloc: Regex::new(&core.get_bda_analysis_malloc_pattern()).unwrap(),
+            input: Regex::new(&core.get_bda_analysis_input_pattern()).unwrap(),
+            free: Regex::new(&core.get_bda_analysis_free_pattern()).unwrap(),
+            excluded_addrs,
+            excluded: excluded_patterns
+                .iter()
+                .filter_map(|pattern| compile_exclude_pattern(pattern))
+                .collect(),
+            sinks: core
+                .get_bda_taint_sinks()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(pattern, args)| Some((compile_sink_pattern(&pattern)?, args)))
+                .collect(),
+        }
+    }
+
+    /// Returns true if the procedure [name] at [address] is excluded from sampling.
+    fn is_excluded(&self, address: Address, name: &str) -> bool {
+        self.excluded_addrs.contains(&address) || self.excluded.iter().any(|p| p.is_match(name))
+    }
+
+    /// Returns the tainted arguments of the procedure [name], if it is a taint sink.
+    fn get_sink_args(&self, name: &str) -> Vec<usize> {
+        self.sinks
+            .iter()
+            .filter(|(pattern, _)| pattern.is_match(name))
+            .flat_map(|(_, args)| args.iter().cloned())
+            .collect()
+    }
+}
+
 /// Sets up a procedure by pulling all relevant data
 /// from Rizin and initializing the Procedure struct.
-pub fn setup_procedure_at_addr(core: &RzCoreWrapper, address: Address) -> Option<Procedure> {
+/// The procedure is classified by the name [patterns].
+pub fn setup_procedure_at_addr(
+    core: &RzCoreWrapper,
+    address: Address,
+    patterns: &ProcedurePatterns,
+) -> Option<Procedure> {
     unsafe {
         let mut is_unmapped = false;
         let mut fcn_ptr = rz_analysis_get_function_at(core.get_analysis(), address);
@@ -327,20 +584,66 @@ pub fn setup_procedure_at_addr(core: &RzCoreWrapper, address: Address) -> Option
         }
         let mut cfg = CFG::new_graph(get_graph(rz_cfg));
         set_cfg_node_data(&mut cfg, rz_cfg);
-        let malloc_pattern = Regex::new(&core.get_bda_analysis_malloc_pattern()).unwrap();
-        let input_pattern = Regex::new(&core.get_bda_analysis_input_pattern()).unwrap();
-        let proc = Procedure::new(
+        let name = c_to_str(pderef!(fcn_ptr).name);
+        let mut proc = Procedure::new(
             Some(cfg),
-            malloc_pattern.is_match(&c_to_str(pderef!(fcn_ptr).name)),
-            input_pattern.is_match(&c_to_str(pderef!(fcn_ptr).name)),
+            patterns.malloc.is_match(&name),
+            patterns.input.is_match(&name),
             is_unmapped,
         );
+        proc.set_free(patterns.free.is_match(&name));
+        proc.set_excluded(patterns.is_excluded(address, &name));
+        proc.add_sink_args(&patterns.get_sink_args(&name));
         rz_graph_free(rz_cfg);
         return Some(proc);
     }
 }
 
-pub extern "C" fn run_bda_analysis(rz_core: *mut rz_core_t) {
+/// Compiles the procedure name [pattern] of a taint sink.
+/// Returns None and logs the error, if the pattern is invalid.
+pub fn compile_sink_pattern(pattern: &str) -> Option<Regex> {
+    match Regex::new(pattern) {
+        Ok(sink_pattern) => Some(sink_pattern),
+        Err(e) => {
+            log_rz!(
+                LOG_ERROR,
+                Some("BDA"),
+                format!("Invalid sink pattern '{}': {}", pattern, e)
+            );
+            None
+        }
+    }
+}
+
+/// Compiles the procedure name [pattern] of an excluded procedure.
+/// The pattern is anchored. So it must match the whole name
+/// (`printf` excludes `printf`, but not `sprintf`).
+/// The `sym.` and `sym.imp.` prefixes Rizin adds to the names are optional.
+/// So `printf` also excludes `sym.printf` and `sym.imp.printf`.
+/// Returns None and logs the error, if the pattern is invalid.
+pub fn compile_exclude_pattern(pattern: &str) -> Option<Regex> {
+    match Regex::new(&format!(r"^(?:sym\.(?:imp\.)?)?(?:{})$", pattern)) {
+        Ok(excl_pattern) => Some(excl_pattern),
+        Err(e) => {
+            log_rz!(
+                LOG_ERROR,
+                Some("BDA"),
+                format!("Invalid exclusion pattern '{}': {}", pattern, e)
+            );
+            None
+        }
+    }
+}
+
+/// Runs BDA. If [scope] is given, only the procedures at these addresses
+/// and their callees are analysed.
+/// The memory op sequences are kept in the returned state, if [keep_moses] is set.
+/// Returns the final state or None if the analysis failed.
+pub fn run_bda_analysis(
+    rz_core: *mut rz_core_t,
+    scope: Option<Vec<Address>>,
+    keep_moses: bool,
+) -> Option<BDAState> {
     Logger::try_with_env_or_str("info")
         .expect("Logger init failed")
         .log_to_file(FileSpec::try_from("probana_logs/bda.log").unwrap())
@@ -348,16 +651,16 @@ pub extern "C" fn run_bda_analysis(rz_core: *mut rz_core_t) {
         .start()
         .expect("Logger start failed");
     let core: GRzCore = RzCoreWrapper::new(rz_core);
-    let rz_icfg = guarded_rz_core_graph_icfg(core.clone());
-    if rz_icfg.is_null() {
-        log_rz!(LOG_ERROR, Some("BDA"), "No iCFG present.".to_string());
+    let io_cache = core.lock().unwrap().get_core_conf_val("io.cache");
+    let links = load_shared_libraries(core.clone());
+    let patterns = ProcedurePatterns::new(&core.lock().unwrap());
+    let Some(mut icfg) = build_icfg(core.clone(), links.as_ref(), &patterns) else {
+        core.lock()
+            .unwrap()
+            .set_core_conf_val("io.cache", &io_cache);
         rz_notify_error(core, "BDA analysis failed with an error".to_owned());
-        return;
-    }
-    let mut icfg = ICFG::new_graph(get_graph(rz_icfg));
-    unsafe {
-        rz_graph_free(rz_icfg);
-    }
+        return None;
+    };
     let nthreads = core.lock().unwrap().get_bda_threads();
     let runtime = core
         .lock()
@@ -376,13 +679,56 @@ pub extern "C" fn run_bda_analysis(rz_core: *mut rz_core_t) {
         icfg_enforce_update_timeout,
         unknown_code_xrefs_theshold,
     );
-    add_procedures_to_icfg(core.clone(), &mut icfg);
+    if let Some(functions) = scope {
+        state.set_scope(functions);
+    }
+    if let Some(links) = links {
+        state.set_library_files(links.libraries);
+    }
+    state.set_procedure_patterns(patterns);
+    state.set_keep_moses(keep_moses);
+    run_bda(core.clone(), &mut icfg, &mut state, false);
+    // The import slots are patched in the IO cache and read during the whole analysis.
+    core.lock()
+        .unwrap()
+        .set_core_conf_val("io.cache", &io_cache);
+    Some(state)
+}
+
+/// Builds the iCFG with the procedures of all functions known to Rizin.
+/// Import stubs are linked to the functions given in [links].
+/// Returns None if Rizin has no iCFG.
+fn build_icfg(
+    core: GRzCore,
+    links: Option<&ImportLinks>,
+    patterns: &ProcedurePatterns,
+) -> Option<ICFG> {
+    let rz_icfg = guarded_rz_core_graph_icfg(core.clone());
+    if rz_icfg.is_null() {
+        log_rz!(LOG_ERROR, Some("BDA"), "No iCFG present.".to_string());
+        return None;
+    }
+    let mut icfg = ICFG::new_graph(get_graph(rz_icfg));
+    unsafe {
+        rz_graph_free(rz_icfg);
+    }
+    add_procedures_with_patterns(core.clone(), &mut icfg, patterns);
+    if let Some(links) = links {
+        link_import_stubs(core.clone(), &mut icfg, links, patterns);
+    }
     icfg.make_icfg_consistent();
     debug_assert!(icfg.icfg_consistency_check());
-    run_bda(core, &mut icfg, &mut state, false);
+    Some(icfg)
 }
 
 pub fn add_procedures_to_icfg(core: GRzCore, icfg: &mut ICFG) {
+    let patterns = ProcedurePatterns::new(&core.lock().unwrap());
+    add_procedures_with_patterns(core, icfg, &patterns);
+}
+
+/// Adds the procedures of all nodes in [icfg] and the procedures they call.
+/// The procedures are classified by the name [patterns].
+fn add_procedures_with_patterns(core: GRzCore, icfg: &mut ICFG, patterns: &ProcedurePatterns) {
     let dup_cnt = core.lock().unwrap().get_bda_node_duplicates();
     icfg.set_node_dup_count(dup_cnt);
 
@@ -394,9 +740,19 @@ pub fn add_procedures_to_icfg(core: GRzCore, icfg: &mut ICFG) {
     for n in icfg.get_graph().nodes().into_iter() {
         nodes.push(n);
     }
+    let disasm = get_code_probability_filter(&core.lock().unwrap());
+    let proc_addrs: BTreeSet<Address> = nodes.iter().map(|n| n.address).collect();
+    let mut rejected = 0;
+    let mut added = 0;
     let mut done = 0;
     for n in nodes {
-        if let Some(proc) = setup_procedure_at_addr(&core.lock().unwrap(), n.address) {
+        if let Some(mut proc) = setup_procedure_at_addr(&core.lock().unwrap(), n.address, patterns)
+        {
+            if let Some((disasm, min_probability)) = disasm.as_ref() {
+                let cfg = proc.get_cfg_mut();
+                rejected += reject_unlikely_code(cfg, disasm, *min_probability);
+                added += add_likely_code(cfg, disasm, *min_probability, &proc_addrs);
+            }
             icfg.add_procedure(n, proc);
         } else {
             log_rz!(
@@ -408,10 +764,69 @@ pub fn add_procedures_to_icfg(core: GRzCore, icfg: &mut ICFG) {
         done += 1;
         progress_bar.update_print(done, None);
     }
-    add_called_procedures(icfg, core.clone());
+    if disasm.is_some() {
+        log_rz!(
+            LOG_INFO,
+            Some("BDA"),
+            format!(
+                "Removed {} CFG nodes, which are unlikely code. Added {} likely code nodes.",
+                rejected, added
+            )
+        );
+    }
+    add_called_procedures(icfg, core.clone(), patterns);
+}
+
+/// Returns the probabilistic disassembly and the minimal code probability of CFG nodes.
+/// None if the check is disabled or the architecture is not supported.
+fn get_code_probability_filter(core: &RzCoreWrapper) -> Option<(ProbDisassembly, f64)> {
+    let min_probability = core.get_bda_min_code_probability();
+    if min_probability == 0 {
+        return None;
+    }
+    let Some(disasm) = get_prob_disassembly(core) else {
+        log_rz!(
+            LOG_WARN,
+            Some("BDA"),
+            format!(
+                "Code probabilities are not supported for {}. All CFG nodes are kept.",
+                core.get_arch_name()
+            )
+        );
+        return None;
+    };
+    Some((disasm, min_probability as f64 / 100.0))
+}
+
+/// Links the import stubs in the iCFG to the functions they jump to.
+fn link_import_stubs(
+    core: GRzCore,
+    icfg: &mut ICFG,
+    links: &ImportLinks,
+    patterns: &ProcedurePatterns,
+) {
+    let mut linked = 0;
+    for (stub, target) in links.stubs.iter() {
+        let target = NodeId::from(*target);
+        let target_proc = if icfg.has_procedure(&target) {
+            None
+        } else {
+            setup_procedure_at_addr(&core.lock().unwrap(), target.address, patterns)
+        };
+        if icfg.link_import_stub(&NodeId::from(*stub), (target, target_proc)) {
+            linked += 1;
+        }
+    }
+    log_rz!(
+        LOG_INFO,
+        Some("BDA"),
+        format!("Linked {} import stubs", linked)
+    );
+    // The library functions call others.
+    add_called_procedures(icfg, core, patterns);
 }
 
-fn add_called_procedures(icfg: &mut ICFG, core: GRzCore) {
+fn add_called_procedures(icfg: &mut ICFG, core: GRzCore, patterns: &ProcedurePatterns) {
     // Iterate over all call xrefs and ensure they are added as procedures.
     let mut undisc_procs = Vec::<NodeId>::new();
     let mut undisc_edges = Vec::<(NodeId, NodeId)>::new();
@@ -438,7 +853,7 @@ fn add_called_procedures(icfg: &mut ICFG, core: GRzCore) {
     while !undisc_procs.is_empty() {
         let call_target = undisc_procs.pop().unwrap().clone();
         if let Some(called_proc) =
-            setup_procedure_at_addr(&core.lock().unwrap(), call_target.address)
+            setup_procedure_at_addr(&core.lock().unwrap(), call_target.address, patterns)
         {
             // Get the calls of the undiscovered procedure.
             for cp_ct in called_proc.get_cfg().nodes_meta.ct_iter() {
@@ -474,10 +889,43 @@ fn add_called_procedures(icfg: &mut ICFG, core: GRzCore) {
     ));
 }
 
+/// Resolves the function names, flags or addresses in [args] to the start
+/// addresses of the functions containing them.
+fn resolve_functions(core: *mut RzCore, args: &[String]) -> Option<Vec<Address>> {
+    let mut functions = Vec::<Address>::new();
+    for arg in args.iter() {
+        let addr = unsafe { rz_num_math(pderef!(core).num, str_to_c!(arg.as_str())) };
+        let fcn = unsafe {
+            rz_analysis_get_fcn_in(
+                pderef!(core).analysis,
+                addr,
+                RzAnalysisFcnType_RZ_ANALYSIS_FCN_TYPE_NULL as i32,
+            )
+        };
+        if fcn.is_null() {
+            log_rz!(
+                LOG_ERROR,
+                Some("BDA"),
+                format!("No function at {} ({:#x}).", arg, addr)
+            );
+            return None;
+        }
+        let fcn_addr = pderef!(fcn).addr;
+        if !functions.contains(&fcn_addr) {
+            functions.push(fcn_addr);
+        }
+    }
+    Some(functions)
+}
+
+/// Runs BDA.
+/// Arguments: [<function>...]
+/// If functions are given, only they and their callees are analysed.
+/// Otherwise the entries and ranges are taken from the configuration.
 pub extern "C" fn rz_analysis_bda_handler(
     core: *mut RzCore,
-    _argc: i32,
-    _argv: *mut *const i8,
+    argc: i32,
+    argv: *mut *const i8,
 ) -> RzCmdStatus {
     if pderef!(core).analysis.cast_const() == null() {
         log_rz!(
@@ -487,6 +935,108 @@ pub extern "C" fn rz_analysis_bda_handler(
         );
         return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
     }
-    run_bda_analysis(core);
+    let args: Vec<String> = (1..argc as usize)
+        .map(|i| c_to_str(unsafe { *argv.add(i) }))
+        .collect();
+    let scope = if args.is_empty() {
+        None
+    } else {
+        let Some(functions) = resolve_functions(core, &args) else {
+            return rz_cmd_status_t_RZ_CMD_STATUS_WRONG_ARGS;
+        };
+        Some(functions)
+    };
+    let state = run_bda_analysis(core, scope, false);
+    if let Some(data) = get_bda_private_data() {
+        *data.get_state() = state;
+    }
     rz_cmd_status_t_RZ_CMD_STATUS_ERROR
 }
+
+/// Exports the iCFG or the CFG of a single procedure to a file.
+/// Arguments: <icfg|icfg_resolved|cfg|cfg_resolved> <file> [<procedure address>]
+/// The format is determined by the file extension (.dot, .graphml, .json).
+/// Nodes are annotated with the coverage of the last BDA run, if there was one.
+pub extern "C" fn rz_analysis_bda_export_handler(
+    core: *mut RzCore,
+    argc: i32,
+    argv: *mut *const i8,
+) -> RzCmdStatus {
+    if pderef!(core).analysis.cast_const() == null() {
+        log_rz!(
+            LOG_WARN,
+            None,
+            "core.analysis is null. Without it it cannot build the graphs.".to_string()
+        );
+        return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+    }
+    let args: Vec<String> = (1..argc as usize)
+        .map(|i| c_to_str(unsafe { *argv.add(i) }))
+        .collect();
+    if args.len() < 2 {
+        return rz_cmd_status_t_RZ_CMD_STATUS_WRONG_ARGS;
+    }
+    let (graph, path) = (args[0].as_str(), args[1].as_str());
+    let Some(format) = ExportFormat::from_path(path) else {
+        log_rz!(
+            LOG_ERROR,
+            Some("BDA"),
+            format!(
+                "Unknown graph format of {}. Use .dot, .graphml or .json",
+                path
+            )
+        );
+        return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+    };
+    let proc_addr: Option<Address> = args
+        .get(2)
+        .map(|a| unsafe { rz_num_math(pderef!(core).num, str_to_c!(a.as_str())) });
+
+    let gcore: GRzCore = RzCoreWrapper::new(core);
+    let patterns = ProcedurePatterns::new(&gcore.lock().unwrap());
+    let Some(mut icfg) = build_icfg(gcore.clone(), None, &patterns) else {
+        return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+    };
+    let wmap = WeightMap::new();
+    if graph.ends_with("_resolved") {
+        icfg.resolve_loops(gcore.lock().unwrap().get_bda_threads());
+        calc_icfg_weights(&icfg, &wmap);
+    }
+    // Annotate the coverage of the last BDA run, if there was one.
+    let state = get_bda_private_data().map(|data| data.get_state());
+    let coverage = state
+        .as_ref()
+        .and_then(|state| state.as_ref())
+        .map(|state| &state.hits);
+    let export = if graph.starts_with("icfg") {
+        icfg_to_export_graph(&icfg, &wmap, coverage)
+    } else {
+        let Some(pid) = proc_addr.map(NodeId::from) else {
+            log_rz!(
+                LOG_ERROR,
+                Some("BDA"),
+                "The CFG export requires the address of a procedure.".to_string()
+            );
+            return rz_cmd_status_t_RZ_CMD_STATUS_WRONG_ARGS;
+        };
+        if !icfg.has_procedure(&pid) {
+            log_rz!(
+                LOG_ERROR,
+                Some("BDA"),
+                format!("No procedure at {:#x}.", pid.address)
+            );
+            return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+        }
+        let proc = icfg.get_procedure(&pid).read().unwrap();
+        cfg_to_export_graph(proc.get_cfg(), &wmap, coverage)
+    };
+    if let Err(e) = std::fs::File::create(path).and_then(|mut f| export.write(format, &mut f)) {
+        log_rz!(
+            LOG_ERROR,
+            Some("BDA"),
+            format!("Failed to write {}: {}", path, e)
+        );
+        return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+    }
+    rz_cmd_status_t_RZ_CMD_STATUS_OK
+}
@@ -18,7 +18,8 @@ use binding::{
     RzGraphNodeCFGSubType_RZ_GRAPH_NODE_SUBTYPE_CFG_RETURN,
     RzGraphNodeCFGSubType_RZ_GRAPH_NODE_SUBTYPE_CFG_TAIL, LOG_DEBUG,
 };
-use petgraph::Direction::Outgoing;
+use petgraph::Direction::{Incoming, Outgoing};
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
     flow_graphs::{
@@ -292,11 +293,16 @@ impl InsnNodeData {
                 // dynamically, we do not know at this sampling stage, where it will
                 // jump to.
                 // The interpreter would know, but the sampler does not.
-                let ct_nid = &self.call_targets.sample();
+                //
+                // The target only depends on the instruction address. So the weight doesn't
+                // depend on the order or the thread the procedures are weighted in.
+                let ct_nid = &self
+                    .call_targets
+                    .sample(&mut StdRng::seed_from_u64(self.addr));
                 let p: Option<&RwLock<Procedure>> = procedure_map.get(ct_nid);
                 let mut has_procedure = p.is_some();
                 if has_procedure {
-                    // Malloc, input and unmapped CFGs always have a weight of 1
+                    // Malloc, input, free and unmapped CFGs always have a weight of 1
                     has_procedure &= !p
                         .unwrap()
                         .try_read()
@@ -771,6 +777,11 @@ impl CFGNodeDataMap {
         self.map.get_mut(nid)
     }
 
+    pub fn remove(&mut self, key: &NodeId) -> Option<CFGNodeData> {
+        self.call_insns_idx.remove(key);
+        self.map.remove(key)
+    }
+
     pub fn clear(&mut self) {
         self.call_insns_idx.clear();
         self.map.clear();
@@ -875,6 +886,33 @@ impl CFG {
         self.entry = entry;
     }
 
+    /// Turns the jump of the only leaf node into a tail call to the procedure [target].
+    /// This links import stubs (e.g. PLT entries) to the function they jump to.
+    /// Returns false if the CFG has no single leaf which jumps.
+    pub fn set_tail_call_target(&mut self, target: &NodeId) -> bool {
+        let leaves: Vec<NodeId> = self
+            .graph
+            .nodes()
+            .filter(|n| self.graph.neighbors_directed(*n, Outgoing).count() == 0)
+            .collect();
+        let [leaf] = leaves.as_slice() else {
+            return false;
+        };
+        let Some(meta) = self.nodes_meta.get_mut(leaf) else {
+            return false;
+        };
+        let Some(insn) = meta.insns.last_mut() else {
+            return false;
+        };
+        if insn.itype.is_call() || insn.itype.is_return() {
+            return false;
+        }
+        insn.itype = InsnNodeType::TailCall | (insn.itype & InsnNodeType::Entry);
+        insn.orig_jump_targets = NodeIdSet::from_nid(*target);
+        meta.node_type = InsnNodeType::TailCall;
+        true
+    }
+
     /// Clones itself and updates the node IDs with the given iCFG clone id
     pub fn get_clone(&self, icfg_clone_id: i32) -> CFG {
         let mut cloned_cfg: CFG = CFG {
@@ -934,6 +972,9 @@ impl CFG {
         let recalc = wmap.read().unwrap().needs_recalc(&entry_nid);
         if !recalc && self.get_node_weight_id(&entry_nid).is_some() {
             cfg_wid = self.get_node_weight_id(&entry_nid);
+        } else if self.restore_cached_weights(wmap) {
+            cfg_wid = self.get_node_weight_id(&entry_nid);
+            wmap.write().unwrap().set_calc_timestamp(&entry_nid);
         } else {
             cfg_wid = Some(self.calc_node_weight(&entry_nid, procedures, wmap));
             wmap.write().unwrap().set_calc_timestamp(&entry_nid);
@@ -941,6 +982,26 @@ impl CFG {
         cfg_wid
     }
 
+    /// Assigns the node weights persisted in the weight cache of [wmap].
+    /// Returns false if the cache has no weights for every node of this CFG.
+    fn restore_cached_weights(&mut self, wmap: &RwLock<WeightMap>) -> bool {
+        let cached = wmap.write().unwrap().take_cached_weights(&self.get_entry());
+        let Some(cached) = cached else {
+            return false;
+        };
+        if self
+            .nodes_meta
+            .iter()
+            .any(|(nid, _)| !cached.contains_key(nid))
+        {
+            return false;
+        }
+        for (nid, meta) in self.nodes_meta.iter_mut() {
+            meta.weight_id = cached.get(nid).cloned();
+        }
+        true
+    }
+
     /// Get the total weight of the CFG.
     pub fn node_weight_eq_usize(&self, node: NodeId, rhs: usize, wmap: &RwLock<WeightMap>) -> bool {
         self.get_node_weight_id(&node)
@@ -1001,6 +1062,26 @@ impl CFG {
         self.graph.add_node(nid);
     }
 
+    /// Removes the node [nid] and all its edges from the graph.
+    /// The jump and next targets of the predecessors, which point to [nid], are removed as well.
+    pub fn remove_node(&mut self, nid: &NodeId) {
+        let orig_nid = nid.get_orig_node_id();
+        let preds: Vec<NodeId> = self.graph.neighbors_directed(*nid, Incoming).collect();
+        for pred in preds.iter() {
+            let Some(pred_meta) = self.nodes_meta.get_mut(pred) else {
+                continue;
+            };
+            for insn in pred_meta.insns.iter_mut() {
+                insn.orig_jump_targets.remove(&orig_nid);
+                if insn.orig_next == orig_nid {
+                    insn.orig_next = INVALID_NODE_ID;
+                }
+            }
+        }
+        self.graph.remove_node(*nid);
+        self.nodes_meta.remove(nid);
+    }
+
     pub fn add_node_data(&mut self, node_id: NodeId, data: CFGNodeData) {
         assert!(self.graph.contains_node(node_id));
         if data.insns.iter().any(|i| i.itype.is_entry()) {
@@ -1355,6 +1436,13 @@ pub struct Procedure {
     is_input: bool,
     /// Procedure is not mapped, likely because it is dynamically linked.
     is_unmapped: bool,
+    /// Flag if this procedure releases heap memory (e.g. free).
+    is_free: bool,
+    /// Flag if the user excluded this procedure from sampling.
+    is_excluded: bool,
+    /// Argument positions of a taint sink, which must not receive input data.
+    /// Empty if the procedure is no sink.
+    sink_args: BTreeSet<usize>,
 }
 
 impl Procedure {
@@ -1364,9 +1452,29 @@ impl Procedure {
             is_malloc,
             is_input,
             is_unmapped,
+            is_free: false,
+            is_excluded: false,
+            sink_args: BTreeSet::new(),
         }
     }
 
+    /// Marks the procedure as function releasing heap memory.
+    pub fn set_free(&mut self, is_free: bool) {
+        self.is_free = is_free;
+    }
+
+    /// Marks the procedure as excluded. The path sampler never enters it
+    /// and calls to it are handled like calls to unmapped procedures.
+    pub fn set_excluded(&mut self, is_excluded: bool) {
+        self.is_excluded = is_excluded;
+    }
+
+    /// Marks the procedure as taint sink. [args] are the argument positions
+    /// which should not receive input data.
+    pub fn add_sink_args(&mut self, args: &[usize]) {
+        self.sink_args.extend(args);
+    }
+
     pub fn is_cfg_set(&self) -> bool {
         match &self.cfg {
             Some(_) => true,
@@ -1395,6 +1503,9 @@ impl Procedure {
             is_malloc: self.is_malloc,
             is_input: self.is_input,
             is_unmapped: self.is_unmapped,
+            is_free: self.is_free,
+            is_excluded: self.is_excluded,
+            sink_args: self.sink_args.clone(),
         }
     }
 
@@ -1473,10 +1584,69 @@ impl Procedure {
         self.is_unmapped
     }
 
+    /// True if this procedure releases heap memory.
+    /// False otherwise.
+    pub fn is_free(&self) -> bool {
+        self.is_free
+    }
+
+    /// True if this procedure was excluded from sampling by the user.
+    /// False otherwise.
+    pub fn is_excluded(&self) -> bool {
+        self.is_excluded
+    }
+
+    /// True if this procedure is a taint sink.
+    /// False otherwise.
+    pub fn is_sink(&self) -> bool {
+        !self.sink_args.is_empty()
+    }
+
+    /// Returns the argument positions of the sink, which should not receive input data.
+    pub fn get_sink_args(&self) -> &BTreeSet<usize> {
+        &self.sink_args
+    }
+
     /// True if this procedure is not executed.
     /// False otherwise.
     pub fn wont_execute(&self) -> bool {
-        self.is_malloc || self.is_input || self.is_unmapped
+        self.is_malloc || self.is_input || self.is_unmapped || self.is_free || self.is_excluded
+    }
+
+    /// Returns a canonical text of everything the path weights of this procedure depend on.
+    /// The weights of called procedures are not included.
+    pub fn serialize_structure(&self) -> String {
+        let mut text = format!("wont_execute {}\n", self.wont_execute());
+        if !self.is_cfg_set() {
+            return text;
+        }
+        let cfg = self.get_cfg();
+        text.push_str(&format!("entry {}\n", cfg.get_entry()));
+        for (nid, meta) in cfg.nodes_meta.iter() {
+            text.push_str(&format!("node {} {:x}\n", nid, meta.node_type.bits()));
+            for insn in meta.insns.iter() {
+                text.push_str(&format!(
+                    "insn {:x} {:x} {}",
+                    insn.addr,
+                    insn.itype.bits(),
+                    insn.orig_next
+                ));
+                insn.call_targets
+                    .iter()
+                    .for_each(|ct| text.push_str(&format!(" call {}", ct)));
+                insn.orig_jump_targets
+                    .iter()
+                    .for_each(|jt| text.push_str(&format!(" jump {}", jt)));
+                text.push('\n');
+            }
+        }
+        let mut edges: Vec<(NodeId, NodeId)> =
+            cfg.graph.all_edges().map(|(f, t, _)| (f, t)).collect();
+        edges.sort();
+        for (from, to) in edges {
+            text.push_str(&format!("edge {} {}\n", from, to));
+        }
+        text
     }
 
     /// Insert call target at instruction [i] of the node [nid] in the procedures CFG.
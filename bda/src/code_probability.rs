@@ -0,0 +1,232 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::collections::{BTreeSet, VecDeque};
+
+use binding::RzCoreWrapper;
+use iced_x86::FlowControl;
+use petgraph::{visit::Dfs, Direction::Outgoing};
+use stochfuzz::disassembly::{CodeRegion, Evidence, ProbDisassembly};
+
+use crate::{
+    cfg::{CFGNodeData, InsnNodeData, InsnNodeType, CFG},
+    flow_graphs::{Address, NodeId, INVALID_NODE_ID},
+};
+
+/// Maximum number of rounds the probabilities are propagated.
+const ITERATIONS: usize = 16;
+
+/// Returns the probabilistic disassembly of the executable sections.
+/// The entries of all functions known to Rizin are treated as code.
+/// Returns None if the architecture is not supported.
+pub fn get_prob_disassembly(core: &RzCoreWrapper) -> Option<ProbDisassembly> {
+    let bits = core.get_arch_bits();
+    if core.get_arch_name() != "x86" || !matches!(bits, 16 | 32 | 64) {
+        return None;
+    }
+    let regions: Vec<CodeRegion> = core
+        .get_exec_sections()
+        .into_iter()
+        .map(|(addr, size)| CodeRegion::new(addr, core.read_io_at(addr, size as usize)))
+        .collect();
+    let mut disasm = ProbDisassembly::with_bitness(regions, bits as u32);
+    for f in core.get_function_addrs() {
+        disasm.add_evidence(f, Evidence::Code);
+    }
+    disasm.analyse(ITERATIONS);
+    Some(disasm)
+}
+
+/// Returns the probability that all instructions of the node [nid] are code.
+/// Returns None if any instruction is outside of the disassembled regions.
+pub fn get_node_code_probability(cfg: &CFG, nid: &NodeId, disasm: &ProbDisassembly) -> Option<f64> {
+    cfg.nodes_meta
+        .get(nid)?
+        .insns
+        .iter()
+        .map(|i| disasm.get_data_probability(i.addr))
+        .try_fold(1.0, |p: f64, dp| Some(p.min(1.0 - dp?)))
+}
+
+/// Removes the nodes of [cfg], which are code with a probability below [min_probability].
+/// Afterwards, nodes not reachable from the entry anymore are removed as well.
+/// The entry itself and nodes outside of the disassembled regions are never rejected.
+/// Returns the number of removed nodes.
+pub fn reject_unlikely_code(
+    cfg: &mut CFG,
+    disasm: &ProbDisassembly,
+    min_probability: f64,
+) -> usize {
+    let entry = cfg.get_entry();
+    let mut removed: Vec<NodeId> = cfg
+        .graph
+        .nodes()
+        .filter(|n| {
+            *n != entry
+                && get_node_code_probability(cfg, n, disasm).is_some_and(|p| p < min_probability)
+        })
+        .collect();
+    if removed.is_empty() {
+        return 0;
+    }
+    for n in removed.iter() {
+        cfg.remove_node(n);
+    }
+    if cfg.graph.contains_node(entry) {
+        let mut reachable = BTreeSet::<NodeId>::new();
+        let mut dfs = Dfs::new(&cfg.graph, entry);
+        while let Some(n) = dfs.next(&cfg.graph) {
+            reachable.insert(n);
+        }
+        let unreachable: Vec<NodeId> = cfg
+            .graph
+            .nodes()
+            .filter(|n| !reachable.contains(n))
+            .collect();
+        for n in unreachable.iter() {
+            cfg.remove_node(n);
+        }
+        removed.extend(unreachable);
+    }
+    removed.len()
+}
+
+/// Returns the node of the instruction at [addr] decoded from [disasm].
+/// Returns None if there is no valid instruction.
+fn get_decoded_node(disasm: &ProbDisassembly, addr: Address) -> Option<CFGNodeData> {
+    let insn = disasm.get_insn(addr)?;
+    let target = match insn.near_branch_target() {
+        0 => INVALID_NODE_ID,
+        t => NodeId::from(t),
+    };
+    let next = NodeId::from(insn.next_ip());
+    let (itype, jump_target, next, call_target) = match insn.flow_control() {
+        FlowControl::Next | FlowControl::Interrupt | FlowControl::XbeginXabortXend => {
+            (InsnNodeType::Normal, INVALID_NODE_ID, next, INVALID_NODE_ID)
+        }
+        FlowControl::UnconditionalBranch => {
+            (InsnNodeType::Jump, target, INVALID_NODE_ID, INVALID_NODE_ID)
+        }
+        FlowControl::IndirectBranch => (
+            InsnNodeType::Jump,
+            INVALID_NODE_ID,
+            INVALID_NODE_ID,
+            INVALID_NODE_ID,
+        ),
+        FlowControl::ConditionalBranch => (
+            InsnNodeType::Jump | InsnNodeType::Cond,
+            target,
+            next,
+            INVALID_NODE_ID,
+        ),
+        FlowControl::Call | FlowControl::IndirectCall => {
+            (InsnNodeType::Call, INVALID_NODE_ID, next, target)
+        }
+        FlowControl::Return => (
+            InsnNodeType::Return,
+            INVALID_NODE_ID,
+            INVALID_NODE_ID,
+            INVALID_NODE_ID,
+        ),
+        FlowControl::Exception => (
+            InsnNodeType::Exit,
+            INVALID_NODE_ID,
+            INVALID_NODE_ID,
+            INVALID_NODE_ID,
+        ),
+    };
+    let mut node = CFGNodeData::new(NodeId::from(addr));
+    node.node_type = itype;
+    node.insns.push(InsnNodeData::new(
+        addr,
+        itype,
+        call_target,
+        jump_target,
+        next,
+        insn.flow_control() == FlowControl::IndirectCall,
+    ));
+    Some(node)
+}
+
+/// Returns true if the instruction word [nid] continues in the same procedure,
+/// but [cfg] has no successor for it.
+/// Rizin stops at instructions it can't follow, e.g. in procedures it doesn't know.
+fn is_cut_off(cfg: &CFG, nid: &NodeId) -> bool {
+    if cfg
+        .graph
+        .neighbors_directed(*nid, Outgoing)
+        .next()
+        .is_some()
+    {
+        return false;
+    }
+    cfg.nodes_meta.get(nid).is_some_and(|n| {
+        n.insns.iter().all(|i| {
+            !(i.itype.is_call() || i.itype.is_return() || i.itype.is_exit() || i.itype.is_tail())
+        })
+    })
+}
+
+/// Adds the likely code following the nodes of [cfg], which are cut off from their successors.
+/// The successors are decoded from [disasm]. They are only added if they are code
+/// with a probability of at least [min_probability].
+/// The [procedures] are never added. Control flow into them is a call or tail call.
+/// Returns the number of added nodes.
+pub fn add_likely_code(
+    cfg: &mut CFG,
+    disasm: &ProbDisassembly,
+    min_probability: f64,
+    procedures: &BTreeSet<Address>,
+) -> usize {
+    let mut queue: VecDeque<NodeId> = cfg.graph.nodes().filter(|n| is_cut_off(cfg, n)).collect();
+    let mut added = 0;
+    while let Some(nid) = queue.pop_front() {
+        let Some(last) = cfg.nodes_meta.get(&nid).and_then(|n| n.insns.iter().last()) else {
+            continue;
+        };
+        let Some(decoded_node) = get_decoded_node(disasm, last.addr) else {
+            continue;
+        };
+        let decoded = decoded_node
+            .insns
+            .get(0)
+            .expect("Decoded node has an instruction.");
+        let mut succs = decoded.orig_jump_targets.clone();
+        succs.insert(decoded.orig_next);
+        for succ in succs.iter() {
+            if procedures.contains(&succ.address)
+                || !disasm
+                    .get_data_probability(succ.address)
+                    .is_some_and(|dp| 1.0 - dp >= min_probability)
+            {
+                continue;
+            }
+            let decoded_succ = if cfg.nodes_meta.contains_key(succ) {
+                None
+            } else {
+                let Some(n) = get_decoded_node(disasm, succ.address) else {
+                    continue;
+                };
+                Some(n)
+            };
+            let node = cfg.get_nodes_meta_mut(&nid);
+            let insn = node.insns.last_mut().expect("Node has an instruction.");
+            if decoded.orig_jump_targets.contains(succ) {
+                insn.orig_jump_targets.insert(*succ);
+            } else {
+                insn.orig_next = *succ;
+            }
+            let node = node.clone();
+            let succ_node = match decoded_succ {
+                Some(n) => {
+                    added += 1;
+                    queue.push_back(*succ);
+                    n
+                }
+                None => cfg.get_nodes_meta(succ).clone(),
+            };
+            cfg.add_edge((nid, node), (*succ, succ_node));
+        }
+    }
+    added
+}
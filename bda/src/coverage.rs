@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+};
+
+use crate::{flow_graphs::Address, icfg::ICFG};
+
+/// Hit counters of all interpreted paths.
+pub struct HitCounts {
+    /// Number of times each instruction word was executed.
+    iwords: BTreeMap<Address, u64>,
+    /// Number of times the execution went from one instruction word to another.
+    edges: BTreeMap<(Address, Address), u64>,
+}
+
+impl HitCounts {
+    pub fn new() -> HitCounts {
+        HitCounts {
+            iwords: BTreeMap::new(),
+            edges: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the hit counters of a single path.
+    pub fn add(
+        &mut self,
+        iword_hits: BTreeMap<Address, u64>,
+        edge_hits: BTreeMap<(Address, Address), u64>,
+    ) {
+        for (addr, hits) in iword_hits {
+            *self.iwords.entry(addr).or_default() += hits;
+        }
+        for (edge, hits) in edge_hits {
+            *self.edges.entry(edge).or_default() += hits;
+        }
+    }
+
+    pub fn get_iword_hits(&self, addr: &Address) -> u64 {
+        self.iwords.get(addr).cloned().unwrap_or(0)
+    }
+
+    pub fn get_edge_hits(&self, from: &Address, to: &Address) -> u64 {
+        self.edges.get(&(*from, *to)).cloned().unwrap_or(0)
+    }
+
+    pub fn iwords(&self) -> &BTreeMap<Address, u64> {
+        &self.iwords
+    }
+
+    pub fn edges(&self) -> &BTreeMap<(Address, Address), u64> {
+        &self.edges
+    }
+
+    /// Returns the entry addresses of all procedures which were never executed.
+    /// Procedures which are not interpreted (malloc, input etc.) are not included.
+    pub fn get_unreached_procedures(&self, icfg: &ICFG) -> BTreeSet<Address> {
+        icfg.get_procedures()
+            .iter()
+            .filter(|(_, proc)| {
+                let proc = proc.read().unwrap();
+                proc.is_cfg_set() && !proc.wont_execute()
+            })
+            .map(|(pid, _)| pid.address)
+            .filter(|addr| !self.iwords.contains_key(addr))
+            .collect()
+    }
+
+    /// Writes the executed instruction words as drcov (version 2) coverage file.
+    /// The binary at [module_path] is the only module, mapped at [base].
+    /// Instruction words outside of the module are skipped.
+    /// drcov has no notion of instruction words, so each of them is saved as
+    /// basic block of size 1.
+    pub fn write_drcov<W: Write>(
+        &self,
+        out: &mut W,
+        module_path: &str,
+        base: Address,
+    ) -> std::io::Result<()> {
+        let offsets: Vec<u32> = self
+            .iwords
+            .keys()
+            .filter(|addr| **addr >= base)
+            .filter_map(|addr| u32::try_from(addr - base).ok())
+            .collect();
+        let end = base + offsets.last().map_or(0, |o| *o as u64 + 1);
+        writeln!(out, "DRCOV VERSION: 2")?;
+        writeln!(out, "DRCOV FLAVOR: probana")?;
+        writeln!(out, "Module Table: version 2, count 1")?;
+        writeln!(
+            out,
+            "Columns: id, base, end, entry, checksum, timestamp, path"
+        )?;
+        writeln!(
+            out,
+            " 0, {:#018x}, {:#018x}, {:#018x}, 0x00000000, 0x00000000, {}",
+            base, end, 0, module_path
+        )?;
+        writeln!(out, "BB Table: {} bbs", offsets.len())?;
+        for offset in offsets {
+            // struct { uint32 start; uint16 size; uint16 mod_id; }
+            out.write_all(&offset.to_le_bytes())?;
+            out.write_all(&1u16.to_le_bytes())?;
+            out.write_all(&0u16.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
@@ -6,7 +6,7 @@ use petgraph::algo::{is_cyclic_directed, kosaraju_scc, toposort};
 use petgraph::dot::{Config, Dot};
 use petgraph::prelude::DiGraphMap;
 use petgraph::Direction::{Incoming, Outgoing};
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng};
 
 use core::panic;
 use std::collections::{HashMap, HashSet};
@@ -309,6 +309,11 @@ impl NodeIdSet {
         self.vec.is_empty()
     }
 
+    /// Removes the node [nid] from the set.
+    pub fn remove(&mut self, nid: &NodeId) {
+        self.vec.retain(|n| n != nid);
+    }
+
     pub fn iter(&self) -> std::slice::Iter<'_, NodeId> {
         self.vec.iter()
     }
@@ -341,15 +346,15 @@ impl NodeIdSet {
         self.vec.iter().any(|ct| ct.address == nid.address)
     }
 
-    /// Samples a NodeId uniformly at random from the vector
+    /// Samples a NodeId uniformly at random from the vector with [rng].
     /// If the list is empty, it returns an INVALID_NODE_ID
-    pub fn sample(&self) -> NodeId {
+    pub fn sample(&self, rng: &mut StdRng) -> NodeId {
         if self.vec.is_empty() {
             return INVALID_NODE_ID;
         }
         let s = self
             .vec
-            .get(thread_rng().gen_range(0..self.vec.len()))
+            .get(rng.gen_range(0..self.vec.len()))
             .expect("Schroedingers bug encountered.")
             .clone();
         s
@@ -0,0 +1,541 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    cfg::{CFGNodeData, InsnNodeData, InsnNodeType, Procedure, CFG},
+    flow_graphs::{Address, FlowGraphOperations, NodeId, NodeIdSet, INVALID_NODE_ID},
+    icfg::ICFG,
+};
+
+/// Maximum number of procedures of a random graph.
+pub const RANDOM_MAX_PROCS: usize = 3;
+/// Maximum number of nodes in a procedure of a random graph.
+pub const RANDOM_MAX_NODES: usize = 8;
+
+/// A single instruction node of a graph description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeDesc {
+    pub addr: Address,
+    pub itype: InsnNodeType,
+    /// Entries of the called procedures.
+    pub call_targets: Vec<Address>,
+    /// Successors in the CFG. For calls the first one is the next instruction.
+    /// Otherwise the first one is the jump target and the second one the next instruction.
+    pub succs: Vec<Address>,
+}
+
+/// A procedure of a graph description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcDesc {
+    pub addr: Address,
+    pub is_malloc: bool,
+    pub is_input: bool,
+    pub is_unmapped: bool,
+    pub is_free: bool,
+    pub is_excluded: bool,
+    pub nodes: Vec<NodeDesc>,
+}
+
+/// A compact textual description of flow graphs, to build CFGs and iCFGs for tests.
+///
+/// Statements are separated by new lines or ';'. Everything after a '#' is a comment.
+/// Addresses are hexadecimal with a "0x" prefix and decimal otherwise.
+///
+/// ```text
+/// proc <addr> [malloc] [input] [unmapped] [free] [excluded]
+/// <addr> <type>[(<call target>, ...)] [-> <successor>, ...]
+/// ```
+///
+/// A `proc` statement starts a new procedure. The nodes following it belong to it.
+/// It can be omitted for the first procedure. Its address is the one of its entry then.
+/// The node type is given as letters, as accepted by [InsnNodeType::from].
+/// Exactly one node of a procedure must be an entry ("E"). Procedures without nodes
+/// get an empty CFG, as imported or allocating functions.
+///
+/// The successors of a call are its next instruction. For other instructions the
+/// first successor is the jump target and the second the next instruction.
+/// Any further successors are added as jump targets.
+///
+/// Example of gee() and foo() from the paper example:
+///
+/// ```text
+/// proc 0x0
+/// 0 N.E -> 1; 1 N -> 2, 3; 2 N -> 4; 3 N -> 4; 4 R
+/// proc 0x6
+/// 6 N.E -> 7
+/// 7 c(0) -> 8   # Calls gee()
+/// 8 N -> 9, 1; 9 N -> 1; 1 R
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphDesc {
+    pub procs: Vec<ProcDesc>,
+}
+
+fn parse_addr(s: &str) -> Address {
+    let s = s.trim();
+    let addr = match s.strip_prefix("0x") {
+        Some(hex) => Address::from_str_radix(hex, 16),
+        None => s.parse::<Address>(),
+    };
+    addr.unwrap_or_else(|_| panic!("Invalid address: '{}'", s))
+}
+
+fn parse_addr_list(s: &str) -> Vec<Address> {
+    s.split(',')
+        .map(|a| a.trim())
+        .filter(|a| !a.is_empty())
+        .map(parse_addr)
+        .collect()
+}
+
+impl NodeDesc {
+    /// Parses a node statement: <addr> <type>[(<call targets>)] [-> <successors>]
+    fn parse(stmt: &str) -> NodeDesc {
+        let (node, succs) = stmt.split_once("->").unwrap_or((stmt, ""));
+        let (addr, ntype) = node
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or_else(|| panic!("Node without type: '{}'", stmt));
+        let ntype = ntype.trim();
+        let (type_str, call_targets) = match ntype.split_once('(') {
+            Some((t, targets)) => {
+                let Some(targets) = targets.strip_suffix(')') else {
+                    panic!("Missing ')': '{}'", stmt);
+                };
+                (t.trim(), parse_addr_list(targets))
+            }
+            None => (ntype, Vec::new()),
+        };
+        let node = NodeDesc {
+            addr: parse_addr(addr),
+            itype: InsnNodeType::from(type_str),
+            call_targets,
+            succs: parse_addr_list(succs),
+        };
+        assert!(
+            node.itype.is_call() || node.call_targets.is_empty(),
+            "Call targets given for a non-call: '{}'",
+            stmt
+        );
+        assert!(
+            !node.itype.is_call() || node.succs.len() <= 1,
+            "A call has at most one successor: '{}'",
+            stmt
+        );
+        node
+    }
+
+    pub fn get_nid(&self) -> NodeId {
+        NodeId::from(self.addr)
+    }
+
+    pub fn get_jump_targets(&self) -> Vec<Address> {
+        if self.itype.is_call() {
+            return Vec::new();
+        }
+        self.succs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .map(|(_, s)| *s)
+            .collect()
+    }
+
+    pub fn get_next(&self) -> Option<Address> {
+        if self.itype.is_call() {
+            self.succs.first().copied()
+        } else {
+            self.succs.get(1).copied()
+        }
+    }
+
+    /// Returns the CFG node with this instruction.
+    /// The node type gets the hints of the instruction type, as for nodes imported from Rizin.
+    pub fn get_cfg_node(&self) -> CFGNodeData {
+        let nid = self.get_nid();
+        let mut node = CFGNodeData::new(nid);
+        if self.itype.is_entry() {
+            node.node_type |= InsnNodeType::Entry;
+        }
+        if self.itype.is_tail() {
+            node.node_type |= InsnNodeType::Tail;
+        }
+        if self.itype.is_exit() {
+            node.node_type |= InsnNodeType::Exit;
+        }
+        if self.itype.is_return() {
+            node.node_type |= InsnNodeType::Return;
+        }
+        if self.itype.is_cond() {
+            node.node_type |= InsnNodeType::Cond;
+        }
+        let mut call_targets = NodeIdSet::new();
+        self.call_targets
+            .iter()
+            .for_each(|ct| call_targets.insert(NodeId::from(*ct)));
+        let mut jump_targets = self.get_jump_targets().into_iter().map(NodeId::from);
+        let mut orig_jump_targets =
+            NodeIdSet::from_nid(jump_targets.next().unwrap_or(INVALID_NODE_ID));
+        jump_targets.for_each(|jt| orig_jump_targets.insert(jt));
+        node.insns.push(InsnNodeData {
+            addr: self.addr,
+            itype: self.itype,
+            call_targets,
+            orig_jump_targets,
+            orig_next: self.get_next().map_or(INVALID_NODE_ID, NodeId::from),
+            is_indirect_call: false,
+        });
+        node
+    }
+}
+
+impl std::fmt::Display for NodeDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut letters = Vec::new();
+        for (flag, letter) in [
+            (InsnNodeType::Call, "c"),
+            (InsnNodeType::Return, "R"),
+            (InsnNodeType::Exit, "X"),
+            (InsnNodeType::Jump, "J"),
+            (InsnNodeType::Entry, "E"),
+            (InsnNodeType::Cond, "C"),
+            (InsnNodeType::Tail, "T"),
+        ] {
+            if self.itype.contains(flag) {
+                letters.push(letter);
+            }
+        }
+        if letters.is_empty() || letters == ["E"] {
+            letters.insert(0, "N");
+        }
+        write!(f, "{:#x} {}", self.addr, letters.join("."))?;
+        if !self.call_targets.is_empty() {
+            let cts: Vec<String> = self
+                .call_targets
+                .iter()
+                .map(|a| format!("{:#x}", a))
+                .collect();
+            write!(f, "({})", cts.join(", "))?;
+        }
+        if !self.succs.is_empty() {
+            let succs: Vec<String> = self.succs.iter().map(|a| format!("{:#x}", a)).collect();
+            write!(f, " -> {}", succs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl ProcDesc {
+    fn new(addr: Address) -> ProcDesc {
+        ProcDesc {
+            addr,
+            is_malloc: false,
+            is_input: false,
+            is_unmapped: false,
+            is_free: false,
+            is_excluded: false,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Parses a procedure statement: proc <addr> [flags]
+    fn parse(args: &str) -> ProcDesc {
+        let mut args = args.split_whitespace();
+        let Some(addr) = args.next() else {
+            panic!("Procedure without address.");
+        };
+        let mut proc = ProcDesc::new(parse_addr(addr));
+        for flag in args {
+            match flag {
+                "malloc" => proc.is_malloc = true,
+                "input" => proc.is_input = true,
+                "unmapped" => proc.is_unmapped = true,
+                "free" => proc.is_free = true,
+                "excluded" => proc.is_excluded = true,
+                _ => panic!("Unknown procedure flag: '{}'", flag),
+            }
+        }
+        proc
+    }
+
+    pub fn get_node(&self, addr: Address) -> Option<&NodeDesc> {
+        self.nodes.iter().find(|n| n.addr == addr)
+    }
+
+    pub fn get_entry(&self) -> Option<&NodeDesc> {
+        self.nodes.iter().find(|n| n.itype.is_entry())
+    }
+
+    /// Checks the procedure is well-formed and panics if not.
+    fn check(&self) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let entries = self.nodes.iter().filter(|n| n.itype.is_entry()).count();
+        assert_eq!(
+            entries, 1,
+            "Procedure {:#x} has {} entries, expected one.",
+            self.addr, entries
+        );
+        assert_eq!(
+            self.get_entry().unwrap().addr,
+            self.addr,
+            "The entry of procedure {:#x} is not at its address.",
+            self.addr
+        );
+        for (i, node) in self.nodes.iter().enumerate() {
+            assert!(
+                !self.nodes[..i].iter().any(|n| n.addr == node.addr),
+                "Node {:#x} is defined twice in procedure {:#x}.",
+                node.addr,
+                self.addr
+            );
+            for s in node.succs.iter() {
+                assert!(
+                    self.get_node(*s).is_some(),
+                    "Successor {:#x} of {:#x} is not a node of procedure {:#x}.",
+                    s,
+                    node.addr,
+                    self.addr
+                );
+            }
+        }
+    }
+
+    /// Builds the CFG of the procedure.
+    pub fn build_cfg(&self) -> CFG {
+        let mut cfg = CFG::new();
+        for node in self.nodes.iter() {
+            cfg.add_node(node.get_nid(), node.get_cfg_node());
+        }
+        for node in self.nodes.iter() {
+            for s in node.succs.iter() {
+                let succ = self.get_node(*s).unwrap();
+                cfg.add_edge(
+                    (node.get_nid(), node.get_cfg_node()),
+                    (succ.get_nid(), succ.get_cfg_node()),
+                );
+            }
+        }
+        cfg
+    }
+
+    pub fn build_procedure(&self) -> Procedure {
+        let mut proc = Procedure::new(
+            Some(self.build_cfg()),
+            self.is_malloc,
+            self.is_input,
+            self.is_unmapped,
+        );
+        proc.set_free(self.is_free);
+        proc.set_excluded(self.is_excluded);
+        proc
+    }
+}
+
+impl std::fmt::Display for ProcDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "proc {:#x}", self.addr)?;
+        for (is_set, flag) in [
+            (self.is_malloc, "malloc"),
+            (self.is_input, "input"),
+            (self.is_unmapped, "unmapped"),
+            (self.is_free, "free"),
+            (self.is_excluded, "excluded"),
+        ] {
+            if is_set {
+                write!(f, " {}", flag)?;
+            }
+        }
+        for node in self.nodes.iter() {
+            write!(f, "\n{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+impl GraphDesc {
+    /// Parses the graph description [desc]. Panics if it is malformed.
+    pub fn parse(desc: &str) -> GraphDesc {
+        let mut procs: Vec<ProcDesc> = Vec::new();
+        // The first procedure without "proc" statement gets the address of its entry.
+        let mut implicit_proc = false;
+        for stmt in desc
+            .lines()
+            .map(|l| l.split('#').next().unwrap())
+            .flat_map(|l| l.split(';'))
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+        {
+            if let Some(args) = stmt.strip_prefix("proc ") {
+                procs.push(ProcDesc::parse(args));
+                continue;
+            }
+            if procs.is_empty() {
+                procs.push(ProcDesc::new(INVALID_NODE_ID.address));
+                implicit_proc = true;
+            }
+            procs.last_mut().unwrap().nodes.push(NodeDesc::parse(stmt));
+        }
+        if implicit_proc {
+            let proc = procs.first_mut().unwrap();
+            proc.addr = proc.get_entry().expect("Procedure has no entry.").addr;
+        }
+        let graph = GraphDesc { procs };
+        for proc in graph.procs.iter() {
+            proc.check();
+        }
+        graph
+    }
+
+    pub fn get_proc(&self, addr: Address) -> Option<&ProcDesc> {
+        self.procs.iter().find(|p| p.addr == addr)
+    }
+
+    /// Builds the CFG of the first procedure.
+    pub fn build_cfg(&self) -> CFG {
+        self.procs
+            .first()
+            .expect("No procedure described.")
+            .build_cfg()
+    }
+
+    /// Builds the iCFG of all procedures. Every call target must be a described procedure.
+    pub fn build_icfg(&self) -> ICFG {
+        let mut icfg = ICFG::new();
+        for proc in self.procs.iter() {
+            let pid = NodeId::from(proc.addr);
+            assert!(
+                icfg.add_procedure(pid, proc.build_procedure()),
+                "Procedure {:#x} is defined twice.",
+                proc.addr
+            );
+            icfg.get_graph_mut().add_node(pid);
+        }
+        for proc in self.procs.iter() {
+            for node in proc.nodes.iter() {
+                for ct in node.call_targets.iter() {
+                    assert!(
+                        self.get_proc(*ct).is_some(),
+                        "Call target {:#x} of {:#x} is no procedure.",
+                        ct,
+                        node.addr
+                    );
+                    icfg.add_edge_test(
+                        (
+                            NodeId::from(proc.addr),
+                            Procedure::new(None, false, false, false),
+                        ),
+                        (NodeId::from(*ct), Procedure::new(None, false, false, false)),
+                    );
+                }
+            }
+        }
+        icfg
+    }
+}
+
+impl std::fmt::Display for GraphDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, proc) in self.procs.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", proc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the CFG of the first procedure in [desc]. See [GraphDesc] for the format.
+pub fn cfg_from_desc(desc: &str) -> CFG {
+    GraphDesc::parse(desc).build_cfg()
+}
+
+/// Builds the iCFG of all procedures in [desc]. See [GraphDesc] for the format.
+pub fn icfg_from_desc(desc: &str) -> ICFG {
+    GraphDesc::parse(desc).build_icfg()
+}
+
+/// Returns true if the intervals of the loops [a] and [b] overlap without one containing the other.
+fn loops_cross(a: (usize, usize), b: (usize, usize)) -> bool {
+    (a.0 < b.0 && b.0 <= a.1 && a.1 < b.1) || (b.0 < a.0 && a.0 <= b.1 && b.1 < a.1)
+}
+
+/// Generates a random well-formed graph from the [seed].
+///
+/// The first procedure is the entry procedure. The procedures only call procedures
+/// following them, so there is no recursion. Each procedure is a chain of nodes
+/// starting at the entry and ending in a Return. Only the last node of the entry
+/// procedure may be an Exit instead. So every node is reachable and reaches the end.
+/// On top of the chain, forward jumps, calls and properly nested loops are added.
+/// Loops are only entered via their header, which is never the procedure entry.
+pub fn random_graph_desc(seed: u64) -> GraphDesc {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let num_procs = rng.gen_range(1..=RANDOM_MAX_PROCS);
+    let get_addr = |p: usize, i: usize| ((p as Address + 1) << 8) + i as Address;
+    let mut procs = Vec::new();
+    for p in 0..num_procs {
+        let n = rng.gen_range(2..=RANDOM_MAX_NODES);
+        // Loops as (header, latch). Each latch has a single back edge.
+        let mut loops: Vec<(usize, usize)> = Vec::new();
+        if n > 2 {
+            for _ in 0..rng.gen_range(0..=2) {
+                let latch = rng.gen_range(1..n - 1);
+                let header = rng.gen_range(1..=latch);
+                if loops
+                    .iter()
+                    .any(|l| l.1 == latch || loops_cross(*l, (header, latch)))
+                {
+                    continue;
+                }
+                loops.push((header, latch));
+            }
+        }
+        let mut nodes = Vec::new();
+        for i in 0..n {
+            let mut node = NodeDesc {
+                addr: get_addr(p, i),
+                itype: InsnNodeType::Normal,
+                call_targets: Vec::new(),
+                succs: Vec::new(),
+            };
+            if i == n - 1 {
+                node.itype = if p == 0 && rng.gen_bool(0.5) {
+                    InsnNodeType::Exit
+                } else {
+                    InsnNodeType::Return
+                };
+                nodes.push(node);
+                continue;
+            }
+            node.succs.push(get_addr(p, i + 1));
+            if i == 0 {
+                node.itype = InsnNodeType::NormalEntry;
+            }
+            if let Some((header, _)) = loops.iter().find(|l| l.1 == i) {
+                node.succs.insert(0, get_addr(p, *header));
+            } else if i + 2 < n && rng.gen_bool(0.3) {
+                let target = rng.gen_range(i + 2..n);
+                // Jumping from outside into a loop body makes it irreducible.
+                let enters_loop = loops
+                    .iter()
+                    .any(|(h, l)| (i < *h || *l < i) && *h < target && target <= *l);
+                if !enters_loop {
+                    node.succs.insert(0, get_addr(p, target));
+                }
+            } else if i != 0 && p + 1 < num_procs && rng.gen_bool(0.3) {
+                node.itype = InsnNodeType::Call;
+                node.call_targets
+                    .push(get_addr(rng.gen_range(p + 1..num_procs), 0));
+            }
+            nodes.push(node);
+        }
+        let mut proc = ProcDesc::new(get_addr(p, 0));
+        proc.nodes = nodes;
+        procs.push(proc);
+    }
+    GraphDesc { procs }
+}
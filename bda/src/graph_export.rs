@@ -0,0 +1,375 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{io::Write, sync::RwLock};
+
+use crate::{
+    cfg::{InsnNodeType, Procedure, CFG},
+    coverage::HitCounts,
+    flow_graphs::{FlowGraphOperations, NodeId},
+    icfg::ICFG,
+    weight::{WeightID, WeightMap},
+};
+
+/// File formats graphs can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dot,
+    GraphML,
+    Json,
+}
+
+impl ExportFormat {
+    /// Determines the format by the file extension of [path].
+    pub fn from_path(path: &str) -> Option<ExportFormat> {
+        let ext = std::path::Path::new(path).extension()?.to_str()?;
+        match ext.to_lowercase().as_str() {
+            "dot" | "gv" => Some(ExportFormat::Dot),
+            "graphml" => Some(ExportFormat::GraphML),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A node of an exported graph with its attributes.
+struct ExportNode {
+    id: String,
+    attrs: Vec<(&'static str, String)>,
+}
+
+/// An edge of an exported graph with its attributes.
+struct ExportEdge {
+    from: String,
+    to: String,
+    attrs: Vec<(&'static str, String)>,
+}
+
+/// A format independent representation of a graph to export.
+pub struct ExportGraph {
+    name: String,
+    nodes: Vec<ExportNode>,
+    edges: Vec<ExportEdge>,
+}
+
+fn node_key(nid: &NodeId) -> String {
+    format!(
+        "{}:{}:{:#x}",
+        nid.icfg_clone_id, nid.cfg_clone_id, nid.address
+    )
+}
+
+fn type_names(t: InsnNodeType) -> String {
+    t.iter_names()
+        .map(|(name, _)| name)
+        .collect::<Vec<&str>>()
+        .join("|")
+}
+
+fn proc_type_names(proc: &Procedure) -> String {
+    let mut names = Vec::<&str>::new();
+    if proc.is_malloc() {
+        names.push("Malloc");
+    }
+    if proc.is_input() {
+        names.push("Input");
+    }
+    if proc.is_free() {
+        names.push("Free");
+    }
+    if proc.is_unmapped() {
+        names.push("Unmapped");
+    }
+    if proc.is_sink() {
+        names.push("Sink");
+    }
+    if proc.is_excluded() {
+        names.push("Excluded");
+    }
+    if names.is_empty() {
+        names.push("Normal");
+    }
+    names.join("|")
+}
+
+fn weight_str(wid: Option<WeightID>, wmap: &RwLock<WeightMap>) -> Option<String> {
+    Some(wid?.get_weight_const(wmap).to_string())
+}
+
+/// Calculates the weights of all procedures.
+/// The iCFG must be acyclic, so its loops must be resolved before.
+pub fn calc_icfg_weights(icfg: &ICFG, wmap: &RwLock<WeightMap>) {
+    for pid in icfg.get_procedures().keys() {
+        let mut proc = icfg.get_procedure(pid).write().unwrap();
+        if !proc.is_cfg_set() {
+            continue;
+        }
+        proc.get_cfg_mut()
+            .get_entry_weight_id(icfg.get_procedures(), wmap);
+    }
+}
+
+/// Builds the export graph of the [icfg]. Every procedure is one node.
+/// If [coverage] is given, the number of covered instruction words of each procedure
+/// and how often the procedure was entered is added.
+pub fn icfg_to_export_graph(
+    icfg: &ICFG,
+    wmap: &RwLock<WeightMap>,
+    coverage: Option<&HitCounts>,
+) -> ExportGraph {
+    let mut nodes = Vec::<ExportNode>::new();
+    let mut pids: Vec<NodeId> = icfg.get_graph().nodes().collect();
+    pids.sort();
+    for pid in pids.iter() {
+        let mut attrs = vec![
+            ("address", format!("{:#x}", pid.address)),
+            ("icfg_clone", pid.icfg_clone_id.to_string()),
+            ("cfg_clone", pid.cfg_clone_id.to_string()),
+        ];
+        if icfg.has_procedure(pid) {
+            let proc = icfg.get_procedure(pid).read().unwrap();
+            attrs.push(("type", proc_type_names(&proc)));
+            if proc.is_cfg_set() && proc.get_cfg().get_graph().node_count() > 0 {
+                let cfg = proc.get_cfg();
+                attrs.push(("iwords", cfg.nodes_meta.len().to_string()));
+                if let Some(w) = weight_str(cfg.get_node_weight_id(&cfg.get_entry()), wmap) {
+                    attrs.push(("weight", w));
+                }
+                if let Some(cov) = coverage {
+                    let covered = cfg
+                        .nodes_meta
+                        .iter()
+                        .filter(|(nid, _)| cov.get_iword_hits(&nid.address) > 0)
+                        .count();
+                    attrs.push(("covered", covered.to_string()));
+                    attrs.push(("hits", cov.get_iword_hits(&pid.address).to_string()));
+                }
+            }
+        }
+        nodes.push(ExportNode {
+            id: node_key(pid),
+            attrs,
+        });
+    }
+    let mut edges: Vec<(NodeId, NodeId)> = icfg
+        .get_graph()
+        .all_edges()
+        .map(|(from, to, _)| (from, to))
+        .collect();
+    edges.sort();
+    ExportGraph {
+        name: "iCFG".to_string(),
+        nodes,
+        edges: edges
+            .iter()
+            .map(|(from, to)| ExportEdge {
+                from: node_key(from),
+                to: node_key(to),
+                attrs: Vec::new(),
+            })
+            .collect(),
+    }
+}
+
+/// Builds the export graph of the [cfg]. Every instruction word is one node.
+/// If [coverage] is given, it is noted for each instruction word and edge how often it was executed.
+pub fn cfg_to_export_graph(
+    cfg: &CFG,
+    wmap: &RwLock<WeightMap>,
+    coverage: Option<&HitCounts>,
+) -> ExportGraph {
+    let mut nodes = Vec::<ExportNode>::new();
+    let mut nids: Vec<NodeId> = cfg.get_graph().nodes().collect();
+    nids.sort();
+    for nid in nids.iter() {
+        let mut attrs = vec![
+            ("address", format!("{:#x}", nid.address)),
+            ("icfg_clone", nid.icfg_clone_id.to_string()),
+            ("cfg_clone", nid.cfg_clone_id.to_string()),
+        ];
+        if let Some(meta) = cfg.nodes_meta.get(nid) {
+            let itypes = meta
+                .insns
+                .iter()
+                .map(|i| type_names(i.itype))
+                .collect::<Vec<String>>();
+            attrs.push(("type", itypes.join(",")));
+            if let Some(w) = weight_str(cfg.get_node_weight_id(nid), wmap) {
+                attrs.push(("weight", w));
+            }
+        }
+        if let Some(cov) = coverage {
+            attrs.push(("hits", cov.get_iword_hits(&nid.address).to_string()));
+        }
+        nodes.push(ExportNode {
+            id: node_key(nid),
+            attrs,
+        });
+    }
+    let mut edges: Vec<(NodeId, NodeId)> = cfg
+        .get_graph()
+        .all_edges()
+        .map(|(from, to, _)| (from, to))
+        .collect();
+    edges.sort();
+    ExportGraph {
+        name: format!("CFG {:#x}", cfg.get_entry().address),
+        nodes,
+        edges: edges
+            .iter()
+            .map(|(from, to)| ExportEdge {
+                from: node_key(from),
+                to: node_key(to),
+                attrs: coverage.map_or(Vec::new(), |cov| {
+                    vec![(
+                        "hits",
+                        cov.get_edge_hits(&from.address, &to.address).to_string(),
+                    )]
+                }),
+            })
+            .collect(),
+    }
+}
+
+fn escape_quoted(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl ExportGraph {
+    /// Writes the graph in the given [format] to [out].
+    pub fn write<W: Write>(&self, format: ExportFormat, out: &mut W) -> std::io::Result<()> {
+        match format {
+            ExportFormat::Dot => self.write_dot(out),
+            ExportFormat::GraphML => self.write_graphml(out),
+            ExportFormat::Json => self.write_json(out),
+        }
+    }
+
+    fn write_dot<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "digraph \"{}\" {{", escape_quoted(&self.name))?;
+        for node in self.nodes.iter() {
+            write!(out, "  \"{}\" [label=\"{}\"", node.id, node.id)?;
+            for (k, v) in node.attrs.iter() {
+                write!(out, " {}=\"{}\"", k, escape_quoted(v))?;
+            }
+            writeln!(out, "];")?;
+        }
+        for edge in self.edges.iter() {
+            write!(out, "  \"{}\" -> \"{}\"", edge.from, edge.to)?;
+            if !edge.attrs.is_empty() {
+                let attrs = edge
+                    .attrs
+                    .iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, escape_quoted(v)))
+                    .collect::<Vec<String>>();
+                write!(out, " [{}]", attrs.join(" "))?;
+            }
+            writeln!(out, ";")?;
+        }
+        writeln!(out, "}}")
+    }
+
+    fn write_graphml<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            out,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )?;
+        let mut node_keys = Vec::<&str>::new();
+        for (k, _) in self.nodes.iter().flat_map(|n| n.attrs.iter()) {
+            if !node_keys.contains(k) {
+                node_keys.push(k);
+            }
+        }
+        let mut edge_keys = Vec::<&str>::new();
+        for (k, _) in self.edges.iter().flat_map(|e| e.attrs.iter()) {
+            if !edge_keys.contains(k) {
+                edge_keys.push(k);
+            }
+        }
+        for k in node_keys {
+            writeln!(
+                out,
+                "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"string\"/>",
+                k, k
+            )?;
+        }
+        for k in edge_keys {
+            writeln!(
+                out,
+                "  <key id=\"e_{}\" for=\"edge\" attr.name=\"{}\" attr.type=\"string\"/>",
+                k, k
+            )?;
+        }
+        writeln!(
+            out,
+            "  <graph id=\"{}\" edgedefault=\"directed\">",
+            escape_xml(&self.name)
+        )?;
+        for node in self.nodes.iter() {
+            writeln!(out, "    <node id=\"{}\">", node.id)?;
+            for (k, v) in node.attrs.iter() {
+                writeln!(out, "      <data key=\"{}\">{}</data>", k, escape_xml(v))?;
+            }
+            writeln!(out, "    </node>")?;
+        }
+        for edge in self.edges.iter() {
+            if edge.attrs.is_empty() {
+                writeln!(
+                    out,
+                    "    <edge source=\"{}\" target=\"{}\"/>",
+                    edge.from, edge.to
+                )?;
+                continue;
+            }
+            writeln!(
+                out,
+                "    <edge source=\"{}\" target=\"{}\">",
+                edge.from, edge.to
+            )?;
+            for (k, v) in edge.attrs.iter() {
+                writeln!(out, "      <data key=\"e_{}\">{}</data>", k, escape_xml(v))?;
+            }
+            writeln!(out, "    </edge>")?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")
+    }
+
+    fn write_json<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(
+            out,
+            "{{\"name\":\"{}\",\"nodes\":[",
+            escape_quoted(&self.name)
+        )?;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i != 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"id\":\"{}\"", node.id)?;
+            for (k, v) in node.attrs.iter() {
+                write!(out, ",\"{}\":\"{}\"", k, escape_quoted(v))?;
+            }
+            write!(out, "}}")?;
+        }
+        write!(out, "],\"edges\":[")?;
+        for (i, edge) in self.edges.iter().enumerate() {
+            if i != 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"from\":\"{}\",\"to\":\"{}\"", edge.from, edge.to)?;
+            for (k, v) in edge.attrs.iter() {
+                write!(out, ",\"{}\":\"{}\"", k, escape_quoted(v))?;
+            }
+            write!(out, "}}")?;
+        }
+        writeln!(out, "]}}")
+    }
+}
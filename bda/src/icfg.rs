@@ -2,13 +2,15 @@
 // SPDX-License-Identifier: LGPL-3.0-only
 
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    ops::RangeInclusive,
     sync::RwLock,
     thread::{self, ScopedJoinHandle},
 };
 
 use helper::progress::ProgressBar;
 use petgraph::Direction::Outgoing;
+use sha2::{Digest, Sha256};
 
 use crate::{
     cfg::Procedure,
@@ -84,6 +86,142 @@ impl ICFG {
             .is_some_and(|p| p.read().unwrap().is_input())
     }
 
+    pub fn is_free(&self, node_id: &NodeId) -> bool {
+        debug_assert!(self.is_procedure(node_id));
+        self.procedures
+            .get(node_id)
+            .is_some_and(|p| p.read().unwrap().is_free())
+    }
+
+    pub fn is_excluded(&self, node_id: &NodeId) -> bool {
+        self.procedures
+            .get(node_id)
+            .is_some_and(|p| p.read().unwrap().is_excluded())
+    }
+
+    pub fn is_sink(&self, node_id: &NodeId) -> bool {
+        self.procedures
+            .get(node_id)
+            .is_some_and(|p| p.read().unwrap().is_sink())
+    }
+
+    /// Returns all call instructions calling a taint sink.
+    /// Mapped to the argument positions which should not receive input data.
+    pub fn get_sink_call_sites(&self) -> BTreeMap<Address, BTreeSet<usize>> {
+        let mut sites = BTreeMap::<Address, BTreeSet<usize>>::new();
+        for proc in self.procedures.values() {
+            let proc = proc.read().unwrap();
+            if !proc.is_cfg_set() {
+                continue;
+            }
+            for cinsn in proc.get_cfg().nodes_meta.cinsn_iter() {
+                for ct in cinsn.call_targets.iter() {
+                    let Some(target) = self.procedures.get(ct) else {
+                        continue;
+                    };
+                    let target = target.read().unwrap();
+                    if !target.is_sink() {
+                        continue;
+                    }
+                    sites
+                        .entry(cinsn.addr)
+                        .or_default()
+                        .extend(target.get_sink_args());
+                }
+            }
+        }
+        sites
+    }
+
+    /// Returns a fingerprint for every procedure.
+    /// It is the hex SHA-256 digest of the procedure structure and the fingerprints of its callees.
+    /// So it changes if the CFG of the procedure, or of any procedure called by it, changes.
+    pub fn get_fingerprints(&self) -> HashMap<NodeId, String> {
+        let mut fingerprints = HashMap::<NodeId, String>::new();
+        for pid in self.procedures.keys() {
+            self.calc_fingerprint(pid, &mut fingerprints);
+        }
+        fingerprints
+    }
+
+    fn calc_fingerprint(&self, pid: &NodeId, fingerprints: &mut HashMap<NodeId, String>) -> String {
+        if let Some(fp) = fingerprints.get(pid) {
+            return fp.clone();
+        }
+        let Some(proc) = self.procedures.get(pid) else {
+            return String::new();
+        };
+        // Placeholder to break recursive calls.
+        // They only exist if the loops of the iCFG are not resolved yet.
+        fingerprints.insert(*pid, String::new());
+        let proc = proc.read().unwrap();
+        let mut hasher = Sha256::new();
+        hasher.update(proc.serialize_structure());
+        if proc.is_cfg_set() {
+            for ct in proc.get_cfg().nodes_meta.ct_iter() {
+                hasher.update(format!(
+                    "callee {} {}\n",
+                    ct,
+                    self.calc_fingerprint(ct, fingerprints)
+                ));
+            }
+        }
+        let fp = format!("{:x}", hasher.finalize());
+        fingerprints.insert(*pid, fp.clone());
+        fp
+    }
+
+    /// Returns the procedures at [functions] and all procedures called by them,
+    /// transitively up to [depth] calls deep.
+    /// Called procedures which are not executed (malloc, input etc.) are always part of the scope.
+    /// Otherwise the calls to them would be skipped during sampling.
+    pub fn get_scope(&self, functions: &[Address], depth: usize) -> BTreeSet<NodeId> {
+        let mut scope = BTreeSet::<NodeId>::new();
+        let mut todo: VecDeque<(NodeId, usize)> = functions
+            .iter()
+            .map(|addr| (NodeId::new_original(*addr), 0))
+            .collect();
+        while let Some((pid, d)) = todo.pop_front() {
+            if !scope.insert(pid) || !self.graph.contains_node(pid) {
+                continue;
+            }
+            for callee in self.graph.neighbors_directed(pid, Outgoing) {
+                if d < depth || self.is_not_executed(&callee) {
+                    todo.push_back((callee, d + 1));
+                }
+            }
+        }
+        scope
+    }
+
+    fn is_not_executed(&self, pid: &NodeId) -> bool {
+        self.procedures
+            .get(pid)
+            .is_some_and(|p| p.read().unwrap().wont_execute())
+    }
+
+    /// Returns the address ranges of the instruction words of the procedures in [scope].
+    /// Procedures without CFG only cover their entry address.
+    pub fn get_scope_ranges(&self, scope: &BTreeSet<NodeId>) -> Vec<RangeInclusive<Address>> {
+        let mut ranges = Vec::<RangeInclusive<Address>>::new();
+        for pid in scope.iter() {
+            let Some(proc) = self.procedures.get(pid) else {
+                ranges.push(pid.address..=pid.address);
+                continue;
+            };
+            let proc = proc.read().unwrap();
+            if !proc.is_cfg_set() {
+                ranges.push(pid.address..=pid.address);
+                continue;
+            }
+            let addrs = proc.get_cfg().nodes_meta.iter().map(|(nid, _)| nid.address);
+            let min = addrs.clone().min().unwrap_or(pid.address);
+            let max = addrs.max().unwrap_or(pid.address);
+            ranges.push(min.min(pid.address)..=max.max(pid.address));
+        }
+        ranges
+    }
+
     pub fn print_stats(&self) {
         println!("iCFG stats");
         println!("\tCFGs: {}", self.graph.node_count());
@@ -177,6 +315,34 @@ impl ICFG {
         return false;
     }
 
+    /// Links the import stub procedure [stub] to the function [target] it jumps to.
+    /// The stub tail calls [target] afterwards.
+    /// If the iCFG doesn't contain [target] yet, its procedure must be given.
+    /// Returns false if the stub could not be linked.
+    pub fn link_import_stub(&mut self, stub: &NodeId, target: (NodeId, Option<Procedure>)) -> bool {
+        if !self.has_procedure(stub) || self.has_edge(*stub, target.0) {
+            return false;
+        }
+        let (target_nid, target_proc) = target;
+        if !self.has_procedure(&target_nid) {
+            let Some(proc) = target_proc else {
+                return false;
+            };
+            self.add_procedure(target_nid, proc);
+        }
+        if !self
+            .get_procedure(stub)
+            .write()
+            .unwrap()
+            .get_cfg_mut()
+            .set_tail_call_target(&target_nid)
+        {
+            return false;
+        }
+        self.graph.add_edge(*stub, target_nid, 0);
+        true
+    }
+
     /// Adds an edge to the graph.
     /// The edge is only added once.
     pub fn add_edge_test(&mut self, from: (NodeId, Procedure), to: (NodeId, Procedure)) {
@@ -8,16 +8,29 @@
 mod bda;
 pub mod bda_binding;
 pub mod cfg;
+pub mod code_probability;
+pub mod coverage;
 pub mod flow_graphs;
+pub mod graph_builder;
+pub mod graph_export;
 pub mod icfg;
 mod path_sampler;
 mod post_analysis;
+pub mod shared_libs;
 pub mod state;
+mod taint;
+mod test_code_probability;
+mod test_coverage;
 mod test_flow_graphs;
+mod test_graph_builder;
+mod test_graph_export;
 pub mod test_graphs;
 mod test_path_sampler;
 mod test_post_analysis;
+mod test_regression;
+mod test_shared_libs;
 mod test_state;
+mod test_taint;
 mod test_unit;
 mod test_weight;
 pub mod weight;
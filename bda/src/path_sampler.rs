@@ -4,7 +4,7 @@
 use std::{collections::VecDeque, ops::RangeInclusive, sync::RwLock};
 
 use petgraph::Direction::Outgoing;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng};
 use rzil_abstr::interpreter::{IWordInfo, IntrpPath};
 
 use crate::{
@@ -147,13 +147,17 @@ fn approximate_weights(weights: &VecDeque<WeightID>, wmap: &RwLock<WeightMap>) -
 /// taken from the rest and the comparision is done again.
 /// The algorithm terminates if either one of candiates was chosen, all candidates
 /// lost, in which case the last index is returned.
+/// The random numbers are drawn from [rng].
 ///
 /// [^2] https://doi.org/10.25394/PGS.23542014.v1
-fn select_branch(mut weights: VecDeque<WeightID>, wmap: &RwLock<WeightMap>) -> usize {
+fn select_branch(
+    mut weights: VecDeque<WeightID>,
+    wmap: &RwLock<WeightMap>,
+    rng: &mut StdRng,
+) -> usize {
     if weights.len() == 1 {
         return 0;
     }
-    let mut rng = thread_rng();
     let mut candidate = 0;
     let mut next_cnd = 1;
     loop {
@@ -255,6 +259,7 @@ fn sample_cfg_path(
     i: usize,
     wmap: &RwLock<WeightMap>,
     addr_ranges: &Vec<RangeInclusive<Address>>,
+    rng: &mut StdRng,
 ) -> SamplingState {
     let entry = &cfg.get_entry();
     let cfg_needs_recalc = wmap.read().unwrap().needs_recalc(entry);
@@ -283,9 +288,13 @@ fn sample_cfg_path(
         if ninfo.is_call() {
             let call_targets = filter_call_targets(cfg, cur, Some(addr_ranges));
 
-            if ninfo.calls_unmapped() || ninfo.calls_malloc() || ninfo.calls_input() {
+            if ninfo.calls_unmapped()
+                || ninfo.calls_malloc()
+                || ninfo.calls_input()
+                || ninfo.calls_free()
+            {
                 // Either a dynamically linked procedure (without CFG)
-                // a malloc/input call or indirect call with unknown addresses.
+                // a malloc/input/free call or indirect call with unknown addresses.
                 // We don't recurse in those.
                 // These calls are effectively not followed.
                 // So the next instruction does not follow a semantic call.
@@ -293,7 +302,7 @@ fn sample_cfg_path(
             } else {
                 node_follows_call = true;
                 // recurse into CFG to sample a new path.
-                let ct = call_targets.sample();
+                let ct = call_targets.sample(rng);
                 if ct != INVALID_NODE_ID {
                     if sample_cfg_path(
                         icfg,
@@ -303,6 +312,7 @@ fn sample_cfg_path(
                         i + 1,
                         wmap,
                         addr_ranges,
+                        rng,
                     ) == SamplingState::Exit
                     {
                         // Stop sampling if exit was reached deeper in the tree.
@@ -313,7 +323,7 @@ fn sample_cfg_path(
             }
         } else if is_tail_call(cfg, cur) {
             let jump_targets = filter_jump_targets(cfg, cur, addr_ranges);
-            let jt = jump_targets.sample();
+            let jt = jump_targets.sample(rng);
             if jt != INVALID_NODE_ID {
                 res = sample_cfg_path(
                     icfg,
@@ -323,6 +333,7 @@ fn sample_cfg_path(
                     i + 1,
                     wmap,
                     addr_ranges,
+                    rng,
                 );
             }
             // This is not quite correct. Because the jump_target list is already filterd.
@@ -356,7 +367,9 @@ fn sample_cfg_path(
                 SamplingState::Continue
             };
         }
-        let picked_neighbor = *neigh_ids.get(select_branch(neigh_weights, wmap)).unwrap();
+        let picked_neighbor = *neigh_ids
+            .get(select_branch(neigh_weights, wmap, rng))
+            .unwrap();
         if picked_neighbor == cur {
             panic!("Unresolved loop in CFG detected at node {}.", cur);
         }
@@ -397,9 +410,16 @@ fn get_node_info(
         if unfiltered_call_targets.iter().any(|ct| icfg.is_input(ct)) {
             ninfo |= IWordInfo::CallsInput;
         }
+        if unfiltered_call_targets.iter().any(|ct| icfg.is_free(ct)) {
+            ninfo |= IWordInfo::CallsFree;
+        }
+        if unfiltered_call_targets.iter().any(|ct| icfg.is_sink(ct)) {
+            ninfo |= IWordInfo::CallsSink;
+        }
+        // Excluded procedures are not entered, same as unmapped ones.
         if unfiltered_call_targets
             .iter()
-            .any(|ct| icfg.is_unmapped(ct))
+            .any(|ct| icfg.is_unmapped(ct) || icfg.is_excluded(ct))
         {
             ninfo |= IWordInfo::CallsUnmapped;
         }
@@ -438,11 +458,13 @@ fn is_return(cfg: &mut CFG, cur: NodeId) -> bool {
 }
 
 /// Sample a path from the given [icfg] and return it as vector.
+/// The branches are picked with the random numbers of [rng].
 pub fn sample_path(
     icfg: &ICFG,
     entry_point: Address,
     wmap: &RwLock<WeightMap>,
     addr_ranges: &Vec<RangeInclusive<Address>>,
+    rng: &mut StdRng,
 ) -> Path {
     let entry_node: NodeId;
     let mut path = Path::new();
@@ -466,6 +488,7 @@ pub fn sample_path(
         0,
         wmap,
         addr_ranges,
+        rng,
     );
     path
 }
@@ -487,7 +510,11 @@ pub fn testing_addresses_to_path(icfg: &ICFG, addresses: &mut VecDeque<Address>,
         path.push(nid, ninfo);
 
         if ninfo.is_call() {
-            if !(ninfo.calls_unmapped() || ninfo.calls_malloc() || ninfo.calls_input()) {
+            if !(ninfo.calls_unmapped()
+                || ninfo.calls_malloc()
+                || ninfo.calls_input()
+                || ninfo.calls_free())
+            {
                 let next = NodeId::new_original(*addresses.get(0).unwrap());
                 node_follows_call = true;
                 if icfg.has_procedure(&next) {
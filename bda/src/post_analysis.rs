@@ -5,7 +5,7 @@
 
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
-    fmt::LowerHex,
+    fmt::{Display, LowerHex},
     ops::RangeInclusive,
 };
 
@@ -101,6 +101,110 @@ impl LowerHex for MemDefMap {
     }
 }
 
+/// A dependency between a memory read and the write defining the read memory.
+/// Including the call string the read was executed under.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContextDependency {
+    /// The memory reading instruction.
+    read: Address,
+    /// The instruction defining the read memory.
+    write: Address,
+    /// The call sites leading to the read. The most recent call is the last one.
+    /// It is bound to the configured call string length.
+    call_string: Vec<Address>,
+}
+
+impl ContextDependency {
+    pub fn get_read(&self) -> Address {
+        self.read
+    }
+
+    pub fn get_write(&self) -> Address {
+        self.write
+    }
+
+    pub fn get_call_string(&self) -> &Vec<Address> {
+        &self.call_string
+    }
+
+    /// Returns the context insensitive dependency pair.
+    pub fn get_pair(&self) -> (Address, Address) {
+        (self.read, self.write)
+    }
+}
+
+impl Display for ContextDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let call_string = self
+            .call_string
+            .iter()
+            .map(|a| format!("{:#x}", a))
+            .collect::<Vec<String>>()
+            .join(" -> ");
+        write!(
+            f,
+            "{:#x} -> {:#x} @ [{}]",
+            self.read, self.write, call_string
+        )
+    }
+}
+
+/// The support of a dependency by the sampled memory operation sequences (MOS).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepConfidence {
+    /// Number of MOSes the dependency was directly observed in.
+    support: usize,
+    /// Number of MOSes both instructions of the dependency were executed in.
+    executed: usize,
+}
+
+impl DepConfidence {
+    pub fn new(support: usize, executed: usize) -> DepConfidence {
+        DepConfidence { support, executed }
+    }
+
+    pub fn get_support(&self) -> usize {
+        self.support
+    }
+
+    pub fn get_executed(&self) -> usize {
+        self.executed
+    }
+
+    /// Returns the fraction of MOSes executing both instructions, which support the dependency.
+    /// Dependencies which were only inferred by the posterior analysis have a score of 0.
+    pub fn get_score(&self) -> f64 {
+        if self.executed == 0 {
+            return 0.0;
+        }
+        self.support as f64 / self.executed as f64
+    }
+
+    /// True if the dependency was never directly observed, but only inferred
+    /// by the posterior analysis.
+    pub fn is_inferred(&self) -> bool {
+        self.support == 0
+    }
+
+    /// Checks if the dependency has a confidence of at least [min_confidence].
+    /// Inferred dependencies have a score of 0. So they only pass a threshold of 0.
+    pub fn is_confident(&self, min_confidence: f64) -> bool {
+        self.get_score() >= min_confidence
+    }
+}
+
+impl Display for DepConfidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} ({}/{})",
+            self.get_score(),
+            self.support,
+            self.executed
+        )
+    }
+}
+
 /// A state index is the address of the instruction and
 /// the index of the call stack this instruction is executed under.
 type StateIdx = (usize, NodeId);
@@ -244,6 +348,18 @@ impl AbstractProgramState {
     fn add_empty_map(&mut self, state_idx: &StateIdx) {
         self.state.insert(*state_idx, MemDefMap::new());
     }
+
+    /// Returns the call sites of the call stack with index [cs_idx].
+    /// Only the [k] most recent call sites are returned.
+    fn get_call_string(&self, cs_idx: usize, k: usize) -> Vec<Address> {
+        let call_sites: Vec<Address> = self
+            .call_stacks
+            .iter()
+            .take(cs_idx)
+            .filter_map(|cs| cs.back().map(|nid| nid.address))
+            .collect();
+        call_sites[call_sites.len().saturating_sub(k)..].to_vec()
+    }
 }
 
 pub struct PostAnalyzer {
@@ -256,6 +372,9 @@ pub struct PostAnalyzer {
     insn_meta_data: BTreeMap<Address, IWordInfo>,
     /// Dependent instruction pairs. If set, there is a dependency between the instructions.
     DIP: BTreeSet<(Address, Address)>,
+    /// Dependent instruction pairs with the call string of the read.
+    /// Only filled if call strings are enabled.
+    CDIP: BTreeSet<ContextDependency>,
 }
 
 impl PostAnalyzer {
@@ -277,6 +396,7 @@ impl PostAnalyzer {
             programm_graph: edge_matrix,
             insn_meta_data: iword_info,
             DIP: BTreeSet::new(),
+            CDIP: BTreeSet::new(),
         }
     }
 
@@ -303,9 +423,9 @@ impl PostAnalyzer {
 
     fn is_call_to_skip(&self, addr: &Address) -> bool {
         if let Some(info) = self.insn_meta_data.get(addr) {
-            return info
-                .iter()
-                .any(|i| i.calls_malloc() || i.calls_input() || i.calls_unmapped());
+            return info.iter().any(|i| {
+                i.calls_malloc() || i.calls_input() || i.calls_unmapped() || i.calls_free()
+            });
         }
         false
     }
@@ -328,13 +448,16 @@ impl PostAnalyzer {
         "<>".to_string()
     }
 
+    /// Collects the memory dependencies of a single [MOS].
+    /// Returns the addresses of all instructions in the [MOS].
     fn per_sample_analysis(
         &self,
-        MOS: MemOpSeq,
+        MOS: &MemOpSeq,
         I2M: &mut SetMap<Address, AbstrVal>,
         DEP: &mut SetMap<Address, Address>,
         KILL: &mut SetMap<Address, Address>,
-    ) {
+        SUPPORT: &mut BTreeMap<(Address, Address), usize>,
+    ) -> BTreeSet<Address> {
         // println!("MOS: {:?}", self.insn_meta_data);
         debug_assert!(
             MOS.iter()
@@ -342,27 +465,35 @@ impl PostAnalyzer {
             "Some MemOps have no iword meta data."
         );
         let mut DEF = BTreeMap::<AbstrVal, Address>::new();
+        let mut executed = BTreeSet::<Address>::new();
+        let mut supported = BTreeSet::<(Address, Address)>::new();
 
-        for mem_op in MOS.into_iter() {
+        for mem_op in MOS.iter() {
             let iaddr = mem_op.ref_addr;
-            let aval = mem_op.aval;
+            let aval = &mem_op.aval;
             if self.is_mem_write(&iaddr) {
-                if let Some(def_addr) = DEF.get(&aval) {
+                if let Some(def_addr) = DEF.get(aval) {
                     KILL.insert(iaddr, *def_addr);
                 }
                 DEF.insert(aval.clone(), iaddr);
             }
             if self.is_mem_read(&iaddr) {
-                if let Some(def_addr) = DEF.get(&aval) {
+                if let Some(def_addr) = DEF.get(aval) {
                     DEP.insert(iaddr, *def_addr);
+                    supported.insert((iaddr, *def_addr));
                 }
             }
-            I2M.insert(iaddr, aval);
+            I2M.insert(iaddr, aval.clone());
+            executed.insert(iaddr);
+        }
+        for dep in supported.into_iter() {
+            *SUPPORT.entry(dep).or_default() += 1;
         }
         // println!("DEF:");
         // for (k, v) in DEF.iter() {
         //     println!("{} -> {:#x}", k, v);
         // }
+        executed
     }
 
     fn handle_memory_write(
@@ -400,6 +531,7 @@ impl PostAnalyzer {
 
     fn handle_memory_read(
         DIP: &mut BTreeSet<(Address, Address)>,
+        mut CDIP: Option<(&mut BTreeSet<ContextDependency>, Vec<Address>)>,
         iaddr: Address,
         state: &AbstractProgramState,
         curr_state_idx: &StateIdx,
@@ -427,6 +559,13 @@ impl PostAnalyzer {
             };
             for def in m2i_iter {
                 DIP.insert((iaddr, *def));
+                if let Some((cdip, call_string)) = CDIP.as_mut() {
+                    cdip.insert(ContextDependency {
+                        read: iaddr,
+                        write: *def,
+                        call_string: call_string.clone(),
+                    });
+                }
             }
         }
         // }
@@ -457,6 +596,15 @@ impl PostAnalyzer {
         &mut self.DIP
     }
 
+    fn get_dips_mut(
+        &mut self,
+    ) -> (
+        &mut BTreeSet<(Address, Address)>,
+        &mut BTreeSet<ContextDependency>,
+    ) {
+        (&mut self.DIP, &mut self.CDIP)
+    }
+
     fn clone_dip(&self) -> BTreeSet<(Address, Address)> {
         self.DIP.clone()
     }
@@ -492,8 +640,23 @@ pub fn posterior_dependency_analysis(
     let mut I2M = SetMap::<Address, AbstrVal>::new();
     let mut DEP = SetMap::<Address, Address>::new();
     let mut KILL = SetMap::<Address, Address>::new();
-    for mos in state.take_moses() {
-        analyzer.per_sample_analysis(mos, &mut I2M, &mut DEP, &mut KILL);
+    let mut SUPPORT = BTreeMap::<(Address, Address), usize>::new();
+    let mut executed_sets = Vec::<BTreeSet<Address>>::new();
+    let moses = state.mos.take().expect("No memory operations sampled.");
+    for mos in moses.iter() {
+        executed_sets.push(analyzer.per_sample_analysis(
+            mos,
+            &mut I2M,
+            &mut DEP,
+            &mut KILL,
+            &mut SUPPORT,
+        ));
+    }
+    // The sequences are only kept for later analyses which asked for them (e.g. OSPREY).
+    if state.keeps_moses() {
+        state.mos = Some(moses);
+    } else {
+        drop(moses);
     }
     // println!("I2M:\n{:x}", I2M);
     // println!("DEP:\n{:x}", DEP);
@@ -521,8 +684,12 @@ pub fn posterior_dependency_analysis(
             );
         }
         if analyzer.is_mem_read(&iaddr.address) {
+            let call_string_len = state.get_call_string_len();
+            let call_string = abstr_prog_state.get_call_string(cs_idx, call_string_len);
+            let (DIP, CDIP) = analyzer.get_dips_mut();
             PostAnalyzer::handle_memory_read(
-                analyzer.get_dip_mut(),
+                DIP,
+                (call_string_len > 0).then_some((CDIP, call_string)),
                 iaddr.address,
                 &abstr_prog_state,
                 &state_idx,
@@ -566,5 +733,28 @@ pub fn posterior_dependency_analysis(
             }
         }
     }
-    analyzer.clone_dip()
+    state.dep = DEP;
+
+    let mut dip = analyzer.clone_dip();
+    for dep in dip.iter() {
+        let executed = executed_sets
+            .iter()
+            .filter(|set| set.contains(&dep.0) && set.contains(&dep.1))
+            .count();
+        let support = SUPPORT.get(dep).cloned().unwrap_or_default();
+        state
+            .dep_confidence
+            .insert(*dep, DepConfidence::new(support, executed));
+    }
+    let min_confidence = state.get_min_confidence();
+    if min_confidence > 0.0 {
+        let is_confident =
+            |dep: &(Address, Address)| state.dep_confidence[dep].is_confident(min_confidence);
+        dip.retain(|dep| is_confident(dep));
+        analyzer.CDIP.retain(|cdep| is_confident(&cdep.get_pair()));
+    }
+    if state.get_call_string_len() > 0 {
+        state.context_deps = Some(std::mem::take(&mut analyzer.CDIP));
+    }
+    dip
 }
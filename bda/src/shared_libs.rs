@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::flow_graphs::Address;
+
+/// Symbols of a loaded binary, which are relevant to link it with other binaries.
+pub struct BinSymbols {
+    /// Exported functions and objects, indexed by name.
+    pub exports: BTreeMap<String, Address>,
+    /// Import stubs (e.g. PLT entries), indexed by the name of the imported function.
+    pub stubs: BTreeMap<String, Address>,
+    /// Slots the address of an imported symbol is written to (e.g. GOT entries).
+    pub slots: Vec<(String, Address)>,
+}
+
+impl BinSymbols {
+    pub fn new() -> BinSymbols {
+        BinSymbols {
+            exports: BTreeMap::new(),
+            stubs: BTreeMap::new(),
+            slots: Vec::new(),
+        }
+    }
+}
+
+/// The imports of all binaries, resolved to the address of the symbol they import.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportLinks {
+    /// Import stubs and the function they jump to.
+    pub stubs: Vec<(Address, Address)>,
+    /// Import slots and the symbol address written to them.
+    pub slots: Vec<(Address, Address)>,
+    /// Ids of the binary files loaded as shared libraries.
+    pub libraries: BTreeSet<u32>,
+}
+
+/// Resolves the imports of all [binaries] against their exports.
+/// As the dynamic linker does it, the first binary (in load order) exporting a symbol defines it.
+/// Imports without definition are not linked.
+pub fn link_binaries(binaries: &[BinSymbols]) -> ImportLinks {
+    let mut definitions = BTreeMap::<&str, Address>::new();
+    for bin in binaries.iter() {
+        for (name, addr) in bin.exports.iter() {
+            definitions.entry(name).or_insert(*addr);
+        }
+    }
+    let mut links = ImportLinks {
+        stubs: Vec::new(),
+        slots: Vec::new(),
+        libraries: BTreeSet::new(),
+    };
+    for bin in binaries.iter() {
+        for (name, stub) in bin.stubs.iter() {
+            if let Some(def) = definitions.get(name.as_str()) {
+                links.stubs.push((*stub, *def));
+            }
+        }
+        for (name, slot) in bin.slots.iter() {
+            if let Some(def) = definitions.get(name.as_str()) {
+                links.slots.push((*slot, *def));
+            }
+        }
+    }
+    links
+}
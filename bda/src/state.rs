@@ -8,13 +8,31 @@ use std::{
     time::Duration,
 };
 
-use helper::timer::Timer;
-use rzil_abstr::interpreter::{ConcreteCodeXref, IWordInfo, MemOpSeq, MemXref, StackXref};
+use helper::{set_map::SetMap, timer::Timer};
+use rzil_abstr::{
+    interpreter::{
+        ConcreteCodeXref, DataFlow, HeapFinding, HeapFindingType, IWordInfo, MemOpSeq, MemXref,
+        StackXref, TypeHint,
+    },
+    summary::FunctionSummaries,
+};
+use stochfuzz::rewriter::BranchHint;
 
-use crate::{flow_graphs::Address, weight::WeightMap};
+use crate::{
+    bda_binding::ProcedurePatterns,
+    coverage::HitCounts,
+    flow_graphs::Address,
+    post_analysis::{ContextDependency, DepConfidence},
+    weight::WeightMap,
+};
 
-pub fn run_condition_fulfilled(state: &BDAState) -> bool {
-    !state.bda_timed_out()
+/// Returns true if sampling should continue after [paths_walked] paths.
+/// If a path limit is set, it replaces the timeout.
+pub fn run_condition_fulfilled(state: &BDAState, paths_walked: usize) -> bool {
+    match state.max_paths {
+        Some(max_paths) => paths_walked < max_paths,
+        None => !state.bda_timed_out(),
+    }
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
@@ -108,12 +126,50 @@ pub struct BDAState {
     pub stack_xrefs: BTreeSet<StackXref>,
     /// Memory op sequences
     pub mos: Option<BTreeSet<MemOpSeq>>,
+    /// Keep the memory op sequences after the posterior analysis (e.g. for OSPREY).
+    keep_moses: bool,
     /// Meta information collected about each instruction word executed.
     pub iword_info: Option<BTreeMap<Address, IWordInfo>>,
+    /// Number of times each instruction word and edge was executed.
+    pub hits: HitCounts,
+    /// Summaries of procedures, learned on the paths or loaded from a file.
+    pub summaries: FunctionSummaries,
+    /// Number of bytes read or written by each memory accessing instruction word.
+    pub access_sizes: BTreeMap<Address, u64>,
+    /// Hints on the type of the values each memory accessing instruction word reads or writes.
+    pub type_hints: BTreeMap<Address, TypeHint>,
+    /// Use-after-free and double-free findings.
+    /// Indexed by finding type, accessing instruction and releasing instruction.
+    pub heap_findings: BTreeMap<(HeapFindingType, Address, Address), HeapFinding>,
+    /// Flows of input data observed during interpretation.
+    pub data_flows: BTreeSet<DataFlow>,
+    /// Memory dependencies calculated by the posterior analysis.
+    /// Maps a memory reading instruction to the instructions defining the read memory.
+    pub dep: SetMap<Address, Address>,
+    /// Dependencies with the call string of the read.
+    /// Only set if the call string length is greater than 0.
+    pub context_deps: Option<BTreeSet<ContextDependency>>,
+    /// Confidence of each dependency reported by the posterior analysis.
+    pub dep_confidence: BTreeMap<(Address, Address), DepConfidence>,
+    /// Dependencies with a lower confidence score are not reported.
+    min_confidence: f64,
+    /// Maximal number of call sites kept in the call strings of dependencies.
+    /// 0 disables context sensitive dependencies.
+    call_string_len: usize,
     /// Runtime statistics
     pub runtime_stats: RuntimeStats,
     /// Address ranges to analyze
     ranges: Vec<RangeInclusive<Address>>,
+    /// Functions selected by the user to analyze.
+    /// If set, the entries and ranges are derived from them and their callees.
+    scope: Option<Vec<Address>>,
+    /// Ids of the binary files loaded as shared libraries.
+    /// Their entry points are no analysis entries.
+    library_files: BTreeSet<u32>,
+    /// Number of paths to interpret. If set, the run doesn't depend on the timeout.
+    max_paths: Option<usize>,
+    /// The procedure name patterns compiled for this run.
+    procedure_patterns: Option<ProcedurePatterns>,
 }
 
 impl BDAState {
@@ -130,14 +186,30 @@ impl BDAState {
             num_threads,
             weight_map: WeightMap::new(),
             iword_info: Some(BTreeMap::new()),
+            hits: HitCounts::new(),
+            summaries: FunctionSummaries::new(),
+            access_sizes: BTreeMap::new(),
+            type_hints: BTreeMap::new(),
             calls: BTreeSet::new(),
             jumps: BTreeSet::new(),
             unhandled_code_xrefs: BTreeSet::new(),
             mem_xrefs: BTreeSet::new(),
             stack_xrefs: BTreeSet::new(),
             mos: Some(BTreeSet::new()),
+            keep_moses: false,
+            heap_findings: BTreeMap::new(),
+            data_flows: BTreeSet::new(),
+            dep: SetMap::new(),
+            context_deps: None,
+            dep_confidence: BTreeMap::new(),
+            min_confidence: 0.0,
+            call_string_len: 0,
             runtime_stats: RuntimeStats::new(),
             ranges: Vec::from([0x0..=Address::MAX]),
+            scope: None,
+            library_files: BTreeSet::new(),
+            max_paths: None,
+            procedure_patterns: None,
         }
     }
 
@@ -163,6 +235,10 @@ impl BDAState {
                 // Already added to call set during iCFG update.
                 continue;
             }
+            if xref.is_candidate() && self.calls.contains(&xref.as_observed()) {
+                // Was observed already.
+                continue;
+            }
             self.unhandled_code_xrefs.insert(xref);
         }
     }
@@ -173,14 +249,45 @@ impl BDAState {
                 // Already added to jump set during iCFG update.
                 continue;
             }
+            if xref.is_candidate() && self.jumps.contains(&xref.as_observed()) {
+                // Was observed already.
+                continue;
+            }
             self.unhandled_code_xrefs.insert(xref);
         }
     }
 
+    /// Returns the discovered indirect calls and jumps as hints for the StochFuzz rewriter.
+    pub fn get_branch_hints(&self) -> Vec<BranchHint> {
+        self.calls
+            .iter()
+            .chain(self.jumps.iter())
+            .map(|xref| BranchHint {
+                from: xref.get_from(),
+                to: xref.get_to(),
+                candidate: xref.is_candidate(),
+            })
+            .collect()
+    }
+
     pub fn update_mem_xrefs(&mut self, xrefs: BTreeSet<MemXref>) {
         self.mem_xrefs.extend(xrefs);
     }
 
+    pub fn update_access_sizes(&mut self, sizes: BTreeMap<Address, u64>) {
+        for (addr, n_bytes) in sizes.into_iter() {
+            let size = self.access_sizes.entry(addr).or_default();
+            *size = (*size).max(n_bytes);
+        }
+    }
+
+    pub fn update_type_hints(&mut self, hints: BTreeMap<Address, TypeHint>) {
+        for (addr, hint) in hints.into_iter() {
+            let hints = self.type_hints.entry(addr).or_insert(TypeHint::None);
+            *hints |= hint;
+        }
+    }
+
     pub fn update_stack_xrefs(&mut self, xrefs: BTreeSet<StackXref>) {
         self.stack_xrefs.extend(xrefs);
     }
@@ -189,6 +296,37 @@ impl BDAState {
         self.mos.as_mut().unwrap().insert(mos);
     }
 
+    /// Adds the heap findings. For every finding only the one with the
+    /// shortest witness path is kept.
+    pub fn update_heap_findings(&mut self, findings: BTreeSet<HeapFinding>) {
+        for f in findings.into_iter() {
+            let key = (f.get_ftype(), f.get_at(), f.get_freed_at());
+            if self
+                .heap_findings
+                .get(&key)
+                .is_some_and(|known| known.get_witness().len() <= f.get_witness().len())
+            {
+                continue;
+            }
+            self.heap_findings.insert(key, f);
+        }
+    }
+
+    pub fn update_data_flows(&mut self, flows: BTreeSet<DataFlow>) {
+        self.data_flows.extend(flows);
+    }
+
+    /// Returns the addresses of all instruction words calling an input function.
+    pub fn get_input_call_sites(&self) -> BTreeSet<Address> {
+        self.iword_info
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|(_, info)| info.calls_input())
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
     pub fn update_iword_info(&mut self, iword_info: BTreeMap<Address, IWordInfo>) {
         self.iword_info.as_mut().unwrap().extend(iword_info);
     }
@@ -203,6 +341,62 @@ impl BDAState {
         self.ranges.extend(ranges);
     }
 
+    /// Restricts the analysis to the procedures at [functions] and their callees.
+    pub fn set_scope(&mut self, functions: Vec<Address>) {
+        self.scope = Some(functions);
+    }
+
+    pub(crate) fn take_scope(&mut self) -> Option<Vec<Address>> {
+        self.scope.take()
+    }
+
+    pub fn set_library_files(&mut self, library_files: BTreeSet<u32>) {
+        self.library_files = library_files;
+    }
+
+    pub fn set_procedure_patterns(&mut self, patterns: ProcedurePatterns) {
+        self.procedure_patterns = Some(patterns);
+    }
+
+    pub(crate) fn take_procedure_patterns(&mut self) -> Option<ProcedurePatterns> {
+        self.procedure_patterns.take()
+    }
+
+    /// Keeps the memory op sequences after the posterior analysis, if [keep] is set.
+    /// Otherwise they are freed once the dependencies are calculated.
+    pub fn set_keep_moses(&mut self, keep: bool) {
+        self.keep_moses = keep;
+    }
+
+    pub fn keeps_moses(&self) -> bool {
+        self.keep_moses
+    }
+
+    /// Ends the run after [max_paths] interpreted paths instead of the timeout.
+    pub fn set_max_paths(&mut self, max_paths: usize) {
+        self.max_paths = Some(max_paths);
+    }
+
+    pub(crate) fn get_library_files(&self) -> &BTreeSet<u32> {
+        &self.library_files
+    }
+
+    pub(crate) fn set_min_confidence(&mut self, min_confidence: f64) {
+        self.min_confidence = min_confidence;
+    }
+
+    pub(crate) fn get_min_confidence(&self) -> f64 {
+        self.min_confidence
+    }
+
+    pub(crate) fn set_call_string_len(&mut self, len: usize) {
+        self.call_string_len = len;
+    }
+
+    pub(crate) fn get_call_string_len(&self) -> usize {
+        self.call_string_len
+    }
+
     pub(crate) fn get_ranges(&self) -> &Vec<RangeInclusive<Address>> {
         &self.ranges
     }
@@ -0,0 +1,121 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Display,
+};
+
+use helper::set_map::SetMap;
+use rzil_abstr::interpreter::DataFlow;
+
+use crate::flow_graphs::Address;
+
+/// A chain of instructions input data flows along.
+/// It starts at the call to an input function and ends at the call to a sink.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FlowChain {
+    /// Instruction addresses, from the input call to the sink call.
+    chain: Vec<Address>,
+    /// The argument of the sink, which receives the input data.
+    sink_arg: usize,
+}
+
+impl FlowChain {
+    pub fn get_chain(&self) -> &Vec<Address> {
+        &self.chain
+    }
+
+    pub fn get_sink_arg(&self) -> usize {
+        self.sink_arg
+    }
+}
+
+impl Display for FlowChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chain = self
+            .chain
+            .iter()
+            .map(|a| format!("{:#x}", a))
+            .collect::<Vec<String>>()
+            .join(" -> ");
+        write!(f, "[{}] (arg {})", chain, self.sink_arg)
+    }
+}
+
+/// Answers which input data reaches which sink arguments.
+/// Input data flows along the data flows captured during interpretation
+/// and from memory writes to the reads depending on them.
+pub struct TaintQuery {
+    /// Instruction -> instructions the input data flows to.
+    flows: SetMap<Address, Address>,
+    /// Instruction -> (sink call, argument) the input data is passed to.
+    sink_flows: SetMap<Address, (Address, usize)>,
+}
+
+impl TaintQuery {
+    /// Builds the flow graph from the interpreter [data_flows] and the
+    /// memory dependencies [dep] (read -> defining writes).
+    pub fn new(data_flows: &BTreeSet<DataFlow>, dep: &SetMap<Address, Address>) -> TaintQuery {
+        let mut flows = SetMap::<Address, Address>::new();
+        let mut sink_flows = SetMap::<Address, (Address, usize)>::new();
+        for df in data_flows.iter() {
+            if let Some(arg) = df.get_sink_arg() {
+                sink_flows.insert(df.get_from(), (df.get_to(), arg));
+            } else {
+                flows.insert(df.get_from(), df.get_to());
+            }
+        }
+        for (read, writes) in dep.iter() {
+            for write in writes.iter() {
+                flows.insert(*write, *read);
+            }
+        }
+        TaintQuery { flows, sink_flows }
+    }
+
+    /// Returns the shortest flow chain for every pair of input call in [sources]
+    /// and sink argument in [sinks] the input data reaches.
+    /// [sinks] maps the sink call sites to the checked argument positions.
+    pub fn query(
+        &self,
+        sources: &BTreeSet<Address>,
+        sinks: &BTreeMap<Address, BTreeSet<usize>>,
+    ) -> BTreeSet<FlowChain> {
+        let mut chains = BTreeSet::<FlowChain>::new();
+        for source in sources.iter() {
+            // Breadth first search, so the first chain found to a sink argument is the shortest.
+            let mut reached = BTreeSet::<(Address, usize)>::new();
+            let mut parents = BTreeMap::<Address, Address>::new();
+            let mut work_list = VecDeque::<Address>::from([*source]);
+            while let Some(insn) = work_list.pop_front() {
+                for (sink, arg) in self.sink_flows.set_iter(&insn).into_iter().flatten() {
+                    if !sinks.get(sink).is_some_and(|args| args.contains(arg))
+                        || !reached.insert((*sink, *arg))
+                    {
+                        continue;
+                    }
+                    let mut chain = Vec::from([*sink, insn]);
+                    let mut cur = insn;
+                    while let Some(parent) = parents.get(&cur) {
+                        chain.push(*parent);
+                        cur = *parent;
+                    }
+                    chain.reverse();
+                    chains.insert(FlowChain {
+                        chain,
+                        sink_arg: *arg,
+                    });
+                }
+                for succ in self.flows.set_iter(&insn).into_iter().flatten() {
+                    if *succ == *source || parents.contains_key(succ) {
+                        continue;
+                    }
+                    parents.insert(*succ, insn);
+                    work_list.push_back(*succ);
+                }
+            }
+        }
+        chains
+    }
+}
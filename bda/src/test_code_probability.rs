@@ -0,0 +1,144 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use stochfuzz::disassembly::{CodeRegion, Evidence, ProbDisassembly};
+
+    use crate::{
+        cfg::{CFGNodeData, InsnNodeType, CFG},
+        code_probability::{add_likely_code, get_node_code_probability, reject_unlikely_code},
+        flow_graphs::{NodeId, INVALID_NODE_ID},
+    };
+
+    const BASE: u64 = 0x1000;
+
+    // push rbp; mov rbp, rsp; pop rbp; ret; followed by invalid bytes.
+    fn get_disasm() -> ProbDisassembly {
+        let bytes = vec![0x55, 0x48, 0x89, 0xe5, 0x5d, 0xc3, 0xff, 0xff];
+        let mut disasm = ProbDisassembly::new(vec![CodeRegion::new(BASE, bytes)]);
+        disasm.add_evidence(BASE, Evidence::Code);
+        disasm.analyse(8);
+        disasm
+    }
+
+    // 0x1000 -> 0x1001 -> 0x1004 -> 0x1005
+    //       \
+    //        +-> 0x1002 -> 0x1006
+    //                  \
+    //                   +-> 0x2000
+    fn get_cfg_with_occluded_branch() -> CFG {
+        let mut cfg = CFG::new();
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        {
+        let n0 = (NodeId::new(0, 0, 0x1000), CFGNodeData::new_test_single(0x1000, InsnNodeType::NormalEntry, NodeId::new(0, 0, 0x1002), NodeId::new(0, 0, 0x1001)));
+        let n1 = (NodeId::new(0, 0, 0x1001), CFGNodeData::new_test_single(0x1001, InsnNodeType::Normal, INVALID_NODE_ID, NodeId::new(0, 0, 0x1004)));
+        let n4 = (NodeId::new(0, 0, 0x1004), CFGNodeData::new_test_single(0x1004, InsnNodeType::Normal, INVALID_NODE_ID, NodeId::new(0, 0, 0x1005)));
+        let n5 = (NodeId::new(0, 0, 0x1005), CFGNodeData::new_test_single(0x1005, InsnNodeType::Return, INVALID_NODE_ID, INVALID_NODE_ID));
+        let n2 = (NodeId::new(0, 0, 0x1002), CFGNodeData::new_test_single(0x1002, InsnNodeType::Jump, NodeId::new(0, 0, 0x2000), NodeId::new(0, 0, 0x1006)));
+        let n6 = (NodeId::new(0, 0, 0x1006), CFGNodeData::new_test_single(0x1006, InsnNodeType::Return, INVALID_NODE_ID, INVALID_NODE_ID));
+        let n20 = (NodeId::new(0, 0, 0x2000), CFGNodeData::new_test_single(0x2000, InsnNodeType::Return, INVALID_NODE_ID, INVALID_NODE_ID));
+        cfg.add_edge(n0.clone(), n1.clone());
+        cfg.add_edge(n1, n4.clone());
+        cfg.add_edge(n4, n5);
+        cfg.add_edge(n0, n2.clone());
+        cfg.add_edge(n2.clone(), n6);
+        cfg.add_edge(n2, n20);
+        }
+        cfg
+    }
+
+    #[test]
+    fn test_node_code_probability() {
+        let disasm = get_disasm();
+        let cfg = get_cfg_with_occluded_branch();
+        for addr in [0x1000, 0x1001, 0x1004, 0x1005] {
+            assert_eq!(
+                get_node_code_probability(&cfg, &NodeId::new(0, 0, addr), &disasm),
+                Some(1.0)
+            );
+        }
+        for addr in [0x1002, 0x1006] {
+            assert_eq!(
+                get_node_code_probability(&cfg, &NodeId::new(0, 0, addr), &disasm),
+                Some(0.0)
+            );
+        }
+        assert_eq!(
+            get_node_code_probability(&cfg, &NodeId::new(0, 0, 0x2000), &disasm),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reject_unlikely_code() {
+        let disasm = get_disasm();
+        let mut cfg = get_cfg_with_occluded_branch();
+        assert_eq!(reject_unlikely_code(&mut cfg, &disasm, 0.0), 0);
+        assert_eq!(cfg.graph.node_count(), 7);
+
+        // 0x2000 is not rejected itself, but unreachable afterwards.
+        assert_eq!(reject_unlikely_code(&mut cfg, &disasm, 0.5), 3);
+        assert_eq!(cfg.graph.node_count(), 4);
+        assert_eq!(cfg.nodes_meta.len(), 4);
+        for addr in [0x1000, 0x1001, 0x1004, 0x1005] {
+            assert!(cfg.graph.contains_node(NodeId::new(0, 0, addr)));
+        }
+        assert_eq!(cfg.get_entry(), NodeId::new(0, 0, 0x1000));
+        // The jump target of the entry was removed with it.
+        let entry_insn = cfg.get_nodes_meta(&cfg.get_entry()).insns.get(0).unwrap();
+        assert!(entry_insn.orig_jump_targets.is_empty());
+        assert_eq!(entry_insn.orig_next, NodeId::new(0, 0, 0x1001));
+    }
+
+    // A CFG Rizin didn't follow past the entry.
+    fn get_cut_off_cfg() -> CFG {
+        let mut cfg = CFG::new();
+        let entry = NodeId::new(0, 0, 0x1000);
+        cfg.add_node(
+            entry,
+            CFGNodeData::new_test_single(
+                0x1000,
+                InsnNodeType::NormalEntry,
+                INVALID_NODE_ID,
+                NodeId::new(0, 0, 0x1001),
+            ),
+        );
+        cfg
+    }
+
+    #[test]
+    fn test_add_likely_code() {
+        let disasm = get_disasm();
+        let mut cfg = get_cut_off_cfg();
+        assert_eq!(add_likely_code(&mut cfg, &disasm, 0.5, &BTreeSet::new()), 3);
+        assert_eq!(cfg.graph.node_count(), 4);
+        for (from, to) in [(0x1000, 0x1001), (0x1001, 0x1004), (0x1004, 0x1005)] {
+            assert!(cfg
+                .graph
+                .contains_edge(NodeId::new(0, 0, from), NodeId::new(0, 0, to)));
+        }
+        let ret = cfg.get_nodes_meta(&NodeId::new(0, 0, 0x1005));
+        assert!(ret.insns.get(0).unwrap().itype.is_return());
+        assert_eq!(cfg.get_entry(), NodeId::new(0, 0, 0x1000));
+
+        // Nothing is cut off anymore.
+        assert_eq!(add_likely_code(&mut cfg, &disasm, 0.5, &BTreeSet::new()), 0);
+
+        // Other procedures are not added.
+        let mut cfg = get_cut_off_cfg();
+        let procedures = BTreeSet::from([0x1004]);
+        assert_eq!(add_likely_code(&mut cfg, &disasm, 0.5, &procedures), 1);
+        assert!(!cfg.graph.contains_node(NodeId::new(0, 0, 0x1004)));
+
+        // Data is not added.
+        let mut cfg = get_cut_off_cfg();
+        let mut disasm = get_disasm();
+        disasm.add_evidence(0x1001, Evidence::Data);
+        disasm.analyse(8);
+        assert_eq!(add_likely_code(&mut cfg, &disasm, 0.5, &BTreeSet::new()), 0);
+        assert_eq!(cfg.graph.node_count(), 1);
+    }
+}
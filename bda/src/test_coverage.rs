@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use crate::{
+        coverage::HitCounts,
+        test_graphs::{get_paper_example_icfg, FOO_ADDR, GEE_ADDR, MAIN_ADDR},
+    };
+
+    #[test]
+    fn test_hit_counts() {
+        let mut hits = HitCounts::new();
+        hits.add(
+            BTreeMap::from([(0x10, 1), (0x14, 2)]),
+            BTreeMap::from([((0x10, 0x14), 1), ((0x14, 0x14), 1)]),
+        );
+        hits.add(
+            BTreeMap::from([(0x14, 1), (0x18, 1)]),
+            BTreeMap::from([((0x14, 0x18), 1)]),
+        );
+        assert_eq!(hits.get_iword_hits(&0x10), 1);
+        assert_eq!(hits.get_iword_hits(&0x14), 3);
+        assert_eq!(hits.get_iword_hits(&0x18), 1);
+        assert_eq!(hits.get_iword_hits(&0x1c), 0);
+        assert_eq!(hits.get_edge_hits(&0x10, &0x14), 1);
+        assert_eq!(hits.get_edge_hits(&0x14, &0x18), 1);
+        assert_eq!(hits.get_edge_hits(&0x18, &0x14), 0);
+        assert_eq!(hits.iwords().len(), 3);
+        assert_eq!(hits.edges().len(), 3);
+    }
+
+    #[test]
+    fn test_unreached_procedures() {
+        let (icfg, _) = get_paper_example_icfg();
+        let mut hits = HitCounts::new();
+        assert_eq!(
+            hits.get_unreached_procedures(&icfg),
+            BTreeSet::from([GEE_ADDR, FOO_ADDR, MAIN_ADDR])
+        );
+        hits.add(BTreeMap::from([(MAIN_ADDR, 1)]), BTreeMap::new());
+        assert_eq!(
+            hits.get_unreached_procedures(&icfg),
+            BTreeSet::from([GEE_ADDR, FOO_ADDR])
+        );
+    }
+
+    #[test]
+    fn test_drcov() {
+        let mut hits = HitCounts::new();
+        hits.add(
+            BTreeMap::from([(0x8, 1), (0x1010, 4), (0x1020, 1), (0x1_0000_2000, 1)]),
+            BTreeMap::new(),
+        );
+        let mut out = Vec::<u8>::new();
+        hits.write_drcov(&mut out, "/bin/test", 0x1000).unwrap();
+        let header = "DRCOV VERSION: 2\n\
+            DRCOV FLAVOR: probana\n\
+            Module Table: version 2, count 1\n\
+            Columns: id, base, end, entry, checksum, timestamp, path\n \
+            0, 0x0000000000001000, 0x0000000000001021, 0x0000000000000000, 0x00000000, 0x00000000, /bin/test\n\
+            BB Table: 2 bbs\n";
+        assert!(out.starts_with(header.as_bytes()));
+        let bbs = &out[header.len()..];
+        assert_eq!(
+            bbs,
+            &[
+                0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x01, 0x00,
+                0x00, 0x00
+            ]
+        );
+    }
+}
@@ -4,7 +4,10 @@
 #[cfg(test)]
 mod tests {
 
-    use std::{collections::HashSet, sync::RwLock};
+    use std::{
+        collections::{BTreeSet, HashSet},
+        sync::RwLock,
+    };
 
     use petgraph::dot::Dot;
 
@@ -162,6 +165,171 @@ mod tests {
         assert_p_weight(&icfg, &NodeId::new(0, 0, GEE_ADDR), 2, wmap);
     }
 
+    #[test]
+    fn test_icfg_weight_calc_approx() {
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        let wmap = &wmap;
+        wmap.write().unwrap().set_approximate(true);
+        icfg.resolve_loops(1);
+        assert_p_weight(&icfg, &NodeId::new(0, 0, MAIN_ADDR), 6, wmap);
+        assert_p_weight(&icfg, &NodeId::new(0, 0, FOO_ADDR), 4, wmap);
+        assert_p_weight(&icfg, &NodeId::new(0, 0, GEE_ADDR), 2, wmap);
+    }
+
+    #[test]
+    fn test_icfg_scope() {
+        let main = NodeId::new(0, 0, MAIN_ADDR);
+        let foo = NodeId::new(0, 0, FOO_ADDR);
+        let gee = NodeId::new(0, 0, GEE_ADDR);
+
+        let (icfg, _) = get_paper_example_icfg();
+        assert_eq!(icfg.get_scope(&[MAIN_ADDR], 0), BTreeSet::from([main]));
+        assert_eq!(
+            icfg.get_scope(&[MAIN_ADDR], 1),
+            BTreeSet::from([main, foo, gee])
+        );
+        assert_eq!(icfg.get_scope(&[FOO_ADDR], 0), BTreeSet::from([foo]));
+        assert_eq!(icfg.get_scope(&[FOO_ADDR], 5), BTreeSet::from([foo, gee]));
+        assert_eq!(
+            icfg.get_scope(&[GEE_ADDR, FOO_ADDR], 0),
+            BTreeSet::from([foo, gee])
+        );
+        assert_eq!(
+            icfg.get_scope_ranges(&BTreeSet::from([main, gee])),
+            vec![GEE_ADDR..=4, MAIN_ADDR..=15]
+        );
+
+        // Procedures which are not executed are always in scope.
+        icfg.get_procedure(&gee).write().unwrap().set_free(true);
+        assert_eq!(icfg.get_scope(&[MAIN_ADDR], 0), BTreeSet::from([main, gee]));
+    }
+
+    #[test]
+    fn test_link_import_stub() {
+        let stub = NodeId::new(0, 0, 0x1030);
+        let puts = NodeId::new(0, 0, 0x7f0000080000);
+        let main = NodeId::new(0, 0, MAIN_ADDR);
+        let mut stub_cfg = CFG::new();
+        stub_cfg.add_node(
+            stub,
+            CFGNodeData::new_test_single(
+                stub.address,
+                InsnNodeType::Jump | InsnNodeType::Entry,
+                INVALID_NODE_ID,
+                INVALID_NODE_ID,
+            ),
+        );
+        let mut icfg = ICFG::new();
+        icfg.add_edge_test(
+            (
+                main,
+                Procedure::new(Some(get_main_cfg()), false, false, false),
+            ),
+            (stub, Procedure::new(Some(stub_cfg), false, false, false)),
+        );
+
+        // Unknown target without procedure.
+        assert!(!icfg.link_import_stub(&stub, (puts, None)));
+        let puts_proc = Procedure::new(Some(get_gee_cfg()), false, false, false);
+        assert!(icfg.link_import_stub(&stub, (puts, Some(puts_proc))));
+        assert!(icfg.has_procedure(&puts));
+        assert!(icfg.has_edge(stub, puts));
+        // Already linked.
+        assert!(!icfg.link_import_stub(&stub, (puts, None)));
+
+        let stub_proc = icfg.get_procedure(&stub).read().unwrap();
+        let node = stub_proc.get_cfg().get_nodes_meta(&stub);
+        assert!(node.node_type.is_tail_call());
+        let insn = node.insns.get(0).unwrap();
+        assert!(insn.itype.is_entry());
+        assert!(insn.orig_jump_targets.contains(&puts));
+        drop(stub_proc);
+
+        // Procedures which return are no stubs.
+        assert!(!icfg.link_import_stub(&main, (puts, None)));
+    }
+
+    #[test]
+    fn test_icfg_weight_cache() {
+        let cache = std::env::temp_dir().join(format!("probana_weights_{}", std::process::id()));
+        let main = NodeId::new(0, 0, MAIN_ADDR);
+        let foo = NodeId::new(0, 0, FOO_ADDR);
+        let gee = NodeId::new(0, 0, GEE_ADDR);
+
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        let wmap = &wmap;
+        icfg.resolve_loops(1);
+        assert_p_weight(&icfg, &main, 6, wmap);
+        assert_eq!(wmap.write().unwrap().save_cache(&cache, &icfg).unwrap(), 3);
+
+        // Unchanged iCFG. Weights are restored only when requested.
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        let wmap = &wmap;
+        icfg.resolve_loops(1);
+        let fingerprints = icfg.get_fingerprints();
+        assert_eq!(
+            wmap.write()
+                .unwrap()
+                .load_cache(&cache, &fingerprints)
+                .unwrap(),
+            3
+        );
+        assert_p_weight(&icfg, &main, 6, wmap);
+        assert_eq!(wmap.read().unwrap().num_cached_procedures(), 2);
+        assert_p_weight(&icfg, &foo, 4, wmap);
+        assert_p_weight(&icfg, &gee, 2, wmap);
+        assert_eq!(wmap.read().unwrap().num_cached_procedures(), 0);
+
+        // A changed procedure invalidates itself and all its callers.
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        let wmap = &wmap;
+        icfg.resolve_loops(1);
+        icfg.get_procedure(&gee).write().unwrap().set_free(true);
+        let fingerprints = icfg.get_fingerprints();
+        assert_eq!(
+            wmap.write()
+                .unwrap()
+                .load_cache(&cache, &fingerprints)
+                .unwrap(),
+            0
+        );
+
+        // The whole digest is compared. A fingerprint which only shares its
+        // first 64 bits is rejected.
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        let wmap = &wmap;
+        icfg.resolve_loops(1);
+        let fingerprints = icfg.get_fingerprints();
+        let main_fp = &fingerprints[&main];
+        assert_eq!(main_fp.len(), 64);
+        let forged_fp = format!("{}{}", &main_fp[..16], "f".repeat(48));
+        let content = std::fs::read_to_string(&cache).unwrap();
+        std::fs::write(&cache, content.replace(main_fp.as_str(), &forged_fp)).unwrap();
+        assert_eq!(
+            wmap.write()
+                .unwrap()
+                .load_cache(&cache, &fingerprints)
+                .unwrap(),
+            2
+        );
+
+        // Rounded weights of the approximate mode are not loaded into an exact map.
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        let wmap = &wmap;
+        wmap.write().unwrap().set_approximate(true);
+        icfg.resolve_loops(1);
+        assert_p_weight(&icfg, &main, 6, wmap);
+        assert_eq!(wmap.write().unwrap().save_cache(&cache, &icfg).unwrap(), 3);
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        icfg.resolve_loops(1);
+        assert!(wmap
+            .write()
+            .unwrap()
+            .load_cache(&cache, &icfg.get_fingerprints())
+            .is_err());
+        std::fs::remove_file(&cache).unwrap();
+    }
+
     #[test]
     fn test_icfg_no_procedure_duplicates() {
         let (mut icfg, _wmap) = get_paper_example_icfg();
@@ -0,0 +1,357 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use helper::rng::new_rng;
+    use petgraph::Direction::Outgoing;
+
+    use crate::{
+        cfg::{InsnNodeType, CFG},
+        flow_graphs::{Address, FlowGraphOperations, NodeId},
+        graph_builder::{cfg_from_desc, icfg_from_desc, random_graph_desc, GraphDesc, ProcDesc},
+        icfg::ICFG,
+        path_sampler::{sample_path, Path},
+        test_graphs::{
+            get_cfg_simple_loop, get_foo_cfg, get_gee_cfg, FOO_ADDR, GEE_ADDR, MAIN_ADDR,
+        },
+        weight::WeightMap,
+    };
+
+    /// Number of random graphs each property is checked on.
+    const NUM_RANDOM_GRAPHS: u64 = 64;
+
+    /// Number of paths sampled from each random graph.
+    const NUM_RANDOM_SAMPLES: usize = 1000;
+
+    const PAPER_EXAMPLE_DESC: &str = "
+        proc 0x0    # gee()
+        0 N.E -> 1; 1 N -> 2, 3; 2 N -> 4; 3 N -> 4; 4 R
+        proc 0x6    # foo()
+        6 N.E -> 7; 7 c(0) -> 8; 8 N -> 9, 1; 9 N -> 1; 1 R
+        proc 0xb    # main()
+        11 N.E -> 12; 12 N -> 13, 14; 13 c(0) -> 15; 14 c(6) -> 15; 15 R
+    ";
+
+    fn assert_cfg_eq(built: &CFG, fixture: &CFG) {
+        assert_eq!(built.diff(fixture), None);
+        assert_eq!(built.get_entry(), fixture.get_entry());
+        for (nid, meta) in fixture.nodes_meta.iter() {
+            assert_eq!(
+                built.get_nodes_meta(nid).insns,
+                meta.insns,
+                "Instructions of {} differ",
+                nid
+            );
+        }
+    }
+
+    /// Counts the paths from [nid] to a Return or Exit in the acyclic [cfg].
+    fn count_node_paths(
+        icfg: &ICFG,
+        cfg: &CFG,
+        nid: NodeId,
+        counts: &mut HashMap<NodeId, usize>,
+    ) -> usize {
+        if let Some(cnt) = counts.get(&nid) {
+            return *cnt;
+        }
+        let meta = cfg.get_nodes_meta(&nid);
+        let cnt = if meta.node_type.is_return() || meta.node_type.is_exit() {
+            1
+        } else {
+            let succ_cnt: usize = cfg
+                .get_graph()
+                .neighbors_directed(nid, Outgoing)
+                .map(|n| count_node_paths(icfg, cfg, n, counts))
+                .sum();
+            let callee_cnt: usize = meta
+                .insns
+                .iter()
+                .flat_map(|i| i.call_targets.iter())
+                .map(|ct| count_paths(icfg, ct))
+                .product();
+            succ_cnt * callee_cnt
+        };
+        counts.insert(nid, cnt);
+        cnt
+    }
+
+    /// Counts the paths through the procedure [pid] of the acyclic [icfg].
+    fn count_paths(icfg: &ICFG, pid: &NodeId) -> usize {
+        let proc = icfg.get_procedure(pid).read().unwrap();
+        let cfg = proc.get_cfg();
+        count_node_paths(icfg, cfg, cfg.get_entry(), &mut HashMap::new())
+    }
+
+    /// Checks the sampled [path] follows the edges, calls and returns described in [desc].
+    /// It must start at the entry of procedure [entry] and end in an Exit or its Return.
+    /// Returns the first invalid step otherwise.
+    fn check_path(desc: &GraphDesc, entry: Address, path: Path) -> Result<(), String> {
+        let path_str = path.to_string();
+        let mut ipath = path.to_addr_path();
+        let mut proc = desc.get_proc(entry).unwrap();
+        // The callers and the address execution continues at after the call.
+        let mut call_stack: Vec<(&ProcDesc, Address)> = Vec::new();
+        let Some((mut addr, mut info)) = ipath.next() else {
+            return Err("Empty path".to_owned());
+        };
+        if addr != entry {
+            return Err(format!("Path starts at {:#x}: {}", addr, path_str));
+        }
+        while let Some((next, next_info)) = ipath.next() {
+            let Some(node) = proc.get_node(addr) else {
+                return Err(format!(
+                    "{:#x} is not in {:#x}: {}",
+                    addr, proc.addr, path_str
+                ));
+            };
+            if node.itype.is_call() {
+                if !node.call_targets.contains(&next) {
+                    return Err(format!(
+                        "{:#x} doesn't call {:#x}: {}",
+                        addr, next, path_str
+                    ));
+                }
+                call_stack.push((proc, node.get_next().unwrap()));
+                proc = desc.get_proc(next).unwrap();
+            } else if node.itype.is_return() {
+                let Some((caller, ret_addr)) = call_stack.pop() else {
+                    return Err(format!("Path continues after return: {}", path_str));
+                };
+                if next != ret_addr {
+                    return Err(format!("{:#x} returns to {:#x}: {}", addr, next, path_str));
+                }
+                proc = caller;
+            } else if !node.succs.contains(&next) {
+                return Err(format!("No edge {:#x} -> {:#x}: {}", addr, next, path_str));
+            }
+            (addr, info) = (next, next_info);
+        }
+        if !info.is_exit() && !(info.is_return() && call_stack.is_empty()) {
+            return Err(format!("Path ends at {:#x}: {}", addr, path_str));
+        }
+        Ok(())
+    }
+
+    fn has_back_edge(desc: &GraphDesc) -> bool {
+        desc.procs
+            .iter()
+            .flat_map(|p| p.nodes.iter())
+            .any(|n| n.succs.iter().any(|s| *s <= n.addr))
+    }
+
+    #[test]
+    fn test_parse_desc() {
+        let desc = GraphDesc::parse(
+            "
+            # Implicit first procedure.
+            0x10 N.E -> 0x11
+            0x11 c(0x20, 0x30) -> 0x12; 0x12 C.J -> 0x13, 0x14, 0x15
+            0x13 R; 0x14 X; 0x15 R
+            proc 0x20 malloc
+            proc 0x30 input unmapped free excluded
+            ",
+        );
+        assert_eq!(desc.procs.len(), 3);
+        let proc = desc.get_proc(0x10).unwrap();
+        assert_eq!(proc.nodes.len(), 6);
+        assert_eq!(proc.get_entry().unwrap().addr, 0x10);
+        let call = proc.get_node(0x11).unwrap();
+        assert_eq!(call.itype, InsnNodeType::Call);
+        assert_eq!(call.call_targets, vec![0x20, 0x30]);
+        assert_eq!(call.get_next(), Some(0x12));
+        assert!(call.get_jump_targets().is_empty());
+        let jump = proc.get_node(0x12).unwrap();
+        assert_eq!(jump.itype, InsnNodeType::Cond | InsnNodeType::Jump);
+        assert_eq!(jump.get_jump_targets(), vec![0x13, 0x15]);
+        assert_eq!(jump.get_next(), Some(0x14));
+        assert_eq!(
+            proc.get_node(0x14).unwrap().get_cfg_node().node_type,
+            InsnNodeType::Exit
+        );
+
+        let malloc = desc.get_proc(0x20).unwrap();
+        assert!(malloc.is_malloc && malloc.nodes.is_empty());
+        let input = desc.get_proc(0x30).unwrap();
+        assert!(input.is_input && input.is_unmapped && input.is_free && input.is_excluded);
+        assert!(!input.is_malloc);
+
+        let icfg = desc.build_icfg();
+        assert_eq!(icfg.num_procedures(), 3);
+        assert!(icfg.is_malloc(&NodeId::from(0x20)));
+        assert!(icfg.is_input(&NodeId::from(0x30)));
+        assert!(icfg
+            .get_graph()
+            .contains_edge(NodeId::from(0x10), NodeId::from(0x20)));
+        assert!(icfg
+            .get_graph()
+            .contains_edge(NodeId::from(0x10), NodeId::from(0x30)));
+    }
+
+    #[test]
+    #[should_panic = "Successor 0x5 of 0x1 is not a node of procedure 0x0."]
+    fn test_parse_desc_unknown_successor() {
+        cfg_from_desc("0 N.E -> 1; 1 N -> 5; 2 R");
+    }
+
+    #[test]
+    #[should_panic = "Procedure 0x0 has 2 entries, expected one."]
+    fn test_parse_desc_two_entries() {
+        cfg_from_desc("proc 0; 0 N.E -> 1; 1 N.E -> 2; 2 R");
+    }
+
+    #[test]
+    #[should_panic = "Call target 0x10 of 0x1 is no procedure."]
+    fn test_parse_desc_unknown_call_target() {
+        icfg_from_desc("0 N.E -> 1; 1 c(0x10) -> 2; 2 R");
+    }
+
+    #[test]
+    fn test_desc_matches_fixtures() {
+        assert_cfg_eq(
+            &cfg_from_desc("0 N.E -> 1; 1 N -> 2, 3; 2 N -> 4; 3 N -> 4; 4 R"),
+            &get_gee_cfg(),
+        );
+        assert_cfg_eq(
+            &cfg_from_desc("6 N.E -> 7; 7 c(0) -> 8; 8 N -> 9, 1; 9 N -> 1; 1 R"),
+            &get_foo_cfg(),
+        );
+        assert_cfg_eq(
+            &cfg_from_desc("0 N.E -> 1; 1 N -> 2; 2 N -> 3, 1; 3 R"),
+            &get_cfg_simple_loop(),
+        );
+    }
+
+    #[test]
+    fn test_desc_paper_example_weight() {
+        let mut icfg = icfg_from_desc(PAPER_EXAMPLE_DESC);
+        let wmap = &WeightMap::new();
+        icfg.resolve_loops(1);
+        for (addr, weight) in [(MAIN_ADDR, 6), (FOO_ADDR, 4), (GEE_ADDR, 2)] {
+            assert!(icfg
+                .get_procedure(&NodeId::from(addr))
+                .write()
+                .unwrap()
+                .get_cfg_mut()
+                .weight_eq_usize(weight, icfg.get_procedures(), wmap));
+        }
+    }
+
+    #[test]
+    fn test_desc_display_roundtrip() {
+        let desc = GraphDesc::parse(PAPER_EXAMPLE_DESC);
+        assert_eq!(GraphDesc::parse(&desc.to_string()), desc);
+        for seed in 0..NUM_RANDOM_GRAPHS {
+            let desc = random_graph_desc(seed);
+            assert_eq!(
+                GraphDesc::parse(&desc.to_string()),
+                desc,
+                "Seed {}:\n{}",
+                seed,
+                desc
+            );
+        }
+    }
+
+    #[test]
+    fn test_random_graphs_deterministic() {
+        assert_eq!(random_graph_desc(0x5eed), random_graph_desc(0x5eed));
+        let descs: HashSet<String> = (0..NUM_RANDOM_GRAPHS)
+            .map(|seed| random_graph_desc(seed).to_string())
+            .collect();
+        assert!(descs.len() > 1, "All random graphs are equal.");
+    }
+
+    #[test]
+    fn test_random_graphs_acyclic() {
+        let mut num_loops = 0;
+        for seed in 0..NUM_RANDOM_GRAPHS {
+            let desc = random_graph_desc(seed);
+            if has_back_edge(&desc) {
+                num_loops += 1;
+            }
+            let mut icfg = desc.build_icfg();
+            icfg.resolve_loops(1);
+            assert!(icfg.is_acyclic(), "Seed {}:\n{}", seed, desc);
+            for proc in desc.procs.iter() {
+                let p = icfg.get_procedure(&NodeId::from(proc.addr)).read().unwrap();
+                let cfg = p.get_cfg();
+                assert!(cfg.is_acyclic(), "Seed {}:\n{}", seed, desc);
+                for node in proc.nodes.iter() {
+                    assert!(
+                        cfg.get_graph().contains_node(node.get_nid()),
+                        "Seed {}: {:#x} was removed.\n{}",
+                        seed,
+                        node.addr,
+                        desc
+                    );
+                }
+            }
+        }
+        assert!(num_loops > 0, "No random graph has a loop.");
+    }
+
+    #[test]
+    fn test_random_graphs_weight_is_path_count() {
+        for seed in 0..NUM_RANDOM_GRAPHS {
+            let desc = random_graph_desc(seed);
+            let mut icfg = desc.build_icfg();
+            let wmap = &WeightMap::new();
+            icfg.resolve_loops(1);
+            for proc in desc.procs.iter() {
+                let pid = NodeId::from(proc.addr);
+                let num_paths = count_paths(&icfg, &pid);
+                assert!(num_paths > 0, "Seed {}: No paths.\n{}", seed, desc);
+                assert!(
+                    icfg.get_procedure(&pid)
+                        .write()
+                        .unwrap()
+                        .get_cfg_mut()
+                        .weight_eq_usize(num_paths, icfg.get_procedures(), wmap),
+                    "Seed {}: Weight of {:#x} != {} paths.\n{}",
+                    seed,
+                    proc.addr,
+                    num_paths,
+                    desc
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_graphs_sampled_paths_valid() {
+        for seed in 0..NUM_RANDOM_GRAPHS {
+            let desc = random_graph_desc(seed);
+            let entry = desc.procs.first().unwrap().addr;
+            let mut icfg = desc.build_icfg();
+            let wmap = WeightMap::new();
+            icfg.resolve_loops(1);
+            let num_paths = count_paths(&icfg, &NodeId::from(entry));
+
+            let mut sampled = HashSet::new();
+            let mut rng = new_rng(seed + 1);
+            for _ in 0..NUM_RANDOM_SAMPLES {
+                let path = sample_path(&icfg, entry, &wmap, &Vec::new(), &mut rng);
+                let path_str = path.to_string();
+                if let Err(e) = check_path(&desc, entry, path) {
+                    panic!("Seed {}: {}\n{}", seed, e, desc);
+                }
+                sampled.insert(path_str);
+            }
+            assert!(sampled.len() <= num_paths, "Seed {}:\n{}", seed, desc);
+            if num_paths <= 8 {
+                // Every path has a probability of at least 1/8 to be sampled.
+                assert_eq!(
+                    sampled.len(),
+                    num_paths,
+                    "Seed {}: Not all paths sampled.\n{}",
+                    seed,
+                    desc
+                );
+            }
+        }
+    }
+}
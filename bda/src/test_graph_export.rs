@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        coverage::HitCounts,
+        flow_graphs::{FlowGraphOperations, NodeId},
+        graph_export::{
+            calc_icfg_weights, cfg_to_export_graph, icfg_to_export_graph, ExportFormat,
+        },
+        test_graphs::{get_paper_example_icfg, FOO_ADDR, GEE_ADDR, MAIN_ADDR},
+    };
+
+    fn to_string(export: &crate::graph_export::ExportGraph, format: ExportFormat) -> String {
+        let mut out = Vec::<u8>::new();
+        export.write(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_export_format() {
+        assert_eq!(ExportFormat::from_path("a.dot"), Some(ExportFormat::Dot));
+        assert_eq!(
+            ExportFormat::from_path("/tmp/a.GraphML"),
+            Some(ExportFormat::GraphML)
+        );
+        assert_eq!(ExportFormat::from_path("a.json"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::from_path("a.txt"), None);
+        assert_eq!(ExportFormat::from_path("a"), None);
+    }
+
+    #[test]
+    fn test_export_icfg() {
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        icfg.resolve_loops(1);
+        calc_icfg_weights(&icfg, &wmap);
+        let mut coverage = HitCounts::new();
+        coverage.add(BTreeMap::from([(MAIN_ADDR, 2)]), BTreeMap::new());
+        let export = icfg_to_export_graph(&icfg, &wmap, Some(&coverage));
+
+        let json = to_string(&export, ExportFormat::Json);
+        let main_node = format!(
+            "{{\"id\":\"0:0:{:#x}\",\"address\":\"{:#x}\",\"icfg_clone\":\"0\",\"cfg_clone\":\"0\",\"type\":\"Normal\"",
+            MAIN_ADDR, MAIN_ADDR
+        );
+        assert!(json.contains(&main_node), "{}", json);
+        assert!(
+            json.contains("\"weight\":\"6\",\"covered\":\"1\",\"hits\":\"2\""),
+            "{}",
+            json
+        );
+        assert!(
+            json.contains("\"weight\":\"2\",\"covered\":\"0\",\"hits\":\"0\""),
+            "{}",
+            json
+        );
+        assert!(json.contains(&format!(
+            "{{\"from\":\"0:0:{:#x}\",\"to\":\"0:0:{:#x}\"}}",
+            FOO_ADDR, GEE_ADDR
+        )));
+
+        let dot = to_string(&export, ExportFormat::Dot);
+        assert!(dot.starts_with("digraph \"iCFG\" {"));
+        assert!(dot.contains(&format!(
+            "\"0:0:{:#x}\" -> \"0:0:{:#x}\";",
+            MAIN_ADDR, FOO_ADDR
+        )));
+
+        let graphml = to_string(&export, ExportFormat::GraphML);
+        assert!(graphml.contains(
+            "<key id=\"weight\" for=\"node\" attr.name=\"weight\" attr.type=\"string\"/>"
+        ));
+        assert!(graphml.contains(&format!(
+            "<edge source=\"0:0:{:#x}\" target=\"0:0:{:#x}\"/>",
+            MAIN_ADDR, GEE_ADDR
+        )));
+        assert_eq!(graphml.matches("<node id=").count(), 3);
+    }
+
+    #[test]
+    fn test_export_cfg() {
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        icfg.resolve_loops(1);
+        calc_icfg_weights(&icfg, &wmap);
+        let proc = icfg
+            .get_procedure(&NodeId::new(0, 0, GEE_ADDR))
+            .read()
+            .unwrap();
+        let export = cfg_to_export_graph(proc.get_cfg(), &wmap, None);
+        let json = to_string(&export, ExportFormat::Json);
+        assert!(json.starts_with(&format!("{{\"name\":\"CFG {:#x}\"", GEE_ADDR)));
+        assert!(json.contains("\"weight\":\"2\""), "{}", json);
+        assert!(!json.contains("hits"));
+
+        let (from, to, _) = proc.get_cfg().get_graph().all_edges().next().unwrap();
+        let mut coverage = HitCounts::new();
+        coverage.add(
+            BTreeMap::from([(from.address, 3)]),
+            BTreeMap::from([((from.address, to.address), 3)]),
+        );
+        let export = cfg_to_export_graph(proc.get_cfg(), &wmap, Some(&coverage));
+        let json = to_string(&export, ExportFormat::Json);
+        assert!(json.contains("\"hits\":\"3\"}"), "{}", json);
+        assert!(json.contains("\"hits\":\"0\"}"), "{}", json);
+        assert!(
+            json.contains(&format!(
+                "{{\"from\":\"{}:{}:{:#x}\",\"to\":\"{}:{}:{:#x}\",\"hits\":\"3\"}}",
+                from.icfg_clone_id,
+                from.cfg_clone_id,
+                from.address,
+                to.icfg_clone_id,
+                to.cfg_clone_id,
+                to.address
+            )),
+            "{}",
+            json
+        );
+        let dot = to_string(&export, ExportFormat::Dot);
+        assert!(dot.contains(&format!(
+            "\"{}:{}:{:#x}\" -> \"{}:{}:{:#x}\" [hits=\"3\"];",
+            from.icfg_clone_id,
+            from.cfg_clone_id,
+            from.address,
+            to.icfg_clone_id,
+            to.cfg_clone_id,
+            to.address
+        )));
+        let graphml = to_string(&export, ExportFormat::GraphML);
+        assert!(graphml
+            .contains("<key id=\"e_hits\" for=\"edge\" attr.name=\"hits\" attr.type=\"string\"/>"));
+    }
+}
@@ -5,6 +5,9 @@
 mod tests {
     use std::collections::HashMap;
 
+    use helper::rng::new_rng;
+    use rzil_abstr::interpreter::IWordInfo;
+
     use crate::{
         cfg::Procedure,
         flow_graphs::{Address, FlowGraphOperations, NodeId},
@@ -12,10 +15,10 @@ mod tests {
         path_sampler::{sample_path, Path},
         test_graphs::{
             get_A, get_B, get_C, get_D, get_cfg_linear, get_cfg_simple_loop, get_gee_cfg,
-            get_unset_indirect_call_to_0_cfg, CFG_ENTRY_A, CFG_ENTRY_A_CALL, CFG_ENTRY_B,
-            CFG_ENTRY_B_CALL_1, CFG_ENTRY_B_CALL_2, CFG_ENTRY_C, CFG_ENTRY_D, GEE_ADDR,
-            LINEAR_CFG_ENTRY, SIMPLE_LOOP_ENTRY, UNSET_INDIRECT_CALL_TO_0_CALL,
-            UNSET_INDIRECT_CALL_TO_0_ENTRY,
+            get_paper_example_icfg, get_unset_indirect_call_to_0_cfg, CFG_ENTRY_A,
+            CFG_ENTRY_A_CALL, CFG_ENTRY_B, CFG_ENTRY_B_CALL_1, CFG_ENTRY_B_CALL_2, CFG_ENTRY_C,
+            CFG_ENTRY_D, GEE_ADDR, LINEAR_CFG_ENTRY, MAIN_ADDR, SIMPLE_LOOP_ENTRY,
+            UNSET_INDIRECT_CALL_TO_0_CALL, UNSET_INDIRECT_CALL_TO_0_ENTRY,
         },
         weight::WeightMap,
     };
@@ -64,8 +67,9 @@ mod tests {
         sample_size: usize,
     ) -> HashMap<Path, usize> {
         let mut path_stats = HashMap::<Path, usize>::new();
+        let mut rng = new_rng(0);
         for _ in 0..sample_size {
-            let path = sample_path(&icfg, entry, &wmap, &Vec::new());
+            let path = sample_path(&icfg, entry, &wmap, &Vec::new(), &mut rng);
             let cnt = path_stats.get(&path);
             path_stats.insert(path, if cnt.is_none() { 1 } else { *cnt.unwrap() + 1 });
         }
@@ -94,9 +98,10 @@ mod tests {
         icfg.resolve_loops(1);
 
         let mut path_stats = HashMap::<Path, usize>::new();
+        let mut rng = new_rng(0);
         // Over TEST_SAMPLE_SIZE iterations we should get the same path with a probability of 1.
         for _ in 0..TEST_SAMPLE_SIZE {
-            let path = sample_path(&icfg, LINEAR_CFG_ENTRY, &wmap, &Vec::new());
+            let path = sample_path(&icfg, LINEAR_CFG_ENTRY, &wmap, &Vec::new(), &mut rng);
             let cnt = path_stats.get(&path);
             path_stats.insert(path, if cnt.is_none() { 1 } else { *cnt.unwrap() + 1 });
         }
@@ -162,6 +167,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sample_excluded_procedure() {
+        let (mut icfg, wmap) = get_paper_example_icfg();
+        icfg.get_procedure(&NodeId::from(GEE_ADDR))
+            .write()
+            .unwrap()
+            .set_excluded(true);
+        icfg.resolve_loops(1);
+
+        let path_stats = sample(&icfg, MAIN_ADDR, &wmap, 1000);
+        assert!(!path_stats.is_empty());
+        for (path, _) in path_stats.into_iter() {
+            let mut ipath = path.to_addr_path();
+            while let Some((addr, info)) = ipath.next() {
+                assert_ne!(addr, GEE_ADDR, "Sampled excluded procedure.");
+                // Call sites of gee() in foo() and main().
+                if addr == 7 || addr == 13 {
+                    assert!(info.contains(IWordInfo::CallsUnmapped));
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_sample_simple_loop() {
         let mut icfg = ICFG::new();
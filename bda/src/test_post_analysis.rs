@@ -14,6 +14,7 @@ mod tests {
         bda_binding::{add_procedures_to_icfg, get_graph},
         flow_graphs::Address,
         icfg::ICFG,
+        post_analysis::DepConfidence,
         state::BDAState,
     };
 
@@ -153,6 +154,36 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_post_x86_dep_paper_example_call_strings() {
+        let (core, mut icfg) = get_x86_paper_dep_example();
+        core.lock()
+            .unwrap()
+            .set_conf_val("plugins.bda.post.call_string_len", "1");
+        let mut state = BDAState::new(3, 2, 1, 1);
+        let result = run_bda(core, &mut icfg, &mut state, false);
+        let Some(dip) = result else {
+            panic!("Is none");
+        };
+        let Some(context_deps) = state.context_deps.as_ref() else {
+            panic!("No context dependencies");
+        };
+        // Every context sensitive dependency must be a dependency without context as well.
+        let pairs: BTreeSet<(Address, Address)> =
+            context_deps.iter().map(|cd| cd.get_pair()).collect();
+        assert_eq!(pairs, dip);
+
+        // Reads in the callee happen under the call at 0x8000113.
+        assert!(context_deps
+            .iter()
+            .any(|cd| cd.get_pair() == (0x800009c, 0x8000098)
+                && cd.get_call_string() == &Vec::from([0x8000113])));
+        // Reads in the entry procedure have no call string.
+        assert!(context_deps
+            .iter()
+            .any(|cd| cd.get_pair() == (0x800011f, 0x8000100) && cd.get_call_string().is_empty()));
+    }
+
     #[test]
     /// Run only two paths, get products and check if the post analysis does the inference correctly.
     pub fn test_post_x86_dep_paper_example_inference() {
@@ -205,6 +236,61 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_post_x86_dep_paper_example_confidence() {
+        let (core, mut icfg) = get_x86_paper_dep_example();
+        let mut state = BDAState::new(3, 2, 1, 1);
+        state.set_min_confidence(0.5);
+        let paths = Vec::from([
+            VecDeque::from([
+                0x08000100, 0x08000101, 0x08000104, 0x08000108, 0x0800010f, 0x08000113, 0x08000090,
+                0x08000091, 0x08000094, 0x08000098, 0x0800009c, 0x080000a0, 0x080000a3, 0x080000a8,
+                0x080000ab, 0x080000b1, 0x080000b5, 0x080000b8, 0x080000bc, 0x080000bf, 0x080000c4,
+                0x080000c9, 0x080000cc, 0x080000e0, 0x080000e4, 0x080000e7, 0x080000ea, 0x080000ed,
+                0x080000f1, 0x080000f5, 0x080000f6, 0x08000118, 0x0800011b, 0x0800011f, 0x08000120,
+            ]),
+            VecDeque::from([
+                0x08000100, 0x08000101, 0x08000104, 0x08000108, 0x0800010f, 0x08000113, 0x08000090,
+                0x08000091, 0x08000094, 0x08000098, 0x0800009c, 0x080000a0, 0x080000a3, 0x080000a8,
+                0x080000ab, 0x080000c4, 0x080000c9, 0x080000cc, 0x080000d2, 0x080000d6, 0x080000d8,
+                0x080000db, 0x080000ed, 0x080000f1, 0x080000f5, 0x080000f6, 0x08000118, 0x0800011b,
+                0x0800011f, 0x08000120,
+            ]),
+        ]);
+        let result = testing_bda_on_paths(core, &mut icfg, &mut state, paths);
+        let Some(dip) = result else {
+            panic!("Is none");
+        };
+        print_dip(&dip);
+
+        // Observed on both paths.
+        let conf = state.dep_confidence.get(&(0x800009c, 0x8000098)).unwrap();
+        assert_eq!(conf.get_support(), 2);
+        assert_eq!(conf.get_executed(), 2);
+        assert!(dip.contains(&(0x800009c, 0x8000098)));
+        // Only inferred, never observed on a single path.
+        // It has no support, so the threshold rejects it.
+        let conf = state.dep_confidence.get(&(0x80000d6, 0x80000a0)).unwrap();
+        assert_eq!(conf.get_support(), 0);
+        assert_eq!(conf.get_score(), 0.0);
+        assert!(conf.is_inferred());
+        assert!(!dip.contains(&(0x80000d6, 0x80000a0)));
+        assert!(dip
+            .iter()
+            .all(|dep| state.dep_confidence.get(dep).unwrap().get_score() >= 0.5));
+    }
+
+    #[test]
+    pub fn test_dep_confidence_threshold() {
+        let observed = DepConfidence::new(1, 4);
+        assert!(observed.is_confident(0.25));
+        assert!(!observed.is_confident(0.5));
+        let inferred = DepConfidence::new(0, 4);
+        assert!(inferred.is_inferred());
+        assert!(inferred.is_confident(0.0));
+        assert!(!inferred.is_confident(0.5));
+    }
+
     #[test]
     pub fn test_post_x86_post_loop_offsets() {
         let (core, mut icfg) = get_x86_post_loop_offsets();
@@ -0,0 +1,270 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, path::Path};
+
+    use binding::{
+        get_pkg_path, get_test_bin_path, init_rizin_instance, rz_core_graph_icfg, RzCoreWrapper,
+    };
+
+    use crate::{
+        bda::run_bda,
+        bda_binding::{add_procedures_to_icfg, get_graph},
+        flow_graphs::Address,
+        icfg::ICFG,
+        state::BDAState,
+    };
+
+    /// Seed used if the expected file doesn't set plugins.bda.seed.
+    const REGRESSION_SEED: &str = "0x5eed";
+
+    /// If this environment variable is set, the expected files are regenerated
+    /// from the results of the seeded runs, instead of being checked.
+    const REGENERATE_VAR: &str = "BDA_REGENERATE_EXPECTED";
+
+    /// A result item of BDA, which can be compared against the ground truth.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Item {
+        /// Memory dependency (read, write).
+        Dep(Address, Address),
+        /// Resolved indirect call (from, to).
+        ICall(Address, Address),
+    }
+
+    /// The content of an expected-dependencies file.
+    ///
+    /// The files are golden snapshots. Their items are taken from the results asserted
+    /// in test_post_analysis.rs and test_unit.rs and checked against the C sources.
+    /// A changed result must be verified against the sources before a file is updated.
+    ///
+    /// To regenerate the items of all files from a seeded run, use:
+    ///
+    ///   BDA_REGENERATE_EXPECTED=1 cargo test -p bda test_regression_suite
+    ///
+    /// It replaces everything after the settings with the found items.
+    /// Review the diff against the C sources and add back the comments before committing it.
+    ///
+    /// Each line holds one entry. Empty lines and lines starting with '#' are ignored.
+    ///
+    ///   bin <file>              Test binary in tests/bins
+    ///   conf <key> <value>      Rizin config value set before the analysis
+    ///   cmd <command>           Rizin command run before the analysis
+    ///   paths <number>          Number of paths BDA interprets
+    ///   min_precision <float>   Minimal precision, the test fails below it
+    ///   min_recall <float>      Minimal recall, the test fails below it
+    ///   dep <read> <write>      Expected memory dependency
+    ///   icall <from> <to>       Expected resolved indirect call
+    struct Expected {
+        bin: String,
+        confs: Vec<(String, String)>,
+        cmds: Vec<String>,
+        paths: usize,
+        min_precision: f64,
+        min_recall: f64,
+        items: BTreeSet<Item>,
+    }
+
+    fn parse_addr(s: &str) -> Address {
+        Address::from_str_radix(s.trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("Invalid address: {}", s))
+    }
+
+    fn parse_pair(args: &str) -> (Address, Address) {
+        let mut addrs = args.split_whitespace().map(parse_addr);
+        let (Some(a0), Some(a1), None) = (addrs.next(), addrs.next(), addrs.next()) else {
+            panic!("Expected two addresses: {}", args);
+        };
+        (a0, a1)
+    }
+
+    fn parse_expected(path: &Path) -> Expected {
+        let content = std::fs::read_to_string(path).expect("Could not read expected file");
+        let mut expected = Expected {
+            bin: String::new(),
+            confs: Vec::new(),
+            cmds: Vec::new(),
+            paths: 200,
+            min_precision: 1.0,
+            min_recall: 1.0,
+            items: BTreeSet::new(),
+        };
+        for line in content.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, args) = line.split_once(' ').unwrap_or((line, ""));
+            let args = args.trim();
+            match key {
+                "bin" => expected.bin = args.to_owned(),
+                "conf" => {
+                    let (k, v) = args.split_once(' ').expect("conf needs a key and a value");
+                    expected.confs.push((k.to_owned(), v.trim().to_owned()));
+                }
+                "cmd" => expected.cmds.push(args.to_owned()),
+                "paths" => expected.paths = args.parse().expect("Invalid number of paths"),
+                "min_precision" => expected.min_precision = args.parse().expect("Invalid float"),
+                "min_recall" => expected.min_recall = args.parse().expect("Invalid float"),
+                "dep" => {
+                    let (r, w) = parse_pair(args);
+                    expected.items.insert(Item::Dep(r, w));
+                }
+                "icall" => {
+                    let (from, to) = parse_pair(args);
+                    expected.items.insert(Item::ICall(from, to));
+                }
+                _ => panic!("{}: Unknown entry '{}'", path.display(), key),
+            }
+        }
+        assert!(
+            !expected.bin.is_empty(),
+            "{}: No binary given",
+            path.display()
+        );
+        expected
+    }
+
+    /// Runs BDA as described by [expected] with a fixed seed and a single thread.
+    /// The run is bound by the number of paths, so the results don't depend on the machine speed.
+    /// Returns the found dependencies and resolved indirect calls.
+    fn run_regression(expected: &Expected) -> BTreeSet<Item> {
+        let bin = get_test_bin_path().join(&expected.bin);
+        let rz_core = RzCoreWrapper::new(init_rizin_instance(bin.to_str().expect("Path wrong")));
+        {
+            let core = rz_core.lock().unwrap();
+            core.set_conf_val("plugins.bda.skip_questions", "true");
+            core.set_conf_val("plugins.bda.seed", REGRESSION_SEED);
+            for (k, v) in expected.confs.iter() {
+                core.set_conf_val(k, v);
+            }
+            for cmd in expected.cmds.iter() {
+                assert!(core.run_cmd(cmd), "Running '{}' failed.", cmd);
+            }
+        }
+        let rz_icfg = unsafe { rz_core_graph_icfg(rz_core.lock().unwrap().get_ptr()) };
+        let mut icfg = ICFG::new_graph(get_graph(rz_icfg));
+        add_procedures_to_icfg(rz_core.clone(), &mut icfg);
+
+        // Every new code xref updates the iCFG immediately. So no timer affects the run.
+        let mut state = BDAState::new(1, u64::MAX, u64::MAX, 1);
+        state.set_max_paths(expected.paths);
+        let Some(dip) = run_bda(rz_core, &mut icfg, &mut state, false) else {
+            panic!("{}: BDA failed", expected.bin);
+        };
+        let mut found: BTreeSet<Item> = dip.into_iter().map(|(r, w)| Item::Dep(r, w)).collect();
+        found.extend(
+            state
+                .calls
+                .iter()
+                .filter(|c| !c.is_candidate())
+                .map(|c| Item::ICall(c.get_from(), c.get_to())),
+        );
+        found
+    }
+
+    /// Replaces the items of the expected file at [path] with the [found] ones.
+    /// The header comments and settings before the first item are kept.
+    fn regenerate_expected(path: &Path, found: &BTreeSet<Item>) {
+        let content = std::fs::read_to_string(path).expect("Could not read expected file");
+        let lines: Vec<&str> = content
+            .lines()
+            .take_while(|l| !l.starts_with("dep ") && !l.starts_with("icall "))
+            .collect();
+        // Comments directly before the first item belong to it.
+        let n_settings = lines
+            .iter()
+            .rposition(|l| !l.trim().is_empty() && !l.starts_with('#'))
+            .map_or(lines.len(), |i| i + 1);
+        let mut out = lines[..n_settings].join("\n");
+        out.push('\n');
+        out.push('\n');
+        for item in found.iter() {
+            match item {
+                Item::Dep(r, w) => out.push_str(&format!("dep {:#x} {:#x}\n", r, w)),
+                Item::ICall(from, to) => out.push_str(&format!("icall {:#x} {:#x}\n", from, to)),
+            }
+        }
+        std::fs::write(path, out).expect("Could not write expected file");
+    }
+
+    /// Returns (precision, recall) of [found] compared to [expected].
+    fn get_precision_recall(found: &BTreeSet<Item>, expected: &BTreeSet<Item>) -> (f64, f64) {
+        let true_pos = found.intersection(expected).count() as f64;
+        let precision = if found.is_empty() {
+            1.0
+        } else {
+            true_pos / found.len() as f64
+        };
+        let recall = if expected.is_empty() {
+            1.0
+        } else {
+            true_pos / expected.len() as f64
+        };
+        (precision, recall)
+    }
+
+    #[test]
+    fn test_precision_recall() {
+        let expected = BTreeSet::from([Item::Dep(0x10, 0x0), Item::Dep(0x20, 0x0)]);
+        let found = BTreeSet::from([Item::Dep(0x10, 0x0), Item::ICall(0x30, 0x40)]);
+        assert_eq!(get_precision_recall(&found, &expected), (0.5, 0.5));
+        assert_eq!(get_precision_recall(&expected, &expected), (1.0, 1.0));
+        assert_eq!(
+            get_precision_recall(&BTreeSet::new(), &expected),
+            (1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_regression_suite() {
+        let expected_dir = get_pkg_path().join("tests").join("expected");
+        let mut files: Vec<_> = std::fs::read_dir(&expected_dir)
+            .expect("No expected directory")
+            .map(|e| e.expect("Invalid entry").path())
+            .filter(|p| p.extension().is_some_and(|e| e == "deps"))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty(), "No expected files found.");
+
+        let regenerate = std::env::var_os(REGENERATE_VAR).is_some();
+        let mut regressions = Vec::new();
+        for file in files.iter() {
+            let expected = parse_expected(file);
+            let found = run_regression(&expected);
+            if regenerate {
+                regenerate_expected(file, &found);
+                println!("{}: regenerated {} items", file.display(), found.len());
+                continue;
+            }
+            let (precision, recall) = get_precision_recall(&found, &expected.items);
+            println!(
+                "{}: precision = {:.3} recall = {:.3}",
+                expected.bin, precision, recall
+            );
+            for missing in expected.items.difference(&found) {
+                println!("  missing: {:x?}", missing);
+            }
+            for unexpected in found.difference(&expected.items) {
+                println!("  unexpected: {:x?}", unexpected);
+            }
+            if precision < expected.min_precision {
+                regressions.push(format!(
+                    "{}: precision {:.3} < {:.3}",
+                    expected.bin, precision, expected.min_precision
+                ));
+            }
+            if recall < expected.min_recall {
+                regressions.push(format!(
+                    "{}: recall {:.3} < {:.3}",
+                    expected.bin, recall, expected.min_recall
+                ));
+            }
+        }
+        assert!(
+            regressions.is_empty(),
+            "Regressions:\n{}",
+            regressions.join("\n")
+        );
+    }
+}
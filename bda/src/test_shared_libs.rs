@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::shared_libs::{link_binaries, BinSymbols};
+
+    #[test]
+    fn test_link_binaries() {
+        let mut main = BinSymbols::new();
+        main.exports.insert("main".to_owned(), 0x1100);
+        main.stubs.insert("puts".to_owned(), 0x1030);
+        main.stubs.insert("missing".to_owned(), 0x1040);
+        main.slots.push(("puts".to_owned(), 0x3fd0));
+        main.slots.push(("stdout".to_owned(), 0x3fd8));
+
+        let mut libc = BinSymbols::new();
+        libc.exports = BTreeMap::from([
+            ("puts".to_owned(), 0x7f0000080000),
+            ("stdout".to_owned(), 0x7f0000200000),
+            ("malloc".to_owned(), 0x7f0000090000),
+        ]);
+        libc.stubs.insert("malloc".to_owned(), 0x7f0000010000);
+        libc.slots.push(("malloc".to_owned(), 0x7f0000100000));
+
+        // Loaded after libc, so its malloc is never used.
+        let mut libmalloc = BinSymbols::new();
+        libmalloc
+            .exports
+            .insert("malloc".to_owned(), 0x7f1000001000);
+
+        let links = link_binaries(&[main, libc, libmalloc]);
+        assert_eq!(
+            links.stubs,
+            vec![(0x1030, 0x7f0000080000), (0x7f0000010000, 0x7f0000090000)]
+        );
+        assert_eq!(
+            links.slots,
+            vec![
+                (0x3fd0, 0x7f0000080000),
+                (0x3fd8, 0x7f0000200000),
+                (0x7f0000100000, 0x7f0000090000)
+            ]
+        );
+    }
+}
@@ -3,6 +3,14 @@
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
+    use rzil_abstr::{
+        bitvector::BitVector,
+        interpreter::{AbstrVal, CodeXrefType, ConcreteCodeXref, HeapFinding, HeapFindingType},
+    };
+    use stochfuzz::rewriter::BranchHint;
+
     use crate::{flow_graphs::Address, state::BDAState};
 
     #[test]
@@ -24,4 +32,107 @@ mod tests {
         assert!(!state.addr_in_ranges(&0x6));
         assert!(!state.addr_in_ranges(&0x1));
     }
+
+    #[test]
+    pub fn test_state_heap_findings() {
+        let mut state = BDAState::new(0, 0, 0, 0);
+        let region = AbstrVal::new_heap(1, BitVector::new_zero(64), 0x10)
+            .get_mem_region()
+            .clone();
+        let mut findings = BTreeSet::new();
+        findings.insert(HeapFinding::new(
+            HeapFindingType::UseAfterFree,
+            0x30,
+            0x20,
+            region.clone(),
+            Vec::from([0x0, 0x10, 0x20, 0x28, 0x30]),
+        ));
+        findings.insert(HeapFinding::new(
+            HeapFindingType::DoubleFree,
+            0x40,
+            0x20,
+            region.clone(),
+            Vec::from([0x0, 0x10, 0x20, 0x40]),
+        ));
+        state.update_heap_findings(findings);
+        assert_eq!(state.heap_findings.len(), 2);
+
+        // Same finding with a shorter witness replaces the old one.
+        let mut findings = BTreeSet::new();
+        findings.insert(HeapFinding::new(
+            HeapFindingType::UseAfterFree,
+            0x30,
+            0x20,
+            region.clone(),
+            Vec::from([0x10, 0x20, 0x30]),
+        ));
+        state.update_heap_findings(findings);
+        assert_eq!(state.heap_findings.len(), 2);
+        let uaf = state
+            .heap_findings
+            .get(&(HeapFindingType::UseAfterFree, 0x30, 0x20))
+            .unwrap();
+        assert_eq!(uaf.get_witness(), &Vec::from([0x10, 0x20, 0x30]));
+
+        // A longer witness is ignored.
+        let mut findings = BTreeSet::new();
+        findings.insert(HeapFinding::new(
+            HeapFindingType::DoubleFree,
+            0x40,
+            0x20,
+            region,
+            Vec::from([0x0, 0x8, 0x10, 0x20, 0x38, 0x40]),
+        ));
+        state.update_heap_findings(findings);
+        let df = state
+            .heap_findings
+            .get(&(HeapFindingType::DoubleFree, 0x40, 0x20))
+            .unwrap();
+        assert_eq!(df.get_witness().len(), 4);
+    }
+
+    #[test]
+    pub fn test_state_branch_hints() {
+        let mut state = BDAState::new(0, 0, 0, 0);
+        assert!(state.get_branch_hints().is_empty());
+        state.calls.insert(ConcreteCodeXref::new(
+            CodeXrefType::IndirectCall,
+            0x1000,
+            0x1010,
+            0x2000,
+        ));
+        state.calls.insert(ConcreteCodeXref::new_candidate(
+            CodeXrefType::IndirectCall,
+            0x1000,
+            0x1010,
+            0x2100,
+        ));
+        state.jumps.insert(ConcreteCodeXref::new(
+            CodeXrefType::IndirectJump,
+            0x1000,
+            0x1020,
+            0x1030,
+        ));
+        let hints: BTreeSet<BranchHint> = state.get_branch_hints().into_iter().collect();
+        assert_eq!(
+            hints,
+            BTreeSet::from([
+                BranchHint {
+                    from: 0x1010,
+                    to: 0x2000,
+                    candidate: false,
+                },
+                BranchHint {
+                    from: 0x1010,
+                    to: 0x2100,
+                    candidate: true,
+                },
+                BranchHint {
+                    from: 0x1020,
+                    to: 0x1030,
+                    candidate: false,
+                },
+            ])
+        );
+    }
 }
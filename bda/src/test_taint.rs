@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use helper::set_map::SetMap;
+    use rzil_abstr::interpreter::DataFlow;
+
+    use crate::{flow_graphs::Address, taint::TaintQuery};
+
+    #[test]
+    pub fn test_taint_query() {
+        // 0x10: buf = input()
+        // 0x20: x = buf[0]
+        // 0x30: stack[0] = x
+        // 0x40: y = stack[0]
+        // 0x50: sink(y, buf)
+        // 0x60: sink(0, y) - Argument 0 is not checked.
+        let data_flows = BTreeSet::from([
+            DataFlow::new(0x10, 0x20, None),
+            DataFlow::new(0x20, 0x30, None),
+            DataFlow::new(0x40, 0x50, Some(0)),
+            DataFlow::new(0x10, 0x50, Some(1)),
+            DataFlow::new(0x40, 0x60, Some(0)),
+        ]);
+        let mut dep = SetMap::<Address, Address>::new();
+        dep.insert(0x40, 0x30);
+        let sinks = BTreeMap::from([(0x50, BTreeSet::from([0, 1])), (0x60, BTreeSet::from([1]))]);
+
+        let query = TaintQuery::new(&data_flows, &dep);
+        let chains = query.query(&BTreeSet::from([0x10]), &sinks);
+        assert_eq!(chains.len(), 2);
+        let chains: Vec<(Vec<Address>, usize)> = chains
+            .iter()
+            .map(|c| (c.get_chain().clone(), c.get_sink_arg()))
+            .collect();
+        assert!(chains.contains(&(Vec::from([0x10, 0x20, 0x30, 0x40, 0x50]), 0)));
+        assert!(chains.contains(&(Vec::from([0x10, 0x50]), 1)));
+
+        // Without the memory dependency, the input never reaches argument 0.
+        let query = TaintQuery::new(&data_flows, &SetMap::new());
+        let chains = query.query(&BTreeSet::from([0x10]), &sinks);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(
+            chains.first().unwrap().get_chain(),
+            &Vec::from([0x10, 0x50])
+        );
+
+        // Unknown sources reach nothing.
+        let query = TaintQuery::new(&data_flows, &dep);
+        assert!(query.query(&BTreeSet::from([0x20000]), &sinks).is_empty());
+    }
+}
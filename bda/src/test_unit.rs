@@ -13,7 +13,10 @@ mod tests {
 
     use crate::{
         bda::run_bda,
-        bda_binding::{add_procedures_to_icfg, get_graph, setup_procedure_at_addr},
+        bda_binding::{
+            add_procedures_to_icfg, compile_exclude_pattern, get_graph, setup_procedure_at_addr,
+            ProcedurePatterns,
+        },
         flow_graphs::{FlowGraphOperations, NodeId},
         icfg::ICFG,
         state::BDAState,
@@ -61,7 +64,8 @@ mod tests {
     #[test]
     pub fn test_setup_unmapped_procedure() {
         let rz_core = RzCoreWrapper::new(init_rizin_instance("="));
-        let mut unk_procedure = setup_procedure_at_addr(&rz_core.lock().unwrap(), 0x0);
+        let patterns = ProcedurePatterns::new(&rz_core.lock().unwrap());
+        let mut unk_procedure = setup_procedure_at_addr(&rz_core.lock().unwrap(), 0x0, &patterns);
         assert!(
             unk_procedure.is_some(),
             "Procedure was not intiazlized. But should be."
@@ -70,7 +74,7 @@ mod tests {
             rz_core.lock().unwrap().run_cmd("f+ malloc @ 0x2"),
             "Running command failed."
         );
-        unk_procedure = setup_procedure_at_addr(&rz_core.lock().unwrap(), 0x2);
+        unk_procedure = setup_procedure_at_addr(&rz_core.lock().unwrap(), 0x2, &patterns);
         assert!(unk_procedure.is_some(), "Procedure was not intiazlized.");
         assert!(
             unk_procedure.unwrap().is_malloc(),
@@ -97,6 +101,27 @@ mod tests {
         (rz_core, icfg)
     }
 
+    #[test]
+    fn test_exclude_pattern_anchored() {
+        let printf = compile_exclude_pattern("printf").unwrap();
+        assert!(printf.is_match("printf"));
+        assert!(!printf.is_match("sprintf"));
+        assert!(!printf.is_match("vsnprintf"));
+        assert!(printf.is_match("sym.printf"));
+        assert!(printf.is_match("sym.imp.printf"));
+        assert!(!printf.is_match("sym.imp.sprintf"));
+        assert!(!printf.is_match("printf.sym"));
+        let imports = compile_exclude_pattern(r"sym\.imp\..*").unwrap();
+        assert!(imports.is_match("sym.imp.puts"));
+        assert!(!imports.is_match("sym.main"));
+        let log = compile_exclude_pattern(".*log.*|puts").unwrap();
+        assert!(log.is_match("syslog"));
+        assert!(log.is_match("puts"));
+        assert!(!log.is_match("fputs"));
+        assert!(log.is_match("sym.imp.puts"));
+        assert!(log.is_match("sym.imp.syslog"));
+    }
+
     #[test]
     fn test_x86_discover_recurse() {
         let (core, mut icfg) = get_x86_discover_recurse();
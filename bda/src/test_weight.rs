@@ -3,6 +3,8 @@
 
 #[cfg(test)]
 mod tests {
+    use rug::Integer;
+
     use crate::weight::{u128_to_integer_wid, WeightMap};
 
     #[test]
@@ -31,17 +33,16 @@ mod tests {
         assert_eq!(wmap.read().unwrap().num_weights(), 2);
         assert_eq!(wmap.read().unwrap().num_constants(), 2);
 
+        // Sums are only evaluated when their value is requested.
         res = n1.add(&n1, wmap);
         assert_eq!(wmap.read().unwrap().num_weights(), 3);
+        assert_eq!(wmap.read().unwrap().num_constants(), 2);
+        assert!(wmap.read().unwrap().get_wid_of_u128(2).is_none());
+        assert!(res.eq_usize(2, wmap));
         assert_eq!(wmap.read().unwrap().num_constants(), 3);
-        let n2 = wmap
-            .read()
-            .unwrap()
-            .get_wid_of_u128(2)
-            .expect("Weight was not saved during addition.");
+        let n2 = n1.add(&n1, wmap);
         assert!(res.eq(&n2));
-        assert!(res.eq_w(&n2, wmap));
-        assert!(res.eq_usize(2, wmap));
+        assert_eq!(wmap.read().unwrap().num_weights(), 3);
 
         let n256max = wmap.write().unwrap().add_root_const_digits(
             &[
@@ -54,98 +55,113 @@ mod tests {
         assert_eq!(wmap.read().unwrap().num_constants(), 4);
         res = n1.add(&n256max, wmap);
         assert_eq!(wmap.read().unwrap().num_weights(), 5);
-        assert_eq!(wmap.read().unwrap().num_constants(), 5);
-
-        let n257bit0 = wmap
-            .read()
-            .unwrap()
-            .get_wid_of_digits(
-                &[
-                    0x1u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                ],
-                rug::integer::Order::Msf,
-            )
-            .expect("Weight was not added during addition.");
-        assert!(res.eq(&n257bit0));
-        assert!(res.eq_w(&n257bit0, wmap));
+        assert_eq!(wmap.read().unwrap().num_constants(), 4);
+        // Operands are commutative.
+        assert!(res.eq(&n256max.add(&n1, wmap)));
 
-        res = n1.add(&n257bit0, wmap);
+        let n257bit0 = wmap.write().unwrap().add_root_const_digits(
+            &[
+                0x1u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+            rug::integer::Order::Msf,
+        );
         assert_eq!(wmap.read().unwrap().num_weights(), 6);
+        assert_eq!(wmap.read().unwrap().num_constants(), 5);
+        assert!(res.eq_w(&n257bit0, wmap));
         assert_eq!(wmap.read().unwrap().num_constants(), 6);
 
-        let n257bit1 = wmap
-            .read()
-            .unwrap()
-            .get_wid_of_digits(
-                &[
-                    0x1u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
-                ],
-                rug::integer::Order::Msf,
-            )
-            .expect("Weight was not added during addition.");
-        assert!(res.eq(&n257bit1));
-        assert!(res.eq_w(&n257bit1, wmap));
+        let res_257bit1 = n1.add(&n257bit0, wmap);
+        let n257bit1 = wmap.write().unwrap().add_root_const_digits(
+            &[
+                0x1u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            ],
+            rug::integer::Order::Msf,
+        );
+        assert_eq!(wmap.read().unwrap().num_weights(), 8);
+        assert_eq!(wmap.read().unwrap().num_constants(), 7);
+        assert!(res_257bit1.eq_w(&n257bit1, wmap));
+        assert_eq!(wmap.read().unwrap().num_constants(), 8);
 
         wmap.read().unwrap().print();
 
         // Clean the whole constant map and check if the operations still work.
         wmap.write().unwrap().clear_derived_constants();
-
-        assert_eq!(wmap.read().unwrap().num_weights(), 6);
-        assert_eq!(wmap.read().unwrap().num_constants(), 3); // the 256bit number is now also a root const.
-        let n0 = wmap.read().unwrap().get_zero();
-        let n1 = wmap.read().unwrap().get_one();
-
-        let mut res = n0.add(&n0, wmap);
-        assert!(res.eq(&n0));
-        assert!(res.eq_w(&n0, wmap));
-        assert!(res.eq_usize(0, wmap));
-        assert_eq!(wmap.read().unwrap().num_weights(), 6);
-        assert_eq!(wmap.read().unwrap().num_constants(), 3);
-
-        res = n0.add(&n1, wmap);
-        assert!(res.eq(&n1));
-        assert!(res.eq_w(&n1, wmap));
-        assert!(res.eq_usize(1, wmap));
-        assert_eq!(wmap.read().unwrap().num_weights(), 6);
-        assert_eq!(wmap.read().unwrap().num_constants(), 3);
-
-        res = n1.add(&n1, wmap);
-        assert_eq!(wmap.read().unwrap().num_weights(), 6);
-        assert_eq!(wmap.read().unwrap().num_constants(), 4);
-        assert!(res.eq(&n2));
-        assert!(res.eq_w(&n2, wmap));
-        assert!(res.eq_usize(2, wmap));
-
-        res = n1.add(&n256max, wmap);
-        assert_eq!(wmap.read().unwrap().num_weights(), 6);
+        assert_eq!(wmap.read().unwrap().num_weights(), 8);
         assert_eq!(wmap.read().unwrap().num_constants(), 5);
 
-        assert!(res.eq(&n257bit0));
-        assert!(res.eq_w(&n257bit0, wmap));
-
-        res = n1.add(&n257bit0, wmap);
-        assert_eq!(wmap.read().unwrap().num_weights(), 6);
+        assert!(res_257bit1.eq_w(&n257bit1, wmap));
         assert_eq!(wmap.read().unwrap().num_constants(), 6);
+        assert!(n1.add(&n1, wmap).eq(&n2));
+        assert!(n2.eq_usize(2, wmap));
+        assert_eq!(wmap.read().unwrap().num_weights(), 8);
+        assert_eq!(wmap.read().unwrap().num_constants(), 7);
+    }
+
+    #[test]
+    fn test_lazy_deep_dag() {
+        let wmap = &WeightMap::new();
+        let n1 = wmap.read().unwrap().get_one();
+        let n2 = n1.add(&n1, wmap);
+        // Constants get the same identifier in every weight map.
+        assert_eq!(n1, WeightMap::new().read().unwrap().get_one());
+
+        let mut w = n1;
+        for _ in 0..10000 {
+            w = w.mul(&n2, wmap).add(&n1, wmap);
+        }
+        assert_eq!(wmap.read().unwrap().num_constants(), 2);
+        // Evaluating the deep DAG must not overflow the stack.
+        assert_eq!(w.log2(wmap), 10001);
+        // 1 * 2 is simplified to 2. So the first iteration adds a single expression.
+        assert_eq!(wmap.read().unwrap().num_constants(), 3 + 1 + 2 * 9999);
+    }
 
-        let n257bit1 = wmap
-            .read()
+    #[test]
+    fn test_approx() {
+        let wmap = &WeightMap::new();
+        wmap.write().unwrap().set_approximate(true);
+        assert_eq!(wmap.read().unwrap().num_weights(), 2);
+        let n1 = wmap.read().unwrap().get_one();
+        let n2 = n1.add(&n1, wmap);
+        assert!(n2.eq_usize(2, wmap));
+        // Exactly represented weights keep the ids of exact weights.
+        assert_eq!(u128_to_integer_wid(2).1, n2);
+        assert_eq!(wmap.read().unwrap().get_wid_of_u128(2), Some(n2));
+
+        // 2^64 + 1 is rounded down to 2^64
+        let max = wmap
+            .write()
+            .unwrap()
+            .add_root_const_usize(u64::MAX as usize);
+        let res = max.add(&n2, wmap);
+        assert_eq!(res.get_weight_const(wmap), Integer::from(1) << 64);
+        assert_eq!(res.log2(wmap), 65);
+        assert_eq!(res.get_msbs(wmap, 64), 1 << 63);
+
+        // The relative error stays in the documented bound.
+        let exact_map = &WeightMap::new();
+        let exact_max = exact_map
+            .write()
             .unwrap()
-            .get_wid_of_digits(
-                &[
-                    0x1u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
-                ],
-                rug::integer::Order::Msf,
-            )
-            .expect("Weight was not added during addition.");
-        assert!(res.eq(&n257bit1));
-        assert!(res.eq_w(&n257bit1, wmap));
+            .add_root_const_usize(u64::MAX as usize);
+        let exact_one = exact_map.read().unwrap().get_one();
+        let mut approx = n1;
+        let mut exact = exact_one;
+        let k = 16;
+        for _ in 0..k / 2 {
+            approx = approx.mul(&max, wmap).add(&n1, wmap);
+            exact = exact.mul(&exact_max, exact_map).add(&exact_one, exact_map);
+        }
+        let approx = approx.get_weight_const(wmap);
+        let exact = exact.get_weight_const(exact_map);
+        assert_eq!(approx.significant_bits(), exact.significant_bits());
+        assert!(approx <= exact);
+        let err = (exact.clone() - approx) << 62;
+        assert!(err < exact * k);
     }
 
     #[test]
@@ -6,16 +6,156 @@ use rug::integer::{MiniInteger, Order, UnsignedPrimitive};
 use rug::{Complete, Integer};
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
+use std::fs::File;
 use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Error, ErrorKind, Write};
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::RwLock;
 use std::time::Instant;
 
 /// An weight expression which can be evaluated to a constant value.
 type WeightExpr = Expr<WeightID>;
 
+/// First line of a weight cache file. It is followed by the weight mode.
+/// Approximated weights are rounded, so they are never loaded into an exact map and vice versa.
+const WEIGHT_CACHE_HEADER: &str = "probana-weights 3";
+
+/// Returns the hash of the constant [v]. It is the first identifier probed for [v].
+/// Weight identifiers are never persisted, so the hash doesn't need to be stable
+/// across Rust releases.
+fn const_hash(v: &Integer) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    v.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the hash of the expression [expr]. It is the first identifier probed for [expr].
+fn expr_hash(expr: &WeightExpr) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match expr.operation {
+        Operation::CONST => 0u8,
+        Operation::ADD => 1,
+        Operation::MUL => 2,
+    }
+    .hash(&mut hasher);
+    expr.get_lhs().hash(&mut hasher);
+    expr.get_rhs().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// An approximated weight of the value `sig * 2^exp`.
+///
+/// Weights with up to 64 significant bits are represented exactly (`exp == 0`).
+/// Bigger weights keep their 64 most significant bits and round down.
+/// So every addition or multiplication has a relative error below `2^-62`
+/// (one rounding of the result, one of the smaller summand).
+/// A weight calculated with `k` operations is therefore at most by a factor of
+/// `(1 - 2^-62)^k` smaller than the exact weight.
+/// The probability of a branch, sampled with weights of at most `k` operations,
+/// deviates relatively by less than `k * 2^-61` from the exact probability.
+/// For any realistic program the sampling bias is far below the noise of the
+/// random number generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApproxWeight {
+    sig: u64,
+    exp: u64,
+}
+
+impl ApproxWeight {
+    /// Builds the normalized approximation of `v * 2^exp`.
+    /// Normalized weights either have `exp == 0` or the most significant bit of `sig` set.
+    /// This makes the representation of every value unique.
+    fn normalize(v: u128, exp: u64) -> ApproxWeight {
+        if v == 0 {
+            return ApproxWeight { sig: 0, exp: 0 };
+        }
+        let bits = 128 - v.leading_zeros() as u64;
+        let (mut sig, mut exp) = if bits > 64 {
+            ((v >> (bits - 64)) as u64, exp.saturating_add(bits - 64))
+        } else {
+            (v as u64, exp)
+        };
+        let shift = u64::min(sig.leading_zeros() as u64, exp);
+        sig <<= shift;
+        exp -= shift;
+        ApproxWeight { sig, exp }
+    }
+
+    pub fn from_u128(v: u128) -> ApproxWeight {
+        ApproxWeight::normalize(v, 0)
+    }
+
+    pub fn from_integer(v: &Integer) -> ApproxWeight {
+        let bits = v.significant_bits();
+        if bits <= 64 {
+            return ApproxWeight::normalize(v.to_u64().expect("Weights are positive.") as u128, 0);
+        }
+        let shift = bits - 64;
+        let sig = (v >> shift)
+            .complete()
+            .to_u64()
+            .expect("Should fit into 64 bits.");
+        ApproxWeight::normalize(sig as u128, shift as u64)
+    }
+
+    /// Returns the exact value of this approximation.
+    pub fn to_integer(&self) -> Integer {
+        Integer::from(self.sig) << self.exp as u32
+    }
+
+    pub fn add(&self, rhs: &ApproxWeight) -> ApproxWeight {
+        let (hi, lo) = if self.exp >= rhs.exp {
+            (self, rhs)
+        } else {
+            (rhs, self)
+        };
+        let d = hi.exp - lo.exp;
+        let lo_sig = if d >= 64 { 0 } else { lo.sig >> d };
+        ApproxWeight::normalize(hi.sig as u128 + lo_sig as u128, hi.exp)
+    }
+
+    pub fn mul(&self, rhs: &ApproxWeight) -> ApproxWeight {
+        ApproxWeight::normalize(
+            self.sig as u128 * rhs.sig as u128,
+            self.exp.saturating_add(rhs.exp),
+        )
+    }
+
+    pub fn significant_bits(&self) -> u64 {
+        if self.sig == 0 {
+            return 0;
+        }
+        64 - self.sig.leading_zeros() as u64 + self.exp
+    }
+
+    /// Returns the n most significant bits.
+    pub fn get_msbs(&self, n: u32) -> u64 {
+        let shift = u64::max(self.significant_bits(), n as u64 - 1) - (n as u64 - 1);
+        if shift >= self.exp {
+            let d = shift - self.exp;
+            return if d >= 64 { 0 } else { self.sig >> d };
+        }
+        // The value has less than n bits. So it can be shifted without overflow.
+        self.sig << (self.exp - shift)
+    }
+
+    /// Returns the hash of this approximation. It is the first identifier probed for it.
+    /// Exactly represented weights get the same hash as the exact Integer.
+    fn get_hash(&self) -> u64 {
+        if self.exp == 0 {
+            return const_hash(&Integer::from(self.sig));
+        }
+        let mut hasher = DefaultHasher::new();
+        (self.sig, self.exp).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 /// An identifier for a weight. It is an index into the weight map,
 /// which stores the actual Integers for each weight.
+/// Constants are identified by their value, expressions by their operation and operands.
+/// Identifiers are only valid for the weight map which created them and never persisted.
 #[derive(Debug, Copy, Clone, Eq)]
 pub struct WeightID {
     id: u64,
@@ -54,52 +194,56 @@ impl std::fmt::Display for WeightID {
 
 macro_rules! get_const {
     ($wmap:expr, $wid:expr) => {
-        $wmap.write().unwrap().get_const($wid)
+        $wmap.write().unwrap().touch_get_const($wid)
     };
 }
 
 impl WeightID {
     pub fn log2(&self, wmap: &RwLock<WeightMap>) -> u64 {
+        if wmap.read().unwrap().is_approximate() {
+            return wmap.read().unwrap().get_approx(self).significant_bits();
+        }
         let lhs_value = wmap.write().unwrap().significant_bits(self) as u64;
         lhs_value
     }
 
     /// Returns the n most significant bits.
     pub fn get_msbs(&self, wmap: &RwLock<WeightMap>, n: u32) -> u64 {
+        if wmap.read().unwrap().is_approximate() {
+            return wmap.read().unwrap().get_approx(self).get_msbs(n);
+        }
         let shift = u32::max(get_const!(wmap, self).significant_bits(), n - 1) - (n - 1);
         let msbs = (get_const!(wmap, self) >> shift).complete();
         msbs.to_u64()
             .expect("This operation should always produce a 64bit value.")
     }
 
+    /// Returns the weight of `self + rhs`.
+    /// The sum is not evaluated until its value is requested.
     pub fn add(&self, rhs: &WeightID, wmap: &RwLock<WeightMap>) -> WeightID {
-        let expr = WeightExpr {
-            operation: Operation::ADD,
-            lhs: Some(self.clone()),
-            rhs: Some(rhs.clone()),
-        };
-        wmap.try_write().unwrap().touch_const(self);
-        wmap.try_write().unwrap().touch_const(rhs);
-        let res = (wmap.try_read().unwrap().get_const(self)
-            + wmap.try_read().unwrap().get_const(rhs))
-        .complete();
-        let wid = wmap.try_write().unwrap().add_expr(res.clone(), expr);
-        wid
+        if wmap.read().unwrap().is_approximate() {
+            return wmap
+                .write()
+                .unwrap()
+                .add_approx_op(self, rhs, Operation::ADD);
+        }
+        wmap.try_write()
+            .unwrap()
+            .add_expr(Operation::ADD, self, rhs)
     }
 
+    /// Returns the weight of `self * rhs`.
+    /// The product is not evaluated until its value is requested.
     pub fn mul(&self, rhs: &WeightID, wmap: &RwLock<WeightMap>) -> WeightID {
-        let expr = WeightExpr {
-            operation: Operation::MUL,
-            lhs: Some(self.clone()),
-            rhs: Some(rhs.clone()),
-        };
-        wmap.try_write().unwrap().touch_const(self);
-        wmap.try_write().unwrap().touch_const(rhs);
-        let res = (wmap.try_read().unwrap().get_const(self)
-            * wmap.try_read().unwrap().get_const(rhs))
-        .complete();
-        let wid = wmap.try_write().unwrap().add_expr(res, expr);
-        wid
+        if wmap.read().unwrap().is_approximate() {
+            return wmap
+                .write()
+                .unwrap()
+                .add_approx_op(self, rhs, Operation::MUL);
+        }
+        wmap.try_write()
+            .unwrap()
+            .add_expr(Operation::MUL, self, rhs)
     }
 
     fn new(id: u64) -> WeightID {
@@ -109,11 +253,19 @@ impl WeightID {
     /// Compares the constant values of the given WeightIDs.
     /// This performs a clone on the first left hand side constant.
     pub fn eq_w(&self, other: &WeightID, wmap: &RwLock<WeightMap>) -> bool {
+        if wmap.read().unwrap().is_approximate() {
+            let wm = wmap.read().unwrap();
+            return wm.get_approx(self) == wm.get_approx(other);
+        }
         let c0 = get_const!(wmap, self).clone();
         return &c0 == get_const!(wmap, other);
     }
 
     pub fn eq_usize(&self, other: usize, wmap: &RwLock<WeightMap>) -> bool {
+        if wmap.read().unwrap().is_approximate() {
+            return *wmap.read().unwrap().get_approx(self)
+                == ApproxWeight::from_u128(other as u128);
+        }
         if !wmap.read().unwrap().wmap.contains_key(self) {
             panic!("Cannot compare a WeightID which is not stored in the WeightMap");
         }
@@ -132,7 +284,7 @@ impl WeightID {
 pub struct WeightMap {
     /// The map to match weight identifiers to their abstract (possibly not evaluated) weights.
     wmap: HashMap<WeightID, Weight>,
-    /// Constant values map. Buffers the constant values of weights.
+    /// Constant values map. Buffers the constant values of evaluated weights.
     cmap: HashMap<WeightID, Integer>,
     /// The weight identifier for a weight of 1
     const_one_id: WeightID,
@@ -145,6 +297,15 @@ pub struct WeightMap {
     /// CFG last calculated timestamps
     /// If the value is None, it must be recalculated.
     cfg_last_calc: HashMap<NodeId, Option<Instant>>,
+    /// Node weights of procedures loaded from a weight cache file.
+    /// Indexed by the procedure entry. The weights are kept as hex strings
+    /// and only parsed, when the procedure weight is requested.
+    cache: HashMap<NodeId, Vec<(NodeId, String)>>,
+    /// Flag if weights are approximated. If set, all weights are stored in [amap]
+    /// and [wmap] and [cmap] stay empty.
+    approximate: bool,
+    /// Approximated weights.
+    amap: HashMap<WeightID, ApproxWeight>,
 }
 
 impl WeightMap {
@@ -156,12 +317,99 @@ impl WeightMap {
             const_zero_id: WeightID { id: 0 },
             root_constants: HashSet::new(),
             cfg_last_calc: HashMap::new(),
+            cache: HashMap::new(),
+            approximate: false,
+            amap: HashMap::new(),
         };
         wm.const_zero_id = wm.add_root_const_usize(0);
         wm.const_one_id = wm.add_root_const_usize(1);
         RwLock::new(wm)
     }
 
+    pub fn is_approximate(&self) -> bool {
+        self.approximate
+    }
+
+    /// Switches between exact and approximated weights.
+    /// If the mode changes, all weights are dropped and every CFG must be recalculated.
+    pub fn set_approximate(&mut self, approximate: bool) {
+        if self.approximate == approximate {
+            return;
+        }
+        self.wmap.clear();
+        self.cmap.clear();
+        self.root_constants.clear();
+        self.amap.clear();
+        self.cfg_last_calc.clear();
+        self.approximate = approximate;
+        self.const_zero_id = self.add_root_const_usize(0);
+        self.const_one_id = self.add_root_const_usize(1);
+    }
+
+    /// Returns the approximated weight of [wid].
+    /// It panics if the weight map is not in approximation mode.
+    fn get_approx(&self, wid: &WeightID) -> &ApproxWeight {
+        self.amap
+            .get(wid)
+            .expect("WeightMap is inconsistent! WeightID should have been in the map.")
+    }
+
+    /// Adds the approximated result of [lhs] [op] [rhs] to the map.
+    fn add_approx_op(&mut self, lhs: &WeightID, rhs: &WeightID, op: Operation) -> WeightID {
+        let (l, r) = (self.get_approx(lhs), self.get_approx(rhs));
+        let res = match op {
+            Operation::ADD => l.add(r),
+            Operation::MUL => l.mul(r),
+            Operation::CONST => panic!("Constants are not calculated."),
+        };
+        self.add_approx(res)
+    }
+
+    fn add_approx(&mut self, aw: ApproxWeight) -> WeightID {
+        let wid = self.find_approx_wid(&aw);
+        self.amap.entry(wid).or_insert(aw);
+        wid
+    }
+
+    /// Returns the identifier of the approximation [aw].
+    /// Colliding hashes are resolved by probing the following identifiers,
+    /// until a free one or the one of [aw] is found.
+    fn find_approx_wid(&self, aw: &ApproxWeight) -> WeightID {
+        let mut wid = WeightID::new(aw.get_hash());
+        while self.amap.get(&wid).is_some_and(|w| w != aw) {
+            wid = WeightID::new(wid.id.wrapping_add(1));
+        }
+        wid
+    }
+
+    /// Returns the identifier of the constant [v].
+    /// Colliding hashes are resolved by probing the following identifiers,
+    /// until a free one or the one of [v] is found.
+    fn find_const_wid(&self, v: &Integer) -> WeightID {
+        let mut wid = WeightID::new(const_hash(v));
+        while self
+            .wmap
+            .get(&wid)
+            .is_some_and(|w| w.expr.operation != Operation::CONST || self.cmap.get(&wid) != Some(v))
+        {
+            wid = WeightID::new(wid.id.wrapping_add(1));
+        }
+        wid
+    }
+
+    /// Returns the identifier of the expression [expr].
+    /// Colliding hashes are resolved by probing the following identifiers,
+    /// until a free one or the one of [expr] is found.
+    fn find_expr_wid(&self, expr: &WeightExpr) -> WeightID {
+        let mut wid = WeightID::new(expr_hash(expr));
+        while self.wmap.get(&wid).is_some_and(|w| {
+            w.expr.operation != expr.operation || w.expr.lhs != expr.lhs || w.expr.rhs != expr.rhs
+        }) {
+            wid = WeightID::new(wid.id.wrapping_add(1));
+        }
+        wid
+    }
+
     pub fn needs_recalc(&self, cfg_id: &NodeId) -> bool {
         let timestamp = self.cfg_last_calc.get(cfg_id);
         return timestamp.is_none() || timestamp.unwrap().is_none();
@@ -209,6 +457,10 @@ impl WeightMap {
         for w in self.cmap.values() {
             println!("| {}", w);
         }
+        println!("| \n| Approximations");
+        for w in self.amap.values() {
+            println!("| {} * 2^{}", w.sig, w.exp);
+        }
         println!("| \n| Expressions");
         for w in self.wmap.values() {
             println!("| {:?}", w.expr);
@@ -229,16 +481,7 @@ impl WeightMap {
         v: &[T],
         order: Order,
     ) -> WeightID {
-        let (const_val, wid) = digits_to_integer_wid(v, order);
-        self.root_constants.insert(wid);
-        if !self.wmap.contains_key(&wid) {
-            let weight = Weight::new_const(wid);
-            self.wmap.insert(wid, weight);
-        }
-        if !self.cmap.contains_key(&wid) {
-            self.cmap.insert(wid, const_val.to_owned());
-        }
-        wid
+        self.add_root_const(Integer::from_digits(v, order))
     }
 
     /// Adds the constant value [v] to the weight map.
@@ -246,64 +489,215 @@ impl WeightMap {
     /// constant map is cleared. It is considered a root value from which all
     /// expressions can be calculated.
     pub fn add_root_const_usize(&mut self, v: usize) -> WeightID {
-        let (const_val, wid) = u128_to_integer_wid(v as u128);
+        self.add_root_const(Integer::from(v))
+    }
+
+    /// Adds the constant [const_val] to the weight map.
+    /// Like the other root constants, it is never deleted from the constant map.
+    fn add_root_const(&mut self, const_val: Integer) -> WeightID {
+        if self.approximate {
+            return self.add_approx(ApproxWeight::from_integer(&const_val));
+        }
+        let wid = self.find_const_wid(&const_val);
         self.root_constants.insert(wid);
         if !self.wmap.contains_key(&wid) {
-            let weight = Weight::new_const(wid);
-            self.wmap.insert(wid, weight);
+            self.wmap.insert(wid, Weight::new_const(wid));
         }
         if !self.cmap.contains_key(&wid) {
-            self.cmap.insert(wid, const_val.to_owned());
+            self.cmap.insert(wid, const_val);
         }
         wid
     }
 
-    pub fn add_expr(&mut self, expr_val: Integer, expr: WeightExpr) -> WeightID {
-        let mut hasher = DefaultHasher::new();
-        expr_val.hash(&mut hasher);
-        let wid = WeightID::new(hasher.finish());
-        if !self.wmap.contains_key(&wid) {
-            let weight = Weight::new(wid, expr);
-            self.wmap.insert(wid, weight);
-        }
-        if !self.cmap.contains_key(&wid) {
-            self.cmap.insert(wid, expr_val.to_owned());
-        }
-        wid
+    /// Returns the first line of weight cache files written in the current weight mode.
+    fn get_cache_header(&self) -> String {
+        format!(
+            "{} {}",
+            WEIGHT_CACHE_HEADER,
+            if self.approximate { "approx" } else { "exact" }
+        )
     }
 
-    /// Evaluate the weight if it is not in the constant map.
-    fn touch_const(&mut self, wid: &WeightID) {
-        if self.cmap.contains_key(wid) {
-            return;
+    /// Loads the node weights of a weight cache file at [path].
+    /// Files written in another weight mode are rejected.
+    /// Only procedures with a matching fingerprint in [fingerprints] are loaded.
+    /// Returns the number of loaded procedures.
+    pub fn load_cache(
+        &mut self,
+        path: &Path,
+        fingerprints: &HashMap<NodeId, String>,
+    ) -> std::io::Result<usize> {
+        let content = std::fs::read_to_string(path)?;
+        let mut lines = content.lines();
+        let Some(header) = lines.next() else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Weight cache file has an unknown format.",
+            ));
+        };
+        if header != self.get_cache_header() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                if header.starts_with(WEIGHT_CACHE_HEADER) {
+                    "Weight cache file was written in another weight mode."
+                } else {
+                    "Weight cache file has an unknown format."
+                },
+            ));
+        }
+        let mut loaded = 0;
+        let mut current: Option<NodeId> = None;
+        for line in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some((nid, value)) = fields
+                .get(1)
+                .and_then(|n| parse_cache_node_id(n))
+                .zip(fields.get(2))
+            else {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Malformed weight cache line: '{}'", line),
+                ));
+            };
+            match fields[0] {
+                "P" => {
+                    current = None;
+                    if fingerprints.get(&nid).is_some_and(|fp| fp == value) {
+                        self.cache.insert(nid, Vec::new());
+                        current = Some(nid);
+                        loaded += 1;
+                    }
+                }
+                "N" => {
+                    if let Some(pid) = current {
+                        self.cache
+                            .get_mut(&pid)
+                            .unwrap()
+                            .push((nid, value.to_string()));
+                    }
+                }
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Malformed weight cache line: '{}'", line),
+                    ))
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Writes the node weights of all procedures with up to date weights
+    /// into the weight cache file at [path].
+    /// Returns the number of saved procedures.
+    pub fn save_cache(&mut self, path: &Path, icfg: &ICFG) -> std::io::Result<usize> {
+        let fingerprints = icfg.get_fingerprints();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", self.get_cache_header())?;
+        let mut saved = 0;
+        for (pid, proc) in icfg.get_procedures().iter() {
+            let proc = proc.read().unwrap();
+            if !proc.is_cfg_set() || self.needs_recalc(pid) {
+                continue;
+            }
+            let cfg = proc.get_cfg();
+            if cfg.graph.node_count() == 0 {
+                continue;
+            }
+            let mut node_wids = Vec::<(NodeId, WeightID)>::new();
+            for nid in cfg.nodes_meta.iter().map(|(nid, _)| nid) {
+                let Some(wid) = cfg.get_node_weight_id(nid) else {
+                    break;
+                };
+                node_wids.push((*nid, wid));
+            }
+            if node_wids.len() != cfg.nodes_meta.len() {
+                continue;
+            }
+            writeln!(
+                writer,
+                "P {}:{}:{:x} {}",
+                pid.icfg_clone_id, pid.cfg_clone_id, pid.address, fingerprints[pid]
+            )?;
+            for (nid, wid) in node_wids {
+                writeln!(
+                    writer,
+                    "N {}:{}:{:x} {:x}",
+                    nid.icfg_clone_id,
+                    nid.cfg_clone_id,
+                    nid.address,
+                    self.get_weight_const(&wid)
+                )?;
+            }
+            saved += 1;
+        }
+        writer.flush()?;
+        Ok(saved)
+    }
+
+    /// Removes the cached node weights of the procedure with the entry [entry]
+    /// and adds them to the map.
+    /// Returns None if there are no (valid) cached weights for the procedure.
+    pub fn take_cached_weights(&mut self, entry: &NodeId) -> Option<HashMap<NodeId, WeightID>> {
+        let cached = self.cache.remove(entry)?;
+        let mut node_wids = HashMap::<NodeId, WeightID>::new();
+        for (nid, hex) in cached {
+            let value = Integer::from_str_radix(&hex, 16).ok()?;
+            node_wids.insert(nid, self.add_root_const(value));
         }
+        Some(node_wids)
+    }
 
-        let weight = self
-            .wmap
-            .get(wid)
-            .expect("WeightMap is inconsistent! WeightID should have been in the map.")
-            .clone();
-        self.eval_w(&weight);
+    /// Number of procedures with cached weights, which were not used yet.
+    pub fn num_cached_procedures(&self) -> usize {
+        self.cache.len()
     }
 
-    fn get_const(&self, wid: &WeightID) -> &Integer {
-        return self.cmap.get(wid).expect("Did not contain weight");
+    /// Adds the expression [lhs] [op] [rhs] to the map and returns its identifier.
+    /// The expression is not evaluated.
+    /// Additions of 0 and multiplications with 0 or 1 are simplified.
+    fn add_expr(&mut self, op: Operation, lhs: &WeightID, rhs: &WeightID) -> WeightID {
+        for operand in [lhs, rhs] {
+            if !self.wmap.contains_key(operand) {
+                panic!("WeightMap is inconsistent! WeightID should have been in the map.");
+            }
+        }
+        let (zero, one) = (self.const_zero_id, self.const_one_id);
+        match op {
+            Operation::ADD if *lhs == zero => return *rhs,
+            Operation::ADD if *rhs == zero => return *lhs,
+            Operation::MUL if *lhs == zero || *rhs == zero => return zero,
+            Operation::MUL if *lhs == one => return *rhs,
+            Operation::MUL if *rhs == one => return *lhs,
+            Operation::CONST => panic!("Constants are not calculated."),
+            _ => (),
+        }
+        // Both operations are commutative. Sorting the operands gives
+        // `a + b` and `b + a` the same identifier.
+        let (lhs, rhs) = if lhs.id <= rhs.id {
+            (lhs, rhs)
+        } else {
+            (rhs, lhs)
+        };
+        let expr = WeightExpr {
+            operation: op,
+            lhs: Some(*lhs),
+            rhs: Some(*rhs),
+        };
+        let wid = self.find_expr_wid(&expr);
+        if !self.wmap.contains_key(&wid) {
+            self.wmap.insert(wid, Weight::new(wid, expr));
+        }
+        wid
     }
 
     /// Returns the constant value for the given Weight id.
     /// If the Weight needs to be evaluated, it is done so.
     /// It will panic, if there is no weight for the given weight id.
     fn touch_get_const(&mut self, wid: &WeightID) -> &Integer {
-        if self.cmap.contains_key(wid) {
-            return self.cmap.get(wid).unwrap();
+        if !self.cmap.contains_key(wid) {
+            self.eval_w(wid);
         }
-
-        let weight = self
-            .wmap
-            .get(wid)
-            .expect("WeightMap is inconsistent! WeightID should have been in the map.")
-            .clone();
-        self.eval_w(&weight);
         self.cmap.get(wid).unwrap()
     }
 
@@ -313,6 +707,9 @@ impl WeightMap {
     }
 
     pub fn significant_bits(&mut self, wid: &WeightID) -> u32 {
+        if self.approximate {
+            return self.get_approx(wid).significant_bits() as u32;
+        }
         self.touch_get_const(wid).significant_bits()
     }
 
@@ -322,18 +719,22 @@ impl WeightMap {
     }
 
     /// Checks if the [wid] is the id for the value [v].
+    /// Only meaningful for constants which never collided with another weight.
     pub fn is_weight_id_of_usize(wid: &WeightID, v: usize) -> bool {
-        let val = Integer::from(v);
-        let mut hasher = DefaultHasher::new();
-        val.hash(&mut hasher);
-        WeightID::new(hasher.finish()) == *wid
+        const_hash(&Integer::from(v)) == wid.id
     }
 
     pub fn num_weights(&self) -> usize {
+        if self.approximate {
+            return self.amap.len();
+        }
         self.wmap.len()
     }
 
     pub fn num_constants(&self) -> usize {
+        if self.approximate {
+            return self.amap.len();
+        }
         self.cmap.len()
     }
 
@@ -347,92 +748,109 @@ impl WeightMap {
         })
     }
 
-    /// Evaluates a weight recursively and returns it's constant value.
-    fn eval_w(&mut self, weight: &Weight) -> &Integer {
-        if weight.expr.operation == Operation::CONST {
-            return self
-                .cmap
-                .get(&weight.id)
-                .expect("Constant should have been added at this point.");
-        }
-        let lhs: &Weight = &self
-            .wmap
-            .get(weight.expr.get_lhs())
-            .expect("Weight should have been added at this point.")
-            .clone();
-        let rhs: &Weight = &self
-            .wmap
-            .get(&weight.expr.get_rhs())
-            .expect("Weight should have been added at this point.")
-            .clone();
-        let const_val = match weight.expr.operation {
-            Operation::ADD => {
-                self.eval_w(lhs);
-                self.eval_w(rhs);
-                (self.cmap.get(&lhs.id).unwrap() + self.cmap.get(&rhs.id).unwrap()).complete()
+    /// Evaluates the weight [wid] and all weights it depends on,
+    /// which are not in the constant map yet.
+    /// The weight DAG can be deep. So it is traversed with an explicit stack.
+    fn eval_w(&mut self, wid: &WeightID) {
+        let mut todo = vec![*wid];
+        while let Some(id) = todo.last().cloned() {
+            if self.cmap.contains_key(&id) {
+                todo.pop();
+                continue;
             }
-            Operation::MUL => {
-                self.eval_w(lhs);
-                self.eval_w(rhs);
-                (self.cmap.get(&lhs.id).unwrap() * self.cmap.get(&rhs.id).unwrap()).complete()
+            let weight = self
+                .wmap
+                .get(&id)
+                .expect("WeightMap is inconsistent! WeightID should have been in the map.");
+            if weight.expr.operation == Operation::CONST {
+                panic!("Root constants should never be removed from the constant map.");
             }
-            Operation::CONST => panic!("CONST case should have been covered earlier."),
-        };
-        self.cmap.insert(weight.id, const_val);
-        self.cmap.get(&weight.id).expect("We just added it?!")
+            let (lhs, rhs) = (*weight.expr.get_lhs(), *weight.expr.get_rhs());
+            let (Some(l), Some(r)) = (self.cmap.get(&lhs), self.cmap.get(&rhs)) else {
+                todo.extend([lhs, rhs].iter().filter(|o| !self.cmap.contains_key(*o)));
+                continue;
+            };
+            let const_val = match weight.expr.operation {
+                Operation::ADD => (l + r).complete(),
+                Operation::MUL => (l * r).complete(),
+                Operation::CONST => panic!("CONST case should have been covered earlier."),
+            };
+            self.cmap.insert(weight.id, const_val);
+            todo.pop();
+        }
     }
 
     /// Returns the constant number of the WeightID.
     pub fn get_weight_const(&mut self, wid: &WeightID) -> Integer {
+        if self.approximate {
+            return self.get_approx(wid).to_integer();
+        }
         self.touch_get_const(wid).clone()
     }
 
     pub fn contains_wid(&self, wid: &WeightID) -> bool {
-        self.wmap.contains_key(wid)
+        self.wmap.contains_key(wid) || self.amap.contains_key(wid)
     }
 
     pub fn contains_const(&self, wid: &WeightID) -> bool {
-        self.wmap.contains_key(wid)
+        self.wmap.contains_key(wid) || self.amap.contains_key(wid)
     }
 
-    /// Returns the weight id of the given number if it is in the map.
+    /// Returns the weight id of the given constant if it is in the map.
     pub fn get_wid_of_u128(&self, n: u128) -> Option<WeightID> {
-        let (_, wid) = u128_to_integer_wid(n);
-        if self.wmap.get(&wid).is_some() {
+        let wid = if self.approximate {
+            self.find_approx_wid(&ApproxWeight::from_u128(n))
+        } else {
+            self.find_const_wid(&Integer::from(n))
+        };
+        if self.contains_wid(&wid) {
             return Some(wid);
         }
         return None;
     }
 
-    /// Returns the weight id of the given digits if it is in the map.
+    /// Returns the weight id of the given constant digits if it is in the map.
     pub fn get_wid_of_digits<T: UnsignedPrimitive>(
         &self,
         v: &[T],
         order: Order,
     ) -> Option<WeightID> {
-        let (_, wid) = digits_to_integer_wid(v, order);
-        if self.wmap.get(&wid).is_some() {
+        let const_val = Integer::from_digits(v, order);
+        let wid = if self.approximate {
+            self.find_approx_wid(&ApproxWeight::from_integer(&const_val))
+        } else {
+            self.find_const_wid(&const_val)
+        };
+        if self.contains_wid(&wid) {
             return Some(wid);
         }
         return None;
     }
 }
 
+/// Parses a node id of the form `<icfg_clone_id>:<cfg_clone_id>:<address_hex>`.
+fn parse_cache_node_id(s: &str) -> Option<NodeId> {
+    let mut parts = s.split(':');
+    let icfg = parts.next()?.parse::<i32>().ok()?;
+    let cfg = parts.next()?.parse::<i32>().ok()?;
+    let addr = u64::from_str_radix(parts.next()?, 16).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(NodeId::new(icfg, cfg, addr))
+}
+
 /// PUBLICLY USABLE BY TESTING MODULE ONLY.
 pub fn digits_to_integer_wid<T: UnsignedPrimitive>(v: &[T], order: Order) -> (Integer, WeightID) {
     let const_val = Integer::from_digits(v, order);
-    let mut hasher = DefaultHasher::new();
-    const_val.hash(&mut hasher);
-    let wid = WeightID::new(hasher.finish());
+    let wid = WeightID::new(const_hash(&const_val));
     (const_val, wid)
 }
 
 /// PUBLICLY USABLE BY TESTING MODULE ONLY.
 pub fn u128_to_integer_wid(v: u128) -> (Integer, WeightID) {
     let const_val = Integer::from(v);
-    let mut hasher = DefaultHasher::new();
-    const_val.hash(&mut hasher);
-    let wid = WeightID::new(hasher.finish());
+    let wid = WeightID::new(const_hash(&const_val));
     (const_val, wid)
 }
 
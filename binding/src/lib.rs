@@ -7,7 +7,10 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-use helper::rz::{parse_bda_entry_list, parse_bda_range_conf_val, parse_bda_timeout};
+use helper::rz::{
+    parse_bda_entry_list, parse_bda_exclude_list, parse_bda_range_conf_val, parse_bda_sink_list,
+    parse_bda_timeout, resolve_bda_entries, resolve_bda_ranges, AddrResolver,
+};
 use std::{
     ffi::{CStr, CString},
     ops::RangeInclusive,
@@ -227,6 +230,29 @@ macro_rules! get_bda_config_val_i {
     }};
 }
 
+macro_rules! get_osprey_config {
+    ($self:expr) => {{
+        let osprey_name = CString::new("osprey").expect("Conversion failed.");
+        let osprey_config = ht_sp_find(
+            uderef!($self.ptr).plugins_config,
+            osprey_name.as_ptr(),
+            std::ptr::null_mut(),
+        );
+        assert!(
+            osprey_config != std::ptr::null_mut(),
+            "Failed to get plugin config."
+        );
+        osprey_config as *mut rz_config_t
+    }};
+}
+
+macro_rules! get_osprey_config_val_i {
+    ($self:expr, $entry_name:expr) => {{
+        let n = CString::new($entry_name).expect("Conversion failed.");
+        unsafe { rz_config_get_i(get_osprey_config!($self), n.as_ptr()) }
+    }};
+}
+
 /// Wrapper struct around a *mut rz_core_t
 /// Clone and Copy should definitely not be implemented for this struct.
 pub struct RzCoreWrapper {
@@ -249,14 +275,25 @@ impl RzCoreWrapper {
         unsafe { rz_config_set(get_bda_config!(self), k.as_ptr(), v.as_ptr()) };
     }
 
+    /// Returns the value of [key] in the Rizin core configuration.
+    pub fn get_core_conf_val(&self, key: &str) -> String {
+        let k = CString::new(key).expect("Conversion failed.");
+        c_to_str(unsafe { rz_config_get(pderef!(self.ptr).config, k.as_ptr()) })
+    }
+
+    /// Sets [key] in the Rizin core configuration to [val].
+    pub fn set_core_conf_val(&self, key: &str, val: &str) {
+        let k = CString::new(key).expect("Conversion failed.");
+        let v = CString::new(val).expect("Conversion failed.");
+        unsafe { rz_config_set(pderef!(self.ptr).config, k.as_ptr(), v.as_ptr()) };
+    }
+
     pub fn get_bda_analysis_range(&self) -> Option<Vec<RangeInclusive<Address>>> {
         let c = get_bda_config_val_str!(self, "plugins.bda.sampling.range");
         assert!(c != std::ptr::null_mut(), "Failed to get range.");
-        if let Some(ranges) = parse_bda_range_conf_val(c_to_str(c)) {
-            Some(Vec::from_iter(ranges.into_iter().map(|(b, e)| b..=e)))
-        } else {
-            None
-        }
+        let items = parse_bda_range_conf_val(c_to_str(c))?;
+        let ranges = resolve_bda_ranges(&items, self)?;
+        Some(Vec::from_iter(ranges.into_iter().map(|(b, e)| b..=e)))
     }
 
     pub fn get_bda_analysis_malloc_pattern(&self) -> String {
@@ -271,10 +308,111 @@ impl RzCoreWrapper {
         c_to_str(c)
     }
 
+    pub fn get_bda_analysis_free_pattern(&self) -> String {
+        let c = get_bda_config_val_str!(self, "plugins.bda.free_name_pattern");
+        assert!(c != std::ptr::null_mut(), "Failed to get the regex.");
+        c_to_str(c)
+    }
+
+    pub fn get_bda_taint_sinks(&self) -> Option<Vec<(String, Vec<usize>)>> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.taint.sinks");
+        assert!(c != std::ptr::null_mut(), "Failed to get sinks.");
+        parse_bda_sink_list(c_to_str(c))
+    }
+
+    /// Returns the addresses and name patterns of procedures excluded from sampling.
+    pub fn get_bda_excluded_procedures(&self) -> Option<(Vec<u64>, Vec<String>)> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.sampling.exclude");
+        assert!(
+            c != std::ptr::null_mut(),
+            "Failed to get excluded procedures."
+        );
+        let (exprs, patterns) = parse_bda_exclude_list(c_to_str(c))?;
+        Some((resolve_bda_entries(&exprs, self)?, patterns))
+    }
+
     pub fn get_bda_analysis_entries(&self) -> Option<Vec<u64>> {
         let c = get_bda_config_val_str!(self, "plugins.bda.entries");
         assert!(c != std::ptr::null_mut(), "Failed to get entries.");
-        parse_bda_entry_list(c_to_str(c))
+        let exprs = parse_bda_entry_list(c_to_str(c))?;
+        resolve_bda_entries(&exprs, self)
+    }
+
+    /// Returns the path of the weight cache file.
+    /// Or None if weights should not be persisted.
+    pub fn get_bda_weight_cache_path(&self) -> Option<String> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.weight_cache");
+        assert!(
+            c != std::ptr::null_mut(),
+            "Failed to get weight cache path."
+        );
+        let path = c_to_str(c);
+        if path.is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Returns the path of the function summary file.
+    /// Or None if no summaries should be loaded or saved.
+    pub fn get_bda_summaries_path(&self) -> Option<String> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.summaries");
+        assert!(c != std::ptr::null_mut(), "Failed to get summaries path.");
+        let path = c_to_str(c);
+        if path.is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Returns the directory shared libraries are loaded from.
+    /// Or None if no shared libraries should be loaded.
+    pub fn get_bda_sysroot(&self) -> Option<String> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.sysroot");
+        assert!(c != std::ptr::null_mut(), "Failed to get sysroot.");
+        let path = c_to_str(c);
+        if path.is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Returns the path the sampled iCFG is exported to.
+    /// Or None if it should not be exported.
+    pub fn get_bda_export_graph_path(&self) -> Option<String> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.export_graph");
+        assert!(
+            c != std::ptr::null_mut(),
+            "Failed to get graph export path."
+        );
+        let path = c_to_str(c);
+        if path.is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Returns the path of the drcov file the hit counts are written to.
+    /// Or None if no coverage file should be written.
+    pub fn get_bda_coverage_file(&self) -> Option<String> {
+        let c = get_bda_config_val_str!(self, "plugins.bda.coverage.file");
+        assert!(
+            c != std::ptr::null_mut(),
+            "Failed to get coverage file path."
+        );
+        let path = c_to_str(c);
+        if path.is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    pub fn get_bda_coverage_annotate(&self) -> bool {
+        get_bda_config_val_b!(self, "plugins.bda.coverage.annotate")
+    }
+
+    pub fn get_bda_approx_weights(&self) -> bool {
+        get_bda_config_val_b!(self, "plugins.bda.approx_weights")
     }
 
     pub fn get_bda_skip_questions(&self) -> bool {
@@ -296,6 +434,19 @@ impl RzCoreWrapper {
         get_bda_config_val_i!(self, "plugins.bda.sampling.unknown_xref_threshold") as usize
     }
 
+    pub fn get_bda_scope_depth(&self) -> usize {
+        get_bda_config_val_i!(self, "plugins.bda.scope.depth") as usize
+    }
+
+    pub fn get_bda_call_string_len(&self) -> usize {
+        get_bda_config_val_i!(self, "plugins.bda.post.call_string_len") as usize
+    }
+
+    /// Returns the minimal confidence of dependencies in percent.
+    pub fn get_bda_min_confidence(&self) -> u64 {
+        get_bda_config_val_i!(self, "plugins.bda.post.min_confidence")
+    }
+
     pub fn get_bda_threads(&self) -> usize {
         get_bda_config_val_i!(self, "plugins.bda.threads") as usize
     }
@@ -314,10 +465,62 @@ impl RzCoreWrapper {
         c as usize
     }
 
+    /// Returns the minimal code probability of CFG nodes in percent.
+    pub fn get_bda_min_code_probability(&self) -> u64 {
+        get_bda_config_val_i!(self, "plugins.bda.min_code_probability")
+    }
+
+    pub fn get_bda_seed(&self) -> u64 {
+        get_bda_config_val_i!(self, "plugins.bda.seed")
+    }
+
+    pub fn get_osprey_iterations(&self) -> usize {
+        get_osprey_config_val_i!(self, "plugins.osprey.iterations") as usize
+    }
+
+    /// Returns the minimal probability of recovered variables in percent.
+    pub fn get_osprey_min_probability(&self) -> u64 {
+        get_osprey_config_val_i!(self, "plugins.osprey.min_probability")
+    }
+
+    /// Returns the minimal probability of exported types in percent.
+    pub fn get_osprey_types_min_probability(&self) -> u64 {
+        get_osprey_config_val_i!(self, "plugins.osprey.types.min_probability")
+    }
+
     pub fn get_arch_bits(&self) -> usize {
         pderef!(self.get_analysis()).bits as usize
     }
 
+    /// Returns the name of the current analysis plugin (e.g. "x86").
+    pub fn get_arch_name(&self) -> String {
+        c_to_str(pderef!(self.get_cur()).name)
+    }
+
+    /// Returns the executable sections of the current binary as (address, size).
+    pub fn get_exec_sections(&self) -> Vec<(Address, u64)> {
+        let mut sections = Vec::new();
+        unsafe {
+            let obj = rz_bin_cur_object(pderef!(self.ptr).bin);
+            if obj.is_null() {
+                return sections;
+            }
+            for sec in cpvec_to_vec::<RzBinSection>(rz_bin_object_get_sections_all(obj)) {
+                let sec = uderef!(sec);
+                if !sec.is_segment && sec.vsize > 0 && (sec.perm & RZ_PERM_X) != 0 {
+                    sections.push((sec.vaddr, sec.vsize));
+                }
+            }
+        }
+        sections
+    }
+
+    /// Returns the entry addresses of all functions known to Rizin.
+    pub fn get_function_addrs(&self) -> Vec<Address> {
+        let fcns = unsafe { rz_analysis_function_list(self.get_analysis()) };
+        list_to_vec::<Address>(fcns, |e| pderef!(e as *mut RzAnalysisFunction).addr)
+    }
+
     pub fn get_analysis_op(&self, addr: u64) -> *mut RzAnalysisOp {
         let iop: *mut RzAnalysisOp = unsafe {
             rz_core_analysis_op(
@@ -355,6 +558,36 @@ impl RzCoreWrapper {
         Some(buf)
     }
 
+    /// Writes [data] at [addr]. The IO cache must be enabled for non-writable files.
+    pub fn write_io_at(&self, addr: u64, data: &[u8]) -> bool {
+        unsafe { rz_io_write_at(self.get_io(), addr, data.as_ptr(), data.len() as i32) }
+    }
+
+    /// Returns true if [addr] is mapped executable.
+    pub fn is_exec_addr(&self, addr: u64) -> bool {
+        let map = unsafe { rz_io_map_get_at(self.get_io(), addr) };
+        !map.is_null() && (pderef!(map).perm & RZ_PERM_X as i32) != 0
+    }
+
+    /// Returns true if [addr] is mapped readable, but not writable.
+    pub fn is_read_only_addr(&self, addr: u64) -> bool {
+        let map = unsafe { rz_io_map_get_at(self.get_io(), addr) };
+        !map.is_null()
+            && (pderef!(map).perm & RZ_PERM_R as i32) != 0
+            && (pderef!(map).perm & RZ_PERM_W as i32) == 0
+    }
+
+    /// Returns the lowest address above [from], where [size] bytes can be mapped.
+    pub fn get_free_map_addr(&self, from: u64, size: u64) -> u64 {
+        unsafe { rz_io_map_next_available(self.get_io(), from, size, 0x1000) }
+    }
+
+    /// Opens the binary at [path] and loads it at the base address [base].
+    pub fn load_bin_file(&self, path: &str, base: u64) -> bool {
+        let c_path = CString::new(path).expect("Conversion failed.");
+        unsafe { rz_core_file_open_load(self.ptr, c_path.as_ptr(), base, RZ_PERM_RX as i32, false) }
+    }
+
     pub fn get_iword(&self, addr: u64) -> *mut RzAnalysisInsnWord {
         let iword_decoder = self.get_iword_decoder();
         unsafe {
@@ -398,6 +631,22 @@ impl RzCoreWrapper {
         }
     }
 
+    /// Returns the file path and base address of the currently opened binary.
+    pub fn get_cur_bin_file(&self) -> Option<(String, u64)> {
+        let bin = pderef!(self.ptr).bin;
+        let bf = unsafe { rz_bin_cur(bin) };
+        if bf.is_null() {
+            return None;
+        }
+        let file = pderef!(bf).file;
+        let path = if file.is_null() {
+            String::new()
+        } else {
+            c_to_str(file)
+        };
+        Some((path, unsafe { rz_bin_get_baddr(bin) }))
+    }
+
     pub fn get_ptr(&self) -> *mut rz_core_t {
         self.ptr
     }
@@ -475,6 +724,85 @@ impl RzCoreWrapper {
     }
 }
 
+extern "C" fn collect_flag_range(fi: *mut RzFlagItem, user: *mut std::os::raw::c_void) -> bool {
+    let ranges = unsafe { &mut *(user as *mut Vec<(u64, u64)>) };
+    let fi = pderef!(fi);
+    ranges.push((fi.offset, fi.offset.saturating_add(fi.size.max(1) - 1)));
+    true
+}
+
+/// Resolves the names of range and entry expressions with the flags, functions
+/// and sections known to Rizin.
+impl AddrResolver for RzCoreWrapper {
+    fn resolve_addr(&self, name: &str) -> Option<u64> {
+        if name == "$$" {
+            return Some(pderef!(self.ptr).offset);
+        }
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            let fcn = rz_analysis_get_function_byname(self.get_analysis(), c_name.as_ptr());
+            if !fcn.is_null() {
+                return Some(uderef!(fcn).addr);
+            }
+            let flag = rz_flag_get(self.get_flags(), c_name.as_ptr());
+            if !flag.is_null() {
+                return Some(uderef!(flag).offset);
+            }
+        }
+        None
+    }
+
+    fn resolve_extent(&self, name: &str) -> Option<(u64, u64)> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            let obj = rz_bin_cur_object(pderef!(self.ptr).bin);
+            if !obj.is_null() {
+                for sec in cpvec_to_vec::<RzBinSection>(rz_bin_object_get_sections_all(obj)) {
+                    let sec = uderef!(sec);
+                    if sec.vsize > 0 && !sec.name.is_null() && c_to_str(sec.name) == name {
+                        return Some((sec.vaddr, sec.vaddr + sec.vsize - 1));
+                    }
+                }
+            }
+            let fcn = rz_analysis_get_function_byname(self.get_analysis(), c_name.as_ptr());
+            if !fcn.is_null() {
+                let min = rz_analysis_function_min_addr(fcn);
+                let max = rz_analysis_function_max_addr(fcn);
+                return Some((min, max.saturating_sub(1).max(min)));
+            }
+            let flag = rz_flag_get(self.get_flags(), c_name.as_ptr());
+            if !flag.is_null() {
+                let flag = uderef!(flag);
+                return Some((
+                    flag.offset,
+                    flag.offset.saturating_add(flag.size.max(1) - 1),
+                ));
+            }
+        }
+        None
+    }
+
+    fn resolve_flag_space(&self, name: &str) -> Option<Vec<(u64, u64)>> {
+        let c_name = CString::new(name).ok()?;
+        let mut ranges = Vec::<(u64, u64)>::new();
+        unsafe {
+            let flags = self.get_flags();
+            null_check!(flags);
+            let space = rz_spaces_get(&mut (*flags).spaces, c_name.as_ptr());
+            if space.is_null() {
+                return None;
+            }
+            rz_flag_foreach_space(
+                flags,
+                space,
+                Some(collect_flag_range),
+                &mut ranges as *mut Vec<(u64, u64)> as *mut std::os::raw::c_void,
+            );
+        }
+        Some(ranges)
+    }
+}
+
 pub fn effect_to_str(effect: *mut rz_il_op_effect_t) -> String {
     unsafe {
         let sb = rz_strbuf_new(std::ptr::null_mut());
@@ -556,6 +884,13 @@ pub fn init_rizin_instance(binary: &str) -> *mut RzCore {
     core
 }
 
+/// Prints [msg] to the Rizin console.
+/// So it can be piped or grepped like the output of any other command.
+pub fn rz_cons_out(mut msg: String) {
+    msg.push('\0');
+    unsafe { rz_cons_print(msg.as_ptr().cast()) };
+}
+
 pub fn rz_notify_begin(rz_core: GRzCore, mut msg: String) {
     msg.push('\0');
     let core = rz_core.lock().unwrap();
@@ -5,9 +5,14 @@ pub mod expression;
 pub mod matrix;
 pub mod num;
 pub mod progress;
+pub mod rng;
 pub mod rz;
 pub mod set_map;
 pub mod spinner;
+pub mod sysroot;
+mod test_rng;
+mod test_rz;
+mod test_sysroot;
 pub mod timer;
 pub mod user;
 pub mod vec_map;
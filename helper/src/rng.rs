@@ -0,0 +1,21 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Returns a new random number generator seeded with [seed].
+/// If it is 0, the generator is seeded from the OS.
+pub fn new_rng(seed: u64) -> StdRng {
+    if seed == 0 {
+        return StdRng::from_entropy();
+    }
+    StdRng::seed_from_u64(seed)
+}
+
+/// Returns a new generator, seeded with the next number of [rng].
+/// Each path gets its own generator this way. So its numbers only depend on
+/// the seed of the run and the order the paths are forked in.
+/// Not on the thread or the order the paths are interpreted in.
+pub fn fork_rng(rng: &mut StdRng) -> StdRng {
+    StdRng::seed_from_u64(rng.gen())
+}
@@ -1,63 +1,479 @@
 // SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
 // SPDX-License-Identifier: LGPL-3.0-only
 
-pub fn parse_bda_range_conf_val(val: String) -> Option<Vec<(u64, u64)>> {
+/// Resolves the names used in address expressions.
+pub trait AddrResolver {
+    /// Returns the address of a symbol, function or flag. `$$` is the current seek.
+    fn resolve_addr(&self, name: &str) -> Option<u64>;
+    /// Returns the first and last address of a section, function, symbol or flag.
+    fn resolve_extent(&self, name: &str) -> Option<(u64, u64)>;
+    /// Returns the first and last address of every flag in the flag space [name].
+    fn resolve_flag_space(&self, name: &str) -> Option<Vec<(u64, u64)>>;
+}
+
+/// An address expression. A sum of hex numbers and names, e.g. `sym.main+0x10`.
+/// Hex numbers are prefixed with `0x` or start with a decimal digit.
+/// So names like `add` or `cafe` are not taken for numbers.
+/// `+` and `-` only separate terms, if they are next to whitespace, a parenthesis,
+/// a number or are followed by a decimal digit. Otherwise they are part of the name
+/// (e.g. `operator+` or `sym.foo-bar`).
+/// Names are resolved with an [AddrResolver] when the expression is evaluated.
+/// So they still point to the right address after the binary was rebased or rebuilt.
+/// A name which doesn't resolve, but is a valid hex number (e.g. `a000`), is taken as
+/// that number. So hex numbers without prefix keep working as in older configurations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddrExpr {
+    Num(u64),
+    /// Symbol, function or flag name. Or `$$` for the current seek.
+    Name(String),
+    Add(Box<AddrExpr>, Box<AddrExpr>),
+    Sub(Box<AddrExpr>, Box<AddrExpr>),
+}
+
+impl AddrExpr {
+    pub fn eval(&self, resolver: &dyn AddrResolver) -> Option<u64> {
+        match self {
+            AddrExpr::Num(n) => Some(*n),
+            AddrExpr::Name(name) => {
+                let addr = resolver.resolve_addr(name).or_else(|| parse_bare_hex(name));
+                if addr.is_none() {
+                    println!("Unknown symbol or flag '{}'", name);
+                }
+                addr
+            }
+            AddrExpr::Add(lhs, rhs) => {
+                let sum = lhs.eval(resolver)?.checked_add(rhs.eval(resolver)?);
+                if sum.is_none() {
+                    println!("Address overflow in '{}'", self);
+                }
+                sum
+            }
+            AddrExpr::Sub(lhs, rhs) => {
+                let diff = lhs.eval(resolver)?.checked_sub(rhs.eval(resolver)?);
+                if diff.is_none() {
+                    println!("Address underflow in '{}'", self);
+                }
+                diff
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AddrExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddrExpr::Num(n) => write!(f, "{:#x}", n),
+            AddrExpr::Name(name) => write!(f, "{}", name),
+            AddrExpr::Add(lhs, rhs) => write!(f, "({}+{})", lhs, rhs),
+            AddrExpr::Sub(lhs, rhs) => write!(f, "({}-{})", lhs, rhs),
+        }
+    }
+}
+
+/// A single element of the range list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RangeExpr {
+    /// `<expr>-<expr>`. Both ends are inclusive.
+    Span(AddrExpr, AddrExpr),
+    /// The extent of a section, function, symbol or flag. E.g. `.text` or `main`.
+    Extent(String),
+    /// All flags of a flag space: `fs:<name>`.
+    FlagSpace(String),
+}
+
+/// A range of the range list. Excluded ranges are prefixed with `!`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeItem {
+    pub exclude: bool,
+    pub range: RangeExpr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Atom(String),
+    Plus,
+    Minus,
+    LParen,
+    RParen,
+}
+
+/// Checks if the `+` or `-` following [atom] and followed by [next] is an operator.
+/// [atom] is empty if the operator follows whitespace, a parenthesis or another operator.
+fn is_operator(atom: &str, next: Option<&char>) -> bool {
+    atom.is_empty()
+        || parse_hex(atom).is_some()
+        || next.is_some_and(|c| c.is_whitespace() || *c == '(' || c.is_ascii_digit())
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut atom = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let t = match c {
+            '+' if is_operator(&atom, chars.peek()) => Some(Token::Plus),
+            '-' if is_operator(&atom, chars.peek()) => Some(Token::Minus),
+            '(' => Some(Token::LParen),
+            ')' => Some(Token::RParen),
+            c if c.is_whitespace() => None,
+            c => {
+                atom.push(c);
+                continue;
+            }
+        };
+        if !atom.is_empty() {
+            tokens.push(Token::Atom(std::mem::take(&mut atom)));
+        }
+        if let Some(t) = t {
+            tokens.push(t);
+        }
+    }
+    if !atom.is_empty() {
+        tokens.push(Token::Atom(atom));
+    }
+    tokens
+}
+
+/// Parses a hex number. It must be prefixed with `0x` or start with a decimal digit.
+fn parse_hex(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    if !is_num_atom(s) {
+        return None;
+    }
+    u64::from_str_radix(s, 16).ok()
+}
+
+/// Parses a hex number without `0x` prefix, e.g. `a000`.
+/// It is only used for names, which don't resolve.
+fn parse_bare_hex(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(s, 16).ok()
+}
+
+/// Parses a range of hex numbers without `0x` prefix, e.g. `a000-b000`.
+/// It is only used for extent names, which don't resolve.
+fn parse_bare_hex_range(s: &str) -> Option<(u64, u64)> {
+    let (begin, end) = s.split_once('-')?;
+    Some((parse_bare_hex(begin)?, parse_bare_hex(end)?))
+}
+
+/// Checks if [s] is meant to be a number. Names never start with a decimal digit.
+fn is_num_atom(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Recursive descent parser of address expressions.
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn new(s: &str) -> ExprParser {
+        ExprParser {
+            tokens: tokenize(s),
+            pos: 0,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn next_is(&self, t: Token) -> bool {
+        self.tokens.get(self.pos) == Some(&t)
+    }
+
+    /// Parses a sum of terms. If [allow_sub] is false, a `-` ends the expression.
+    /// Because it separates the ends of a range.
+    fn parse_expr(&mut self, allow_sub: bool) -> Option<AddrExpr> {
+        let mut lhs = self.parse_term()?;
+        loop {
+            if self.next_is(Token::Plus) {
+                self.pos += 1;
+                lhs = AddrExpr::Add(Box::new(lhs), Box::new(self.parse_term()?));
+            } else if allow_sub && self.next_is(Token::Minus) {
+                self.pos += 1;
+                lhs = AddrExpr::Sub(Box::new(lhs), Box::new(self.parse_term()?));
+            } else {
+                return Some(lhs);
+            }
+        }
+    }
+
+    fn parse_term(&mut self) -> Option<AddrExpr> {
+        match self.tokens.get(self.pos)? {
+            Token::Atom(a) => {
+                let term = match parse_hex(a) {
+                    Some(n) => AddrExpr::Num(n),
+                    None if is_num_atom(a) => return None,
+                    None => AddrExpr::Name(a.clone()),
+                };
+                self.pos += 1;
+                Some(term)
+            }
+            Token::LParen => {
+                self.pos += 1;
+                let expr = self.parse_expr(true)?;
+                if !self.next_is(Token::RParen) {
+                    return None;
+                }
+                self.pos += 1;
+                Some(expr)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn parse_range_item(item: &str) -> Option<RangeItem> {
+    let item = item.trim();
+    let (exclude, item) = match item.strip_prefix('!') {
+        Some(i) => (true, i.trim()),
+        None => (false, item),
+    };
+    if let Some(space) = item.strip_prefix("fs:") {
+        if space.is_empty() {
+            return None;
+        }
+        return Some(RangeItem {
+            exclude,
+            range: RangeExpr::FlagSpace(space.to_owned()),
+        });
+    }
+    let mut parser = ExprParser::new(item);
+    if let [Token::Atom(name)] = parser.tokens.as_slice() {
+        if !is_num_atom(name) {
+            return Some(RangeItem {
+                exclude,
+                range: RangeExpr::Extent(name.clone()),
+            });
+        }
+    }
+    let begin = parser.parse_expr(false)?;
+    if !parser.next_is(Token::Minus) {
+        return None;
+    }
+    parser.pos += 1;
+    let end = parser.parse_expr(false)?;
+    if !parser.at_end() {
+        return None;
+    }
+    Some(RangeItem {
+        exclude,
+        range: RangeExpr::Span(begin, end),
+    })
+}
+
+/// Parses the comma separated list of address ranges.
+/// Each range is either
+///   - `<expr>-<expr>`, the ends are address expressions (see [AddrExpr]).
+///     Subtractions must be put in parentheses: `(main-0x10)-(main+0x20)`.
+///     Between two names the `-` needs whitespace: `main - sym.foo`.
+///   - `<name>`, the extent of a section, function, symbol or flag (e.g. `.text`).
+///     If no such name exists, `<hex>-<hex>` without `0x` prefixes (e.g. `a000-b000`)
+///     is taken as range of hex numbers.
+///   - `fs:<name>`, all flags of a flag space.
+///
+/// Ranges prefixed with `!` are excluded from the other ranges.
+/// The names are not resolved here, only the syntax is checked. See [resolve_bda_ranges].
+pub fn parse_bda_range_conf_val(val: String) -> Option<Vec<RangeItem>> {
     let mut vec = Vec::new();
     for s in val.split(',') {
-        let r = s.trim();
-        let mut range: (u64, u64) = (0, 0);
-        if !s.contains('-') {
+        let Some(item) = parse_range_item(s) else {
             println!(
-                "Range list must be of the form: '<hex_num>-<hex_num>, <hex_num>-<hex_num>, ...'"
+                "Range list must be of the form: '[!]<expr>-<expr>, [!]<section|symbol>, [!]fs:<flag space>, ...'"
             );
+            println!("Failed to parse '{}'", s.trim());
             return None;
+        };
+        vec.push(item);
+    }
+    Some(vec)
+}
+
+/// Removes [excluded] from [ranges].
+fn subtract_ranges(ranges: Vec<(u64, u64)>, excluded: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut result = ranges;
+    for (eb, ee) in excluded.iter() {
+        let mut remaining = Vec::new();
+        for (b, e) in result.into_iter() {
+            if e < *eb || b > *ee {
+                remaining.push((b, e));
+                continue;
+            }
+            if b < *eb {
+                remaining.push((b, eb - 1));
+            }
+            if e > *ee {
+                remaining.push((ee + 1, e));
+            }
         }
-        for (i, n) in r.split("-").enumerate() {
-            if i > 1 {
-                println!("Range list must be of the form: '<hex_num>-<hex_num>, <hex_num>-<hex_num>, ...'");
-                return None;
+        result = remaining;
+    }
+    result
+}
+
+/// Resolves the names in the range list [items] and returns the ranges to analyse.
+/// If the list only contains exclusions, they are excluded from the whole address space.
+pub fn resolve_bda_ranges(
+    items: &[RangeItem],
+    resolver: &dyn AddrResolver,
+) -> Option<Vec<(u64, u64)>> {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    for item in items.iter() {
+        let ranges = match &item.range {
+            RangeExpr::Span(begin, end) => {
+                let range = (begin.eval(resolver)?, end.eval(resolver)?);
+                if range.0 > range.1 {
+                    println!(
+                        "Invalid range: [{:#x},{:#x}] - (begin > end)",
+                        range.0, range.1
+                    );
+                    return None;
+                }
+                vec![range]
             }
-            if let Ok(n) = u64::from_str_radix(n.trim_start_matches("0x"), 16) {
-                if i == 0 {
-                    range.0 = n;
-                } else if i == 1 {
-                    range.1 = n;
+            RangeExpr::Extent(name) => {
+                let Some(range) = resolver
+                    .resolve_extent(name)
+                    .or_else(|| parse_bare_hex_range(name))
+                else {
+                    println!("Unknown section, symbol or flag '{}'", name);
+                    return None;
+                };
+                if range.0 > range.1 {
+                    println!(
+                        "Invalid range: [{:#x},{:#x}] - (begin > end)",
+                        range.0, range.1
+                    );
+                    return None;
                 }
-            } else {
-                println!("Failed to parse '{}'", n);
-                return None;
+                vec![range]
             }
+            RangeExpr::FlagSpace(name) => {
+                let Some(ranges) = resolver.resolve_flag_space(name) else {
+                    println!("Unknown flag space '{}'", name);
+                    return None;
+                };
+                ranges
+            }
+        };
+        if item.exclude {
+            excluded.extend(ranges);
+        } else {
+            included.extend(ranges);
         }
-        if range.0 > range.1 {
-            println!(
-                "Invalid range: [{:#x},{:#x}] - (begin > end)",
-                range.0, range.1
-            );
-            return None;
-        }
+    }
+    if included.is_empty() {
+        included.push((0, u64::MAX));
+    }
+    Some(subtract_ranges(included, &excluded))
+}
 
-        vec.push(range);
+/// Parses the comma separated list of entry points.
+/// Each entry is an address expression (see [AddrExpr]), e.g. `main, sym.handler+0x4, 0x1000`.
+/// The names are not resolved here, only the syntax is checked. See [resolve_bda_entries].
+pub fn parse_bda_entry_list(val: String) -> Option<Vec<AddrExpr>> {
+    let mut vec = Vec::new();
+    if val.is_empty() {
+        return Some(vec);
     }
+    for str in val.split(',') {
+        let mut parser = ExprParser::new(str);
+        match parser.parse_expr(true) {
+            Some(expr) if parser.at_end() => vec.push(expr),
+            _ => {
+                println!("Failed to parse '{}'", str);
+                return None;
+            }
+        }
+    }
+
     Some(vec)
 }
 
-pub fn parse_bda_entry_list(val: String) -> Option<Vec<u64>> {
+/// Resolves the names in the entry list [exprs] and returns the entry addresses.
+pub fn resolve_bda_entries(exprs: &[AddrExpr], resolver: &dyn AddrResolver) -> Option<Vec<u64>> {
+    exprs.iter().map(|e| e.eval(resolver)).collect()
+}
+
+/// Parses the list of taint sinks.
+/// Each sink is a function name pattern, followed by the argument positions
+/// which must not receive input data: `<pattern>:<arg>[:<arg>...], ...`.
+/// Argument positions start at 0.
+pub fn parse_bda_sink_list(val: String) -> Option<Vec<(String, Vec<usize>)>> {
     let mut vec = Vec::new();
     if val.is_empty() {
         return Some(vec);
     }
-    for str in val.split(',') {
-        if let Ok(n) = u64::from_str_radix(str.trim().trim_start_matches("0x"), 16) {
-            vec.push(n);
-        } else {
-            println!("Failed to parse '{}'", str);
+    for sink in val.split(',') {
+        let mut parts = sink.trim().split(':');
+        let pattern = parts.next().unwrap_or_default();
+        if pattern.is_empty() {
+            println!("Sink list must be of the form: '<pattern>:<arg>[:<arg>...], ...'");
             return None;
         }
+        let mut args = Vec::new();
+        for a in parts {
+            if let Ok(n) = a.trim().parse::<usize>() {
+                args.push(n);
+            } else {
+                println!("Failed to parse argument position '{}'", a);
+                return None;
+            }
+        }
+        if args.is_empty() {
+            println!("Sink '{}' has no argument positions.", pattern);
+            return None;
+        }
+        vec.push((pattern.to_owned(), args));
     }
 
     Some(vec)
 }
 
+/// Parses the list of procedures the path sampler must not enter.
+/// Each element is either the address of a procedure or a regex matching
+/// the whole procedure name: `<expr>|<regex>, ...`.
+/// Addresses are address expressions (see [AddrExpr]), which start with a
+/// decimal digit or `$$` (e.g. `0x4010`, `$$`, `0x4000+0x10`).
+/// Returns the addresses and the patterns.
+/// The addresses are not resolved here, only the syntax is checked. See [resolve_bda_entries].
+pub fn parse_bda_exclude_list(val: String) -> Option<(Vec<AddrExpr>, Vec<String>)> {
+    let mut addrs = Vec::new();
+    let mut patterns = Vec::new();
+    if val.is_empty() {
+        return Some((addrs, patterns));
+    }
+    for e in val.split(',') {
+        let e = e.trim();
+        if e.is_empty() {
+            println!("Exclusion list must be of the form: '<address>|<regex>, ...'");
+            return None;
+        }
+        if is_num_atom(e) || e.starts_with("$$") {
+            let mut parser = ExprParser::new(e);
+            match parser.parse_expr(true) {
+                Some(expr) if parser.at_end() => addrs.push(expr),
+                _ => {
+                    println!("Failed to parse '{}'", e);
+                    return None;
+                }
+            }
+            continue;
+        }
+        patterns.push(e.to_owned());
+    }
+
+    Some((addrs, patterns))
+}
+
 macro_rules! nope {
     () => {
         println!("Sure mate, I'd be delighted to welcome you back in a few billion years.");
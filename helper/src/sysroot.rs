@@ -0,0 +1,50 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::path::{Path, PathBuf};
+
+/// Directories of a system root, shared libraries are searched in.
+/// Their direct sub-directories are searched as well (e.g. `usr/lib/x86_64-linux-gnu`).
+const LIB_DIRS: [&str; 6] = [
+    "lib",
+    "lib64",
+    "usr/lib",
+    "usr/lib64",
+    "usr/local/lib",
+    "usr/local/lib64",
+];
+
+/// Returns the path of the shared library [name] in [sysroot].
+/// If [name] is a path, it is resolved relative to [sysroot].
+/// Otherwise the library directories of [sysroot] are searched.
+/// Returns None if the library was not found.
+pub fn find_library(sysroot: &Path, name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = sysroot.join(name.trim_start_matches('/'));
+        return path.is_file().then_some(path);
+    }
+    for dir in LIB_DIRS.iter().map(|d| sysroot.join(d)) {
+        let path = dir.join(name);
+        if path.is_file() {
+            return Some(path);
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut sub_dirs: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect();
+        // Directory iteration order is not defined.
+        sub_dirs.sort();
+        if let Some(path) = sub_dirs
+            .into_iter()
+            .map(|d| d.join(name))
+            .find(|p| p.is_file())
+        {
+            return Some(path);
+        }
+    }
+    None
+}
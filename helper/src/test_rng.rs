@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng};
+
+    use crate::rng::{fork_rng, new_rng};
+
+    fn sample(rng: &mut StdRng, n: usize) -> Vec<u64> {
+        (0..n).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let first = sample(&mut new_rng(0x5eed), 8);
+        assert_eq!(sample(&mut new_rng(0x5eed), 8), first);
+        assert_ne!(sample(&mut new_rng(0x5eee), 8), first);
+        // Seeded from the OS
+        assert_ne!(sample(&mut new_rng(0), 8), sample(&mut new_rng(0), 8));
+    }
+
+    #[test]
+    fn test_forked_rng() {
+        let mut run_rng = new_rng(0x5eed);
+        let mut path_rngs: Vec<StdRng> = (0..4).map(|_| fork_rng(&mut run_rng)).collect();
+        let paths: Vec<Vec<u64>> = path_rngs.iter_mut().map(|r| sample(r, 8)).collect();
+        assert_ne!(paths[0], paths[1]);
+
+        // The numbers of a path don't depend on the thread or order it is sampled in.
+        let mut run_rng = new_rng(0x5eed);
+        let mut path_rngs: Vec<StdRng> = (0..4).map(|_| fork_rng(&mut run_rng)).collect();
+        let mut last = path_rngs.pop().unwrap();
+        let last_thread = std::thread::spawn(move || sample(&mut last, 8));
+        for (i, rng) in path_rngs.iter_mut().enumerate().rev() {
+            assert_eq!(sample(rng, 8), paths[i]);
+        }
+        assert_eq!(last_thread.join().unwrap(), paths[3]);
+    }
+}
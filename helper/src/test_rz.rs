@@ -0,0 +1,187 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use crate::rz::{
+        parse_bda_entry_list, parse_bda_exclude_list, parse_bda_range_conf_val,
+        resolve_bda_entries, resolve_bda_ranges, AddrExpr, AddrResolver, RangeExpr,
+    };
+
+    struct TestResolver {}
+
+    impl AddrResolver for TestResolver {
+        fn resolve_addr(&self, name: &str) -> Option<u64> {
+            match name {
+                "$$" => Some(0x1100),
+                "main" => Some(0x1200),
+                "sym.foo" => Some(0x1400),
+                "add" => Some(0x1500),
+                "cafe" => Some(0x1600),
+                "sym.foo-bar" => Some(0x1700),
+                "operator+" => Some(0x1800),
+                _ => None,
+            }
+        }
+
+        fn resolve_extent(&self, name: &str) -> Option<(u64, u64)> {
+            match name {
+                ".text" => Some((0x1000, 0x1fff)),
+                "main" => Some((0x1200, 0x12ff)),
+                "cafe" => Some((0x1600, 0x16ff)),
+                _ => None,
+            }
+        }
+
+        fn resolve_flag_space(&self, name: &str) -> Option<Vec<(u64, u64)>> {
+            match name {
+                "imports" => Some(vec![(0x3000, 0x3007), (0x3010, 0x3017)]),
+                _ => None,
+            }
+        }
+    }
+
+    fn ranges(val: &str) -> Option<Vec<(u64, u64)>> {
+        resolve_bda_ranges(&parse_bda_range_conf_val(val.to_owned())?, &TestResolver {})
+    }
+
+    fn entries(val: &str) -> Option<Vec<u64>> {
+        resolve_bda_entries(&parse_bda_entry_list(val.to_owned())?, &TestResolver {})
+    }
+
+    #[test]
+    fn test_range_hex() {
+        assert_eq!(ranges("0x0-0xffffffffffffffff"), Some(vec![(0, u64::MAX)]));
+        assert_eq!(
+            ranges("1000-2000, 0x3000-0x3000"),
+            Some(vec![(0x1000, 0x2000), (0x3000, 0x3000)])
+        );
+        assert_eq!(ranges("0x2000-0x1000"), None);
+        assert_eq!(ranges("0x1000"), None);
+        assert_eq!(ranges(""), None);
+        assert_eq!(ranges("0x1000-0x2000-0x3000"), None);
+    }
+
+    #[test]
+    fn test_range_bare_hex() {
+        // Hex numbers without 0x prefix are accepted, if they are no known name.
+        assert_eq!(ranges("a000-b000"), Some(vec![(0xa000, 0xb000)]));
+        assert_eq!(ranges("a000-0xb000"), Some(vec![(0xa000, 0xb000)]));
+        assert_eq!(ranges("1000-b000"), Some(vec![(0x1000, 0xb000)]));
+        assert_eq!(ranges("cafe-ffff"), Some(vec![(0xcafe, 0xffff)]));
+        assert_eq!(ranges("b000-a000"), None);
+        assert_eq!(ranges("a000-xyz"), None);
+        assert_eq!(ranges("a000"), None);
+    }
+
+    #[test]
+    fn test_range_names() {
+        assert_eq!(ranges(".text"), Some(vec![(0x1000, 0x1fff)]));
+        assert_eq!(ranges("main"), Some(vec![(0x1200, 0x12ff)]));
+        assert_eq!(ranges("main - main+0x10"), Some(vec![(0x1200, 0x1210)]));
+        assert_eq!(
+            ranges("(main-0x100)-(sym.foo+0x4)"),
+            Some(vec![(0x1100, 0x1404)])
+        );
+        assert_eq!(ranges("$$-0x2000"), Some(vec![(0x1100, 0x2000)]));
+        assert_eq!(
+            ranges("fs:imports"),
+            Some(vec![(0x3000, 0x3007), (0x3010, 0x3017)])
+        );
+        assert_eq!(ranges("cafe"), Some(vec![(0x1600, 0x16ff)]));
+        // Names are preferred over bare hex numbers.
+        assert_eq!(ranges("add-cafe"), Some(vec![(0xadd, 0xcafe)]));
+        assert_eq!(ranges("add - cafe"), Some(vec![(0x1500, 0x1600)]));
+        assert_eq!(ranges(".data"), None);
+        assert_eq!(ranges("fs:strings"), None);
+        assert_eq!(ranges("unknown-0x2000"), None);
+        assert_eq!(ranges("(main-0x2000)-0x3000"), None);
+        assert!(parse_bda_range_conf_val("(main-0x10-0x20".to_owned()).is_none());
+        assert!(parse_bda_range_conf_val("fs:".to_owned()).is_none());
+        // Only the syntax is checked during parsing.
+        assert_eq!(
+            parse_bda_range_conf_val(".data".to_owned()).unwrap()[0].range,
+            RangeExpr::Extent(".data".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_range_exclusion() {
+        assert_eq!(
+            ranges(".text, !main"),
+            Some(vec![(0x1000, 0x11ff), (0x1300, 0x1fff)])
+        );
+        assert_eq!(
+            ranges(".text, !0x1000-0x1800, !0x1f00-0x2fff"),
+            Some(vec![(0x1801, 0x1eff)])
+        );
+        assert_eq!(ranges("!0x0-0xfff"), Some(vec![(0x1000, u64::MAX)]));
+        assert_eq!(ranges("main, !.text"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_entry_list() {
+        assert_eq!(entries(""), Some(vec![]));
+        assert_eq!(entries("0x1000, 2000"), Some(vec![0x1000, 0x2000]));
+        assert_eq!(
+            entries("main, sym.foo+0x4, $$, main-0x200+0x10"),
+            Some(vec![0x1200, 0x1404, 0x1100, 0x1010])
+        );
+        assert_eq!(entries("main-0x2000"), None);
+        assert_eq!(entries("unknown"), None);
+        assert_eq!(entries("main+"), None);
+        assert_eq!(entries("0xzz"), None);
+        assert_eq!(entries("1zz+main"), None);
+        assert_eq!(
+            parse_bda_entry_list("main+0x10".to_owned()),
+            Some(vec![AddrExpr::Add(
+                Box::new(AddrExpr::Name("main".to_owned())),
+                Box::new(AddrExpr::Num(0x10))
+            )])
+        );
+    }
+
+    #[test]
+    fn test_entry_names() {
+        // Names which are valid hex numbers without the 0x prefix.
+        assert_eq!(entries("add, cafe"), Some(vec![0x1500, 0x1600]));
+        assert_eq!(entries("f"), Some(vec![0xf]));
+        assert_eq!(entries("a000, a000+0x10"), Some(vec![0xa000, 0xa010]));
+        assert_eq!(entries("xyz"), None);
+        assert_eq!(entries("cafe+0x10, 0xcafe"), Some(vec![0x1610, 0xcafe]));
+        // + and - inside of names.
+        assert_eq!(
+            entries("sym.foo-bar, sym.foo-bar+0x4, operator+"),
+            Some(vec![0x1700, 0x1704, 0x1800])
+        );
+        assert_eq!(
+            entries("sym.foo-0x4, sym.foo - main, (sym.foo)-(0x4)"),
+            Some(vec![0x13fc, 0x200, 0x13fc])
+        );
+        assert_eq!(entries("sym.foo - bar"), None);
+    }
+
+    #[test]
+    fn test_exclude_list() {
+        assert_eq!(
+            parse_bda_exclude_list("".to_owned()),
+            Some((vec![], vec![]))
+        );
+        assert_eq!(
+            parse_bda_exclude_list("printf, .*log.*,0x4010".to_owned()),
+            Some((
+                vec![AddrExpr::Num(0x4010)],
+                vec!["printf".to_owned(), ".*log.*".to_owned()]
+            ))
+        );
+        // Addresses are resolved like entries.
+        let (addrs, _) = parse_bda_exclude_list("$$, 0x4000+0x10, 4020".to_owned()).unwrap();
+        assert_eq!(
+            resolve_bda_entries(&addrs, &TestResolver {}),
+            Some(vec![0x1100, 0x4010, 0x4020])
+        );
+        assert_eq!(parse_bda_exclude_list("0xzz".to_owned()), None);
+        assert_eq!(parse_bda_exclude_list("0x10+".to_owned()), None);
+        assert_eq!(parse_bda_exclude_list("printf,,puts".to_owned()), None);
+    }
+}
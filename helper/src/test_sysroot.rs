@@ -0,0 +1,37 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, File};
+
+    use crate::sysroot::find_library;
+
+    #[test]
+    fn test_find_library() {
+        let sysroot = std::env::temp_dir().join(format!("probana_sysroot_{}", std::process::id()));
+        create_dir_all(sysroot.join("lib64")).unwrap();
+        create_dir_all(sysroot.join("usr/lib/x86_64-linux-gnu")).unwrap();
+        File::create(sysroot.join("lib64/ld-linux-x86-64.so.2")).unwrap();
+        File::create(sysroot.join("usr/lib/x86_64-linux-gnu/libc.so.6")).unwrap();
+        File::create(sysroot.join("usr/lib/libfoo.so")).unwrap();
+
+        assert_eq!(
+            find_library(&sysroot, "libc.so.6"),
+            Some(sysroot.join("usr/lib/x86_64-linux-gnu/libc.so.6"))
+        );
+        assert_eq!(
+            find_library(&sysroot, "libfoo.so"),
+            Some(sysroot.join("usr/lib/libfoo.so"))
+        );
+        assert_eq!(
+            find_library(&sysroot, "/lib64/ld-linux-x86-64.so.2"),
+            Some(sysroot.join("lib64/ld-linux-x86-64.so.2"))
+        );
+        assert_eq!(find_library(&sysroot, "libbar.so"), None);
+        assert_eq!(find_library(&sysroot, "/lib/libc.so.6"), None);
+        // Directories are no libraries.
+        assert_eq!(find_library(&sysroot, "x86_64-linux-gnu"), None);
+        remove_dir_all(&sysroot).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use bda::flow_graphs::Address;
+use binding::{log_rizin, log_rz, GRzCore, LOG_INFO, LOG_WARN};
+
+use crate::{
+    recovery::ObjectRegion,
+    types::{TypedObject, VarType},
+};
+
+/// Defines the type of a typed object in Rizin and returns the name to refer to it.
+/// Arrays get a typedef, because variables can only be linked to named types.
+fn define_type(core: &GRzCore, name: &str, ty: &VarType) -> Option<String> {
+    let core = core.lock().expect("Should not be locked");
+    let type_name = match ty {
+        VarType::Array(..) => {
+            let type_name = format!("{}_t", name);
+            let def = format!("typedef {};", ty.to_c_decl(&type_name));
+            if !core.run_cmd(&format!("td \"{}\"", def)) {
+                log_rz!(
+                    LOG_WARN,
+                    Some("OSPREY"),
+                    format!("Failed to define: {}", def)
+                );
+                return None;
+            }
+            type_name
+        }
+        _ => ty.to_string(),
+    };
+    Some(type_name)
+}
+
+/// Returns the name of the stack variable at [offset] in the frame of [proc].
+fn get_stack_var_name(proc: Address, offset: i64) -> String {
+    let sign = if offset < 0 { "m" } else { "" };
+    format!("osprey_var_{:x}_{}{:x}", proc, sign, offset.unsigned_abs())
+}
+
+/// Exports the most probable types of the [objects] to Rizin.
+/// Types of heap objects are defined and noted at their allocation site.
+/// Global and stack variables are added with their types to the global
+/// and the function variables respectively.
+/// Types less probable than [min_probability] are not exported.
+pub fn export_types(core: GRzCore, objects: &[TypedObject], min_probability: f64) {
+    let mut n_structs = 0;
+    let mut n_vars = 0;
+    for obj in objects.iter() {
+        match obj.object.region {
+            ObjectRegion::Heap(_) | ObjectRegion::Global
+                if obj.hypotheses[0].probability < min_probability =>
+            {
+                continue;
+            }
+            ObjectRegion::Heap(site) => {
+                let core = core.lock().expect("Should not be locked");
+                if let Some(def) = obj.to_c_struct() {
+                    if !core.run_cmd(&format!("td \"{}\"", def)) {
+                        log_rz!(
+                            LOG_WARN,
+                            Some("OSPREY"),
+                            format!("Failed to define: {}", def)
+                        );
+                        continue;
+                    }
+                    n_structs += 1;
+                }
+                core.run_cmd(&format!(
+                    "CCu OSPREY: allocates {} (p = {:.2}) @ {:#x}",
+                    obj.get_type(),
+                    obj.hypotheses[0].probability,
+                    site
+                ));
+            }
+            ObjectRegion::Global => {
+                let Some(type_name) = define_type(&core, &obj.name, obj.get_type()) else {
+                    continue;
+                };
+                let core = core.lock().expect("Should not be locked");
+                if core.run_cmd(&format!(
+                    "avga {} {} @ {:#x}",
+                    obj.name, type_name, obj.object.offset
+                )) {
+                    n_vars += 1;
+                }
+            }
+            ObjectRegion::Stack(proc) => {
+                for f in obj
+                    .fields
+                    .iter()
+                    .filter(|f| f.hypotheses[0].probability >= min_probability)
+                {
+                    let name = get_stack_var_name(proc, f.field.offset);
+                    let Some(type_name) = define_type(&core, &name, f.get_type()) else {
+                        continue;
+                    };
+                    let core = core.lock().expect("Should not be locked");
+                    if core.run_cmd(&format!(
+                        "afvs {} {} {} @ {:#x}",
+                        f.field.offset, name, type_name, proc
+                    )) {
+                        n_vars += 1;
+                    }
+                }
+            }
+        }
+    }
+    log_rz!(
+        LOG_INFO,
+        Some("OSPREY"),
+        format!("{} structs defined. {} variables typed.", n_structs, n_vars)
+    );
+}
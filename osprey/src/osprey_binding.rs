@@ -0,0 +1,101 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::ptr::null;
+
+use bda::bda_binding::{get_bda_private_data, run_bda_analysis};
+use binding::{
+    log_rizin, log_rz, pderef, rz_cmd_status_t_RZ_CMD_STATUS_ERROR,
+    rz_cmd_status_t_RZ_CMD_STATUS_OK, rz_cons_out, rz_notify_begin, rz_notify_done,
+    rz_notify_error, GRzCore, RzCmdStatus, RzCore, RzCoreWrapper, LOG_ERROR, LOG_INFO, LOG_WARN,
+};
+
+use crate::{export::export_types, products::recover_typed_variables, recovery::OspreyConfig};
+
+/// Reads the OSPREY settings from the plugin configuration.
+fn get_osprey_config(core: &GRzCore) -> OspreyConfig {
+    let core = core.lock().expect("Should not be locked");
+    let mut config = OspreyConfig::new();
+    config.iterations = core.get_osprey_iterations();
+    config.min_probability = core.get_osprey_min_probability() as f64 / 100.0;
+    config
+}
+
+/// Runs OSPREY on the result of the last BDA run.
+/// If BDA didn't run yet or freed its memory op sequences (as aaaaPb does), it is run first.
+/// Its result, including the memory op sequences, is kept for the next run.
+/// Returns false if no BDA result could be obtained.
+pub fn run_osprey_analysis(rz_core: *mut RzCore) -> bool {
+    let core: GRzCore = RzCoreWrapper::new(rz_core);
+    let Some(bda_data) = get_bda_private_data() else {
+        log_rz!(
+            LOG_ERROR,
+            Some("OSPREY"),
+            "The BDA plugin is not loaded. OSPREY requires its results.".to_string()
+        );
+        return false;
+    };
+    let mut bda_state = bda_data.get_state();
+    // Runs of aaaaPb free the memory op sequences OSPREY needs.
+    if bda_state.as_ref().map_or(true, |state| state.mos.is_none()) {
+        log_rz!(
+            LOG_INFO,
+            Some("OSPREY"),
+            "No BDA result with memory operations present. Running BDA first.".to_string()
+        );
+        *bda_state = run_bda_analysis(rz_core, None, true);
+    }
+    let Some(state) = bda_state.as_ref() else {
+        rz_notify_error(core, "OSPREY failed, because BDA failed.".to_owned());
+        return false;
+    };
+    let config = get_osprey_config(&core);
+    let (ptr_size, types_min_probability) = {
+        let c = core.lock().expect("Should not be locked");
+        (
+            c.get_arch_bits() as u64 / 8,
+            c.get_osprey_types_min_probability() as f64 / 100.0,
+        )
+    };
+    rz_notify_begin(core.clone(), "OSPREY variable recovery".to_owned());
+    let objects = recover_typed_variables(state, &config, ptr_size);
+    let mut out = String::new();
+    for obj in objects.iter() {
+        out.push_str(&format!(
+            "{}  type: {} (p = {:.2})\n",
+            obj.object,
+            obj.get_type(),
+            obj.hypotheses[0].probability
+        ));
+    }
+    rz_cons_out(out);
+    export_types(core.clone(), &objects, types_min_probability);
+    rz_notify_done(
+        core,
+        format!(
+            "Finished OSPREY ({} memory objects recovered)",
+            objects.len()
+        ),
+    );
+    true
+}
+
+/// Recovers variables and their types from the BDA result.
+pub extern "C" fn rz_analysis_osprey_handler(
+    core: *mut RzCore,
+    _argc: i32,
+    _argv: *mut *const i8,
+) -> RzCmdStatus {
+    if pderef!(core).analysis.cast_const() == null() {
+        log_rz!(
+            LOG_WARN,
+            None,
+            "core.analysis is null. Without it it cannot run the analysis.".to_string()
+        );
+        return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+    }
+    if !run_osprey_analysis(core) {
+        return rz_cmd_status_t_RZ_CMD_STATUS_ERROR;
+    }
+    rz_cmd_status_t_RZ_CMD_STATUS_OK
+}
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bda::{flow_graphs::Address, state::BDAState};
+use rzil_abstr::interpreter::{AbstrVal, MemOpSeq, MemXref};
+
+use crate::{
+    recovery::{recover_objects, Access, ObjectRegion, OspreyConfig, RecoveredObject},
+    types::{infer_types, TypedObject},
+};
+
+/// Returns the offset of [aval] into its memory region, sign extended by its width.
+fn get_signed_offset(aval: &AbstrVal) -> i64 {
+    let c = aval.get_const();
+    match aval.get_width() {
+        0..=8 => c.as_i8() as i64,
+        9..=16 => c.as_i16() as i64,
+        17..=32 => c.as_i32() as i64,
+        _ => c.as_i64(),
+    }
+}
+
+/// Converts the stack and heap operations of the sampled memory operation sequences into accesses.
+/// Global operations are skipped. They are covered by the memory xrefs.
+/// Operations of instructions without known access size are skipped as well.
+pub fn get_mos_accesses(
+    moses: &BTreeSet<MemOpSeq>,
+    access_sizes: &BTreeMap<Address, u64>,
+) -> Vec<Access> {
+    let mut accesses = Vec::new();
+    for (sample, mos) in moses.iter().enumerate() {
+        for mo in mos.iter() {
+            let Some(size) = access_sizes.get(&mo.ref_addr) else {
+                continue;
+            };
+            let mem_region = mo.aval.get_mem_region();
+            let region = if mem_region.is_stack() {
+                ObjectRegion::Stack(mem_region.get_base())
+            } else if mem_region.is_heap() {
+                ObjectRegion::Heap(mem_region.get_base())
+            } else {
+                continue;
+            };
+            accesses.push(Access {
+                insn: mo.ref_addr,
+                region,
+                instance: mem_region.get_ic(),
+                sample,
+                offset: get_signed_offset(&mo.aval),
+                size: *size,
+            });
+        }
+    }
+    accesses
+}
+
+/// Converts the memory xrefs into accesses of global memory.
+pub fn get_global_accesses(mem_xrefs: &BTreeSet<MemXref>) -> Vec<Access> {
+    mem_xrefs
+        .iter()
+        .map(|xref| Access {
+            insn: xref.get_from(),
+            region: ObjectRegion::Global,
+            instance: 0,
+            sample: 0,
+            offset: xref.get_to() as i64,
+            size: xref.get_size(),
+        })
+        .collect()
+}
+
+/// Collects the memory accesses from the products of the BDA run.
+/// If the memory operation sequences were already taken from the [state],
+/// only the global accesses are returned.
+pub fn collect_accesses(state: &BDAState) -> Vec<Access> {
+    let mut accesses = get_global_accesses(&state.mem_xrefs);
+    if let Some(moses) = state.mos.as_ref() {
+        accesses.extend(get_mos_accesses(moses, &state.access_sizes));
+    }
+    accesses
+}
+
+/// Recovers the memory objects and variables from the products of the BDA run.
+pub fn recover_variables(state: &BDAState, config: &OspreyConfig) -> Vec<RecoveredObject> {
+    recover_objects(&collect_accesses(state), config)
+}
+
+/// Recovers the variables from the products of the BDA run and infers their types.
+/// Pointers are [ptr_size] bytes wide.
+pub fn recover_typed_variables(
+    state: &BDAState,
+    config: &OspreyConfig,
+    ptr_size: u64,
+) -> Vec<TypedObject> {
+    infer_types(
+        recover_variables(state, config),
+        &state.type_hints,
+        ptr_size,
+    )
+}
@@ -0,0 +1,405 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bda::flow_graphs::Address;
+
+/// Probability that an instruction accessing a variable, accesses it at its start.
+const INSN_HINT: f64 = 0.6;
+/// Probability added by each additional access of the same instruction.
+const HIT_HINT: f64 = 0.05;
+/// Probability that an array element access doesn't hit the array by chance.
+const ELEM_HINT: f64 = 0.5;
+/// Minimal number of elements accessed by an instruction to hint an array.
+const MIN_ARRAY_ELEMS: usize = 3;
+/// Probabilities are kept away from 0 and 1, so contradictions can still change them.
+const MAX_PROB: f64 = 0.99;
+const MIN_PROB: f64 = 0.01;
+
+/// The memory object an access refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ObjectRegion {
+    /// Global memory. The offsets are the addresses.
+    Global,
+    /// The stack frame of the procedure at the address.
+    Stack(Address),
+    /// The heap objects allocated at the address.
+    Heap(Address),
+}
+
+impl std::fmt::Display for ObjectRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectRegion::Global => write!(f, "global"),
+            ObjectRegion::Stack(proc) => write!(f, "stack frame of {:#x}", proc),
+            ObjectRegion::Heap(site) => write!(f, "heap object allocated at {:#x}", site),
+        }
+    }
+}
+
+/// A memory access observed on a sampled path.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Access {
+    /// Address of the accessing instruction.
+    pub insn: Address,
+    /// The memory object accessed.
+    pub region: ObjectRegion,
+    /// The instance of the object. E.g. the invocation of the procedure the stack frame belongs to.
+    pub instance: u32,
+    /// The sampled path the access was observed on.
+    pub sample: usize,
+    /// Offset into the object.
+    pub offset: i64,
+    /// Number of bytes accessed.
+    pub size: u64,
+}
+
+/// Parameters of the recovery.
+#[derive(Clone, Debug)]
+pub struct OspreyConfig {
+    /// Number of iterations to propagate the probabilities.
+    pub iterations: usize,
+    /// Variables with a lower probability are not reported.
+    pub min_probability: f64,
+}
+
+impl OspreyConfig {
+    pub fn new() -> OspreyConfig {
+        OspreyConfig {
+            iterations: 32,
+            min_probability: 0.5,
+        }
+    }
+}
+
+impl Default for OspreyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A recovered variable or field of an object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveredField {
+    /// Offset of the field in the memory region.
+    pub offset: i64,
+    /// Size of the field in bytes. For arrays the size of an element.
+    pub size: u64,
+    /// The number of elements, if the field is an array.
+    pub count: Option<u64>,
+    /// Probability that the field exists as recovered.
+    pub probability: f64,
+    /// Instructions accessing the field.
+    pub accessed_by: BTreeSet<Address>,
+}
+
+impl RecoveredField {
+    /// Returns the number of bytes the field occupies.
+    pub fn get_total_size(&self) -> u64 {
+        self.size * self.count.unwrap_or(1)
+    }
+
+    /// Returns the offset of the first byte after the field.
+    pub fn get_end(&self) -> i64 {
+        self.offset + self.get_total_size() as i64
+    }
+
+    fn contains(&self, offset: i64, size: u64) -> bool {
+        self.offset <= offset && offset + size as i64 <= self.get_end()
+    }
+
+    fn overlaps(&self, other: &RecoveredField) -> bool {
+        self.offset < other.get_end() && other.offset < self.get_end()
+    }
+
+    /// Checks if [other] lies within a single element of this field.
+    fn is_element_access(&self, other: &RecoveredField) -> bool {
+        if !self.contains(other.offset, other.get_total_size()) {
+            return false;
+        }
+        let elem_offset = (other.offset - self.offset) % self.size as i64;
+        elem_offset + other.get_total_size() as i64 <= self.size as i64
+    }
+}
+
+/// A recovered memory object with its fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveredObject {
+    pub region: ObjectRegion,
+    /// Offset of the first field in the region.
+    pub offset: i64,
+    /// Size in bytes, from the first to the end of the last field.
+    pub size: u64,
+    /// The fields ordered by their offset.
+    pub fields: Vec<RecoveredField>,
+}
+
+fn fmt_offset(offset: i64) -> String {
+    if offset < 0 {
+        return format!("-{:#x}", offset.unsigned_abs());
+    }
+    format!("+{:#x}", offset)
+}
+
+impl std::fmt::Display for RecoveredObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.region {
+            ObjectRegion::Global => write!(f, "global {:#x}", self.offset)?,
+            _ => write!(f, "{} {}", self.region, fmt_offset(self.offset))?,
+        }
+        writeln!(f, " ({} bytes)", self.size)?;
+        for field in self.fields.iter() {
+            write!(
+                f,
+                "  {}: {} bytes",
+                fmt_offset(field.offset - self.offset),
+                field.size
+            )?;
+            if let Some(count) = field.count {
+                write!(f, " x {}", count)?;
+            }
+            writeln!(f, " (p = {:.2})", field.probability)?;
+        }
+        Ok(())
+    }
+}
+
+/// A possible variable and the hints for it.
+struct Candidate {
+    field: RecoveredField,
+    /// Probability from the hints of this candidate alone.
+    prior: f64,
+}
+
+fn clamp_prob(p: f64) -> f64 {
+    p.clamp(MIN_PROB, MAX_PROB)
+}
+
+fn logit(p: f64) -> f64 {
+    (p / (1.0 - p)).ln()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Collects a candidate for every distinct access of the region.
+fn get_scalar_candidates(accesses: &[&Access]) -> Vec<Candidate> {
+    let mut hints = BTreeMap::<(i64, u64), (u64, BTreeSet<Address>)>::new();
+    for acc in accesses.iter() {
+        let hint = hints.entry((acc.offset, acc.size)).or_default();
+        hint.0 += 1;
+        hint.1.insert(acc.insn);
+    }
+    hints
+        .into_iter()
+        .map(|((offset, size), (hits, insns))| {
+            let n_insns = insns.len() as i32;
+            let n_repeats = (hits as i32 - n_insns).min(64);
+            let prior = 1.0 - (1.0 - INSN_HINT).powi(n_insns) * (1.0 - HIT_HINT).powi(n_repeats);
+            Candidate {
+                field: RecoveredField {
+                    offset,
+                    size,
+                    count: None,
+                    probability: 0.0,
+                    accessed_by: insns,
+                },
+                prior: clamp_prob(prior),
+            }
+        })
+        .collect()
+}
+
+/// Collects a candidate array for every instruction, which accesses the region
+/// at several offsets with a common stride.
+fn get_array_candidates(accesses: &[&Access]) -> Vec<Candidate> {
+    // Offsets accessed per instruction and object instance on a path.
+    let mut elements = BTreeMap::<(Address, usize, u32), (u64, BTreeSet<i64>)>::new();
+    for acc in accesses.iter() {
+        let elems = elements
+            .entry((acc.insn, acc.sample, acc.instance))
+            .or_default();
+        elems.0 = elems.0.max(acc.size);
+        elems.1.insert(acc.offset);
+    }
+    // Merged over all paths: (first offset, last offset, stride, element offsets)
+    let mut arrays = BTreeMap::<Address, (i64, i64, u64, BTreeSet<i64>)>::new();
+    for ((insn, _, _), (size, offsets)) in elements.into_iter() {
+        if offsets.len() < MIN_ARRAY_ELEMS {
+            continue;
+        }
+        let first = *offsets.first().unwrap();
+        let stride = offsets.iter().map(|o| (o - first) as u64).fold(0, gcd);
+        if stride < size {
+            continue;
+        }
+        let last = *offsets.last().unwrap();
+        let array = arrays
+            .entry(insn)
+            .or_insert((first, last, stride, BTreeSet::new()));
+        array.0 = array.0.min(first);
+        array.1 = array.1.max(last);
+        array.2 = gcd(array.2, stride);
+        array.3.extend(offsets);
+    }
+    arrays
+        .into_iter()
+        .filter(|(_, (_, _, stride, _))| *stride > 0)
+        .map(|(insn, (first, last, stride, elems))| {
+            let prior = 1.0 - (1.0 - ELEM_HINT).powi((elems.len() - MIN_ARRAY_ELEMS + 1) as i32);
+            Candidate {
+                field: RecoveredField {
+                    offset: first,
+                    size: stride,
+                    count: Some((last - first) as u64 / stride + 1),
+                    probability: 0.0,
+                    accessed_by: BTreeSet::from([insn]),
+                },
+                prior: clamp_prob(prior),
+            }
+        })
+        .collect()
+}
+
+/// Infers the probability of each candidate.
+/// Overlapping candidates contradict each other, unless one is an element access of the other array.
+/// Element accesses support the array they are part of.
+fn infer_probabilities(candidates: &mut [Candidate], iterations: usize) {
+    let n = candidates.len();
+    let mut conflicts: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut supports: Vec<Vec<usize>> = vec![Vec::new(); n];
+    // Sweep over the candidates ordered by offset.
+    // Only the candidates which didn't end yet can overlap the next one.
+    let mut by_offset: Vec<usize> = (0..n).collect();
+    by_offset.sort_by_key(|i| candidates[*i].field.offset);
+    let mut active = Vec::<usize>::new();
+    for k in by_offset.into_iter() {
+        let offset = candidates[k].field.offset;
+        active.retain(|a| candidates[*a].field.get_end() > offset);
+        for a in active.iter() {
+            let (i, j) = (k.min(*a), k.max(*a));
+            let (a, b) = (&candidates[i].field, &candidates[j].field);
+            if !a.overlaps(b) {
+                continue;
+            }
+            if a.count.is_some() && a.is_element_access(b) {
+                supports[i].push(j);
+            } else if b.count.is_some() && b.is_element_access(a) {
+                supports[j].push(i);
+            } else {
+                conflicts[i].push(j);
+                conflicts[j].push(i);
+            }
+        }
+        active.push(k);
+    }
+    // Same summation order as in candidate order.
+    conflicts.iter_mut().for_each(|c| c.sort_unstable());
+    supports.iter_mut().for_each(|s| s.sort_unstable());
+    let mut probs: Vec<f64> = candidates.iter().map(|c| c.prior).collect();
+    for _ in 0..iterations {
+        let updated: Vec<f64> = (0..n)
+            .map(|i| {
+                let against: f64 = conflicts[i].iter().map(|j| (1.0 - probs[*j]).ln()).sum();
+                let support: f64 = supports[i].iter().map(|j| probs[*j]).sum();
+                clamp_prob(sigmoid(logit(candidates[i].prior) + against + support))
+            })
+            .collect();
+        // Damped, so mutually contradicting candidates don't oscillate.
+        probs = probs
+            .iter()
+            .zip(updated.iter())
+            .map(|(p, u)| (p + u) / 2.0)
+            .collect();
+    }
+    for (c, p) in candidates.iter_mut().zip(probs) {
+        c.field.probability = p;
+    }
+}
+
+/// Selects the most probable, non-overlapping candidates.
+/// Accesses within a selected variable are attributed to it.
+fn select_fields(mut candidates: Vec<Candidate>, min_probability: f64) -> Vec<RecoveredField> {
+    candidates.sort_by(|a, b| {
+        b.field
+            .probability
+            .total_cmp(&a.field.probability)
+            .then(b.field.get_total_size().cmp(&a.field.get_total_size()))
+            .then(a.field.offset.cmp(&b.field.offset))
+    });
+    let mut selected = Vec::<RecoveredField>::new();
+    let mut rejected = Vec::<RecoveredField>::new();
+    for c in candidates.into_iter() {
+        if c.field.probability < min_probability || selected.iter().any(|s| s.overlaps(&c.field)) {
+            rejected.push(c.field);
+            continue;
+        }
+        selected.push(c.field);
+    }
+    for r in rejected.into_iter() {
+        if let Some(s) = selected
+            .iter_mut()
+            .find(|s| s.contains(r.offset, r.get_total_size()))
+        {
+            s.accessed_by.extend(r.accessed_by);
+        }
+    }
+    selected.sort_by_key(|f| f.offset);
+    selected
+}
+
+/// Recovers the variables of a single memory region.
+fn recover_region_fields(accesses: &[&Access], config: &OspreyConfig) -> Vec<RecoveredField> {
+    let mut candidates = get_scalar_candidates(accesses);
+    candidates.extend(get_array_candidates(accesses));
+    infer_probabilities(&mut candidates, config.iterations);
+    select_fields(candidates, config.min_probability)
+}
+
+fn new_object(region: ObjectRegion, fields: Vec<RecoveredField>) -> RecoveredObject {
+    let offset = fields.iter().map(|f| f.offset).min().unwrap_or(0);
+    let end = fields.iter().map(|f| f.get_end()).max().unwrap_or(0);
+    RecoveredObject {
+        region,
+        offset,
+        size: (end - offset) as u64,
+        fields,
+    }
+}
+
+/// Recovers the memory objects and their fields from the observed [accesses].
+/// Every distinct access hints a variable. Instructions accessing an object at
+/// several offsets with a common stride hint arrays. Hints of overlapping variables
+/// contradict each other. The probabilities of the candidates are inferred by propagating
+/// these contradictions (a mean-field approximation of the factor graph connecting them).
+/// Stack frames and heap objects of the same allocation site are recovered as one object each.
+/// Every recovered global variable is an object on its own.
+pub fn recover_objects(accesses: &[Access], config: &OspreyConfig) -> Vec<RecoveredObject> {
+    let mut regions = BTreeMap::<ObjectRegion, Vec<&Access>>::new();
+    for acc in accesses.iter().filter(|a| a.size > 0) {
+        regions.entry(acc.region).or_default().push(acc);
+    }
+    let mut objects = Vec::new();
+    for (region, region_accesses) in regions.into_iter() {
+        let fields = recover_region_fields(&region_accesses, config);
+        if fields.is_empty() {
+            continue;
+        }
+        if region == ObjectRegion::Global {
+            objects.extend(fields.into_iter().map(|f| new_object(region, vec![f])));
+            continue;
+        }
+        objects.push(new_object(region, fields));
+    }
+    objects
+}
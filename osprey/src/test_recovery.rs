@@ -0,0 +1,113 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::recovery::{recover_objects, Access, ObjectRegion, OspreyConfig};
+
+    fn acc(insn: u64, region: ObjectRegion, sample: usize, offset: i64, size: u64) -> Access {
+        Access {
+            insn,
+            region,
+            instance: 0,
+            sample,
+            offset,
+            size,
+        }
+    }
+
+    fn layout(fields: &[crate::recovery::RecoveredField]) -> Vec<(i64, u64, Option<u64>)> {
+        fields.iter().map(|f| (f.offset, f.size, f.count)).collect()
+    }
+
+    #[test]
+    fn test_heap_struct() {
+        let heap = ObjectRegion::Heap(0x1000);
+        let accesses = vec![
+            acc(0x2000, heap, 0, 0, 8),
+            acc(0x2010, heap, 0, 8, 4),
+            acc(0x2020, heap, 1, 0, 8),
+            acc(0x2030, heap, 1, 8, 4),
+        ];
+        let objects = recover_objects(&accesses, &OspreyConfig::new());
+        assert_eq!(objects.len(), 1);
+        let obj = &objects[0];
+        assert_eq!(obj.region, heap);
+        assert_eq!(obj.offset, 0);
+        assert_eq!(obj.size, 12);
+        assert_eq!(layout(&obj.fields), vec![(0, 8, None), (8, 4, None)]);
+        assert_eq!(obj.fields[0].accessed_by, BTreeSet::from([0x2000, 0x2020]));
+    }
+
+    #[test]
+    fn test_array() {
+        // A loop writes four elements, another instruction reads one of them.
+        let heap = ObjectRegion::Heap(0x1000);
+        let mut accesses: Vec<Access> = (0..4).map(|i| acc(0x2000, heap, 0, i * 4, 4)).collect();
+        accesses.push(acc(0x2100, heap, 0, 8, 4));
+        let objects = recover_objects(&accesses, &OspreyConfig::new());
+        assert_eq!(objects.len(), 1);
+        let obj = &objects[0];
+        assert_eq!(layout(&obj.fields), vec![(0, 4, Some(4))]);
+        assert_eq!(obj.size, 16);
+        assert_eq!(obj.fields[0].accessed_by, BTreeSet::from([0x2000, 0x2100]));
+    }
+
+    #[test]
+    fn test_partial_access() {
+        // The lower half of a 64bit variable is read by a single instruction.
+        let accesses = vec![
+            acc(0x2000, ObjectRegion::Global, 0, 0x4000, 8),
+            acc(0x2010, ObjectRegion::Global, 0, 0x4000, 8),
+            acc(0x2020, ObjectRegion::Global, 0, 0x4000, 4),
+            acc(0x2030, ObjectRegion::Global, 0, 0x4010, 1),
+        ];
+        let objects = recover_objects(&accesses, &OspreyConfig::new());
+        // Each global variable is an object on its own.
+        assert_eq!(objects.len(), 2);
+        assert_eq!(layout(&objects[0].fields), vec![(0x4000, 8, None)]);
+        assert_eq!(
+            objects[0].fields[0].accessed_by,
+            BTreeSet::from([0x2000, 0x2010, 0x2020])
+        );
+        assert_eq!(layout(&objects[1].fields), vec![(0x4010, 1, None)]);
+    }
+
+    #[test]
+    fn test_min_probability() {
+        // Two instructions contradict each other equally.
+        // Neither variable is likely enough to be reported by default.
+        let heap = ObjectRegion::Heap(0x1000);
+        let accesses = vec![acc(0x2000, heap, 0, 0, 8), acc(0x2010, heap, 0, 4, 8)];
+        let mut config = OspreyConfig::new();
+        assert!(recover_objects(&accesses, &config).is_empty());
+        config.min_probability = 0.2;
+        let objects = recover_objects(&accesses, &config);
+        assert_eq!(layout(&objects[0].fields), vec![(0, 8, None)]);
+        assert_eq!(objects[0].fields[0].accessed_by, BTreeSet::from([0x2000]));
+    }
+
+    #[test]
+    fn test_stack_frame() {
+        let stack = ObjectRegion::Stack(0x1000);
+        let accesses = vec![
+            acc(0x1004, stack, 0, -0x10, 8),
+            acc(0x1008, stack, 0, -0x8, 4),
+            acc(0x100c, stack, 0, -0x4, 4),
+        ];
+        let objects = recover_objects(&accesses, &OspreyConfig::new());
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].offset, -0x10);
+        assert_eq!(objects[0].size, 0x10);
+        assert_eq!(
+            layout(&objects[0].fields),
+            vec![(-0x10, 8, None), (-0x8, 4, None), (-0x4, 4, None)]
+        );
+        assert_eq!(
+            objects[0].to_string(),
+            "stack frame of 0x1000 -0x10 (16 bytes)\n  +0x0: 8 bytes (p = 0.60)\n  +0x8: 4 bytes (p = 0.60)\n  +0xc: 4 bytes (p = 0.60)\n"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use rzil_abstr::interpreter::TypeHint;
+
+    use crate::{
+        recovery::{ObjectRegion, RecoveredField, RecoveredObject},
+        types::{infer_types, VarType},
+    };
+
+    fn field(offset: i64, size: u64, count: Option<u64>, insn: u64) -> RecoveredField {
+        RecoveredField {
+            offset,
+            size,
+            count,
+            probability: 0.9,
+            accessed_by: BTreeSet::from([insn]),
+        }
+    }
+
+    fn object(region: ObjectRegion, fields: Vec<RecoveredField>) -> RecoveredObject {
+        let offset = fields.first().unwrap().offset;
+        let end = fields.last().unwrap().get_end();
+        RecoveredObject {
+            region,
+            offset,
+            size: (end - offset) as u64,
+            fields,
+        }
+    }
+
+    #[test]
+    fn test_scalar_types() {
+        let hints = BTreeMap::from([
+            (0x10, TypeHint::HeapPtr),
+            (0x20, TypeHint::Arith),
+            (0x30, TypeHint::Deref | TypeHint::Arith),
+        ]);
+        let objects: Vec<RecoveredObject> = [0x10, 0x20, 0x30, 0x40]
+            .iter()
+            .map(|insn| {
+                object(
+                    ObjectRegion::Global,
+                    vec![field(*insn as i64 * 0x100, 8, None, *insn)],
+                )
+            })
+            .collect();
+        let typed = infer_types(objects, &hints, 8);
+        assert_eq!(typed[0].get_type(), &VarType::Ptr);
+        assert!(typed[0].hypotheses[0].probability > 0.85);
+        assert_eq!(typed[1].get_type(), &VarType::Int(8));
+        assert!(typed[1].hypotheses[0].probability > 0.9);
+        // Dereferenced, but also used in arithmetic.
+        assert_eq!(typed[2].get_type(), &VarType::Ptr);
+        assert!(typed[2].hypotheses[0].probability < typed[0].hypotheses[0].probability);
+        // Without hints, the prior decides.
+        assert_eq!(typed[3].get_type(), &VarType::Int(8));
+        assert_eq!(typed[3].hypotheses[0].probability, 0.75);
+        assert_eq!(typed[3].hypotheses[1].ty, VarType::Ptr);
+
+        // Values not of pointer size are never pointers.
+        let typed = infer_types(
+            vec![object(ObjectRegion::Global, vec![field(0, 4, None, 0x10)])],
+            &hints,
+            8,
+        );
+        assert_eq!(typed[0].hypotheses.len(), 1);
+        assert_eq!(typed[0].get_type(), &VarType::Int(4));
+        assert_eq!(typed[0].name, "osprey_global_0");
+    }
+
+    #[test]
+    fn test_array_types() {
+        let hints = BTreeMap::from([(0x10, TypeHint::FreeArg)]);
+        let typed = infer_types(
+            vec![object(
+                ObjectRegion::Stack(0x1000),
+                vec![field(-0x20, 8, Some(4), 0x10)],
+            )],
+            &hints,
+            8,
+        );
+        assert_eq!(
+            typed[0].get_type(),
+            &VarType::Array(Box::new(VarType::Ptr), 4)
+        );
+        assert_eq!(typed[0].get_type().to_string(), "void *[4]");
+        assert_eq!(
+            VarType::Array(Box::new(VarType::Int(16)), 2).to_c_decl("buf"),
+            "uint8_t buf[2][16]"
+        );
+        // Single field objects have no struct definition.
+        assert_eq!(typed[0].to_c_struct(), None);
+    }
+
+    #[test]
+    fn test_struct_types() {
+        let hints = BTreeMap::from([(0x10, TypeHint::StackPtr)]);
+        let typed = infer_types(
+            vec![object(
+                ObjectRegion::Heap(0x1000),
+                vec![
+                    field(0, 8, None, 0x10),
+                    field(0xc, 4, None, 0x20),
+                    field(0x10, 2, Some(4), 0x30),
+                ],
+            )],
+            &hints,
+            8,
+        );
+        let obj = &typed[0];
+        assert_eq!(
+            obj.get_type(),
+            &VarType::Struct("osprey_heap_1000".to_string())
+        );
+        assert!((obj.hypotheses[0].probability - 0.9).abs() < 1e-9);
+        assert_eq!(
+            obj.hypotheses[1].ty,
+            VarType::Array(Box::new(VarType::Int(1)), 0x18)
+        );
+        assert_eq!(
+            obj.to_c_struct().unwrap(),
+            "struct osprey_heap_1000 { void *f_0; uint8_t pad_8[4]; uint32_t f_c; uint16_t f_10[4]; };"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::collections::BTreeMap;
+
+use bda::flow_graphs::Address;
+use rzil_abstr::interpreter::TypeHint;
+
+use crate::recovery::{ObjectRegion, RecoveredField, RecoveredObject};
+
+/// Prior probability that a pointer sized variable is a pointer.
+const PTR_PRIOR: f64 = 0.25;
+/// Likelihood ratios of the hints: How much more likely a hint is observed
+/// for a pointer than for an integer.
+const PTR_REGION_LR: f64 = 20.0;
+const DEREF_LR: f64 = 16.0;
+const FREE_ARG_LR: f64 = 20.0;
+const ARITH_LR: f64 = 1.0 / 4.0;
+const SIZE_ARG_LR: f64 = 1.0 / 8.0;
+/// Probabilities are kept away from 0 and 1, so contradicting hints can still change them.
+const MAX_PROB: f64 = 0.99;
+const MIN_PROB: f64 = 0.01;
+
+/// The type of a recovered variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VarType {
+    /// An integer of the given size in bytes.
+    Int(u64),
+    /// A pointer.
+    Ptr,
+    /// An array of elements with the given count.
+    Array(Box<VarType>, u64),
+    /// The struct with the given name.
+    Struct(String),
+}
+
+impl VarType {
+    /// Returns the C declaration of a variable [name] with this type.
+    pub fn to_c_decl(&self, name: &str) -> String {
+        match self {
+            VarType::Int(size @ (1 | 2 | 4 | 8)) => format!("uint{}_t {}", size * 8, name),
+            VarType::Int(size) => format!("uint8_t {}[{}]", name, size),
+            VarType::Ptr => format!("void *{}", name),
+            VarType::Array(elem, count) => elem.to_c_decl(&format!("{}[{}]", name, count)),
+            VarType::Struct(sname) => format!("struct {} {}", sname, name),
+        }
+    }
+}
+
+impl std::fmt::Display for VarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_c_decl("").trim_end())
+    }
+}
+
+/// A possible type and its probability.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeHypothesis {
+    pub ty: VarType,
+    pub probability: f64,
+}
+
+/// A recovered variable with its possible types.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedField {
+    pub field: RecoveredField,
+    /// The possible types, the most probable first.
+    pub hypotheses: Vec<TypeHypothesis>,
+}
+
+impl TypedField {
+    /// Returns the most probable type.
+    pub fn get_type(&self) -> &VarType {
+        &self.hypotheses[0].ty
+    }
+}
+
+/// A recovered memory object with its possible types.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedObject {
+    pub object: RecoveredObject,
+    /// Name of the object. Used as struct name as well.
+    pub name: String,
+    pub fields: Vec<TypedField>,
+    /// The possible types of the whole object, the most probable first.
+    pub hypotheses: Vec<TypeHypothesis>,
+}
+
+impl TypedObject {
+    /// Returns the most probable type.
+    pub fn get_type(&self) -> &VarType {
+        &self.hypotheses[0].ty
+    }
+
+    /// Returns the C definition of the struct, if the object is most likely one.
+    /// Gaps between the fields are filled with padding bytes.
+    pub fn to_c_struct(&self) -> Option<String> {
+        let VarType::Struct(sname) = self.get_type() else {
+            return None;
+        };
+        let mut def = format!("struct {} {{", sname);
+        let mut end = self.object.offset;
+        for f in self.fields.iter() {
+            let offset = f.field.offset - self.object.offset;
+            if f.field.offset > end {
+                let pad = VarType::Int(1).to_c_decl(&format!(
+                    "pad_{:x}[{}]",
+                    end - self.object.offset,
+                    f.field.offset - end
+                ));
+                def.push_str(&format!(" {};", pad));
+            }
+            def.push_str(&format!(
+                " {};",
+                f.get_type().to_c_decl(&format!("f_{:x}", offset))
+            ));
+            end = f.field.get_end();
+        }
+        def.push_str(" };");
+        Some(def)
+    }
+}
+
+fn clamp_prob(p: f64) -> f64 {
+    p.clamp(MIN_PROB, MAX_PROB)
+}
+
+fn logit(p: f64) -> f64 {
+    (p / (1.0 - p)).ln()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Returns the log likelihood ratio of pointer versus integer for the [hint].
+fn get_log_ratio(hint: TypeHint) -> f64 {
+    [
+        (TypeHint::HeapPtr, PTR_REGION_LR),
+        (TypeHint::StackPtr, PTR_REGION_LR),
+        (TypeHint::Deref, DEREF_LR),
+        (TypeHint::FreeArg, FREE_ARG_LR),
+        (TypeHint::Arith, ARITH_LR),
+        (TypeHint::SizeArg, SIZE_ARG_LR),
+    ]
+    .iter()
+    .filter(|(flag, _)| hint.contains(*flag))
+    .map(|(_, lr)| lr.ln())
+    .sum()
+}
+
+/// Infers the type of a scalar of [size] bytes, from the [hints] of the instructions accessing it.
+/// Only pointer sized values can be pointers.
+fn infer_scalar_types(
+    size: u64,
+    hints: impl Iterator<Item = TypeHint>,
+    ptr_size: u64,
+) -> Vec<TypeHypothesis> {
+    if size != ptr_size {
+        return vec![TypeHypothesis {
+            ty: VarType::Int(size),
+            probability: 1.0,
+        }];
+    }
+    let x = hints.fold(logit(PTR_PRIOR), |x, h| x + get_log_ratio(h));
+    let p_ptr = clamp_prob(sigmoid(x));
+    let mut hypotheses = vec![
+        TypeHypothesis {
+            ty: VarType::Ptr,
+            probability: p_ptr,
+        },
+        TypeHypothesis {
+            ty: VarType::Int(size),
+            probability: 1.0 - p_ptr,
+        },
+    ];
+    hypotheses.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+    hypotheses
+}
+
+/// Infers the types of the [field]. Arrays get the types of their elements.
+fn infer_field_types(
+    field: &RecoveredField,
+    hints: &BTreeMap<Address, TypeHint>,
+    ptr_size: u64,
+) -> Vec<TypeHypothesis> {
+    let field_hints = field
+        .accessed_by
+        .iter()
+        .filter_map(|a| hints.get(a))
+        .cloned();
+    let mut hypotheses = infer_scalar_types(field.size, field_hints, ptr_size);
+    if let Some(count) = field.count {
+        for h in hypotheses.iter_mut() {
+            h.ty = VarType::Array(Box::new(h.ty.clone()), count);
+        }
+    }
+    hypotheses
+}
+
+fn get_object_name(object: &RecoveredObject) -> String {
+    match object.region {
+        ObjectRegion::Global => format!("osprey_global_{:x}", object.offset),
+        ObjectRegion::Stack(proc) => format!("osprey_stack_{:x}", proc),
+        ObjectRegion::Heap(site) => format!("osprey_heap_{:x}", site),
+    }
+}
+
+/// Infers the types of the [object] and its fields.
+/// An object with a single field is of the field's type.
+/// Otherwise it is either a struct of its fields, or an unstructured byte buffer.
+/// The struct is as likely as all its fields together (geometric mean of their probabilities).
+fn infer_object_types(
+    object: RecoveredObject,
+    hints: &BTreeMap<Address, TypeHint>,
+    ptr_size: u64,
+) -> TypedObject {
+    let name = get_object_name(&object);
+    let fields: Vec<TypedField> = object
+        .fields
+        .iter()
+        .map(|f| TypedField {
+            field: f.clone(),
+            hypotheses: infer_field_types(f, hints, ptr_size),
+        })
+        .collect();
+    let hypotheses = if fields.len() == 1 {
+        fields[0].hypotheses.clone()
+    } else {
+        let log_p: f64 = fields.iter().map(|f| f.field.probability.ln()).sum();
+        let p_struct = clamp_prob((log_p / fields.len() as f64).exp());
+        let mut hypotheses = vec![
+            TypeHypothesis {
+                ty: VarType::Struct(name.clone()),
+                probability: p_struct,
+            },
+            TypeHypothesis {
+                ty: VarType::Array(Box::new(VarType::Int(1)), object.size),
+                probability: 1.0 - p_struct,
+            },
+        ];
+        hypotheses.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+        hypotheses
+    };
+    TypedObject {
+        object,
+        name,
+        fields,
+        hypotheses,
+    }
+}
+
+/// Infers the types of the recovered [objects] from the type [hints]
+/// of the instructions accessing them. Pointers are [ptr_size] bytes wide.
+/// The hints of each instruction are combined as independent evidence
+/// for a pointer or an integer (naive Bayes).
+pub fn infer_types(
+    objects: Vec<RecoveredObject>,
+    hints: &BTreeMap<Address, TypeHint>,
+    ptr_size: u64,
+) -> Vec<TypedObject> {
+    objects
+        .into_iter()
+        .map(|o| infer_object_types(o, hints, ptr_size))
+        .collect()
+}
@@ -5,21 +5,30 @@
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
 
-use helper::rz::{parse_bda_entry_list, parse_bda_range_conf_val, parse_bda_timeout};
+use helper::rz::{
+    parse_bda_entry_list, parse_bda_exclude_list, parse_bda_range_conf_val, parse_bda_sink_list,
+    parse_bda_timeout,
+};
 use std::ffi::CString;
 use std::ptr::null;
 
-use bda::bda_binding::{rz_analysis_bda_handler, BDAPrivateData};
+use bda::bda_binding::{
+    compile_exclude_pattern, compile_sink_pattern, register_bda_private_data,
+    rz_analysis_bda_export_handler, rz_analysis_bda_handler, BDAPrivateData,
+};
 use binding::{
-    c_to_str, log_rizin, log_rz, pderef, rz_cmd_desc_arg_t__bindgen_ty_1,
-    rz_cmd_desc_arg_t__bindgen_ty_1__bindgen_ty_1, rz_cmd_desc_argv_new, rz_cmd_desc_group_new,
-    rz_cmd_desc_remove, rz_cmd_get_desc, rz_cmd_status_t_RZ_CMD_STATUS_OK, rz_config_lock,
-    rz_config_new, rz_config_node_desc, rz_config_set_cb, rz_config_set_i, rz_config_set_i_cb,
-    rz_core_cmd_help, str_to_c, RzCmdDesc, RzCmdDescArg, RzCmdDescHelp, RzCmdStatus, RzConfig,
-    RzConfigNode, RzCore, RzCorePlugin, RzLibStruct, RzLibType_RZ_LIB_TYPE_CORE, LOG_ERROR,
+    c_to_str, log_rizin, log_rz, pderef, rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_CHOICES,
+    rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_FILE, rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_RZNUM,
+    rz_cmd_desc_arg_t__bindgen_ty_1, rz_cmd_desc_arg_t__bindgen_ty_1__bindgen_ty_1,
+    rz_cmd_desc_argv_new, rz_cmd_desc_group_new, rz_cmd_desc_remove, rz_cmd_get_desc,
+    rz_cmd_status_t_RZ_CMD_STATUS_OK, rz_config_lock, rz_config_new, rz_config_node_desc,
+    rz_config_set_cb, rz_config_set_i, rz_config_set_i_cb, rz_core_cmd_help, rz_core_plugin_add,
+    str_to_c, RzCmdDesc, RzCmdDescArg, RzCmdDescHelp, RzCmdStatus, RzConfig, RzConfigNode, RzCore,
+    RzCorePlugin, RzLibStruct, RzLibType_RZ_LIB_TYPE_CORE, LOG_ERROR, RZ_CMD_ARG_FLAG_ARRAY,
     RZ_VERSION,
 };
 use cty::c_void;
+use osprey::osprey_binding::rz_analysis_osprey_handler;
 
 pub enum HighestPluginFunction {
     Binding,
@@ -115,11 +124,100 @@ pub const analysis_bda_help_args: RzCmdDescArg = RzCmdDescArg {
     },
 };
 
+pub const analysis_bda_run_help_args: [RzCmdDescArg; 2] = [
+    RzCmdDescArg {
+        name: "function\0".as_ptr().cast(),
+        optional: true,
+        no_space: false,
+        type_: rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_RZNUM,
+        flags: RZ_CMD_ARG_FLAG_ARRAY as i32,
+        default_value: null(),
+        __bindgen_anon_1: rz_cmd_desc_arg_t__bindgen_ty_1 {
+            choices: rz_cmd_desc_arg_t__bindgen_ty_1__bindgen_ty_1 {
+                choices: "\0".as_ptr() as *mut *const i8,
+                choices_cb: None,
+            },
+        },
+    },
+    analysis_bda_help_args,
+];
+
 pub const analysis_bda_help: RzCmdDescHelp = RzCmdDescHelp {
     summary: "Run bda dependency analysis (algorithm: BDA).\0"
         .as_ptr()
         .cast(),
-    description: "Detect memory dependencies via abstract interpretation over sampled paths.\0"
+    description: "Detect memory dependencies via abstract interpretation over sampled paths. If functions (names, flags or addresses, e.g. $$ for the current seek) are given, only they and their callees up to plugins.bda.scope.depth are analysed. Otherwise plugins.bda.entries and plugins.bda.sampling.range are used.\0"
+        .as_ptr()
+        .cast(),
+    args_str: null(),
+    usage: null(),
+    options: null(),
+    sort_subcommands: false,
+    details: null(),
+    details_cb: None,
+    args: &analysis_bda_run_help_args as *const [RzCmdDescArg; 2] as *const RzCmdDescArg,
+};
+
+pub const analysis_bda_export_graph_choices: [*const i8; 5] = [
+    "icfg\0".as_ptr().cast(),
+    "icfg_resolved\0".as_ptr().cast(),
+    "cfg\0".as_ptr().cast(),
+    "cfg_resolved\0".as_ptr().cast(),
+    null(),
+];
+
+pub const analysis_bda_export_help_args: [RzCmdDescArg; 4] = [
+    RzCmdDescArg {
+        name: "graph\0".as_ptr().cast(),
+        optional: false,
+        no_space: false,
+        type_: rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_CHOICES,
+        flags: 0,
+        default_value: null(),
+        __bindgen_anon_1: rz_cmd_desc_arg_t__bindgen_ty_1 {
+            choices: rz_cmd_desc_arg_t__bindgen_ty_1__bindgen_ty_1 {
+                choices: &analysis_bda_export_graph_choices as *const [*const i8; 5]
+                    as *mut *const i8,
+                choices_cb: None,
+            },
+        },
+    },
+    RzCmdDescArg {
+        name: "file\0".as_ptr().cast(),
+        optional: false,
+        no_space: false,
+        type_: rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_FILE,
+        flags: 0,
+        default_value: null(),
+        __bindgen_anon_1: rz_cmd_desc_arg_t__bindgen_ty_1 {
+            choices: rz_cmd_desc_arg_t__bindgen_ty_1__bindgen_ty_1 {
+                choices: "\0".as_ptr() as *mut *const i8,
+                choices_cb: None,
+            },
+        },
+    },
+    RzCmdDescArg {
+        name: "procedure\0".as_ptr().cast(),
+        optional: true,
+        no_space: false,
+        type_: rz_cmd_arg_type_t_RZ_CMD_ARG_TYPE_RZNUM,
+        flags: 0,
+        default_value: null(),
+        __bindgen_anon_1: rz_cmd_desc_arg_t__bindgen_ty_1 {
+            choices: rz_cmd_desc_arg_t__bindgen_ty_1__bindgen_ty_1 {
+                choices: "\0".as_ptr() as *mut *const i8,
+                choices_cb: None,
+            },
+        },
+    },
+    analysis_bda_help_args,
+];
+
+pub const analysis_bda_export_help: RzCmdDescHelp = RzCmdDescHelp {
+    summary: "Export the iCFG or a CFG as used by BDA.\0"
+        .as_ptr()
+        .cast(),
+    description: "Export the original or loop resolved iCFG, or the CFG of a single procedure. The format is chosen by the file extension: .dot, .graphml or .json\0"
         .as_ptr()
         .cast(),
     args_str: null(),
@@ -128,7 +226,7 @@ pub const analysis_bda_help: RzCmdDescHelp = RzCmdDescHelp {
     sort_subcommands: false,
     details: null(),
     details_cb: None,
-    args: &analysis_bda_help_args,
+    args: &analysis_bda_export_help_args as *const [RzCmdDescArg; 4] as *const RzCmdDescArg,
 };
 
 pub extern "C" fn rz_set_bda_range(core: *mut c_void, node: *mut c_void) -> bool {
@@ -155,6 +253,30 @@ pub extern "C" fn rz_set_bda_entry(core: *mut c_void, node: *mut c_void) -> bool
     true
 }
 
+pub extern "C" fn rz_set_bda_sinks(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    // Just perform a check on the given value.
+    let Some(sinks) = parse_bda_sink_list(c_to_str(pderef!(rz_node).value)) else {
+        return false;
+    };
+    sinks
+        .iter()
+        .all(|(pattern, _)| compile_sink_pattern(pattern).is_some())
+}
+
+pub extern "C" fn rz_set_bda_exclude(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    // Just perform a check on the given value.
+    let Some((_, patterns)) = parse_bda_exclude_list(c_to_str(pderef!(rz_node).value)) else {
+        return false;
+    };
+    patterns
+        .iter()
+        .all(|pattern| compile_exclude_pattern(pattern).is_some())
+}
+
 pub extern "C" fn rz_set_bda_iterations(core: *mut c_void, node: *mut c_void) -> bool {
     let _ = core as *mut RzCore;
     let rz_node = node as *mut RzConfigNode;
@@ -200,6 +322,36 @@ pub extern "C" fn rz_set_bda_node_dups(core: *mut c_void, node: *mut c_void) ->
     true
 }
 
+pub extern "C" fn rz_set_bda_min_confidence(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    // Just perform a check on the given value.
+    if pderef!(rz_node).i_value > 100 {
+        log_rz!(
+            LOG_ERROR,
+            None,
+            "Confidence must be given in percent (0-100)."
+        );
+        return false;
+    }
+    true
+}
+
+pub extern "C" fn rz_set_bda_min_code_probability(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    // Just perform a check on the given value.
+    if pderef!(rz_node).i_value > 100 {
+        log_rz!(
+            LOG_ERROR,
+            None,
+            "Code probability must be given in percent (0-100)."
+        );
+        return false;
+    }
+    true
+}
+
 pub extern "C" fn rz_set_bda_skip_questions(core: *mut c_void, node: *mut c_void) -> bool {
     let _ = core as *mut RzCore;
     let rz_node = node as *mut RzConfigNode;
@@ -211,6 +363,17 @@ pub extern "C" fn rz_set_bda_skip_questions(core: *mut c_void, node: *mut c_void
     true
 }
 
+pub extern "C" fn rz_check_bool(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    let s = c_to_str(pderef!(rz_node).value);
+    if s != "true" && s != "false" {
+        log_rz!(LOG_ERROR, None, "Value must be: 'true' or 'false'");
+        return false;
+    }
+    true
+}
+
 pub extern "C" fn rz_check_timeout(core: *mut c_void, node: *mut c_void) -> bool {
     let _ = core as *mut RzCore;
     let rz_node = node as *mut RzConfigNode;
@@ -233,7 +396,7 @@ pub unsafe extern "C" fn rz_bda_get_config_core(private_data: *mut c_void) -> *m
                 str_to_c!("0x0-0xffffffffffffffff"),
                 Some(rz_set_bda_range),
             ),
-            str_to_c!("Comma separated list of address ranges to analyse. Any instruction outside of the ranges, will be ignored by the path sampler."),
+            str_to_c!("Comma separated list of address ranges to analyse. Any instruction outside of the ranges, will be ignored by the path sampler. A range is either <begin>-<end>, a section, function or symbol name (.text, main) or all flags of a flag space (fs:<name>). Begin and end are hex numbers, names or $$, with +/- offsets (subtractions in parentheses: (main-0x10)-(main+0x20)). Between two names, the range - must be surrounded by spaces: main - sym.foo. Ranges prefixed with ! are excluded. Names are resolved when the analysis starts. Hex numbers without 0x prefix are only taken as numbers if no name of that spelling exists (a000-b000)."),
         );
     rz_config_node_desc(
         rz_config_set_cb(
@@ -244,6 +407,15 @@ pub unsafe extern "C" fn rz_bda_get_config_core(private_data: *mut c_void) -> *m
         ),
         str_to_c!("The regex pattern to identify memory allocating functions."),
     );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.free_name_pattern"),
+            str_to_c!(r"^(sym\.(imp\.)?)?free$"),
+            Some(rz_set_regex_pattern),
+        ),
+        str_to_c!("The regex pattern to identify functions releasing heap memory. The default matches only free() (free, sym.free, sym.imp.free)."),
+    );
     rz_config_node_desc(
         rz_config_set_cb(
             config,
@@ -253,6 +425,24 @@ pub unsafe extern "C" fn rz_bda_get_config_core(private_data: *mut c_void) -> *m
         ),
         str_to_c!("The regex pattern to identify input functions."),
     );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.sampling.exclude"),
+            str_to_c!(""),
+            Some(rz_set_bda_exclude),
+        ),
+        str_to_c!("Comma separated list of procedures the path sampler never enters. Calls to them are skipped like calls to unmapped procedures. Each element is an address or a regex matching the whole procedure name. Addresses start with a digit or $$ and may have +/- offsets (0x4010, $$+0x10). The sym. and sym.imp. prefixes of names are optional. E.g.: printf,.*log.*,0x4010"),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.taint.sinks"),
+            str_to_c!("system:0,popen:0,exec.*:0"),
+            Some(rz_set_bda_sinks),
+        ),
+        str_to_c!("Comma separated list of sink functions and their argument positions (starting at 0), which should not receive input data. Format: <regex>:<arg>[:<arg>...]"),
+    );
     rz_config_node_desc(
             rz_config_set_cb(
                 config,
@@ -261,7 +451,7 @@ pub unsafe extern "C" fn rz_bda_get_config_core(private_data: *mut c_void) -> *m
                 Some(rz_set_bda_entry),
             ),
             str_to_c!(
-                "Comma separated list of address to start path sampling from. Addresses must point to a function start. If empty, the binary entry points are used."
+                "Comma separated list of address to start path sampling from. Addresses must point to a function start. They are hex numbers, function, symbol or flag names or $$, with +/- offsets (e.g. main, sym.handler, 0x1000). Hex numbers without 0x prefix are only taken as numbers if no name of that spelling exists. If empty, the binary entry points are used."
             ),
         );
     rz_config_node_desc(
@@ -311,6 +501,107 @@ pub unsafe extern "C" fn rz_bda_get_config_core(private_data: *mut c_void) -> *m
         ),
         str_to_c!("Number of path samples to buffer at a maximum."),
     );
+    rz_config_node_desc(
+        rz_config_set_i(config, str_to_c!("plugins.bda.seed"), 0),
+        str_to_c!("Seed for the random decisions of BDA. 0 uses a random seed. Results are only reproducible with a single thread and the same number of sampled paths."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.approx_weights"),
+            str_to_c!("false"),
+            Some(rz_check_bool),
+        ),
+        str_to_c!("Approximate path weights with a 64bit significand and an exponent, instead of calculating them exactly. Saves memory and time for binaries with huge path counts. The relative error of a weight is below 2^-61 per addition or multiplication it is computed with."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.export_graph"),
+            str_to_c!(""),
+            None,
+        ),
+        str_to_c!("File to export the sampled iCFG to, after sampling finished. Nodes include the number of executed instruction words. The format is chosen by the file extension: .dot, .graphml or .json. If empty, nothing is exported."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.coverage.file"),
+            str_to_c!(""),
+            None,
+        ),
+        str_to_c!("File to write the executed instruction words to, after sampling finished. It is written in the drcov format, so coverage viewers (Lighthouse, Cutter, bncov) can import it. If empty, nothing is written."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.coverage.annotate"),
+            str_to_c!("false"),
+            Some(rz_check_bool),
+        ),
+        str_to_c!("Annotate the executed instruction words with their hit count as comment, after sampling finished. Procedures never reached are flagged as bda.unreached.<address>."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.weight_cache"),
+            str_to_c!(""),
+            None,
+        ),
+        str_to_c!("File to persist the path weights of procedures in. Weights of unchanged procedures are reused on the next run. If empty, no weights are persisted."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.sysroot"),
+            str_to_c!(""),
+            None,
+        ),
+        str_to_c!("Directory to load the shared libraries of the binary from. Calls through PLT stubs and GOT entries are followed into the library functions. If empty, no libraries are loaded."),
+    );
+    rz_config_node_desc(
+        rz_config_set_cb(
+            config,
+            str_to_c!("plugins.bda.summaries"),
+            str_to_c!(""),
+            None,
+        ),
+        str_to_c!("File with summaries of the memory procedures read, write and return relative to their arguments. They are applied at calls which are not followed. Summaries learned during the run are saved to it. The file is bound to the binary by the SHA-256 digest of the binary file in its header. Files of other binaries are rejected. If empty, no summaries are used."),
+    );
+    rz_config_node_desc(
+        rz_config_set_i(
+            config,
+            str_to_c!("plugins.bda.scope.depth"),
+            2,
+        ),
+        str_to_c!("If functions are passed to aaaaPb, procedures called by them up to this call depth are analysed as well. 0 analyses only the given functions."),
+    );
+    rz_config_node_desc(
+        rz_config_set_i(
+            config,
+            str_to_c!("plugins.bda.post.call_string_len"),
+            0,
+        ),
+        str_to_c!("Number of call sites kept as context of each dependency. 0 reports dependencies without context."),
+    );
+    rz_config_node_desc(
+        rz_config_set_i_cb(
+            config,
+            str_to_c!("plugins.bda.post.min_confidence"),
+            0,
+            Some(rz_set_bda_min_confidence),
+        ),
+        str_to_c!("Minimal confidence (in percent) of reported dependencies. The confidence is the fraction of sampled paths executing both instructions, which observed the dependency. Dependencies which were never observed, but only inferred, have a confidence of 0. So they are only reported with a minimal confidence of 0."),
+    );
+    rz_config_node_desc(
+        rz_config_set_i_cb(
+            config,
+            str_to_c!("plugins.bda.min_code_probability"),
+            0,
+            Some(rz_set_bda_min_code_probability),
+        ),
+        str_to_c!("Minimal probability (in percent) of CFG nodes being code. Nodes below it are removed before sampling. Successors Rizin did not follow are added, if they are above it. The probabilities are determined by a probabilistic disassembly of the executable sections (x86 only). 0 keeps all nodes."),
+    );
     rz_config_node_desc(
         rz_config_set_i_cb(
             config,
@@ -359,10 +650,18 @@ pub unsafe extern "C" fn rz_bda_init_core(
         Some(rz_analysis_bda_handler),
         &analysis_bda_help,
     );
+    rz_cmd_desc_argv_new(
+        (*core).rcmd,
+        binding_cd,
+        "aaaaPbg\0".as_ptr().cast(),
+        Some(rz_analysis_bda_export_handler),
+        &analysis_bda_export_help,
+    );
     // Allocate and assign private data to has table spot.
     let data = Box::new(BDAPrivateData::new());
     let private_data_casted = private_data as *mut *mut BDAPrivateData;
     *private_data_casted = Box::<BDAPrivateData>::into_raw(data);
+    register_bda_private_data(*private_data_casted);
     true
 }
 
@@ -373,7 +672,10 @@ pub unsafe extern "C" fn rz_bda_fini_core(
     // Remove description
     let binding_cd: *mut RzCmdDesc = rz_cmd_get_desc(pderef!(core).rcmd, str_to_c!("aaaaPb"));
     rz_cmd_desc_remove((*core).rcmd, binding_cd);
+    let export_cd: *mut RzCmdDesc = rz_cmd_get_desc(pderef!(core).rcmd, str_to_c!("aaaaPbg"));
+    rz_cmd_desc_remove((*core).rcmd, export_cd);
     // Free private data
+    register_bda_private_data(std::ptr::null_mut());
     drop(Box::<BDAPrivateData>::from_raw(
         *private_data as *mut BDAPrivateData,
     ));
@@ -406,6 +708,151 @@ pub extern "C" fn rizin_plugin_function_bda() -> RzLibStruct {
     rizin_plugin_bda
 }
 
+pub const analysis_osprey_help_args: [RzCmdDescArg; 1] = [analysis_bda_help_args];
+
+pub const analysis_osprey_help: RzCmdDescHelp = RzCmdDescHelp {
+    summary: "Recover variables and their types (algorithm: OSPREY).\0"
+        .as_ptr()
+        .cast(),
+    description: "Recover memory objects, variables and their types from the memory accesses sampled by BDA. The result of the last BDA run of aaaaPo is used. If there is none, BDA is run first (aaaaPb doesn't keep the memory accesses). Recovered types are defined with td and linked to global and stack variables.\0"
+        .as_ptr()
+        .cast(),
+    args_str: null(),
+    usage: null(),
+    options: null(),
+    sort_subcommands: false,
+    details: null(),
+    details_cb: None,
+    args: &analysis_osprey_help_args as *const [RzCmdDescArg; 1] as *const RzCmdDescArg,
+};
+
+pub extern "C" fn rz_set_osprey_iterations(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    // Just perform a check on the given value.
+    if (1..=1024).contains(&pderef!(rz_node).i_value) {
+        return true;
+    }
+    log_rz!(
+        LOG_ERROR,
+        None,
+        "Number of iterations must be between 1 and 1024."
+    );
+    false
+}
+
+pub extern "C" fn rz_set_osprey_probability(core: *mut c_void, node: *mut c_void) -> bool {
+    let _ = core as *mut RzCore;
+    let rz_node = node as *mut RzConfigNode;
+    // Just perform a check on the given value.
+    if pderef!(rz_node).i_value > 100 {
+        log_rz!(
+            LOG_ERROR,
+            None,
+            "Probability must be given in percent (0-100)."
+        );
+        return false;
+    }
+    true
+}
+
+pub unsafe extern "C" fn rz_osprey_get_config_core(private_data: *mut c_void) -> *mut RzConfig {
+    let config = rz_config_new(private_data);
+
+    rz_config_lock(config, 0);
+    // Add settings for OSPREY
+    rz_config_node_desc(
+        rz_config_set_i_cb(
+            config,
+            str_to_c!("plugins.osprey.iterations"),
+            32,
+            Some(rz_set_osprey_iterations),
+        ),
+        str_to_c!(
+            "Number of iterations to propagate the probabilities of contradicting variable hints."
+        ),
+    );
+    rz_config_node_desc(
+        rz_config_set_i_cb(
+            config,
+            str_to_c!("plugins.osprey.min_probability"),
+            50,
+            Some(rz_set_osprey_probability),
+        ),
+        str_to_c!("Minimal probability (in percent) of recovered variables. Less probable variables are dropped."),
+    );
+    rz_config_node_desc(
+        rz_config_set_i_cb(
+            config,
+            str_to_c!("plugins.osprey.types.min_probability"),
+            50,
+            Some(rz_set_osprey_probability),
+        ),
+        str_to_c!("Minimal probability (in percent) of inferred types to be exported to Rizin."),
+    );
+    rz_config_lock(config, 1);
+    config
+}
+
+pub unsafe extern "C" fn rz_osprey_init_core(
+    core: *mut RzCore,
+    _private_data: *mut *mut c_void,
+) -> bool {
+    // OSPREY builds on BDA. Only one plugin per library is loaded, so BDA is added from here.
+    if !rz_core_plugin_add(core, &rz_core_plugin_bda as *const _ as *mut RzCorePlugin) {
+        log_rz!(LOG_ERROR, None, "Failed to add the BDA plugin.");
+        return false;
+    }
+    let mut group_cd: *mut RzCmdDesc = rz_cmd_get_desc((*core).rcmd, str_to_c!("aaaaP"));
+    if group_cd.is_null() {
+        group_cd = get_new_probana_cmd_desc(core);
+    }
+    rz_cmd_desc_argv_new(
+        (*core).rcmd,
+        group_cd,
+        "aaaaPo\0".as_ptr().cast(),
+        Some(rz_analysis_osprey_handler),
+        &analysis_osprey_help,
+    );
+    true
+}
+
+pub unsafe extern "C" fn rz_osprey_fini_core(
+    core: *mut RzCore,
+    _private_data: *mut *mut c_void,
+) -> bool {
+    // Remove description
+    let osprey_cd: *mut RzCmdDesc = rz_cmd_get_desc(pderef!(core).rcmd, str_to_c!("aaaaPo"));
+    rz_cmd_desc_remove((*core).rcmd, osprey_cd);
+    true
+}
+
+pub const rz_core_plugin_osprey: RzCorePlugin = RzCorePlugin {
+    name: "osprey\0".as_ptr().cast(),
+    desc: "Variable and type recovery algorithm by Zhuo Zhang.\0"
+        .as_ptr()
+        .cast(),
+    license: "LGPL-3.0-only\0".as_ptr().cast(),
+    author: "Rot127\0".as_ptr().cast(),
+    version: "0.1\0".as_ptr().cast(),
+    init: Some(rz_osprey_init_core),
+    fini: Some(rz_osprey_fini_core),
+    analysis: None,
+    get_config: Some(rz_osprey_get_config_core),
+};
+
+pub const rizin_plugin_osprey: RzLibStruct = RzLibStruct {
+    type_: RzLibType_RZ_LIB_TYPE_CORE, // Until RzArch is introduced, we leave this as a core plugin, so we can add the command.
+    data: &rz_core_plugin_osprey as *const _ as *const c_void,
+    version: RZ_VERSION.as_ptr().cast(),
+    free: None,
+    is_plugin_owned: true,
+};
+
+pub extern "C" fn rizin_plugin_function_osprey() -> RzLibStruct {
+    rizin_plugin_osprey
+}
+
 pub extern "C" fn rizin_plugin_function_probana() -> RzLibStruct {
     rizin_plugin_probana
 }
@@ -413,9 +860,9 @@ pub extern "C" fn rizin_plugin_function_probana() -> RzLibStruct {
 #[no_mangle]
 pub extern "C" fn rizin_plugin_function() -> RzLibStruct {
     // TODO Check this during build
-    let highest_defined_function = HighestPluginFunction::Bda;
+    let highest_defined_function = HighestPluginFunction::Osprey;
     match highest_defined_function {
-        // HighestPluginFunction::Osprey => rizin_plugin_function_osprey,
+        HighestPluginFunction::Osprey => rizin_plugin_function_osprey(),
         HighestPluginFunction::Bda => rizin_plugin_function_bda(),
         HighestPluginFunction::Binding => rizin_plugin_function_probana(),
         _ => panic!("Cannot call rizin_plugin_function."),
@@ -4,23 +4,30 @@
 use bitflags::bitflags;
 use helper::num::subscript;
 use log::{debug, error, trace, warn};
-use rand::Rng;
+use rand::{rngs::StdRng, Rng};
 use rand_distr::{Distribution, Normal};
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::Display,
     hash::Hash,
-    io::Read,
-    sync::mpsc::Sender,
+    sync::{mpsc::Sender, Arc},
 };
 
 use binding::{
-    c_to_str, effect_to_str, pderef, rz_analysis_insn_word_free, rz_analysis_op_free, GRzCore,
-    RzAnalysisInsnWord, RzAnalysisOp, RzRegisterId, RzRegisterId_RZ_REG_NAME_BP,
-    RzRegisterId_RZ_REG_NAME_R0, RzRegisterId_RZ_REG_NAME_SP,
+    effect_to_str, pderef, rz_analysis_insn_word_free, rz_analysis_op_free, RzAnalysisInsnWord,
+    RzAnalysisOp, RzRegisterId, RzRegisterId_RZ_REG_NAME_A0, RzRegisterId_RZ_REG_NAME_A1,
+    RzRegisterId_RZ_REG_NAME_A2, RzRegisterId_RZ_REG_NAME_A3, RzRegisterId_RZ_REG_NAME_A4,
+    RzRegisterId_RZ_REG_NAME_A5, RzRegisterId_RZ_REG_NAME_BP, RzRegisterId_RZ_REG_NAME_R0,
+    RzRegisterId_RZ_REG_NAME_SP,
 };
 
-use crate::{bitvector::BitVector, op_handler::eval_effect};
+use crate::{
+    bitvector::BitVector,
+    jump_table::{bytes_to_u64, get_code_ptr_table, IndexExpr, JumpTable},
+    op_handler::eval_effect,
+    summary::{ArgAccess, FunctionSummaries, FunctionSummary, SummaryReturn},
+    vm_core::GVMCore,
+};
 
 /// If this plugin is still used, when 128bit address space is a thing, do grep "64".
 pub type Address = u64;
@@ -99,6 +106,10 @@ bitflags! {
         const IsMemRead = 1 << 9;
         /// IWord contains a memory write
         const IsMemWrite = 1 << 10;
+        /// IWord calls a function releasing heap memory.
+        const CallsFree = 1 << 11 | Self::IsCall.bits();
+        /// IWord calls a taint sink.
+        const CallsSink = 1 << 12 | Self::IsCall.bits();
         /// A tail call to another function.
         const IsTailCall = Self::IsTail.bits() | Self::IsJump.bits();
         /// Exits the program by calling a function (e.g. `abort`, `stack_chk_fail`).
@@ -152,6 +163,14 @@ impl IWordInfo {
     pub fn calls_unmapped(&self) -> bool {
         (*self & IWordInfo::CallsUnmapped) == IWordInfo::CallsUnmapped
     }
+
+    pub fn calls_free(&self) -> bool {
+        (*self & IWordInfo::CallsFree) == IWordInfo::CallsFree
+    }
+
+    pub fn calls_sink(&self) -> bool {
+        (*self & IWordInfo::CallsSink) == IWordInfo::CallsSink
+    }
 }
 
 impl Display for IWordInfo {
@@ -219,10 +238,61 @@ impl Display for IWordInfo {
                 return Err(e);
             }
         }
+
+        if self.calls_free() {
+            if let Err(e) = write!(f, "f") {
+                return Err(e);
+            }
+        }
+
+        if self.calls_sink() {
+            if let Err(e) = write!(f, "s") {
+                return Err(e);
+            }
+        }
         write!(f, "")
     }
 }
 
+bitflags! {
+    /// Hints on the type of the values an instruction word reads from or writes to memory.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct TypeHint: u64 {
+        /// No hint observed.
+        const None = 0;
+        /// The value points into a heap region.
+        const HeapPtr = 1 << 0;
+        /// The value points into a stack frame.
+        const StackPtr = 1 << 1;
+        /// The value is used as memory address.
+        const Deref = 1 << 2;
+        /// The value is an operand of integer arithmetic (multiplication, division, shifts, bit operations).
+        const Arith = 1 << 3;
+        /// The value is passed to a function releasing heap memory.
+        const FreeArg = 1 << 4;
+        /// The value is passed as size to an allocating function.
+        const SizeArg = 1 << 5;
+    }
+}
+
+impl TypeHint {
+    pub fn is_none(&self) -> bool {
+        *self == TypeHint::None
+    }
+
+    /// True if the value was observed to be a pointer.
+    pub fn is_pointer(&self) -> bool {
+        self.intersects(
+            TypeHint::HeapPtr | TypeHint::StackPtr | TypeHint::Deref | TypeHint::FreeArg,
+        )
+    }
+
+    /// True if the value was observed to be used as integer.
+    pub fn is_integer(&self) -> bool {
+        self.intersects(TypeHint::Arith | TypeHint::SizeArg)
+    }
+}
+
 pub struct IntrpPath {
     /// Execution path of instructions.
     path: VecDeque<(Address, IWordInfo)>,
@@ -298,6 +368,9 @@ pub struct ConcreteCodeXref {
     from: Address,
     /// The callee
     to: Address,
+    /// Set if the callee was not observed, but is a plausible target.
+    /// E.g. the other entries of a vtable an observed target was read from.
+    candidate: bool,
 }
 
 impl ConcreteCodeXref {
@@ -307,9 +380,45 @@ impl ConcreteCodeXref {
             proc_addr,
             from,
             to,
+            candidate: false,
+        }
+    }
+
+    pub fn new_candidate(
+        xtype: CodeXrefType,
+        proc_addr: Address,
+        from: Address,
+        to: Address,
+    ) -> Self {
+        Self {
+            xtype,
+            proc_addr,
+            from,
+            to,
+            candidate: true,
         }
     }
 
+    /// Returns the same xref, but as observed one.
+    pub fn as_observed(&self) -> Self {
+        Self {
+            candidate: false,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the same xref, but as candidate.
+    pub fn as_candidate(&self) -> Self {
+        Self {
+            candidate: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_candidate(&self) -> bool {
+        self.candidate
+    }
+
     pub fn get_xtype(&self) -> CodeXrefType {
         self.xtype
     }
@@ -339,8 +448,11 @@ impl std::fmt::Display for ConcreteCodeXref {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "icodexref {:#x} : {:#x} -> {:#x}",
-            self.proc_addr, self.from, self.to
+            "icodexref {:#x} : {:#x} -> {:#x}{}",
+            self.proc_addr,
+            self.from,
+            self.to,
+            if self.candidate { " (candidate)" } else { "" }
         )
     }
 }
@@ -361,6 +473,18 @@ impl MemXref {
     pub fn new(from: Address, to: Address, size: u64) -> MemXref {
         MemXref { from, to, size }
     }
+
+    pub fn get_from(&self) -> Address {
+        self.from
+    }
+
+    pub fn get_to(&self) -> Address {
+        self.to
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
 }
 
 impl std::fmt::Display for MemXref {
@@ -399,6 +523,125 @@ impl std::fmt::Display for StackXref {
     }
 }
 
+#[derive(Eq, PartialEq, Hash, Clone, Copy, PartialOrd, Ord, Debug)]
+pub enum HeapFindingType {
+    /// A heap region was read or written after it was released.
+    UseAfterFree,
+    /// A heap region was released twice.
+    DoubleFree,
+}
+
+impl std::fmt::Display for HeapFindingType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeapFindingType::UseAfterFree => write!(f, "use-after-free"),
+            HeapFindingType::DoubleFree => write!(f, "double-free"),
+        }
+    }
+}
+
+/// An access to a released heap region, detected on a sampled path.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HeapFinding {
+    ftype: HeapFindingType,
+    /// The instruction accessing or releasing the already released region.
+    at: Address,
+    /// The instruction which released the region first.
+    freed_at: Address,
+    /// The released heap region.
+    region: MemRegion,
+    /// The executed path up to (and including) the instruction at [at].
+    witness: Vec<Address>,
+}
+
+impl HeapFinding {
+    pub fn new(
+        ftype: HeapFindingType,
+        at: Address,
+        freed_at: Address,
+        region: MemRegion,
+        witness: Vec<Address>,
+    ) -> HeapFinding {
+        HeapFinding {
+            ftype,
+            at,
+            freed_at,
+            region,
+            witness,
+        }
+    }
+
+    pub fn get_ftype(&self) -> HeapFindingType {
+        self.ftype
+    }
+
+    pub fn get_at(&self) -> Address {
+        self.at
+    }
+
+    pub fn get_freed_at(&self) -> Address {
+        self.freed_at
+    }
+
+    pub fn get_region(&self) -> &MemRegion {
+        &self.region
+    }
+
+    pub fn get_witness(&self) -> &Vec<Address> {
+        &self.witness
+    }
+}
+
+impl std::fmt::Display for HeapFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:#x} : {} released at {:#x}",
+            self.ftype, self.at, self.region, self.freed_at
+        )
+    }
+}
+
+/// A flow of input data from one instruction to another, observed during interpretation.
+/// Either via a register or because the value was loaded from the buffer of an input function.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataFlow {
+    /// The instruction defining the value.
+    /// This is either a load or the call to an input function.
+    from: Address,
+    /// The instruction using the value.
+    to: Address,
+    /// The argument position, if [to] is a call to a sink.
+    sink_arg: Option<usize>,
+}
+
+impl DataFlow {
+    pub fn new(from: Address, to: Address, sink_arg: Option<usize>) -> DataFlow {
+        DataFlow { from, to, sink_arg }
+    }
+
+    pub fn get_from(&self) -> Address {
+        self.from
+    }
+
+    pub fn get_to(&self) -> Address {
+        self.to
+    }
+
+    pub fn get_sink_arg(&self) -> Option<usize> {
+        self.sink_arg
+    }
+}
+
+impl std::fmt::Display for DataFlow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(arg) = self.sink_arg {
+            return write!(f, "{:#x} -> {:#x} (arg {})", self.from, self.to, arg);
+        }
+        write!(f, "{:#x} -> {:#x}", self.from, self.to)
+    }
+}
+
 /// Memory region classes: Global, Stack, Heap
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum MemRegionClass {
@@ -430,6 +673,28 @@ pub struct MemRegion {
     ic: u32,
 }
 
+impl MemRegion {
+    pub fn is_global(&self) -> bool {
+        self.class == MemRegionClass::Global
+    }
+
+    pub fn is_stack(&self) -> bool {
+        self.class == MemRegionClass::Stack
+    }
+
+    pub fn is_heap(&self) -> bool {
+        self.class == MemRegionClass::Heap
+    }
+
+    pub fn get_base(&self) -> Address {
+        self.base
+    }
+
+    pub fn get_ic(&self) -> u32 {
+        self.ic
+    }
+}
+
 impl std::fmt::Display for MemRegion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let letter = match self.class {
@@ -457,6 +722,22 @@ pub struct AbstrVal {
     ///
     /// This value is not compared or used for hashing!
     il_gvar: Option<String>,
+    /// The instruction which defined the input data this value holds.
+    /// It is passed on to the results of operations on this value.
+    /// None if the value holds no input data.
+    ///
+    /// This value is not compared or used for hashing!
+    input_origin: Option<Address>,
+    /// How this value depends on an unknown index (e.g. of a jump table).
+    /// None if it doesn't depend on unknown values.
+    ///
+    /// This value is not compared or used for hashing!
+    index: Option<IndexExpr>,
+    /// The memory accessing instruction words, which read or wrote this value.
+    /// Uses of the value are hints on the type of the accessed memory.
+    ///
+    /// This value is not compared or used for hashing!
+    accesses: BTreeSet<Address>,
 }
 
 impl Hash for AbstrVal {
@@ -522,7 +803,14 @@ impl AbstrVal {
             base,
             ic,
         };
-        AbstrVal { m, c, il_gvar }
+        AbstrVal {
+            m,
+            c,
+            il_gvar,
+            input_origin: None,
+            index: None,
+            accesses: BTreeSet::new(),
+        }
     }
 
     pub fn new_stack(ic: u32, offset: BitVector, base: Address) -> AbstrVal {
@@ -535,6 +823,9 @@ impl AbstrVal {
             m,
             c: offset,
             il_gvar: None,
+            input_origin: None,
+            index: None,
+            accesses: BTreeSet::new(),
         }
     }
 
@@ -548,6 +839,9 @@ impl AbstrVal {
             m,
             c: offset,
             il_gvar: None,
+            input_origin: None,
+            index: None,
+            accesses: BTreeSet::new(),
         }
     }
 
@@ -555,6 +849,32 @@ impl AbstrVal {
         self.il_gvar = gvar;
     }
 
+    pub fn get_input_origin(&self) -> Option<Address> {
+        self.input_origin
+    }
+
+    pub fn set_input_origin(&mut self, origin: Option<Address>) {
+        self.input_origin = origin;
+    }
+
+    /// Derives the memory accesses of this value, which is the sum or difference of [v1] and [v2].
+    /// They are taken from the operand read from or written to memory (e.g. a pointer to a struct member).
+    pub fn derive_accesses(&mut self, v1: &AbstrVal, v2: &AbstrVal) {
+        match (v1.accesses.is_empty(), v2.accesses.is_empty()) {
+            (false, true) => self.accesses = v1.accesses.clone(),
+            (true, false) => self.accesses = v2.accesses.clone(),
+            _ => (),
+        }
+    }
+
+    pub fn get_index_expr(&self) -> Option<&IndexExpr> {
+        self.index.as_ref()
+    }
+
+    pub fn set_index_expr(&mut self, index: Option<IndexExpr>) {
+        self.index = index;
+    }
+
     pub fn get_width(&self) -> u32 {
         self.c.width()
     }
@@ -568,7 +888,14 @@ impl AbstrVal {
     }
 
     pub fn new(m: MemRegion, c: BitVector, il_gvar: Option<String>) -> AbstrVal {
-        AbstrVal { m, c, il_gvar }
+        AbstrVal {
+            m,
+            c,
+            il_gvar,
+            input_origin: None,
+            index: None,
+            accesses: BTreeSet::new(),
+        }
     }
 
     /// Checks if the abstract value is equal to global zero (a.k.a False).
@@ -624,6 +951,9 @@ impl AbstrVal {
             m: av.m.clone(),
             c,
             il_gvar: av.il_gvar.clone(),
+            input_origin: av.input_origin,
+            index: av.index,
+            accesses: av.accesses,
         }
     }
 
@@ -634,7 +964,7 @@ impl AbstrVal {
 }
 
 /// An operation on the constant share of abstract values
-type AbstrOp2 = fn(v1: &BitVector, v2: &BitVector) -> BitVector;
+pub(crate) type AbstrOp2 = fn(v1: &BitVector, v2: &BitVector) -> BitVector;
 type AbstrOp1 = fn(v1: &BitVector) -> BitVector;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -690,6 +1020,29 @@ impl std::fmt::Display for CallFrame {
 /// With every return, a CallFrame is popped.
 type CallStack = Vec<CallFrame>;
 
+/// Argument registers in the order of their index.
+const ARG_ROLES: [RzRegisterId; 6] = [
+    RzRegisterId_RZ_REG_NAME_A0,
+    RzRegisterId_RZ_REG_NAME_A1,
+    RzRegisterId_RZ_REG_NAME_A2,
+    RzRegisterId_RZ_REG_NAME_A3,
+    RzRegisterId_RZ_REG_NAME_A4,
+    RzRegisterId_RZ_REG_NAME_A5,
+];
+
+/// The effects of a followed call, observed until the callee returns.
+struct SummaryFrame {
+    /// Entry of the called procedure.
+    entry: Address,
+    /// Size of the call stack, after the call frame of the callee was pushed.
+    depth: usize,
+    /// Argument values at the call.
+    args: Vec<Option<AbstrVal>>,
+    /// Value of the return register at the call.
+    ret_at_call: Option<AbstrVal>,
+    summary: FunctionSummary,
+}
+
 /// Resulting by-products of the abstract interpretation.
 #[derive(Debug)]
 pub struct IntrpProducts {
@@ -701,6 +1054,20 @@ pub struct IntrpProducts {
     pub mem_xrefs: BTreeSet<MemXref>,
     pub stack_xrefs: BTreeSet<StackXref>,
     pub mos: MemOpSeq,
+    /// Accesses to released heap regions.
+    pub heap_findings: BTreeSet<HeapFinding>,
+    /// Flows of input data into registers, memory and sink arguments.
+    pub data_flows: BTreeSet<DataFlow>,
+    /// Number of times each instruction word was executed.
+    pub iword_hits: BTreeMap<Address, u64>,
+    /// Number of times the execution went from one instruction word to another.
+    pub edge_hits: BTreeMap<(Address, Address), u64>,
+    /// Summaries of the procedures which returned on the path.
+    pub summaries: FunctionSummaries,
+    /// Number of bytes read or written by each memory accessing instruction word.
+    pub access_sizes: BTreeMap<Address, u64>,
+    /// Hints on the type of the values each memory accessing instruction word reads or writes.
+    pub type_hints: BTreeMap<Address, TypeHint>,
 }
 
 impl IntrpProducts {
@@ -712,10 +1079,30 @@ impl IntrpProducts {
             mem_xrefs: BTreeSet::new(),
             stack_xrefs: BTreeSet::new(),
             mos: MemOpSeq::new(),
+            heap_findings: BTreeSet::new(),
+            data_flows: BTreeSet::new(),
+            iword_hits: BTreeMap::new(),
+            edge_hits: BTreeMap::new(),
+            summaries: FunctionSummaries::new(),
+            access_sizes: BTreeMap::new(),
+            type_hints: BTreeMap::new(),
         }
     }
 }
 
+/// Counts how often each instruction word and each edge between them was [walked].
+fn count_hits(walked: &[Address]) -> (BTreeMap<Address, u64>, BTreeMap<(Address, Address), u64>) {
+    let mut iword_hits = BTreeMap::<Address, u64>::new();
+    let mut edge_hits = BTreeMap::<(Address, Address), u64>::new();
+    for addr in walked.iter() {
+        *iword_hits.entry(*addr).or_default() += 1;
+    }
+    for edge in walked.windows(2) {
+        *edge_hits.entry((edge[0], edge[1])).or_default() += 1;
+    }
+    (iword_hits, edge_hits)
+}
+
 /// An abstract interpreter VM. It will perform the abstract execution.
 pub struct AbstrVM {
     /// ID of the thread this VM is executed in.
@@ -777,30 +1164,71 @@ pub struct AbstrVM {
     mem_xrefs: BTreeSet<MemXref>,
     /// Stack references
     stack_xrefs: BTreeSet<StackXref>,
-    /// Rizin Core
-    rz_core: GRzCore,
+    /// Released heap regions. Mapped to the address of the releasing instruction.
+    freed_regions: BTreeMap<MemRegion, Address>,
+    /// Accesses to released heap regions.
+    heap_findings: BTreeSet<HeapFinding>,
+    /// Addresses of the instruction words executed so far.
+    /// Used as witness for heap findings.
+    walked: Vec<Address>,
+    /// Heap regions returned by input functions.
+    input_regions: BTreeSet<MemRegion>,
+    /// Flows of input data observed so far.
+    data_flows: BTreeSet<DataFlow>,
+    /// Rizin core or a mock of it.
+    core: GVMCore,
     /// Normal distribution
     dist: Normal<f64>,
+    /// Random number generator of the path.
+    rng: StdRng,
     /// Maximum number of REPEAT iterations, if they are not static
     limit_repeat: usize,
     /// Buffer for instruction words. Indexed by address.
     iword_buffer: BTreeMap<Address, *mut RzAnalysisInsnWord>,
     /// Buffer for instruction words. Indexed by address.
     aop_buffer: BTreeMap<Address, *mut RzAnalysisOp>,
+    /// Targets of the jump tables read so far.
+    jump_table_buffer: BTreeMap<JumpTable, Vec<Address>>,
+    /// Largest values of the unknown indices, learned from comparisons.
+    index_bounds: BTreeMap<u64, u64>,
+    /// Identifier of the next unknown index.
+    next_index: u64,
+    /// True if memory is read as big endian.
+    big_endian: bool,
+    /// Number of bytes read or written by each memory accessing instruction word.
+    access_sizes: BTreeMap<Address, u64>,
+    /// Hints on the type of the values each memory accessing instruction word reads or writes.
+    type_hints: BTreeMap<Address, TypeHint>,
+    /// Code pointer tables read so far. Indexed by the address of the called entry.
+    code_ptr_table_buffer: BTreeMap<Address, Vec<Address>>,
+    /// Summaries of procedures. Applied at calls which are not followed.
+    summaries: Arc<FunctionSummaries>,
+    /// Effects of the followed calls which did not return yet.
+    summary_frames: Vec<SummaryFrame>,
+    /// Summaries of the procedures which returned on this path.
+    learned_summaries: FunctionSummaries,
 }
 
 macro_rules! unlocked_core {
     ($self:expr) => {
-        $self.get_rz_core().clone().lock().unwrap()
+        $self.get_core().clone().lock().unwrap()
     };
 }
 
 impl AbstrVM {
     /// Creates a new abstract interpreter VM.
-    /// It takes the initial program counter [pc], the [path] to walk
-    /// and the sampling function for generating random values for input values.
-    pub fn new(rz_core: GRzCore, entry: PC, path: IntrpPath) -> AbstrVM {
-        let limit_repeat = rz_core.lock().unwrap().get_bda_max_iterations() as usize;
+    /// It takes the initial program counter [pc], the [path] to walk,
+    /// the [summaries] of procedures, which might not be followed,
+    /// and the generator [rng] random values are sampled with.
+    pub fn new(
+        core: GVMCore,
+        entry: PC,
+        path: IntrpPath,
+        summaries: Arc<FunctionSummaries>,
+        rng: StdRng,
+    ) -> AbstrVM {
+        let limit_repeat = core.lock().unwrap().get_max_iterations() as usize;
+        let big_endian = core.lock().unwrap().is_big_endian();
         let mut vm = AbstrVM {
             thread_id: usize::MAX,
             pc: entry,
@@ -825,14 +1253,30 @@ impl AbstrVM {
             jumps_xref: BTreeSet::new(),
             mem_xrefs: BTreeSet::new(),
             stack_xrefs: BTreeSet::new(),
-            rz_core: rz_core.clone(),
+            freed_regions: BTreeMap::new(),
+            heap_findings: BTreeSet::new(),
+            walked: Vec::new(),
+            input_regions: BTreeSet::new(),
+            data_flows: BTreeSet::new(),
+            core: core.clone(),
             dist: Normal::new(0.0, 32768.0_f64.powi(2)).unwrap(),
+            rng,
             limit_repeat,
             iword_buffer: BTreeMap::new(),
             aop_buffer: BTreeMap::new(),
+            jump_table_buffer: BTreeMap::new(),
+            index_bounds: BTreeMap::new(),
+            next_index: 0,
+            big_endian,
+            access_sizes: BTreeMap::new(),
+            type_hints: BTreeMap::new(),
+            code_ptr_table_buffer: BTreeMap::new(),
             state_backup: VecDeque::new(),
+            summaries,
+            summary_frames: Vec::new(),
+            learned_summaries: FunctionSummaries::new(),
         };
-        vm.init_register_file(rz_core);
+        vm.init_register_file(core);
         vm
     }
 
@@ -870,32 +1314,56 @@ impl AbstrVM {
         self.limit_repeat
     }
 
-    pub fn get_rz_core(&self) -> &GRzCore {
-        &self.rz_core
+    pub fn get_core(&self) -> &GVMCore {
+        &self.core
+    }
+
+    pub(crate) fn get_rng(&mut self) -> &mut StdRng {
+        &mut self.rng
     }
 
     pub fn add_call_xref(&mut self, proc_addr: Address, to: Address) {
         if self.is_invalid_addr(to) {
             return;
         }
-        self.calls_xref.insert(ConcreteCodeXref {
-            xtype: CodeXrefType::IndirectCall,
-            proc_addr,
-            from: self.pc,
-            to,
-        });
+        let xref = ConcreteCodeXref::new(CodeXrefType::IndirectCall, proc_addr, self.pc, to);
+        self.calls_xref.remove(&xref.as_candidate());
+        self.calls_xref.insert(xref);
+    }
+
+    /// Adds a call xref to a callee, which is plausible but was not observed.
+    pub fn add_candidate_call_xref(&mut self, proc_addr: Address, to: Address) {
+        if self.is_invalid_addr(to) {
+            return;
+        }
+        let xref =
+            ConcreteCodeXref::new_candidate(CodeXrefType::IndirectCall, proc_addr, self.pc, to);
+        if self.calls_xref.contains(&xref.as_observed()) {
+            return;
+        }
+        self.calls_xref.insert(xref);
     }
 
     pub fn add_jump_xref(&mut self, proc_addr: Address, to: Address) {
         if self.is_invalid_addr(to) {
             return;
         }
-        self.jumps_xref.insert(ConcreteCodeXref {
-            xtype: CodeXrefType::IndirectJump,
-            proc_addr,
-            from: self.pc,
-            to,
-        });
+        let xref = ConcreteCodeXref::new(CodeXrefType::IndirectJump, proc_addr, self.pc, to);
+        self.jumps_xref.remove(&xref.as_candidate());
+        self.jumps_xref.insert(xref);
+    }
+
+    /// Adds a jump xref to a target, which is plausible but was not observed.
+    pub fn add_candidate_jump_xref(&mut self, proc_addr: Address, to: Address) {
+        if self.is_invalid_addr(to) {
+            return;
+        }
+        let xref =
+            ConcreteCodeXref::new_candidate(CodeXrefType::IndirectJump, proc_addr, self.pc, to);
+        if self.jumps_xref.contains(&xref.as_observed()) {
+            return;
+        }
+        self.jumps_xref.insert(xref);
     }
 
     pub fn add_mem_xref(&mut self, to: Address, size: u64) {
@@ -913,6 +1381,114 @@ impl AbstrVM {
         self.stack_xrefs.insert(StackXref { at: self.pc, var });
     }
 
+    fn add_heap_finding(&mut self, ftype: HeapFindingType, region: MemRegion, freed_at: Address) {
+        let finding = HeapFinding {
+            ftype,
+            at: self.pc,
+            freed_at,
+            region,
+            witness: self.walked.clone(),
+        };
+        warn!(target: "AbstrInterpreter", "TID: {} - {}", self.thread_id, finding);
+        self.heap_findings.insert(finding);
+    }
+
+    /// Checks if the memory at [key] belongs to a released heap region.
+    /// If so, a use-after-free is recorded.
+    pub fn check_heap_access(&mut self, key: &AbstrVal) {
+        if !key.is_heap() {
+            return;
+        }
+        if let Some(freed_at) = self.freed_regions.get(key.get_mem_region()) {
+            self.add_heap_finding(
+                HeapFindingType::UseAfterFree,
+                key.get_mem_region().clone(),
+                *freed_at,
+            );
+        }
+    }
+
+    /// Marks the heap region, the first argument register points to, as released.
+    /// This function is usually called instead of a call to a free-like function.
+    /// If the region was already released, a double-free is recorded.
+    pub fn release_heap_region_of_arg(&mut self) {
+        let Some(arg_name) = self.reg_roles.get(&RzRegisterId_RZ_REG_NAME_A0).cloned() else {
+            warn!(target: "AbstrInterpreter", "TID: {} - No register with the A0 role. Cannot release heap region.", self.thread_id);
+            return;
+        };
+        let Some(ptr) = self.get_varg(&arg_name) else {
+            return;
+        };
+        if !ptr.is_heap() {
+            // free(NULL) or a pointer we can't track.
+            return;
+        }
+        let region = ptr.get_mem_region().clone();
+        if let Some(freed_at) = self.freed_regions.get(&region) {
+            let freed_at = *freed_at;
+            self.add_heap_finding(HeapFindingType::DoubleFree, region, freed_at);
+            return;
+        }
+        debug!(target: "AbstrInterpreter", "TID: {} - FREE: {}", self.thread_id, region);
+        self.freed_regions.insert(region, self.pc);
+    }
+
+    /// Returns the instruction which defined the input data [val] holds.
+    /// Pointers into the buffer of an input function originate from the input call.
+    /// Returns None if [val] holds no input data.
+    fn get_input_origin(&self, val: &AbstrVal) -> Option<Address> {
+        if val.is_heap() && self.input_regions.contains(val.get_mem_region()) {
+            return Some(val.get_mem_region().base);
+        }
+        val.input_origin
+    }
+
+    /// Tracks input data loaded from [key] into the value [val].
+    /// The load becomes the origin of the input data [val] holds.
+    pub fn add_load_flow(&mut self, key: &AbstrVal, val: &mut AbstrVal) {
+        if key.is_heap() && self.input_regions.contains(key.get_mem_region()) {
+            self.data_flows
+                .insert(DataFlow::new(key.get_mem_region().base, self.pc, None));
+        } else if val.input_origin.is_none() {
+            return;
+        }
+        // Loads of values written before are connected to the store via the memory dependencies.
+        val.input_origin = Some(self.pc);
+    }
+
+    /// Tracks input data stored to memory with the value [val].
+    pub fn add_store_flow(&mut self, val: &AbstrVal) {
+        if let Some(origin) = self.get_input_origin(val) {
+            self.data_flows.insert(DataFlow::new(origin, self.pc, None));
+        }
+    }
+
+    /// Records input data passed to the sink called at the current PC.
+    /// An argument receives input data, if it holds input data itself
+    /// or points to memory holding input data.
+    fn add_sink_flows(&mut self) {
+        for (i, role) in ARG_ROLES.iter().enumerate() {
+            let Some(arg_name) = self.reg_roles.get(role).cloned() else {
+                continue;
+            };
+            let Some(arg) = self.get_varg(&arg_name) else {
+                continue;
+            };
+            let pointee = self
+                .ms
+                .get(&self.normalize_val(arg.clone(), false))
+                .cloned();
+            let origin = self
+                .get_input_origin(&arg)
+                .or_else(|| pointee.and_then(|v| self.get_input_origin(&v)));
+            if let Some(origin) = origin {
+                debug!(target: "AbstrInterpreter", "TID: {} - Input from {:#x} reaches sink argument {}", self.thread_id, origin, i);
+                self.data_flows
+                    .insert(DataFlow::new(origin, self.pc, Some(i)));
+            }
+        }
+    }
+
     pub fn get_varg(&self, name: &str) -> Option<AbstrVal> {
         if self.gvars.get(name).is_none() {
             warn!(
@@ -987,24 +1563,21 @@ impl AbstrVM {
     /// simulate input for the program.
     /// It takes the address of an input-functions at [address] and the current
     /// [invocation] of the function.
-    pub fn rv(&self, width: u32) -> BitVector {
+    pub fn rv(&mut self, width: u32) -> BitVector {
         if width <= 64 {
-            return BitVector::new_from_u64(
-                width,
-                self.dist.sample(&mut rand::thread_rng()) as u64,
-            );
+            return BitVector::new_from_u64(width, self.dist.sample(&mut self.rng) as u64);
         }
         let samples_cnt = width + 7 >> 3;
         let mut v_buf = Vec::<u8>::new();
         for _ in 0..samples_cnt {
-            v_buf.push(self.dist.sample(&mut rand::thread_rng()) as u8);
+            v_buf.push(self.dist.sample(&mut self.rng) as u8);
         }
         BitVector::from_bytes_be(width, v_buf)
     }
 
     /// Samples with a 0.5 chance a true (1) or false (0) value.
-    pub fn rvb(&self) -> BitVector {
-        if rand::thread_rng().gen_bool(0.5) {
+    pub fn rvb(&mut self) -> BitVector {
+        if self.rng.gen_bool(0.5) {
             BitVector::new_true()
         } else {
             BitVector::new_false()
@@ -1014,30 +1587,29 @@ impl AbstrVM {
     /// Initializes the register profile, register alias and their initial
     /// abstract values.
     /// Returns false if it fails.
-    fn init_register_file(&mut self, rz_core: GRzCore) {
+    fn init_register_file(&mut self, vm_core: GVMCore) {
         debug!(
             target: "AbstrInterpreter",
             "TID: {} - register file for abstract interpreter.",
             self.thread_id
         );
 
-        if rz_core.is_poisoned() {
-            rz_core.clear_poison();
+        if vm_core.is_poisoned() {
+            vm_core.clear_poison();
         }
-        let core = rz_core.lock().unwrap();
+        let core = vm_core.lock().unwrap();
 
         self.pc_bit_width = core.get_arch_bits();
 
         // Set the register alias
         let alias = core.get_reg_alias();
-        for ralias in alias {
-            let ra = pderef!(ralias);
-            if let Some(_) = self.reg_roles.insert(ra.role, c_to_str(ra.reg_name)) {
+        for (role, reg_name) in alias {
+            if let Some(_) = self.reg_roles.insert(role, reg_name.clone()) {
                 warn!(
                     target: "AbstrInterpreter",
                         "TID: {} - Duplicate role of register {} detected",
                         self.thread_id,
-                        c_to_str(ra.reg_name)
+                        reg_name
                 );
             }
         }
@@ -1053,14 +1625,8 @@ impl AbstrVM {
 
         // Initialize complete register file
         let reg_bindings = core.get_reg_bindings().expect("Could not get reg_bindings");
-        let reg_count = pderef!(reg_bindings).regs_count;
-        let regs = pderef!(reg_bindings).regs;
         let mut stack_access_size = 0;
-        for i in 0..reg_count {
-            let reg = unsafe { regs.offset(i as isize) };
-            let rsize = pderef!(reg).size;
-            let rname = pderef!(reg).name;
-            let name = c_to_str(rname);
+        for (name, rsize) in reg_bindings {
             debug!(
                 target: "AbstrInterpreter",
                 "TID: {} - \t-> {}", self.thread_id, name
@@ -1097,7 +1663,7 @@ impl AbstrVM {
         sample_bool: bool,
     ) -> (AbstrVal, TaintFlag) {
         let tainted: TaintFlag;
-        let v3: AbstrVal;
+        let mut v3: AbstrVal;
         if v1.m.class == MemRegionClass::Global {
             v3 = AbstrVal::new(v1.m.clone(), op(&v1.c), None);
             tainted = TaintFlag::Unset;
@@ -1114,6 +1680,8 @@ impl AbstrVM {
             );
             tainted = TaintFlag::Set;
         }
+        v3.input_origin = v1.input_origin;
+        v3.index = v1.index.map(|_| IndexExpr::Opaque);
         (v3, tainted)
     }
 
@@ -1130,7 +1698,7 @@ impl AbstrVM {
         sample_bool: bool,
     ) -> (AbstrVal, TaintFlag) {
         let tainted: TaintFlag;
-        let v3: AbstrVal;
+        let mut v3: AbstrVal;
         if v1.m.class == MemRegionClass::Global {
             v3 = AbstrVal::new(v2.m.clone(), op(&v1.c, &v2.c), None);
             tainted = TaintFlag::Unset;
@@ -1152,6 +1720,9 @@ impl AbstrVM {
             );
             tainted = TaintFlag::Set;
         }
+        v3.input_origin = v1.input_origin.or(v2.input_origin);
+        // Operations which keep the dependency on an index linear refine this.
+        v3.index = v1.index.or(v2.index).map(|_| IndexExpr::Opaque);
         (v3, tainted)
     }
 
@@ -1318,10 +1889,168 @@ impl AbstrVM {
     }
 
     pub fn read_io_at_u64(&self, addr: Address, n_bytes: usize) -> u64 {
-        let data = self.read_io_at(addr, n_bytes);
-        let mut buf: [u8; 8] = [0; 8];
-        data.as_slice().read_exact(&mut buf[..n_bytes]).unwrap();
-        u64::from_le_bytes(buf)
+        bytes_to_u64(&self.read_io_at(addr, n_bytes), self.big_endian)
+    }
+
+    /// Returns the targets of the jump table [table].
+    pub(crate) fn get_jump_table_targets(&mut self, table: &JumpTable) -> Vec<Address> {
+        if let Some(targets) = self.jump_table_buffer.get(table) {
+            return targets.clone();
+        }
+        let targets = {
+            let vm_core = self.get_core().clone();
+            let core = vm_core.lock().unwrap();
+            table.get_targets(
+                |addr, n_bytes| {
+                    let data = core.read_io_mapped_at(addr, n_bytes)?;
+                    Some(bytes_to_u64(&data, self.big_endian))
+                },
+                |addr| !self.is_invalid_addr(addr) && core.is_exec_addr(addr),
+            )
+        };
+        self.jump_table_buffer
+            .insert(table.clone(), targets.clone());
+        targets
+    }
+
+    /// Sets the index expression of the value [val] loaded with [key].
+    /// Values loaded with an index are table entries.
+    /// Values of memory which was not written and is not read-only are unknown indices.
+    pub(crate) fn set_load_index(&mut self, key: &AbstrVal, val: &mut AbstrVal, n_bytes: usize) {
+        if self.ms.contains_key(key) {
+            // Stored values keep their expression.
+            return;
+        }
+        val.index = match &key.index {
+            Some(key_index) if key.is_global() => Some(IndexExpr::load(key_index, n_bytes)),
+            Some(_) => Some(IndexExpr::Opaque),
+            None if key.is_global()
+                && unlocked_core!(self).is_read_only_addr(key.get_as_addr()) =>
+            {
+                None
+            }
+            None => Some(self.new_index(None)),
+        };
+    }
+
+    /// Returns a new unknown index, which is at most [max_index] (if known).
+    pub(crate) fn new_index(&mut self, max_index: Option<u64>) -> IndexExpr {
+        let index = self.next_index;
+        self.next_index += 1;
+        if let Some(max_index) = max_index {
+            self.index_bounds.insert(index, max_index);
+        }
+        IndexExpr::new_index(index)
+    }
+
+    /// Bounds the index of [x] by the comparison `x <= max`.
+    /// The path is not known to take the branch of the comparison being true.
+    /// But the bound only over-approximates the other branch by one value.
+    /// So it is used either way.
+    pub(crate) fn bound_index(&mut self, x: &AbstrVal, max: &AbstrVal) {
+        if max.index.is_some() || !max.is_global() {
+            return;
+        }
+        let Some((index, max_index)) = x
+            .index
+            .as_ref()
+            .and_then(|e| IndexExpr::get_bound(e, max.get_as_addr()))
+        else {
+            return;
+        };
+        let bound = self.index_bounds.entry(index).or_insert(max_index);
+        *bound = (*bound).min(max_index);
+    }
+
+    /// Returns the largest value of the unknown [index], if it is known.
+    pub(crate) fn get_index_bound(&self, index: u64) -> Option<u64> {
+        self.index_bounds.get(&index).copied()
+    }
+
+    /// Returns the code pointers of the read-only table, which holds the entry at [slot].
+    /// Returns an empty vector if [slot] is writable or doesn't point to code.
+    pub(crate) fn get_code_ptr_table_targets(
+        &mut self,
+        slot: Address,
+        entry_size: usize,
+    ) -> Vec<Address> {
+        if let Some(targets) = self.code_ptr_table_buffer.get(&slot) {
+            return targets.clone();
+        }
+        let targets = {
+            let vm_core = self.get_core().clone();
+            let core = vm_core.lock().unwrap();
+            if !core.is_read_only_addr(slot) {
+                Vec::new()
+            } else {
+                get_code_ptr_table(
+                    slot,
+                    entry_size,
+                    |addr, n_bytes| {
+                        if !core.is_read_only_addr(addr) {
+                            return None;
+                        }
+                        let data = core.read_io_mapped_at(addr, n_bytes)?;
+                        Some(bytes_to_u64(&data, self.big_endian))
+                    },
+                    |addr| !self.is_invalid_addr(addr) && core.is_exec_addr(addr),
+                )
+            }
+        };
+        self.code_ptr_table_buffer.insert(slot, targets.clone());
+        targets
+    }
+
+    /// Logs the number of bytes the instruction word at the PC accesses.
+    /// If it accesses memory multiple times, the largest size is kept.
+    pub(crate) fn add_access_size(&mut self, n_bytes: u64) {
+        let size = self.access_sizes.entry(self.pc).or_default();
+        *size = (*size).max(n_bytes);
+    }
+
+    /// Adds the [hint] to the instruction word at [addr].
+    fn add_type_hint_at(&mut self, addr: Address, hint: TypeHint) {
+        let hints = self.type_hints.entry(addr).or_insert(TypeHint::None);
+        *hints |= hint;
+    }
+
+    /// Adds the hints of the value [val] the instruction word at the PC reads from or writes to memory.
+    /// The access is added to [val], so its uses can be attributed to it.
+    pub(crate) fn add_mem_val_type_hints(&mut self, val: &mut AbstrVal) {
+        if val.is_heap() {
+            self.add_type_hint_at(self.pc, TypeHint::HeapPtr);
+        } else if val.is_stack() {
+            self.add_type_hint_at(self.pc, TypeHint::StackPtr);
+        }
+        val.accesses.insert(self.pc);
+    }
+
+    /// Adds the [hint] about a use of [val] to the memory accesses, which read or wrote it.
+    pub(crate) fn add_use_type_hint(&mut self, val: &AbstrVal, hint: TypeHint) {
+        for addr in val.accesses.iter() {
+            self.add_type_hint_at(*addr, hint);
+        }
+    }
+
+    /// Adds the hint of an integer operation to the accesses of its operands.
+    pub(crate) fn add_arith_type_hints(&mut self, v1: &AbstrVal, v2: &AbstrVal) {
+        self.add_use_type_hint(v1, TypeHint::Arith);
+        self.add_use_type_hint(v2, TypeHint::Arith);
+    }
+
+    /// Adds the [hint] to the accesses of the first argument of the called function.
+    fn add_arg_type_hint(&mut self, hint: TypeHint) {
+        let Some(arg_name) = self.reg_roles.get(&RzRegisterId_RZ_REG_NAME_A0).cloned() else {
+            return;
+        };
+        let Some(arg) = self.get_varg(&arg_name) else {
+            return;
+        };
+        self.add_use_type_hint(&arg, hint);
+    }
+
+    pub(crate) fn get_pc_bit_width(&self) -> usize {
+        self.pc_bit_width
     }
 
     pub fn read_mem(&self, addr: Address, n_bytes: usize) -> Vec<u8> {
@@ -1451,6 +2180,202 @@ impl AbstrVM {
         self.set_varg(&rr_name, hval);
     }
 
+    /// Marks the heap region, the return register points to, as buffer of an input function.
+    fn mark_ret_reg_as_input(&mut self) {
+        let rr_name = self.get_reg_name_by_role(RzRegisterId_RZ_REG_NAME_R0);
+        let Some(rval) = self.get_varg(&rr_name) else {
+            return;
+        };
+        self.input_regions.insert(rval.get_mem_region().clone());
+    }
+
+    /// Returns the values of the argument registers, indexed like [ARG_ROLES].
+    fn get_arg_vals(&self) -> Vec<Option<AbstrVal>> {
+        ARG_ROLES
+            .iter()
+            .map(|role| {
+                self.reg_roles
+                    .get(role)
+                    .and_then(|name| self.gvars.get(name))
+                    .cloned()
+            })
+            .collect()
+    }
+
+    fn get_ret_val(&self) -> Option<AbstrVal> {
+        self.reg_roles
+            .get(&RzRegisterId_RZ_REG_NAME_R0)
+            .and_then(|name| self.gvars.get(name))
+            .cloned()
+    }
+
+    /// Starts recording the effects of the procedure at [entry],
+    /// which was just called.
+    fn summary_frame_begin(&mut self, entry: Address) {
+        self.summary_frames.push(SummaryFrame {
+            entry,
+            depth: self.cs.len(),
+            args: self.get_arg_vals(),
+            ret_at_call: self.get_ret_val(),
+            summary: FunctionSummary::new(),
+        });
+    }
+
+    /// Ends the recording of the procedure which returns now.
+    /// Its summary is added to the learned ones.
+    fn summary_frame_end(&mut self) {
+        if self
+            .summary_frames
+            .last()
+            .map_or(true, |frame| frame.depth != self.cs.len())
+        {
+            // Call and return are not paired up (e.g. the call target was unknown).
+            return;
+        }
+        let mut frame = self.summary_frames.pop().unwrap();
+        let ret = self.get_ret_val();
+        if ret.is_some() && ret != frame.ret_at_call {
+            let ret = ret.unwrap();
+            let rr_name = self.get_reg_name_by_role(RzRegisterId_RZ_REG_NAME_R0);
+            frame.summary.ret = Some(
+                if let Some(i) = frame.args.iter().position(|a| a.as_ref() == Some(&ret)) {
+                    SummaryReturn::Arg(i)
+                } else if ret.is_heap()
+                    && !frame
+                        .args
+                        .iter()
+                        .flatten()
+                        .any(|a| a.get_mem_region() == ret.get_mem_region())
+                {
+                    SummaryReturn::Heap
+                } else if ret.is_global()
+                    && self.rt.get(&rr_name).is_some_and(|t| t.is_known_const())
+                {
+                    SummaryReturn::Const(ret.get_as_addr())
+                } else {
+                    SummaryReturn::Unknown
+                },
+            );
+        }
+        self.learned_summaries
+            .entry(frame.entry)
+            .or_insert_with(FunctionSummary::new)
+            .merge(&frame.summary);
+    }
+
+    /// Records an access to the memory at [key] in the summaries of all
+    /// procedures, which got a pointer into the same memory region as argument.
+    /// Pointers to global memory are not considered.
+    /// Because every constant argument would point into it.
+    pub(crate) fn add_summary_access(&mut self, key: &AbstrVal, size: u64, is_write: bool) {
+        for frame in self.summary_frames.iter_mut() {
+            for (i, arg) in frame.args.iter().enumerate() {
+                let Some(arg) = arg else {
+                    continue;
+                };
+                if arg.is_global()
+                    || arg.get_mem_region() != key.get_mem_region()
+                    || arg.get_width() != key.get_width()
+                {
+                    continue;
+                }
+                let offset = (key.get_const() - arg.get_const()).as_i64();
+                let access = ArgAccess::new(i, offset, size);
+                if is_write {
+                    frame.summary.writes.insert(access);
+                } else {
+                    frame.summary.reads.insert(access);
+                }
+            }
+        }
+    }
+
+    /// Returns the memory address an [access] of a summary refers to.
+    fn get_summary_access_key(
+        &self,
+        args: &[Option<AbstrVal>],
+        access: &ArgAccess,
+    ) -> Option<AbstrVal> {
+        if access.size == 0 {
+            return None;
+        }
+        let arg = args.get(access.arg)?.as_ref()?;
+        let offset = BitVector::new_from_i64(arg.get_width(), access.offset);
+        let key = AbstrVal::new(
+            arg.get_mem_region().clone(),
+            arg.get_const() + &offset,
+            None,
+        );
+        Some(self.normalize_val(key, false))
+    }
+
+    /// Applies the summary of the procedure at [callee] at the current call site.
+    /// The reads and writes of the callee are attributed to the call instruction.
+    /// Written memory gets an unknown value.
+    /// Returns false if there is no summary for [callee].
+    fn apply_summary(&mut self, callee: Address) -> bool {
+        let Some(summary) = self.summaries.get(&callee).cloned() else {
+            return false;
+        };
+        debug!(target: "AbstrInterpreter", "TID: {} - Apply summary of {:#x}", self.thread_id, callee);
+        let args = self.get_arg_vals();
+        for access in summary.reads.iter() {
+            let Some(key) = self.get_summary_access_key(&args, access) else {
+                continue;
+            };
+            self.enqueue_mos(&key);
+            self.check_heap_access(&key);
+            let (mut v, _) = self.get_mem_val(&key, access.size as usize);
+            self.add_load_flow(&key, &mut v);
+            self.add_iword_info(IWordInfo::IsMemRead);
+        }
+        for access in summary.writes.iter() {
+            let Some(key) = self.get_summary_access_key(&args, access) else {
+                continue;
+            };
+            self.enqueue_mos(&key);
+            self.check_heap_access(&key);
+            // Loads from it sample a new value.
+            self.ms.remove(&key);
+            self.mt.remove(&key);
+            self.add_iword_info(IWordInfo::IsMemWrite);
+        }
+        let rr_name = self.get_reg_name_by_role(RzRegisterId_RZ_REG_NAME_R0);
+        match summary.ret {
+            Some(SummaryReturn::Arg(i)) => {
+                if let Some(Some(v)) = args.get(i) {
+                    self.set_varg(&rr_name, v.clone());
+                }
+            }
+            Some(SummaryReturn::Heap) => self.move_heap_val_into_ret_reg(),
+            Some(SummaryReturn::Const(c)) => {
+                let rr_size = self.get_reg_size(&rr_name);
+                let ic = self.get_pc_ic();
+                let v = AbstrVal::new_global(
+                    ic,
+                    BitVector::new_from_u64(rr_size as u32, c),
+                    None,
+                    self.get_pc(),
+                );
+                self.set_varg(&rr_name, v);
+                self.rt.insert(rr_name, TaintFlag::Unset);
+            }
+            Some(SummaryReturn::Unknown) | None => (),
+        }
+        true
+    }
+
+    /// Returns the constant target of the call at the current PC.
+    /// Or None if the target is not known statically.
+    fn get_direct_call_target(&mut self) -> Option<Address> {
+        let ana_op = self.get_buffered_aop();
+        let target = pderef!(ana_op).jump;
+        if target == u64::MAX || self.is_invalid_addr(target) {
+            return None;
+        }
+        Some(target)
+    }
+
     fn get_reg_name_by_role(&self, role: RzRegisterId) -> String {
         self.reg_roles
             .get(&role)
@@ -1514,6 +2439,7 @@ impl AbstrVM {
             return StepResult::Done;
         }
         self.add_iword_info(self.insn_info);
+        self.walked.push(self.pc);
         debug!(target: "AbstrInterpreter", "TID: {} - pc = {:#x}", self.thread_id, self.pc);
 
         *self.ic.entry(self.pc).or_default() += 1;
@@ -1526,10 +2452,10 @@ impl AbstrVM {
             return StepResult::Exit;
         }
 
-        let iword_decoder = unlocked_core!(self).get_iword_decoder();
+        let has_iword_decoder = unlocked_core!(self).has_iword_decoder();
         let effect;
         let result;
-        if iword_decoder.is_some() {
+        if has_iword_decoder {
             // So if the next address in the path is not ht next address, a call is performed.
             let iword = self.get_buffered_iword();
             self.is.insert(self.pc, pderef!(iword).size_bytes as u64);
@@ -1540,7 +2466,9 @@ impl AbstrVM {
             self.is.insert(self.pc, pderef!(ana_op).size as u64);
             effect = pderef!(ana_op).il_op;
         }
-        let (skip_reason, execute_insn) = if self.insn_info.calls_malloc() {
+        let (skip_reason, execute_insn) = if self.insn_info.calls_free() {
+            ("calls free", false)
+        } else if self.insn_info.calls_malloc() {
             // Not yet done for instruction words. Instruction words must only skip the call part.
             ("calls malloc", false)
         } else if self.insn_info.calls_input() {
@@ -1556,11 +2484,26 @@ impl AbstrVM {
         if dont_commit_to_state {
             self.backup_state();
         }
+        let cs_len = self.cs.len();
+
+        if self.insn_info.calls_sink() {
+            self.add_sink_flows();
+        }
 
         if !execute_insn {
-            if self.insn_info.calls_malloc() || self.insn_info.calls_input() {
+            if self.insn_info.calls_free() {
+                self.add_arg_type_hint(TypeHint::FreeArg);
+                self.release_heap_region_of_arg();
+            } else if self.insn_info.calls_malloc() || self.insn_info.calls_input() {
+                if self.insn_info.calls_malloc() {
+                    // Before the argument register is overwritten with the return value.
+                    self.add_arg_type_hint(TypeHint::SizeArg);
+                }
                 self.move_heap_val_into_ret_reg();
             }
+            if self.insn_info.calls_input() {
+                self.mark_ret_reg_as_input();
+            }
             debug!(target: "AbstrInterpreter", "TID: {} - Skip call: {}", self.thread_id, skip_reason);
             result = true;
         } else if effect != std::ptr::null_mut() {
@@ -1571,21 +2514,38 @@ impl AbstrVM {
             result = true;
         }
 
+        // The callee of a call not followed. Only known, if the call was interpreted.
+        let mut skipped_callee = None;
         if self.pc_is_tail_call() {
             // Pop CallFrame from stack before jumping to the next one.
             debug!(target: "AbstrInterpreter", "TID: {} - Tail call", self.thread_id);
             if let Some(target) = self.peak_next_addr() {
+                self.summary_frame_end();
                 self.call_stack_pop();
                 self.call_stack_push(target);
+                self.summary_frame_begin(target);
             }
         } else if self.pc_is_return() {
+            self.summary_frame_end();
             self.call_stack_pop();
+        } else if self.pc_is_call() && self.cs.len() > cs_len {
+            if dont_commit_to_state {
+                skipped_callee = Some(self.get_cur_entry());
+            } else {
+                self.summary_frame_begin(self.get_cur_entry());
+            }
         }
         self.lvars.clear();
 
         if dont_commit_to_state {
             self.restore_state();
         }
+        if !execute_insn && self.insn_info.calls_unmapped() {
+            skipped_callee = self.get_direct_call_target();
+        }
+        if let Some(callee) = skipped_callee {
+            self.apply_summary(callee);
+        }
         if result {
             return StepResult::Ok;
         }
@@ -1666,9 +2626,19 @@ enum StepResult {
 }
 
 /// Interprets the given path with the given interpreter VM.
-pub fn interpret(thread_id: usize, rz_core: GRzCore, path: IntrpPath, tx: Sender<IntrpProducts>) {
+/// Calls, which are not followed on the path, apply the effects of their callee's
+/// summary in [summaries].
+/// Random values are sampled with the generator [rng] of the path.
+pub fn interpret(
+    thread_id: usize,
+    core: GVMCore,
+    path: IntrpPath,
+    summaries: Arc<FunctionSummaries>,
+    tx: Sender<IntrpProducts>,
+    rng: StdRng,
+) {
     trace!(target: "AbstrInterpreter", "TID: {thread_id}: {}", path);
-    let mut vm = AbstrVM::new(rz_core, path.get(0).0, path);
+    let mut vm = AbstrVM::new(core, path.get(0).0, path, summaries, rng);
     vm.thread_id = thread_id;
 
     let mut step = StepResult::Ok;
@@ -1690,6 +2660,7 @@ pub fn interpret(thread_id: usize, rz_core: GRzCore, path: IntrpPath, tx: Sender
     vm.free_buffers();
 
     debug!(target: "AbstrInterpreter", "TID: {} - EXIT\n", vm.thread_id);
+    let (iword_hits, edge_hits) = count_hits(&vm.walked);
     // Replace with Channel and send/receive
     let products = IntrpProducts {
         iword_info: vm.iword_info.into(),
@@ -1698,6 +2669,13 @@ pub fn interpret(thread_id: usize, rz_core: GRzCore, path: IntrpPath, tx: Sender
         mem_xrefs: vm.mem_xrefs.into(),
         stack_xrefs: vm.stack_xrefs.into(),
         mos: vm.mos.into(),
+        heap_findings: vm.heap_findings.into(),
+        data_flows: vm.data_flows.into(),
+        iword_hits,
+        edge_hits,
+        summaries: vm.learned_summaries,
+        access_sizes: vm.access_sizes,
+        type_hints: vm.type_hints,
     };
 
     if let Err(_) = tx.send(products) {
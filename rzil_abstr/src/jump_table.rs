@@ -0,0 +1,292 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use crate::interpreter::Address;
+
+/// Maximum number of entries read from a single jump table.
+pub const MAX_JUMP_TABLE_ENTRIES: u64 = 1024;
+
+/// A table of jump targets (e.g. of a switch statement),
+/// indexed by a value the interpreter doesn't know.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JumpTable {
+    /// Address of the first entry.
+    pub base: Address,
+    /// Distance between two entries in bytes.
+    pub stride: u64,
+    /// Size of an entry in bytes.
+    pub entry_size: usize,
+    /// If set, the entries are signed offsets relative to this address.
+    pub rel_base: Option<Address>,
+    /// Factor the entries are multiplied with (e.g. half-word offsets).
+    pub entry_scale: u64,
+    /// Largest index the table is read with. If None, the index is not bound.
+    pub max_index: Option<u64>,
+}
+
+impl JumpTable {
+    pub fn new(base: Address, stride: u64, entry_size: usize) -> JumpTable {
+        JumpTable {
+            base,
+            stride,
+            entry_size,
+            rel_base: None,
+            entry_scale: 1,
+            max_index: None,
+        }
+    }
+
+    /// Returns the jump target an entry with the value [entry] encodes.
+    fn decode(&self, entry: u64) -> Address {
+        let Some(rel_base) = self.rel_base else {
+            return entry.wrapping_mul(self.entry_scale);
+        };
+        let bits = (self.entry_size * 8) as u32;
+        let offset = if bits >= 64 {
+            entry as i64
+        } else {
+            ((entry << (64 - bits)) as i64) >> (64 - bits)
+        };
+        rel_base.wrapping_add((offset as u64).wrapping_mul(self.entry_scale))
+    }
+
+    /// Enumerates the jump targets of the table.
+    /// [read] returns the entry of [entry_size] bytes at the given address, or None if it is not mapped.
+    /// [is_code] checks if a target points to executable memory.
+    /// If [max_index] is set, all entries up to it are read and those not pointing to code are skipped.
+    /// Otherwise, the index is assumed to be bound by the number of consecutive entries pointing to code.
+    /// At most [MAX_JUMP_TABLE_ENTRIES] entries are read.
+    pub fn get_targets(
+        &self,
+        read: impl Fn(Address, usize) -> Option<u64>,
+        is_code: impl Fn(Address) -> bool,
+    ) -> Vec<Address> {
+        let mut targets = Vec::new();
+        let n_entries = match self.max_index {
+            _ if self.stride == 0 => 1,
+            Some(max_index) => max_index.saturating_add(1).min(MAX_JUMP_TABLE_ENTRIES),
+            None => MAX_JUMP_TABLE_ENTRIES,
+        };
+        for i in 0..n_entries {
+            let entry_addr = self.base.wrapping_add(i.wrapping_mul(self.stride));
+            let Some(entry) = read(entry_addr, self.entry_size) else {
+                break;
+            };
+            let target = self.decode(entry);
+            if !is_code(target) {
+                if self.max_index.is_some() {
+                    continue;
+                }
+                break;
+            }
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        targets
+    }
+}
+
+/// Returns the value of the [data] bytes read from memory of the given endianness.
+/// At most 8 bytes are read.
+pub fn bytes_to_u64(data: &[u8], big_endian: bool) -> u64 {
+    let n_bytes = data.len().min(8);
+    let mut buf: [u8; 8] = [0; 8];
+    if big_endian {
+        buf[8 - n_bytes..].copy_from_slice(&data[..n_bytes]);
+        return u64::from_be_bytes(buf);
+    }
+    buf[..n_bytes].copy_from_slice(&data[..n_bytes]);
+    u64::from_le_bytes(buf)
+}
+
+/// Describes how an abstract value depends on a value the interpreter doesn't know (the index).
+/// Indices are identified by a number, which is unique within one interpreted path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexExpr {
+    /// The value is `offset + index * scale`.
+    Linear { index: u64, offset: u64, scale: u64 },
+    /// The value is the jump target read from [table] with the index.
+    Entry { index: u64, table: JumpTable },
+    /// The value depends on unknown values in another way.
+    Opaque,
+}
+
+impl IndexExpr {
+    pub fn new_index(index: u64) -> IndexExpr {
+        IndexExpr::Linear {
+            index,
+            offset: 0,
+            scale: 1,
+        }
+    }
+
+    /// Returns the expression of [expr] + [c].
+    fn add_const(expr: &IndexExpr, c: u64) -> IndexExpr {
+        match expr {
+            IndexExpr::Linear {
+                index,
+                offset,
+                scale,
+            } => IndexExpr::Linear {
+                index: *index,
+                offset: offset.wrapping_add(c),
+                scale: *scale,
+            },
+            IndexExpr::Entry { index, table } if table.rel_base.is_none() => {
+                let mut table = table.clone();
+                table.rel_base = Some(c);
+                IndexExpr::Entry {
+                    index: *index,
+                    table,
+                }
+            }
+            _ => IndexExpr::Opaque,
+        }
+    }
+
+    /// Returns the expression of [expr] * [c].
+    fn mul_const(expr: &IndexExpr, c: u64) -> IndexExpr {
+        match expr {
+            IndexExpr::Linear {
+                index,
+                offset,
+                scale,
+            } => IndexExpr::Linear {
+                index: *index,
+                offset: offset.wrapping_mul(c),
+                scale: scale.wrapping_mul(c),
+            },
+            IndexExpr::Entry { index, table } if table.rel_base.is_none() => {
+                let mut table = table.clone();
+                table.entry_scale = table.entry_scale.wrapping_mul(c);
+                IndexExpr::Entry {
+                    index: *index,
+                    table,
+                }
+            }
+            _ => IndexExpr::Opaque,
+        }
+    }
+
+    /// Returns the expression of the sum of two values.
+    /// [x] and [y] are the expressions of the values, [cx] and [cy] their constants.
+    /// Values without expression don't depend on an index.
+    pub fn add(
+        x: Option<&IndexExpr>,
+        cx: u64,
+        y: Option<&IndexExpr>,
+        cy: u64,
+    ) -> Option<IndexExpr> {
+        match (x, y) {
+            (None, None) => None,
+            (Some(x), None) => Some(IndexExpr::add_const(x, cy)),
+            (None, Some(y)) => Some(IndexExpr::add_const(y, cx)),
+            (Some(_), Some(_)) => Some(IndexExpr::Opaque),
+        }
+    }
+
+    /// Returns the expression of the difference of two values. See [IndexExpr::add].
+    pub fn sub(x: Option<&IndexExpr>, y: Option<&IndexExpr>, cy: u64) -> Option<IndexExpr> {
+        match (x, y) {
+            (None, None) => None,
+            (Some(x @ IndexExpr::Linear { .. }), None) => {
+                Some(IndexExpr::add_const(x, cy.wrapping_neg()))
+            }
+            _ => Some(IndexExpr::Opaque),
+        }
+    }
+
+    /// Returns the expression of the product of two values. See [IndexExpr::add].
+    pub fn mul(
+        x: Option<&IndexExpr>,
+        cx: u64,
+        y: Option<&IndexExpr>,
+        cy: u64,
+    ) -> Option<IndexExpr> {
+        match (x, y) {
+            (None, None) => None,
+            (Some(x), None) => Some(IndexExpr::mul_const(x, cy)),
+            (None, Some(y)) => Some(IndexExpr::mul_const(y, cx)),
+            (Some(_), Some(_)) => Some(IndexExpr::Opaque),
+        }
+    }
+
+    /// Returns the expression of a value shifted left. See [IndexExpr::add].
+    pub fn shiftl(x: Option<&IndexExpr>, y: Option<&IndexExpr>, cy: u64) -> Option<IndexExpr> {
+        match (x, y) {
+            (None, None) => None,
+            (Some(x), None) if cy < 64 => Some(IndexExpr::mul_const(x, 1 << cy)),
+            _ => Some(IndexExpr::Opaque),
+        }
+    }
+
+    /// Returns the expression of the value loaded with a key of the expression [key].
+    /// A linear key reads the entries of a table of [entry_size] bytes.
+    /// Unless it is scaled by less than [entry_size]. Then the entries would overlap.
+    pub fn load(key: &IndexExpr, entry_size: usize) -> IndexExpr {
+        match key {
+            IndexExpr::Linear {
+                index,
+                offset,
+                scale,
+            } if (1..=8).contains(&entry_size) && *scale >= entry_size as u64 => IndexExpr::Entry {
+                index: *index,
+                table: JumpTable::new(*offset, *scale, entry_size),
+            },
+            _ => IndexExpr::Opaque,
+        }
+    }
+
+    /// Returns the index and its largest value, if the value of [expr] is at most [max].
+    /// Only values which are the index plus a (signed) offset are bound.
+    pub fn get_bound(expr: &IndexExpr, max: u64) -> Option<(u64, u64)> {
+        let IndexExpr::Linear {
+            index,
+            offset,
+            scale: 1,
+        } = expr
+        else {
+            return None;
+        };
+        let max_index = if (*offset as i64) < 0 {
+            max.checked_add(offset.wrapping_neg())?
+        } else {
+            max.checked_sub(*offset)?
+        };
+        Some((*index, max_index))
+    }
+}
+
+/// Returns the code pointers of the table (e.g. a vtable), which contains the
+/// entry at [slot]. The table is assumed to be bound by the entries next to it,
+/// which don't point to code. At most [MAX_JUMP_TABLE_ENTRIES] entries are read in each direction.
+/// [read] and [is_code] are used as in [JumpTable::get_targets].
+pub fn get_code_ptr_table(
+    slot: Address,
+    entry_size: usize,
+    read: impl Fn(Address, usize) -> Option<u64>,
+    is_code: impl Fn(Address) -> bool,
+) -> Vec<Address> {
+    let stride = entry_size as u64;
+    let mut targets = JumpTable::new(slot, stride, entry_size).get_targets(&read, &is_code);
+    if targets.is_empty() {
+        return targets;
+    }
+    let mut addr = slot;
+    for _ in 0..MAX_JUMP_TABLE_ENTRIES {
+        let Some(prev) = addr.checked_sub(stride) else {
+            break;
+        };
+        addr = prev;
+        match read(addr, entry_size) {
+            Some(target) if is_code(target) => {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+            _ => break,
+        }
+    }
+    targets
+}
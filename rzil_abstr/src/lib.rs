@@ -3,5 +3,12 @@
 
 pub mod bitvector;
 pub mod interpreter;
+pub mod jump_table;
+pub mod mock_core;
 mod op_handler;
+pub mod summary;
 mod test_interpreter;
+mod test_jump_table;
+mod test_mock_core;
+mod test_summary;
+pub mod vm_core;
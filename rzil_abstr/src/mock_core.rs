@@ -0,0 +1,353 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    collections::BTreeMap,
+    ffi::{c_char, CString},
+    sync::{Arc, Mutex},
+};
+
+use binding::{
+    rz_analysis_insn_word_new, rz_analysis_op_new, rz_il_op_new_add, rz_il_op_new_bitv_from_ut64,
+    rz_il_op_new_branch, rz_il_op_new_jmp, rz_il_op_new_loadw, rz_il_op_new_mul, rz_il_op_new_nop,
+    rz_il_op_new_seq, rz_il_op_new_set, rz_il_op_new_signed, rz_il_op_new_storew, rz_il_op_new_sub,
+    rz_il_op_new_ule, rz_il_op_new_var, RzAnalysisInsnWord, RzAnalysisOp, RzILOpEffect, RzILOpPure,
+    RzILVarKind_RZ_IL_VAR_KIND_GLOBAL, RzRegisterId, RzRegisterId_RZ_REG_NAME_A0,
+    RzRegisterId_RZ_REG_NAME_A1, RzRegisterId_RZ_REG_NAME_A2, RzRegisterId_RZ_REG_NAME_A3,
+    RzRegisterId_RZ_REG_NAME_A4, RzRegisterId_RZ_REG_NAME_A5, RzRegisterId_RZ_REG_NAME_BP,
+    RzRegisterId_RZ_REG_NAME_PC, RzRegisterId_RZ_REG_NAME_R0, RzRegisterId_RZ_REG_NAME_SP,
+    RZ_PERM_R, RZ_PERM_W, RZ_PERM_X,
+};
+
+use crate::{
+    interpreter::Address,
+    vm_core::{GVMCore, VMCore},
+};
+
+/// Builds the RzIL effect of a mocked instruction.
+/// It is called on every decoding, because the interpreter frees the effects it got.
+pub type EffectBuilder = Box<dyn Fn() -> *mut RzILOpEffect + Send>;
+
+struct MockInsn {
+    size: u32,
+    /// Jump target set in the analysis op.
+    jump: Option<Address>,
+    effect: EffectBuilder,
+}
+
+struct MockMap {
+    addr: Address,
+    data: Vec<u8>,
+    perm: u32,
+}
+
+impl MockMap {
+    fn contains(&self, addr: Address) -> bool {
+        self.addr <= addr && addr - self.addr < self.data.len() as u64
+    }
+}
+
+/// An in-memory core for the abstract interpreter.
+///
+/// Registers, memory maps, flags and the RzIL effects of each instruction
+/// are set by hand. So the abstract semantics can be tested without Rizin
+/// analysing a binary.
+pub struct MockCore {
+    bits: usize,
+    max_iterations: u64,
+    iwords: bool,
+    reg_alias: Vec<(RzRegisterId, String)>,
+    regs: Vec<(String, u32)>,
+    insns: BTreeMap<Address, MockInsn>,
+    maps: Vec<MockMap>,
+    flags: BTreeMap<Address, String>,
+    big_endian: bool,
+}
+
+impl MockCore {
+    /// Creates a mock of an architecture with [bits] wide addresses and no registers.
+    pub fn new(bits: usize) -> MockCore {
+        MockCore {
+            bits,
+            max_iterations: 32,
+            iwords: false,
+            reg_alias: Vec::new(),
+            regs: Vec::new(),
+            insns: BTreeMap::new(),
+            maps: Vec::new(),
+            flags: BTreeMap::new(),
+            big_endian: false,
+        }
+    }
+
+    /// Creates a mock with the general purpose registers of x86-64
+    /// and the roles of the SysV ABI.
+    pub fn new_x86_64() -> MockCore {
+        let mut core = MockCore::new(64);
+        for name in [
+            "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11",
+            "r12", "r13", "r14", "r15", "rip",
+        ] {
+            core.add_reg(name, 64);
+        }
+        for (role, name) in [
+            (RzRegisterId_RZ_REG_NAME_PC, "rip"),
+            (RzRegisterId_RZ_REG_NAME_SP, "rsp"),
+            (RzRegisterId_RZ_REG_NAME_BP, "rbp"),
+            (RzRegisterId_RZ_REG_NAME_R0, "rax"),
+            (RzRegisterId_RZ_REG_NAME_A0, "rdi"),
+            (RzRegisterId_RZ_REG_NAME_A1, "rsi"),
+            (RzRegisterId_RZ_REG_NAME_A2, "rdx"),
+            (RzRegisterId_RZ_REG_NAME_A3, "rcx"),
+            (RzRegisterId_RZ_REG_NAME_A4, "r8"),
+            (RzRegisterId_RZ_REG_NAME_A5, "r9"),
+        ] {
+            core.set_reg_role(role, name);
+        }
+        core
+    }
+
+    /// Adds a register [name] of [size] bits, which is bound to an RzIL variable.
+    pub fn add_reg(&mut self, name: &str, size: u32) {
+        self.regs.push((name.to_owned(), size));
+    }
+
+    /// Assigns the [role] (SP, BP, A0 etc.) to the register [name].
+    pub fn set_reg_role(&mut self, role: RzRegisterId, name: &str) {
+        self.reg_alias.retain(|(r, _)| *r != role);
+        self.reg_alias.push((role, name.to_owned()));
+    }
+
+    /// Maps [data] at [addr] with the permissions [perm] (RZ_PERM_R etc.).
+    pub fn map(&mut self, addr: Address, data: &[u8], perm: u32) {
+        self.maps.push(MockMap {
+            addr,
+            data: data.to_vec(),
+            perm,
+        });
+    }
+
+    /// Adds an instruction of [size] bytes at [addr], whose RzIL effect is built by [effect].
+    /// The instruction bytes are mapped executable. They are 0x90, if nothing was mapped there.
+    pub fn add_insn<F>(&mut self, addr: Address, size: u32, effect: F)
+    where
+        F: Fn() -> *mut RzILOpEffect + Send + 'static,
+    {
+        if !self.maps.iter().any(|m| m.contains(addr)) {
+            self.map(addr, &vec![0x90; size as usize], RZ_PERM_R | RZ_PERM_X);
+        }
+        self.insns.insert(
+            addr,
+            MockInsn {
+                size,
+                jump: None,
+                effect: Box::new(effect),
+            },
+        );
+    }
+
+    /// Sets the jump target of the analysis op at [addr].
+    pub fn set_insn_jump(&mut self, addr: Address, target: Address) {
+        self.insns
+            .get_mut(&addr)
+            .expect("No instruction mocked there.")
+            .jump = Some(target);
+    }
+
+    /// Adds a flag [name] at [addr].
+    pub fn add_flag(&mut self, addr: Address, name: &str) {
+        self.flags.insert(addr, name.to_owned());
+    }
+
+    /// Decodes the instructions as instruction words, if [iwords] is set.
+    pub fn set_iwords(&mut self, iwords: bool) {
+        self.iwords = iwords;
+    }
+
+    /// Reads memory as big endian, if [big_endian] is set.
+    pub fn set_big_endian(&mut self, big_endian: bool) {
+        self.big_endian = big_endian;
+    }
+
+    pub fn set_max_iterations(&mut self, max_iterations: u64) {
+        self.max_iterations = max_iterations;
+    }
+
+    pub fn into_core(self) -> GVMCore {
+        Arc::new(Mutex::new(self))
+    }
+
+    fn get_insn(&self, addr: Address) -> &MockInsn {
+        self.insns
+            .get(&addr)
+            .unwrap_or_else(|| panic!("No instruction mocked at {:#x}", addr))
+    }
+
+    fn get_map(&self, addr: Address) -> Option<&MockMap> {
+        self.maps.iter().rev().find(|m| m.contains(addr))
+    }
+
+    fn read_byte(&self, addr: Address) -> Option<u8> {
+        self.get_map(addr).map(|m| m.data[(addr - m.addr) as usize])
+    }
+}
+
+impl VMCore for MockCore {
+    fn get_max_iterations(&self) -> u64 {
+        self.max_iterations
+    }
+
+    fn get_arch_bits(&self) -> usize {
+        self.bits
+    }
+
+    fn get_reg_alias(&self) -> Vec<(RzRegisterId, String)> {
+        self.reg_alias.clone()
+    }
+
+    fn get_reg_bindings(&self) -> Option<Vec<(String, u32)>> {
+        Some(self.regs.clone())
+    }
+
+    fn has_iword_decoder(&self) -> bool {
+        self.iwords
+    }
+
+    fn get_iword(&self, addr: Address) -> *mut RzAnalysisInsnWord {
+        let insn = self.get_insn(addr);
+        unsafe {
+            let iword = rz_analysis_insn_word_new();
+            (*iword).addr = addr;
+            (*iword).size_bytes = insn.size as _;
+            (*iword).il_op = (insn.effect)();
+            iword
+        }
+    }
+
+    fn get_analysis_op(&self, addr: Address) -> *mut RzAnalysisOp {
+        let insn = self.get_insn(addr);
+        unsafe {
+            let op = rz_analysis_op_new();
+            (*op).addr = addr;
+            (*op).size = insn.size as _;
+            if let Some(jump) = insn.jump {
+                (*op).jump = jump;
+            }
+            (*op).il_op = (insn.effect)();
+            op
+        }
+    }
+
+    /// Unmapped bytes are read as 0xff, like Rizin's IO does.
+    fn read_io_at(&self, addr: Address, len: usize) -> Vec<u8> {
+        (0..len as u64)
+            .map(|i| self.read_byte(addr.wrapping_add(i)).unwrap_or(0xff))
+            .collect()
+    }
+
+    fn read_io_mapped_at(&self, addr: Address, len: usize) -> Option<Vec<u8>> {
+        (0..len as u64)
+            .map(|i| self.read_byte(addr.wrapping_add(i)))
+            .collect()
+    }
+
+    fn is_exec_addr(&self, addr: Address) -> bool {
+        self.get_map(addr).is_some_and(|m| m.perm & RZ_PERM_X != 0)
+    }
+
+    fn is_read_only_addr(&self, addr: Address) -> bool {
+        self.get_map(addr)
+            .is_some_and(|m| m.perm & RZ_PERM_R != 0 && m.perm & RZ_PERM_W == 0)
+    }
+
+    fn get_flag_name_at(&self, addr: Address) -> Option<String> {
+        self.flags.get(&addr).cloned()
+    }
+
+    fn is_big_endian(&self) -> bool {
+        self.big_endian
+    }
+}
+
+/// Returns a C string of [name], which lives as long as the program.
+/// RzIL ops only reference the names of variables. They don't own them.
+fn intern_name(name: &str) -> *const c_char {
+    static NAMES: Mutex<BTreeMap<String, CString>> = Mutex::new(BTreeMap::new());
+    let mut names = NAMES.lock().unwrap();
+    names
+        .entry(name.to_owned())
+        .or_insert_with(|| CString::new(name).expect("Conversion failed."))
+        .as_ptr()
+}
+
+/// Constant bitvector of [len] bits.
+pub fn il_bv(len: u32, val: u64) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_bitv_from_ut64(len, val) }
+}
+
+/// Value of the global variable (register) [name].
+pub fn il_var(name: &str) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_var(intern_name(name), RzILVarKind_RZ_IL_VAR_KIND_GLOBAL) }
+}
+
+pub fn il_add(x: *mut RzILOpPure, y: *mut RzILOpPure) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_add(x, y) }
+}
+
+pub fn il_sub(x: *mut RzILOpPure, y: *mut RzILOpPure) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_sub(x, y) }
+}
+
+pub fn il_mul(x: *mut RzILOpPure, y: *mut RzILOpPure) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_mul(x, y) }
+}
+
+/// True if [x] <= [y] (unsigned).
+pub fn il_ule(x: *mut RzILOpPure, y: *mut RzILOpPure) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_ule(x, y) }
+}
+
+/// Sign extends or truncates [val] to [len] bits.
+pub fn il_signed(len: u32, val: *mut RzILOpPure) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_signed(len, val) }
+}
+
+/// Loads [n_bits] from the memory at [key].
+pub fn il_loadw(key: *mut RzILOpPure, n_bits: u32) -> *mut RzILOpPure {
+    unsafe { rz_il_op_new_loadw(0, key, n_bits) }
+}
+
+/// Stores [val] in the memory at [key].
+pub fn il_storew(key: *mut RzILOpPure, val: *mut RzILOpPure) -> *mut RzILOpEffect {
+    unsafe { rz_il_op_new_storew(0, key, val) }
+}
+
+/// Sets the global variable (register) [name] to [val].
+pub fn il_set(name: &str, val: *mut RzILOpPure) -> *mut RzILOpEffect {
+    unsafe { rz_il_op_new_set(intern_name(name), false, val) }
+}
+
+pub fn il_jmp(dst: *mut RzILOpPure) -> *mut RzILOpEffect {
+    unsafe { rz_il_op_new_jmp(dst) }
+}
+
+/// Executes [true_eff] if [cond] holds, [false_eff] otherwise.
+pub fn il_branch(
+    cond: *mut RzILOpPure,
+    true_eff: *mut RzILOpEffect,
+    false_eff: *mut RzILOpEffect,
+) -> *mut RzILOpEffect {
+    unsafe { rz_il_op_new_branch(cond, true_eff, false_eff) }
+}
+
+pub fn il_nop() -> *mut RzILOpEffect {
+    unsafe { rz_il_op_new_nop() }
+}
+
+/// Executes the [effects] in order.
+pub fn il_seq(effects: Vec<*mut RzILOpEffect>) -> *mut RzILOpEffect {
+    effects
+        .into_iter()
+        .reduce(|x, y| unsafe { rz_il_op_new_seq(x, y) })
+        .unwrap_or_else(il_nop)
+}
@@ -39,11 +39,12 @@ use binding::{
     RzILVarKind_RZ_IL_VAR_KIND_GLOBAL, RzILVarKind_RZ_IL_VAR_KIND_LOCAL,
     RzILVarKind_RZ_IL_VAR_KIND_LOCAL_PURE, LOG_ERROR, LOG_WARN,
 };
-use rand::Rng;
+use rand::{rngs::StdRng, Rng};
 
 use crate::{
     bitvector::BitVector,
-    interpreter::{AbstrVM, AbstrVal, Address, IWordInfo, TaintFlag},
+    interpreter::{AbstrOp2, AbstrVM, AbstrVal, Address, IWordInfo, TaintFlag, TypeHint},
+    jump_table::IndexExpr,
 };
 
 pub const IL_OP_VAR: RzILOpPureCode = RzILOpPureCode_RZ_IL_OP_VAR;
@@ -319,6 +320,10 @@ fn rz_il_handler_ule(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal>
     check_pure_validity!(v1, None);
     let v2 = eval_pure(vm, unsafe { (*op).op.ule.y });
     check_pure_validity!(v2, None);
+    let (v1, v2) = (v1.unwrap(), v2.unwrap());
+    // Either branch bounds the index to the compared constant (off by one at most).
+    // Only `idx <= const` bounds the index. `const <= idx` only bounds it from below.
+    vm.bound_index(&v1, &v2);
     let (v3, tainted) = vm.calc_value_2(
         |c1, c2| {
             (if c1.ule(c2) {
@@ -327,8 +332,8 @@ fn rz_il_handler_ule(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal>
                 BitVector::new_false()
             })
         },
-        v1.unwrap(),
-        v2.unwrap(),
+        v1,
+        v2,
         true,
     );
     vm.set_taint_flag(&v3, tainted);
@@ -464,7 +469,15 @@ fn rz_il_handler_add(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal>
     check_pure_validity!(v1, None);
     let v2 = eval_pure(vm, unsafe { (*op).op.add.y });
     check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 + c2), v1.unwrap(), v2.unwrap(), false);
+    let (v1, v2) = (v1.unwrap(), v2.unwrap());
+    let (mut v3, tainted) = vm.calc_value_2(|c1, c2| (c1 + c2), v1.clone(), v2.clone(), false);
+    v3.set_index_expr(IndexExpr::add(
+        v1.get_index_expr(),
+        v1.get_as_addr(),
+        v2.get_index_expr(),
+        v2.get_as_addr(),
+    ));
+    v3.derive_accesses(&v1, &v2);
     vm.set_taint_flag(&v3, tainted);
     Some(v3)
 }
@@ -475,132 +488,200 @@ fn rz_il_handler_sub(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal>
     check_pure_validity!(v1, None);
     let v2 = eval_pure(vm, unsafe { (*op).op.sub.y });
     check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 - c2), v1.unwrap(), v2.unwrap(), false);
+    let (v1, v2) = (v1.unwrap(), v2.unwrap());
+    let (mut v3, tainted) = vm.calc_value_2(|c1, c2| (c1 - c2), v1.clone(), v2.clone(), false);
+    v3.set_index_expr(IndexExpr::sub(
+        v1.get_index_expr(),
+        v2.get_index_expr(),
+        v2.get_as_addr(),
+    ));
+    v3.derive_accesses(&v1, &v2);
     vm.set_taint_flag(&v3, tainted);
     Some(v3)
 }
 
-fn rz_il_handler_mul(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
-    null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.mul.x });
+/// Evaluates the integer operation [op] with the operands [x] and [y].
+/// Using the operands as integers is a hint on the type of the memory they were accessed at.
+/// [index] returns the index expression of the result, if the operation keeps it known.
+/// Otherwise, the result depends on the index in an opaque way.
+fn eval_arith_op(
+    vm: &mut AbstrVM,
+    x: *mut RzILOpPure,
+    y: *mut RzILOpPure,
+    op: AbstrOp2,
+    index: fn(&mut AbstrVM, &AbstrVal, &AbstrVal) -> Option<IndexExpr>,
+) -> Option<AbstrVal> {
+    let v1 = eval_pure(vm, x);
     check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.mul.y });
+    let v2 = eval_pure(vm, y);
     check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 * c2), v1.unwrap(), v2.unwrap(), false);
+    let (v1, v2) = (v1.unwrap(), v2.unwrap());
+    vm.add_arith_type_hints(&v1, &v2);
+    let index = index(vm, &v1, &v2);
+    let (mut v3, tainted) = vm.calc_value_2(op, v1, v2, false);
+    if index.is_some() {
+        v3.set_index_expr(index);
+    }
     vm.set_taint_flag(&v3, tainted);
     Some(v3)
 }
 
+fn no_index(_: &mut AbstrVM, _: &AbstrVal, _: &AbstrVal) -> Option<IndexExpr> {
+    None
+}
+
+fn rz_il_handler_mul(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
+    null_check!(op);
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.mul.x },
+        unsafe { (*op).op.mul.y },
+        |c1, c2| (c1 * c2),
+        |_, v1, v2| {
+            IndexExpr::mul(
+                v1.get_index_expr(),
+                v1.get_as_addr(),
+                v2.get_index_expr(),
+                v2.get_as_addr(),
+            )
+        },
+    )
+}
+
 fn rz_il_handler_div(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.div.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.div.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1.div(c2)), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.div.x },
+        unsafe { (*op).op.div.y },
+        |c1, c2| (c1.div(c2)),
+        no_index,
+    )
 }
 
 fn rz_il_handler_sdiv(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.sdiv.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.sdiv.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1.sdiv(c2)), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.sdiv.x },
+        unsafe { (*op).op.sdiv.y },
+        |c1, c2| (c1.sdiv(c2)),
+        no_index,
+    )
 }
 
 fn rz_il_handler_mod(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.mod_.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.mod_.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1.umod(c2)), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.mod_.x },
+        unsafe { (*op).op.mod_.y },
+        |c1, c2| (c1.umod(c2)),
+        no_index,
+    )
 }
 
 fn rz_il_handler_smod(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.smod.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.smod.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1.smod(c2)), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.smod.x },
+        unsafe { (*op).op.smod.y },
+        |c1, c2| (c1.smod(c2)),
+        no_index,
+    )
 }
 
 fn rz_il_handler_shiftl(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.shiftl.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.shiftl.y });
-    check_pure_validity!(v2, None);
+    let v3 = eval_arith_op(
+        vm,
+        unsafe { (*op).op.shiftl.x },
+        unsafe { (*op).op.shiftl.y },
+        |c1, c2| (c1 << c2),
+        |_, v1, v2| IndexExpr::shiftl(v1.get_index_expr(), v2.get_index_expr(), v2.get_as_addr()),
+    );
     let fill_bit = eval_pure(vm, unsafe { (*op).op.shiftl.fill_bit });
     check_pure_validity!(fill_bit, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 << c2), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    v3
 }
 
 fn rz_il_handler_shiftr(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.shiftr.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.shiftr.y });
-    check_pure_validity!(v2, None);
+    let v3 = eval_arith_op(
+        vm,
+        unsafe { (*op).op.shiftr.x },
+        unsafe { (*op).op.shiftr.y },
+        |c1, c2| (c1 >> c2),
+        no_index,
+    );
     let fill_bit = eval_pure(vm, unsafe { (*op).op.shiftr.fill_bit });
     check_pure_validity!(fill_bit, None);
     // TODO fill_bit
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 >> c2), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    v3
 }
 
 fn rz_il_handler_logical_and(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.logand.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.logand.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 & c2), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.logand.x },
+        unsafe { (*op).op.logand.y },
+        |c1, c2| (c1 & c2),
+        |vm, v1, v2| get_masked_index(vm, v1, v2).or_else(|| get_masked_index(vm, v2, v1)),
+    )
+}
+
+/// Returns a new index for [x] & [mask], if [x] depends on an index and
+/// [mask] is a constant of the form 2^n - 1.
+/// The new index is at most [mask] or the bound of [x], if [x] is an index itself.
+fn get_masked_index(vm: &mut AbstrVM, x: &AbstrVal, mask: &AbstrVal) -> Option<IndexExpr> {
+    if x.get_index_expr().is_none() || mask.get_index_expr().is_some() || !mask.is_global() {
+        return None;
+    }
+    let m = mask.get_as_addr();
+    if m.wrapping_add(1) & m != 0 {
+        return None;
+    }
+    let x_bound = match x.get_index_expr() {
+        Some(IndexExpr::Linear {
+            index,
+            offset: 0,
+            scale: 1,
+        }) => vm.get_index_bound(*index),
+        _ => None,
+    };
+    Some(vm.new_index(Some(x_bound.map_or(m, |b| b.min(m)))))
 }
 
 fn rz_il_handler_logical_or(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.logor.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.logor.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 | c2), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.logor.x },
+        unsafe { (*op).op.logor.y },
+        |c1, c2| (c1 | c2),
+        no_index,
+    )
 }
 
 fn rz_il_handler_logical_xor(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal> {
     null_check!(op);
-    let v1 = eval_pure(vm, unsafe { (*op).op.logxor.x });
-    check_pure_validity!(v1, None);
-    let v2 = eval_pure(vm, unsafe { (*op).op.logxor.y });
-    check_pure_validity!(v2, None);
-    let (v3, tainted) = vm.calc_value_2(|c1, c2| (c1 ^ c2), v1.unwrap(), v2.unwrap(), false);
-    vm.set_taint_flag(&v3, tainted);
-    Some(v3)
+    eval_arith_op(
+        vm,
+        unsafe { (*op).op.logxor.x },
+        unsafe { (*op).op.logxor.y },
+        |c1, c2| (c1 ^ c2),
+        no_index,
+    )
 }
 
 /// Returns the a casted value according to [len] and [fill].
 /// Additionally, it returns the taint bit. The taint bit is set, if:
 /// - The [fill] bit was used
 /// AND
-/// - [fill] is not a global true or false value, and has been sampled.
-pub fn cast(bv: &BitVector, len: u32, fill: AbstrVal) -> (BitVector, TaintFlag) {
+/// - [fill] is not a global true or false value, and has been sampled with [rng].
+pub fn cast(bv: &BitVector, len: u32, fill: AbstrVal, rng: &mut StdRng) -> (BitVector, TaintFlag) {
     if len <= bv.width() {
         return (bv.cast(len, false), TaintFlag::Unset);
     }
@@ -609,7 +690,7 @@ pub fn cast(bv: &BitVector, len: u32, fill: AbstrVal) -> (BitVector, TaintFlag)
     } else if fill.is_false() {
         (false, TaintFlag::Unset)
     } else {
-        (rand::thread_rng().gen_bool(0.5), TaintFlag::Set)
+        (rng.gen_bool(0.5), TaintFlag::Set)
     };
     (bv.cast(len, fill_bit), tainted)
 }
@@ -624,7 +705,12 @@ fn rz_il_handler_cast(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal>
     let mut v3_const: BitVector;
     let v3: AbstrVal;
     let mut tainted = TaintFlag::Unset;
-    (v3_const, tainted) = cast(v1.as_ref().unwrap().get_const(), len, fill.unwrap());
+    (v3_const, tainted) = cast(
+        v1.as_ref().unwrap().get_const(),
+        len,
+        fill.unwrap(),
+        vm.get_rng(),
+    );
     v3 = AbstrVal::new_from(v1.unwrap(), v3_const);
     vm.set_taint_flag(&v3, tainted);
     Some(v3)
@@ -982,11 +1068,18 @@ fn rz_il_handler_load(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal>
     check_pure_validity!(k, None);
     let key = k.unwrap();
     let key_t = vm.get_taint_flag(&key);
+    vm.add_use_type_hint(&key, TypeHint::Deref);
     let norm_k = vm.normalize_val(key, false);
     vm.enqueue_mos(&norm_k);
+    vm.check_heap_access(&norm_k);
     // We assume for now a size of 8 bytes. Just as rz_il_mem_value_len() does.
     let size = 8;
-    let (v, is_sampled) = vm.get_mem_val(&norm_k, size);
+    vm.add_summary_access(&norm_k, size as u64, false);
+    vm.add_access_size(size as u64);
+    let (mut v, is_sampled) = vm.get_mem_val(&norm_k, size);
+    vm.add_load_flow(&norm_k, &mut v);
+    vm.set_load_index(&norm_k, &mut v, size);
+    vm.add_mem_val_type_hints(&mut v);
     if is_sampled != TaintFlag::Unset {
         vm.set_taint_flag(&norm_k, is_sampled);
     }
@@ -1009,9 +1102,16 @@ fn rz_il_handler_loadw(vm: &mut AbstrVM, op: *mut RzILOpPure) -> Option<AbstrVal
     check_pure_validity!(k, None);
     let key = k.unwrap();
     let key_t = vm.get_taint_flag(&key);
+    vm.add_use_type_hint(&key, TypeHint::Deref);
     let norm_k = vm.normalize_val(key, false);
     vm.enqueue_mos(&norm_k);
-    let (v, is_sampled) = vm.get_mem_val(&norm_k, n_bytes as usize);
+    vm.check_heap_access(&norm_k);
+    vm.add_summary_access(&norm_k, n_bytes as u64, false);
+    vm.add_access_size(n_bytes as u64);
+    let (mut v, is_sampled) = vm.get_mem_val(&norm_k, n_bytes as usize);
+    vm.add_load_flow(&norm_k, &mut v);
+    vm.set_load_index(&norm_k, &mut v, n_bytes as usize);
+    vm.add_mem_val_type_hints(&mut v);
     if is_sampled.is_set() {
         vm.set_taint_flag(&norm_k, is_sampled);
     }
@@ -1049,10 +1149,17 @@ fn rz_il_handler_store(vm: &mut AbstrVM, op: *mut RzILOpEffect) -> bool {
     let key = k.unwrap();
     let value = eval_pure(vm, unsafe { (*op).op.store.value });
     check_pure_validity!(value, false);
-    let v = value.unwrap();
-    let norm_t = vm.get_taint_flag(&key) | vm.get_taint_flag(&v);
+    let mut v = value.unwrap();
+    let v_t = vm.get_taint_flag(&v);
+    let norm_t = vm.get_taint_flag(&key) | v_t;
+    vm.add_use_type_hint(&key, TypeHint::Deref);
     let norm_k = &vm.normalize_val(key, false);
     vm.enqueue_mos(&norm_k);
+    vm.check_heap_access(&norm_k);
+    vm.add_summary_access(&norm_k, (v.get_width() as u64).div_ceil(8), true);
+    vm.add_access_size((v.get_width() as u64).div_ceil(8));
+    vm.add_store_flow(&v);
+    vm.add_mem_val_type_hints(&mut v);
     vm.set_mem_val(norm_k, v.clone());
     vm.set_taint_flag(&norm_k, norm_t);
     if norm_k.is_global() && vm.get_taint_flag(&norm_k).is_unset() {
@@ -1072,10 +1179,17 @@ fn rz_il_handler_storew(vm: &mut AbstrVM, op: *mut RzILOpEffect) -> bool {
     let key = k.unwrap();
     let value = eval_pure(vm, unsafe { (*op).op.storew.value });
     check_pure_validity!(value, false);
-    let v = value.unwrap();
-    let norm_t = vm.get_taint_flag(&key) | vm.get_taint_flag(&v);
+    let mut v = value.unwrap();
+    let v_t = vm.get_taint_flag(&v);
+    let norm_t = vm.get_taint_flag(&key) | v_t;
+    vm.add_use_type_hint(&key, TypeHint::Deref);
     let norm_k = &vm.normalize_val(key, false);
     vm.enqueue_mos(&norm_k);
+    vm.check_heap_access(&norm_k);
+    vm.add_summary_access(&norm_k, (v.get_width() as u64).div_ceil(8), true);
+    vm.add_access_size((v.get_width() as u64).div_ceil(8));
+    vm.add_store_flow(&v);
+    vm.add_mem_val_type_hints(&mut v);
     vm.set_mem_val(norm_k, v.clone());
     vm.set_taint_flag(&norm_k, norm_t);
     if norm_k.is_global() && !vm.get_taint_flag(&norm_k).is_unset() {
@@ -1113,8 +1227,23 @@ fn rz_il_handler_jmp(vm: &mut AbstrVM, op: *mut RzILOpEffect) -> bool {
     null_check!(op);
     let mut dst = eval_pure(vm, unsafe { (*op).op.jmp.dst });
     check_pure_validity!(dst, false);
+    if vm.pc_is_call() {
+        add_code_ptr_table_candidates(vm, unsafe { (*op).op.jmp.dst });
+    }
 
     let jdst = &dst.unwrap();
+    if let Some(IndexExpr::Entry { index, table }) = jdst.get_index_expr() {
+        // The target is read from a jump table with an unknown index.
+        // So the sampled target is as likely as any other entry.
+        if !vm.pc_is_call() && !vm.pc_is_return() {
+            let mut table = table.clone();
+            table.max_index = vm.get_index_bound(*index);
+            for target in vm.get_jump_table_targets(&table) {
+                vm.add_candidate_jump_xref(vm.get_cur_entry(), target);
+            }
+            return true;
+        }
+    }
     if !jdst.is_global() || !vm.get_taint_flag(jdst).is_known_const() {
         // Tainted addresses rely on sampled/unknown values and are useless to us.
         return true;
@@ -1135,6 +1264,50 @@ fn rz_il_handler_jmp(vm: &mut AbstrVM, op: *mut RzILOpEffect) -> bool {
     true
 }
 
+/// Returns [op] without the casts around it.
+fn strip_casts(mut op: *mut RzILOpPure) -> *mut RzILOpPure {
+    while pderef!(op).code == IL_OP_CAST {
+        op = unsafe { (*op).op.cast.val };
+    }
+    op
+}
+
+/// Returns true if evaluating [op] has no side effects and gives the same value each time.
+/// These are variables and constants combined by additions and multiplications.
+fn is_repeatable(op: *mut RzILOpPure) -> bool {
+    match pderef!(op).code {
+        IL_OP_VAR | IL_OP_BITV => true,
+        IL_OP_ADD => unsafe { is_repeatable((*op).op.add.x) && is_repeatable((*op).op.add.y) },
+        IL_OP_SUB => unsafe { is_repeatable((*op).op.sub.x) && is_repeatable((*op).op.sub.y) },
+        IL_OP_MUL => unsafe { is_repeatable((*op).op.mul.x) && is_repeatable((*op).op.mul.y) },
+        _ => false,
+    }
+}
+
+/// Adds the other entries of a read-only code pointer table (e.g. a vtable or callback registry)
+/// as candidate call targets, if the call target [dst] is loaded from such a table.
+fn add_code_ptr_table_candidates(vm: &mut AbstrVM, dst: *mut RzILOpPure) {
+    let load = strip_casts(dst);
+    let (key_op, entry_size) = match pderef!(load).code {
+        IL_OP_LOAD => unsafe { ((*load).op.load.key, vm.get_pc_bit_width() / 8) },
+        IL_OP_LOADW => unsafe { ((*load).op.loadw.key, (*load).op.loadw.n_bits as usize / 8) },
+        _ => return,
+    };
+    if entry_size == 0 || entry_size > 8 || !is_repeatable(key_op) {
+        return;
+    }
+    // The key was evaluated with the call target already. So this gives the same value.
+    let Some(key) = eval_pure(vm, key_op) else {
+        return;
+    };
+    if !key.is_global() || !vm.get_taint_flag(&key).is_unset() {
+        return;
+    }
+    for target in vm.get_code_ptr_table_targets(key.get_as_addr(), entry_size) {
+        vm.add_candidate_call_xref(vm.get_cur_entry(), target);
+    }
+}
+
 fn add_jump_ref(vm: &mut AbstrVM, target_addr: u64) -> bool {
     let insn_is_last_in_path = vm.peak_next_addr().is_none();
     let jump_target_is_not_next_insn =
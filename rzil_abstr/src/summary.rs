@@ -0,0 +1,248 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::interpreter::Address;
+
+/// First line of a summary file. It is followed by the fingerprint of the binary
+/// the summaries belong to. Because they refer to functions by their address.
+const SUMMARY_FILE_HEADER: &str = "probana function summaries v2";
+
+/// Memory accessed by a function, relative to the value of one of its arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArgAccess {
+    /// Index of the argument register (A0 = 0, A1 = 1 etc.).
+    pub arg: usize,
+    /// Offset from the argument value.
+    pub offset: i64,
+    /// Number of bytes accessed.
+    pub size: u64,
+}
+
+impl ArgAccess {
+    pub fn new(arg: usize, offset: i64, size: u64) -> ArgAccess {
+        ArgAccess { arg, offset, size }
+    }
+}
+
+impl std::fmt::Display for ArgAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a{} {} {}", self.arg, self.offset, self.size)
+    }
+}
+
+/// The value a function returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SummaryReturn {
+    /// Nothing is known about the return value.
+    Unknown,
+    /// The value of the argument with the given index.
+    Arg(usize),
+    /// A pointer to a newly allocated heap region.
+    Heap,
+    /// A constant value.
+    Const(u64),
+}
+
+impl std::fmt::Display for SummaryReturn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SummaryReturn::Unknown => write!(f, "?"),
+            SummaryReturn::Arg(i) => write!(f, "a{}", i),
+            SummaryReturn::Heap => write!(f, "heap"),
+            SummaryReturn::Const(c) => write!(f, "{:#x}", c),
+        }
+    }
+}
+
+/// The effects a function has on the state of its caller.
+/// They are applied at call sites, if the call itself is not followed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionSummary {
+    /// Memory the function reads.
+    pub reads: BTreeSet<ArgAccess>,
+    /// Memory the function writes.
+    pub writes: BTreeSet<ArgAccess>,
+    /// The value the function returns.
+    /// None if the function was never observed to return.
+    pub ret: Option<SummaryReturn>,
+}
+
+impl FunctionSummary {
+    pub fn new() -> FunctionSummary {
+        FunctionSummary {
+            reads: BTreeSet::new(),
+            writes: BTreeSet::new(),
+            ret: None,
+        }
+    }
+
+    /// Adds the effects of [other] to this summary.
+    /// Differing return values make the return value unknown.
+    pub fn merge(&mut self, other: &FunctionSummary) {
+        self.reads.extend(other.reads.iter());
+        self.writes.extend(other.writes.iter());
+        self.ret = match (self.ret, other.ret) {
+            (None, r) | (r, None) => r,
+            (Some(a), Some(b)) if a == b => Some(a),
+            _ => Some(SummaryReturn::Unknown),
+        };
+    }
+}
+
+/// Function summaries, indexed by the entry address of the function.
+pub type FunctionSummaries = BTreeMap<Address, FunctionSummary>;
+
+/// Merges all summaries of [from] into [into].
+pub fn merge_summaries(into: &mut FunctionSummaries, from: &FunctionSummaries) {
+    for (addr, summary) in from.iter() {
+        into.entry(*addr)
+            .or_insert_with(FunctionSummary::new)
+            .merge(summary);
+    }
+}
+
+fn parse_arg(s: &str) -> Option<usize> {
+    s.strip_prefix("a")?.parse::<usize>().ok()
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+fn parse_return(s: &str) -> Option<SummaryReturn> {
+    match s {
+        "?" => Some(SummaryReturn::Unknown),
+        "heap" => Some(SummaryReturn::Heap),
+        _ if s.starts_with("a") => Some(SummaryReturn::Arg(parse_arg(s)?)),
+        _ => Some(SummaryReturn::Const(parse_hex(s)?)),
+    }
+}
+
+fn malformed(line: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Malformed summary line: '{}'", line),
+    )
+}
+
+/// Parses function summaries of the binary with the [fingerprint]. The format is line based:
+///
+/// ```text
+/// probana function summaries v2 <binary fingerprint>
+/// F <function address> [<return: ? | heap | a<N> | <hex constant>>]
+/// R a<N> <offset> <size>
+/// W a<N> <offset> <size>
+/// ```
+///
+/// `R` and `W` lines describe reads and writes of the preceding function.
+/// Empty lines and lines starting with `#` are ignored.
+/// Summaries of another binary are rejected.
+pub fn parse_summaries(content: &str, fingerprint: &str) -> std::io::Result<FunctionSummaries> {
+    let mut lines = content.lines();
+    let Some(file_fp) = lines
+        .next()
+        .and_then(|l| l.trim().strip_prefix(SUMMARY_FILE_HEADER))
+    else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Summary file has an unknown format.",
+        ));
+    };
+    if file_fp.trim() != fingerprint {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Summary file belongs to another binary. Expected fingerprint: {}",
+                fingerprint
+            ),
+        ));
+    }
+    let mut summaries = FunctionSummaries::new();
+    let mut current: Option<Address> = None;
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [] => continue,
+            [c, ..] if c.starts_with("#") => continue,
+            ["F", addr, rest @ ..] if rest.len() <= 1 => {
+                let addr = parse_hex(addr).ok_or_else(|| malformed(line))?;
+                let mut summary = FunctionSummary::new();
+                if let Some(ret) = rest.first() {
+                    summary.ret = Some(parse_return(ret).ok_or_else(|| malformed(line))?);
+                }
+                summaries
+                    .entry(addr)
+                    .or_insert_with(FunctionSummary::new)
+                    .merge(&summary);
+                current = Some(addr);
+            }
+            [kind @ ("R" | "W"), arg, offset, size] => {
+                let (Some(addr), Some(arg), Ok(offset), Ok(size)) = (
+                    current,
+                    parse_arg(arg),
+                    offset.parse::<i64>(),
+                    size.parse::<u64>(),
+                ) else {
+                    return Err(malformed(line));
+                };
+                let summary = summaries.get_mut(&addr).unwrap();
+                let access = ArgAccess::new(arg, offset, size);
+                if *kind == "R" {
+                    summary.reads.insert(access);
+                } else {
+                    summary.writes.insert(access);
+                }
+            }
+            _ => return Err(malformed(line)),
+        }
+    }
+    Ok(summaries)
+}
+
+/// Loads the function summaries of the binary with the [fingerprint]
+/// saved in the file at [path].
+pub fn load_summaries(path: &Path, fingerprint: &str) -> std::io::Result<FunctionSummaries> {
+    parse_summaries(&std::fs::read_to_string(path)?, fingerprint)
+}
+
+/// Writes the [summaries] of the binary with the [fingerprint]
+/// in the format of [parse_summaries()].
+pub fn write_summaries<W: Write>(
+    out: &mut W,
+    summaries: &FunctionSummaries,
+    fingerprint: &str,
+) -> std::io::Result<()> {
+    writeln!(out, "{} {}", SUMMARY_FILE_HEADER, fingerprint)?;
+    for (addr, summary) in summaries.iter() {
+        match summary.ret {
+            Some(ret) => writeln!(out, "F {:#x} {}", addr, ret)?,
+            None => writeln!(out, "F {:#x}", addr)?,
+        }
+        for access in summary.reads.iter() {
+            writeln!(out, "R {}", access)?;
+        }
+        for access in summary.writes.iter() {
+            writeln!(out, "W {}", access)?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves the [summaries] of the binary with the [fingerprint] to the file at [path].
+pub fn save_summaries(
+    path: &Path,
+    summaries: &FunctionSummaries,
+    fingerprint: &str,
+) -> std::io::Result<usize> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_summaries(&mut writer, summaries, fingerprint)?;
+    writer.flush()?;
+    Ok(summaries.len())
+}
@@ -13,6 +13,7 @@ mod tests {
     };
 
     use binding::{get_test_bin_path, init_rizin_instance, RzCoreWrapper};
+    use helper::rng::new_rng;
 
     use crate::{
         bitvector::BitVector,
@@ -21,6 +22,7 @@ mod tests {
             MemXref, StackXref, NO_ADDR_INFO,
         },
         op_handler::cast,
+        summary::FunctionSummaries,
     };
 
     #[test]
@@ -278,7 +280,14 @@ mod tests {
     fn test_x86_icall_discover() {
         let (core, path) = get_x86_icall_test();
         let (tx, rx): (Sender<IntrpProducts>, Receiver<IntrpProducts>) = channel();
-        interpret(0, core, path, tx);
+        interpret(
+            0,
+            core,
+            path,
+            Arc::new(FunctionSummaries::new()),
+            tx,
+            new_rng(0),
+        );
         let products: IntrpProducts;
         if let Ok(prods) = rx.try_recv() {
             products = prods;
@@ -354,7 +363,14 @@ mod tests {
     fn test_hexagon_icall_discover() {
         let (core, path) = get_hexagon_icall_test();
         let (tx, rx): (Sender<IntrpProducts>, Receiver<IntrpProducts>) = channel();
-        interpret(0, core, path, tx);
+        interpret(
+            0,
+            core,
+            path,
+            Arc::new(FunctionSummaries::new()),
+            tx,
+            new_rng(0),
+        );
         let products: IntrpProducts;
         if let Ok(prods) = rx.try_recv() {
             products = prods;
@@ -414,8 +430,9 @@ mod tests {
     #[test]
     #[should_panic = "assertion failed: bv != std::ptr::null_mut()"]
     fn test_constant_cast_0() {
+        let mut rng = new_rng(0);
         let u_32_max = BitVector::new_from_u64(32, 0xffffffff);
-        let (casted, tainted) = cast(&u_32_max, 0, AbstrVal::new_false());
+        let (casted, tainted) = cast(&u_32_max, 0, AbstrVal::new_false(), &mut rng);
         assert!(tainted.is_unset());
         assert_eq!(casted, 0x0u64);
         assert_eq!(casted, 0);
@@ -423,6 +440,7 @@ mod tests {
 
     #[test]
     fn test_constant() {
+        let mut rng = new_rng(0);
         let u_32_max = BitVector::new_from_u64(32, 0xffffffff);
         // Comparison tests. Due to our bit width limitation, we need to check
         // how the converted values are interpreted.
@@ -431,12 +449,12 @@ mod tests {
         assert_eq!(u_32_max, -1);
         assert_ne!(u_32_max, 0xffffffffffffffffu64);
 
-        let (mut casted, mut tainted) = cast(&u_32_max, 64, AbstrVal::new_false());
+        let (mut casted, mut tainted) = cast(&u_32_max, 64, AbstrVal::new_false(), &mut rng);
         assert!(tainted.is_unset());
         assert_eq!(casted, 0xffffffffu32);
         assert_eq!(casted, 0xffffffffu64);
 
-        (casted, tainted) = cast(&u_32_max, 64, AbstrVal::new_true());
+        (casted, tainted) = cast(&u_32_max, 64, AbstrVal::new_true(), &mut rng);
         assert!(tainted.is_unset());
         assert_eq!(casted, 0xffffffffffffffffu64);
         assert_eq!(casted, -1i32);
@@ -447,7 +465,7 @@ mod tests {
         assert_eq!(u_16_half, 0xffffu64);
         assert_ne!(u_16_half, -1i64);
 
-        (casted, tainted) = cast(&u_16_half, 64, AbstrVal::new_true());
+        (casted, tainted) = cast(&u_16_half, 64, AbstrVal::new_true(), &mut rng);
         assert!(tainted.is_unset());
         assert_eq!(casted, 0xffffffffffffffffu64);
         assert_eq!(casted, -1);
@@ -455,7 +473,7 @@ mod tests {
         let u_16_pat = BitVector::new_from_u64(16, 0x1010);
         assert_eq!(u_16_pat, 0x1010u64);
         assert_eq!(u_16_pat, 0x1010u64);
-        (casted, tainted) = cast(&u_16_pat, 64, AbstrVal::new_true());
+        (casted, tainted) = cast(&u_16_pat, 64, AbstrVal::new_true(), &mut rng);
         assert!(tainted.is_unset());
         assert_eq!(casted, 0xffffffffffff1010u64);
         assert_eq!(casted, (0xffffffffffff1010u64 as i64));
@@ -466,6 +484,7 @@ mod tests {
             &u_16_pat,
             64,
             AbstrVal::new_global(1, BitVector::new_from_u64(16, 0xffff), None, 0),
+            &mut rng,
         );
         assert!(tainted.is_set());
     }
@@ -474,7 +493,14 @@ mod tests {
     fn test_x86_malloc() {
         let (core, path) = get_x86_malloc_test();
         let (tx, rx): (Sender<IntrpProducts>, Receiver<IntrpProducts>) = channel();
-        interpret(0, core, path, tx);
+        interpret(
+            0,
+            core,
+            path,
+            Arc::new(FunctionSummaries::new()),
+            tx,
+            new_rng(0),
+        );
         let products: IntrpProducts;
         if let Ok(prods) = rx.try_recv() {
             products = prods;
@@ -537,7 +563,14 @@ mod tests {
     fn test_hexagon_malloc() {
         let (core, path) = get_hexagon_malloc_test();
         let (tx, rx): (Sender<IntrpProducts>, Receiver<IntrpProducts>) = channel();
-        interpret(0, core, path, tx);
+        interpret(
+            0,
+            core,
+            path,
+            Arc::new(FunctionSummaries::new()),
+            tx,
+            new_rng(0),
+        );
         let products: IntrpProducts;
         if let Ok(prods) = rx.try_recv() {
             products = prods;
@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::interpreter::Address;
+    use crate::jump_table::{
+        bytes_to_u64, get_code_ptr_table, IndexExpr, JumpTable, MAX_JUMP_TABLE_ENTRIES,
+    };
+
+    fn read_from(mem: &BTreeMap<Address, u64>) -> impl Fn(Address, usize) -> Option<u64> + '_ {
+        |addr, size| {
+            let mask = if size >= 8 {
+                u64::MAX
+            } else {
+                (1 << (size * 8)) - 1
+            };
+            mem.get(&addr).map(|v| v & mask)
+        }
+    }
+
+    fn is_code(addr: Address) -> bool {
+        (0x1000..0x2000).contains(&addr)
+    }
+
+    #[test]
+    fn test_absolute_jump_table() {
+        // The fourth entry points to data, so the table ends before it.
+        let mem = BTreeMap::from([
+            (0x4000, 0x1100),
+            (0x4008, 0x1200),
+            (0x4010, 0x1100),
+            (0x4018, 0x5000),
+            (0x4020, 0x1300),
+        ]);
+        let table = JumpTable::new(0x4000, 8, 8);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code),
+            vec![0x1100, 0x1200]
+        );
+        // Unmapped entries end the table as well.
+        let table = JumpTable::new(0x3ff8, 8, 8);
+        assert!(table.get_targets(read_from(&mem), is_code).is_empty());
+    }
+
+    #[test]
+    fn test_relative_jump_table() {
+        // Signed 32bit offsets relative to the table.
+        let mut table = JumpTable::new(0x1800, 4, 4);
+        table.rel_base = Some(0x1800);
+        let mem = BTreeMap::from([(0x1800, 0xffff_ff00), (0x1804, 0x10), (0x1808, 0x7fff_0000)]);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code),
+            vec![0x1700, 0x1810]
+        );
+
+        // Half-word offsets of byte entries (e.g. ARM TBB).
+        let mut table = JumpTable::new(0x1004, 1, 1);
+        table.rel_base = Some(0x1004);
+        table.entry_scale = 2;
+        let mem = BTreeMap::from([(0x1004, 0x02), (0x1005, 0x03)]);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code),
+            vec![0x1008, 0x100a]
+        );
+    }
+
+    #[test]
+    fn test_jump_table_limit() {
+        let mem: BTreeMap<Address, u64> = (0..2 * MAX_JUMP_TABLE_ENTRIES)
+            .map(|i| (0x4000 + i * 8, 0x1000 + i))
+            .collect();
+        let table = JumpTable::new(0x4000, 8, 8);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code).len() as u64,
+            MAX_JUMP_TABLE_ENTRIES
+        );
+        // Without stride, the only entry is the table.
+        let table = JumpTable::new(0x4000, 0, 8);
+        assert_eq!(table.get_targets(read_from(&mem), is_code), vec![0x1000]);
+    }
+
+    #[test]
+    fn test_code_ptr_table() {
+        // Two vtables (Itanium ABI): offset-to-top, RTTI pointer, virtual functions.
+        let mem = BTreeMap::from([
+            (0x3000, 0x0),
+            (0x3008, 0x4100),
+            (0x3010, 0x1100),
+            (0x3018, 0x1200),
+            (0x3020, 0x1300),
+            (0x3028, 0x0),
+            (0x3030, 0x4200),
+            (0x3038, 0x1400),
+        ]);
+        assert_eq!(
+            get_code_ptr_table(0x3018, 8, read_from(&mem), is_code),
+            vec![0x1200, 0x1300, 0x1100]
+        );
+        assert_eq!(
+            get_code_ptr_table(0x3038, 8, read_from(&mem), is_code),
+            vec![0x1400]
+        );
+        // The called slot must point to code.
+        assert!(get_code_ptr_table(0x3008, 8, read_from(&mem), is_code).is_empty());
+    }
+
+    #[test]
+    fn test_bounded_jump_table() {
+        let mem = BTreeMap::from([
+            (0x4000, 0x1100),
+            (0x4008, 0x5000),
+            (0x4010, 0x1200),
+            (0x4018, 0x1300),
+        ]);
+        // Entries not pointing to code are skipped, entries beyond the bound not read.
+        let mut table = JumpTable::new(0x4000, 8, 8);
+        table.max_index = Some(2);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code),
+            vec![0x1100, 0x1200]
+        );
+        table.max_index = Some(u64::MAX);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code),
+            vec![0x1100, 0x1200, 0x1300]
+        );
+        // Entry addresses wrap around the address space instead of overflowing.
+        let mem = BTreeMap::from([(0x4000, 0x1100), (0x8000_0000_0000_4000, 0x1200)]);
+        let mut table = JumpTable::new(0x4000, 0x8000_0000_0000_0000, 8);
+        table.max_index = Some(2);
+        assert_eq!(
+            table.get_targets(read_from(&mem), is_code),
+            vec![0x1100, 0x1200]
+        );
+    }
+
+    #[test]
+    fn test_bytes_to_u64() {
+        assert_eq!(bytes_to_u64(&[0x12, 0x34], false), 0x3412);
+        assert_eq!(bytes_to_u64(&[0x12, 0x34], true), 0x1234);
+        assert_eq!(
+            bytes_to_u64(&[1, 2, 3, 4, 5, 6, 7, 8], true),
+            0x0102030405060708
+        );
+    }
+
+    #[test]
+    fn test_index_expr() {
+        let index = IndexExpr::new_index(0);
+        // table + (index - 1) * 4
+        let dec = IndexExpr::sub(Some(&index), None, 1);
+        let scaled = IndexExpr::mul(dec.as_ref(), 0, None, 4);
+        let key = IndexExpr::add(None, 0x3000, scaled.as_ref(), 0).unwrap();
+        assert_eq!(
+            key,
+            IndexExpr::Linear {
+                index: 0,
+                offset: 0x2ffc,
+                scale: 4
+            }
+        );
+        // rel_base + entry * 2
+        let entry = IndexExpr::load(&key, 4);
+        let scaled = IndexExpr::shiftl(Some(&entry), None, 1);
+        let target = IndexExpr::add(scaled.as_ref(), 0, None, 0x1000).unwrap();
+        let mut table = JumpTable::new(0x2ffc, 4, 4);
+        table.rel_base = Some(0x1000);
+        table.entry_scale = 2;
+        assert_eq!(target, IndexExpr::Entry { index: 0, table });
+        // Only the index plus an offset is bound by a comparison.
+        assert_eq!(IndexExpr::get_bound(dec.as_ref().unwrap(), 5), Some((0, 6)));
+        assert_eq!(IndexExpr::get_bound(&key, 0x3010), None);
+        // Two indices or a non linear operation make the value opaque.
+        assert_eq!(
+            IndexExpr::add(Some(&index), 0, Some(&index), 0),
+            Some(IndexExpr::Opaque)
+        );
+        assert_eq!(IndexExpr::load(&entry, 8), IndexExpr::Opaque);
+        // A pointer plus an offset is no table.
+        assert_eq!(IndexExpr::load(&index, 8), IndexExpr::Opaque);
+        assert_eq!(IndexExpr::add(None, 1, None, 2), None);
+    }
+}
@@ -0,0 +1,380 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, VecDeque},
+        sync::{
+            mpsc::{channel, Receiver, Sender},
+            Arc,
+        },
+    };
+
+    use binding::{
+        rz_analysis_insn_word_free, rz_analysis_op_free, RzRegisterId_RZ_REG_NAME_SP, RZ_PERM_R,
+        RZ_PERM_W, RZ_PERM_X,
+    };
+    use helper::rng::new_rng;
+
+    use crate::{
+        bitvector::BitVector,
+        interpreter::{
+            interpret, CodeXrefType, ConcreteCodeXref, DataFlow, HeapFindingType, IWordInfo,
+            IntrpPath, IntrpProducts, MemXref, StackXref, NO_ADDR_INFO,
+        },
+        mock_core::{
+            il_add, il_branch, il_bv, il_jmp, il_loadw, il_mul, il_nop, il_seq, il_set, il_signed,
+            il_storew, il_sub, il_ule, il_var, MockCore,
+        },
+        summary::{ArgAccess, FunctionSummaries, FunctionSummary, SummaryReturn},
+        vm_core::{GVMCore, VMCore},
+    };
+
+    /// Address of the exit, which ends every path. It is never decoded.
+    const MOCK_EXIT_ADDR: u64 = 0xdead;
+
+    /// Interprets the instructions at [addrs] in order.
+    /// The path ends with an exit right after them.
+    fn run_mock(core: GVMCore, addrs: &[u64]) -> IntrpProducts {
+        let path: Vec<(u64, IWordInfo)> = addrs.iter().map(|a| (*a, NO_ADDR_INFO)).collect();
+        run_mock_path(core, &path)
+    }
+
+    /// Interprets the instructions in [path] in order, with the given instruction word info.
+    /// The path ends with an exit right after them.
+    fn run_mock_path(core: GVMCore, path: &[(u64, IWordInfo)]) -> IntrpProducts {
+        run_mock_path_with_summaries(core, path, FunctionSummaries::new())
+    }
+
+    /// Interprets the instructions in [path] in order.
+    /// Calls, which are not followed, apply the [summaries] of their callee.
+    fn run_mock_path_with_summaries(
+        core: GVMCore,
+        path: &[(u64, IWordInfo)],
+        summaries: FunctionSummaries,
+    ) -> IntrpProducts {
+        let mut v = VecDeque::from_iter(path.iter().cloned());
+        v.push_back((MOCK_EXIT_ADDR, IWordInfo::IsExit));
+        let path = IntrpPath::from(v);
+        let (tx, rx): (Sender<IntrpProducts>, Receiver<IntrpProducts>) = channel();
+        interpret(0, core, path, Arc::new(summaries), tx, new_rng(0));
+        rx.try_recv().expect("Received no products.")
+    }
+
+    #[test]
+    fn test_mock_core() {
+        let mut core = MockCore::new_x86_64();
+        core.map(0x2000, &[0x11, 0x22], RZ_PERM_R);
+        core.map(0x3000, &[0x33], RZ_PERM_R | RZ_PERM_W);
+        core.add_insn(0x1000, 4, || il_set("rax", il_bv(64, 1)));
+        core.add_flag(0x1000, "sym.main");
+
+        assert_eq!(core.get_arch_bits(), 64);
+        assert!(core
+            .get_reg_alias()
+            .contains(&(RzRegisterId_RZ_REG_NAME_SP, "rsp".to_owned())));
+        assert!(core
+            .get_reg_bindings()
+            .unwrap()
+            .contains(&("rax".to_owned(), 64)));
+        assert_eq!(core.read_io_at(0x1fff, 4), vec![0xff, 0x11, 0x22, 0xff]);
+        assert_eq!(core.read_io_mapped_at(0x2000, 2), Some(vec![0x11, 0x22]));
+        assert_eq!(core.read_io_mapped_at(0x2001, 2), None);
+        assert!(core.is_exec_addr(0x1003));
+        assert!(!core.is_exec_addr(0x2000));
+        assert!(core.is_read_only_addr(0x2000));
+        assert!(!core.is_read_only_addr(0x3000));
+        assert_eq!(core.get_flag_name_at(0x1000), Some("sym.main".to_owned()));
+        assert_eq!(core.get_flag_name_at(0x1001), None);
+
+        // Every decoding builds a new effect, because the caller frees it.
+        let op = core.get_analysis_op(0x1000);
+        assert_eq!(unsafe { (*op).size }, 4);
+        assert!(!unsafe { (*op).il_op }.is_null());
+        unsafe { rz_analysis_op_free(op.cast()) };
+        let iword = core.get_iword(0x1000);
+        assert_eq!(unsafe { (*iword).size_bytes }, 4);
+        assert!(!unsafe { (*iword).il_op }.is_null());
+        unsafe { rz_analysis_insn_word_free(iword) };
+    }
+
+    #[test]
+    fn test_mock_global_mem() {
+        let mut core = MockCore::new_x86_64();
+        core.map(0x2000, &0x4000u64.to_le_bytes(), RZ_PERM_R | RZ_PERM_W);
+        // mov rax, [0x2000]
+        core.add_insn(0x1000, 7, || il_set("rax", il_loadw(il_bv(64, 0x2000), 64)));
+        // mov rbx, [rax]
+        core.add_insn(0x1007, 3, || il_set("rbx", il_loadw(il_var("rax"), 64)));
+
+        let products = run_mock(core.into_core(), &[0x1000, 0x1007]);
+        assert!(products
+            .mem_xrefs
+            .contains(&MemXref::new(0x1000, 0x2000, 8)));
+        // The pointer was read from the mocked memory.
+        assert!(products
+            .mem_xrefs
+            .contains(&MemXref::new(0x1007, 0x4000, 8)));
+        assert_eq!(products.mem_xrefs.len(), 2);
+        assert!(products.stack_xrefs.is_empty());
+    }
+
+    #[test]
+    fn test_mock_stack() {
+        let mut core = MockCore::new_x86_64();
+        // push rbp
+        core.add_insn(0x1000, 1, || {
+            il_seq(vec![
+                il_set("rsp", il_sub(il_var("rsp"), il_bv(64, 8))),
+                il_storew(il_var("rsp"), il_var("rbp")),
+            ])
+        });
+        // mov rbp, [rsp]
+        core.add_insn(0x1001, 4, || il_set("rbp", il_loadw(il_var("rsp"), 64)));
+
+        let products = run_mock(core.into_core(), &[0x1000, 0x1001]);
+        let offset = BitVector::new_from_i32(64, -0x8);
+        assert!(products
+            .stack_xrefs
+            .contains(&StackXref::new(0x1000, offset.clone(), 0x1000)));
+        assert!(products
+            .stack_xrefs
+            .contains(&StackXref::new(0x1001, offset, 0x1000)));
+        assert_eq!(products.stack_xrefs.len(), 2);
+        assert!(products.mem_xrefs.is_empty());
+        assert_eq!(products.mos.len(), 2);
+    }
+
+    #[test]
+    fn test_mock_heap_findings() {
+        let mut core = MockCore::new_x86_64();
+        // call malloc
+        core.add_insn(0x1000, 5, il_nop);
+        // mov rdi, rax
+        core.add_insn(0x1005, 3, || il_set("rdi", il_var("rax")));
+        // call free
+        core.add_insn(0x1008, 5, il_nop);
+        // mov rbx, [rax]
+        core.add_insn(0x100d, 3, || il_set("rbx", il_loadw(il_var("rax"), 64)));
+        // call free
+        core.add_insn(0x1010, 5, il_nop);
+
+        let products = run_mock_path(
+            core.into_core(),
+            &[
+                (0x1000, IWordInfo::CallsMalloc),
+                (0x1005, NO_ADDR_INFO),
+                (0x1008, IWordInfo::CallsFree),
+                (0x100d, NO_ADDR_INFO),
+                (0x1010, IWordInfo::CallsFree),
+            ],
+        );
+        assert_eq!(products.heap_findings.len(), 2);
+        let uaf = products
+            .heap_findings
+            .iter()
+            .find(|f| f.get_ftype() == HeapFindingType::UseAfterFree)
+            .expect("Use-after-free not detected.");
+        assert_eq!(uaf.get_at(), 0x100d);
+        assert_eq!(uaf.get_freed_at(), 0x1008);
+        assert!(uaf.get_region().is_heap());
+        assert_eq!(uaf.get_region().get_base(), 0x1000);
+        assert_eq!(uaf.get_witness(), &vec![0x1000, 0x1005, 0x1008, 0x100d]);
+        let double_free = products
+            .heap_findings
+            .iter()
+            .find(|f| f.get_ftype() == HeapFindingType::DoubleFree)
+            .expect("Double free not detected.");
+        assert_eq!(double_free.get_at(), 0x1010);
+        assert_eq!(double_free.get_freed_at(), 0x1008);
+        assert_eq!(
+            double_free.get_witness(),
+            &vec![0x1000, 0x1005, 0x1008, 0x100d, 0x1010]
+        );
+    }
+
+    #[test]
+    fn test_mock_input_flows() {
+        let mut core = MockCore::new_x86_64();
+        // call read_input
+        core.add_insn(0x1000, 5, il_nop);
+        // mov rbx, [rax]
+        core.add_insn(0x1005, 3, || il_set("rbx", il_loadw(il_var("rax"), 64)));
+        // add rbx, 1
+        core.add_insn(0x1008, 4, || {
+            il_set("rbx", il_add(il_var("rbx"), il_bv(64, 1)))
+        });
+        // mov rdi, rbx; mov rsi, 0
+        core.add_insn(0x100c, 4, || {
+            il_seq(vec![
+                il_set("rdi", il_var("rbx")),
+                il_set("rsi", il_bv(64, 0)),
+            ])
+        });
+        // call sink
+        core.add_insn(0x1010, 5, il_nop);
+
+        let products = run_mock_path(
+            core.into_core(),
+            &[
+                (0x1000, IWordInfo::CallsInput),
+                (0x1005, NO_ADDR_INFO),
+                (0x1008, NO_ADDR_INFO),
+                (0x100c, NO_ADDR_INFO),
+                (0x1010, IWordInfo::CallsSink),
+            ],
+        );
+        // The input data survives the addition. The constant argument holds no input data,
+        // although it is equal to the result of the addition.
+        assert_eq!(
+            products.data_flows,
+            BTreeSet::from([
+                DataFlow::new(0x1000, 0x1005, None),
+                DataFlow::new(0x1005, 0x1010, Some(0)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_mock_jump_table() {
+        let mut core = MockCore::new_x86_64();
+        core.set_big_endian(true);
+        // Switch index in writable memory.
+        core.map(0x4000, &1u64.to_be_bytes(), RZ_PERM_R | RZ_PERM_W);
+        // Signed offsets relative to the table.
+        // The third entry points to data, the fifth is beyond the compared bound.
+        let table: Vec<u8> = [-0x1f00i32, -0x1e00, 0x10, -0x1d00, -0x1c00]
+            .iter()
+            .flat_map(|o| o.to_be_bytes())
+            .collect();
+        core.map(0x3000, &table, RZ_PERM_R);
+        core.map(0x1100, &[0x90; 0x400], RZ_PERM_R | RZ_PERM_X);
+        // mov rax, [0x4000]
+        core.add_insn(0x1000, 4, || il_set("rax", il_loadw(il_bv(64, 0x4000), 64)));
+        // cmp rax, 3; ja default
+        core.add_insn(0x1004, 4, || {
+            il_branch(il_ule(il_var("rax"), il_bv(64, 3)), il_nop(), il_nop())
+        });
+        // lea rdx, [rip + table]
+        core.add_insn(0x1008, 4, || il_set("rdx", il_bv(64, 0x3000)));
+        // movsxd rax, [rdx + rax * 4]
+        core.add_insn(0x100c, 4, || {
+            il_set(
+                "rax",
+                il_signed(
+                    64,
+                    il_loadw(
+                        il_add(il_var("rdx"), il_mul(il_var("rax"), il_bv(64, 4))),
+                        32,
+                    ),
+                ),
+            )
+        });
+        // add rax, rdx
+        core.add_insn(0x1010, 4, || {
+            il_set("rax", il_add(il_var("rax"), il_var("rdx")))
+        });
+        // jmp rax
+        core.add_insn(0x1014, 2, || il_jmp(il_var("rax")));
+
+        let products = run_mock(
+            core.into_core(),
+            &[0x1000, 0x1004, 0x1008, 0x100c, 0x1010, 0x1014],
+        );
+        // All entries up to the bound are only candidates. The entry pointing to data is skipped.
+        assert_eq!(
+            products.concrete_jumps,
+            BTreeSet::from([
+                ConcreteCodeXref::new_candidate(CodeXrefType::IndirectJump, 0x1000, 0x1014, 0x1100),
+                ConcreteCodeXref::new_candidate(CodeXrefType::IndirectJump, 0x1000, 0x1014, 0x1200),
+                ConcreteCodeXref::new_candidate(CodeXrefType::IndirectJump, 0x1000, 0x1014, 0x1300),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_mock_learn_summary() {
+        let mut core = MockCore::new_x86_64();
+        // call malloc
+        core.add_insn(0x1000, 5, il_nop);
+        // mov rdi, rax
+        core.add_insn(0x1005, 3, || il_set("rdi", il_var("rax")));
+        // mov rax, 0
+        core.add_insn(0x1008, 4, || il_set("rax", il_bv(64, 0)));
+        // call 0x2000
+        core.add_insn(0x100c, 5, || il_jmp(il_bv(64, 0x2000)));
+        // mov qword [rdi + 8], 1
+        core.add_insn(0x2000, 4, || {
+            il_storew(il_add(il_var("rdi"), il_bv(64, 8)), il_bv(64, 1))
+        });
+        // mov rax, rdi
+        core.add_insn(0x2004, 3, || il_set("rax", il_var("rdi")));
+        // ret
+        core.add_insn(0x2007, 1, il_nop);
+        // mov rbx, [rax]
+        core.add_insn(0x1011, 3, || il_set("rbx", il_loadw(il_var("rax"), 64)));
+
+        let products = run_mock_path(
+            core.into_core(),
+            &[
+                (0x1000, IWordInfo::CallsMalloc),
+                (0x1005, NO_ADDR_INFO),
+                (0x1008, NO_ADDR_INFO),
+                (0x100c, IWordInfo::IsCall),
+                (0x2000, NO_ADDR_INFO),
+                (0x2004, NO_ADDR_INFO),
+                (0x2007, IWordInfo::IsReturn),
+                (0x1011, NO_ADDR_INFO),
+            ],
+        );
+        // The write relative to the argument and the returned argument are learned.
+        let mut expected = FunctionSummary::new();
+        expected.writes.insert(ArgAccess::new(0, 8, 8));
+        expected.ret = Some(SummaryReturn::Arg(0));
+        assert_eq!(
+            products.summaries,
+            FunctionSummaries::from([(0x2000, expected)])
+        );
+    }
+
+    #[test]
+    fn test_mock_apply_summary() {
+        let mut core = MockCore::new_x86_64();
+        core.map(0x5000, &[0; 8], RZ_PERM_R | RZ_PERM_W);
+        // call malloc
+        core.add_insn(0x1000, 5, il_nop);
+        // mov rdi, rax
+        core.add_insn(0x1005, 3, || il_set("rdi", il_var("rax")));
+        // call 0x3000
+        core.add_insn(0x1008, 5, il_nop);
+        core.set_insn_jump(0x1008, 0x3000);
+        // mov rbx, [rax]
+        core.add_insn(0x100d, 3, || il_set("rbx", il_loadw(il_var("rax"), 64)));
+
+        let mut summary = FunctionSummary::new();
+        summary.reads.insert(ArgAccess::new(0, 8, 4));
+        summary.writes.insert(ArgAccess::new(0, 0, 8));
+        summary.ret = Some(SummaryReturn::Const(0x5000));
+        let products = run_mock_path_with_summaries(
+            core.into_core(),
+            &[
+                (0x1000, IWordInfo::CallsMalloc),
+                (0x1005, NO_ADDR_INFO),
+                (0x1008, IWordInfo::CallsUnmapped),
+                (0x100d, NO_ADDR_INFO),
+            ],
+            FunctionSummaries::from([(0x3000, summary)]),
+        );
+        // The accesses of the callee are attributed to the call.
+        let call_info = products.iword_info.get(&0x1008).unwrap();
+        assert!(call_info.is_mem_read());
+        assert!(call_info.is_mem_write());
+        // The constant return value is a known pointer.
+        assert!(products
+            .mem_xrefs
+            .contains(&MemXref::new(0x100d, 0x5000, 8)));
+        // Skipped calls don't learn summaries.
+        assert!(products.summaries.is_empty());
+    }
+}
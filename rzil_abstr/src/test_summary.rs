@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use crate::summary::{
+        merge_summaries, parse_summaries, write_summaries, ArgAccess, FunctionSummaries,
+        FunctionSummary, SummaryReturn,
+    };
+
+    #[test]
+    fn test_parse_summaries() {
+        let summaries = parse_summaries(
+            "probana function summaries v2 c0ffee\n\
+            # memcpy\n\
+            F 0x401000 a0\n\
+            R a1 0 8\n\
+            W a0 0 8\n\
+            \n\
+            F 401100 heap\n\
+            F 0x401200\n\
+            W a2 -16 4\n",
+            "c0ffee",
+        )
+        .unwrap();
+        assert_eq!(summaries.len(), 3);
+        let memcpy = summaries.get(&0x401000).unwrap();
+        assert_eq!(memcpy.ret, Some(SummaryReturn::Arg(0)));
+        assert!(memcpy.reads.contains(&ArgAccess::new(1, 0, 8)));
+        assert!(memcpy.writes.contains(&ArgAccess::new(0, 0, 8)));
+        assert_eq!(
+            summaries.get(&0x401100).unwrap().ret,
+            Some(SummaryReturn::Heap)
+        );
+        let f = summaries.get(&0x401200).unwrap();
+        assert_eq!(f.ret, None);
+        assert!(f.reads.is_empty());
+        assert!(f.writes.contains(&ArgAccess::new(2, -16, 4)));
+
+        assert!(parse_summaries("F 0x401000\n", "c0ffee").is_err());
+        let header = "probana function summaries v2 c0ffee\n";
+        assert!(parse_summaries(&format!("{}R a0 0 8\n", header), "c0ffee").is_err());
+        assert!(parse_summaries(&format!("{}F 0x10\nR r0 0 8\n", header), "c0ffee").is_err());
+        assert!(parse_summaries(&format!("{}F 0x10 x\n", header), "c0ffee").is_err());
+        // Summaries of other binaries or without binary are rejected.
+        assert!(parse_summaries(&format!("{}F 0x10\n", header), "beef").is_err());
+        assert!(parse_summaries("probana function summaries v2\nF 0x10\n", "c0ffee").is_err());
+        assert!(parse_summaries("probana function summaries v1\nF 0x10\n", "c0ffee").is_err());
+    }
+
+    #[test]
+    fn test_write_summaries() {
+        let mut summary = FunctionSummary::new();
+        summary.reads.insert(ArgAccess::new(0, 8, 4));
+        summary.writes.insert(ArgAccess::new(1, -8, 8));
+        summary.ret = Some(SummaryReturn::Const(0x20));
+        let summaries =
+            FunctionSummaries::from([(0x1000, summary), (0x2000, FunctionSummary::new())]);
+        let mut out = Vec::<u8>::new();
+        write_summaries(&mut out, &summaries, "c0ffee").unwrap();
+        assert_eq!(
+            String::from_utf8(out.clone()).unwrap(),
+            "probana function summaries v2 c0ffee\n\
+            F 0x1000 0x20\n\
+            R a0 8 4\n\
+            W a1 -8 8\n\
+            F 0x2000\n"
+        );
+        assert_eq!(
+            parse_summaries(&String::from_utf8(out).unwrap(), "c0ffee").unwrap(),
+            summaries
+        );
+    }
+
+    #[test]
+    fn test_merge_summaries() {
+        let mut a = FunctionSummary::new();
+        a.reads.insert(ArgAccess::new(0, 0, 8));
+        a.ret = Some(SummaryReturn::Arg(0));
+        let mut b = FunctionSummary::new();
+        b.writes.insert(ArgAccess::new(0, 8, 8));
+        b.ret = Some(SummaryReturn::Arg(0));
+        let mut c = FunctionSummary::new();
+        c.ret = Some(SummaryReturn::Heap);
+
+        let mut summaries = FunctionSummaries::from([(0x10, a)]);
+        merge_summaries(
+            &mut summaries,
+            &FunctionSummaries::from([(0x10, b), (0x20, c.clone())]),
+        );
+        let merged = summaries.get(&0x10).unwrap();
+        assert_eq!(merged.reads.len(), 1);
+        assert_eq!(merged.writes.len(), 1);
+        assert_eq!(merged.ret, Some(SummaryReturn::Arg(0)));
+        assert_eq!(summaries.get(&0x20), Some(&c));
+
+        // Returns differing values on different paths.
+        merge_summaries(&mut summaries, &FunctionSummaries::from([(0x10, c)]));
+        assert_eq!(
+            summaries.get(&0x10).unwrap().ret,
+            Some(SummaryReturn::Unknown)
+        );
+        // A summary without any observation doesn't change anything.
+        merge_summaries(
+            &mut summaries,
+            &FunctionSummaries::from([(0x20, FunctionSummary::new())]),
+        );
+        assert_eq!(summaries.get(&0x20).unwrap().ret, Some(SummaryReturn::Heap));
+    }
+}
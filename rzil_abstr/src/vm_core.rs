@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::sync::{Arc, Mutex};
+
+use binding::{
+    c_to_str, pderef, rz_il_reg_binding_free, RzAnalysisInsnWord, RzAnalysisOp, RzCoreWrapper,
+    RzRegisterId,
+};
+
+use crate::interpreter::Address;
+
+/// The core the abstract interpreter gets its instructions, registers and memory from.
+pub type GVMCore = Arc<Mutex<dyn VMCore>>;
+
+/// Everything the abstract interpreter needs from Rizin.
+///
+/// It is implemented by the [RzCoreWrapper] and by the in-memory
+/// [crate::mock_core::MockCore], which allows to test the abstract semantics
+/// without a binary.
+pub trait VMCore: Send {
+    /// Maximum number of iterations for REPEAT operations with unknown iteration count.
+    fn get_max_iterations(&self) -> u64;
+
+    /// Bit width of the architecture.
+    fn get_arch_bits(&self) -> usize;
+
+    /// Returns the roles of registers as (role, register name).
+    fn get_reg_alias(&self) -> Vec<(RzRegisterId, String)>;
+
+    /// Returns the registers bound to RzIL variables as (name, size in bits).
+    fn get_reg_bindings(&self) -> Option<Vec<(String, u32)>>;
+
+    /// Returns true, if the architecture decodes instruction words.
+    /// The effects are taken from [VMCore::get_iword] then, otherwise from [VMCore::get_analysis_op].
+    fn has_iword_decoder(&self) -> bool;
+
+    /// Returns the instruction word at [addr]. The caller owns it.
+    fn get_iword(&self, addr: Address) -> *mut RzAnalysisInsnWord;
+
+    /// Returns the analysis op with RzIL effect at [addr]. The caller owns it.
+    fn get_analysis_op(&self, addr: Address) -> *mut RzAnalysisOp;
+
+    /// Reads [len] bytes at [addr]. Unmapped bytes are filled by the IO layer.
+    fn read_io_at(&self, addr: Address, len: usize) -> Vec<u8>;
+
+    /// Reads [len] bytes at [addr]. Returns None if they are not mapped.
+    fn read_io_mapped_at(&self, addr: Address, len: usize) -> Option<Vec<u8>>;
+
+    /// Returns true if [addr] is mapped executable.
+    fn is_exec_addr(&self, addr: Address) -> bool;
+
+    /// Returns true if [addr] is mapped readable, but not writable.
+    fn is_read_only_addr(&self, addr: Address) -> bool;
+
+    /// Returns the name of the flag at [addr].
+    fn get_flag_name_at(&self, addr: Address) -> Option<String>;
+
+    /// Returns true if memory is read as big endian.
+    fn is_big_endian(&self) -> bool;
+}
+
+impl VMCore for RzCoreWrapper {
+    fn get_max_iterations(&self) -> u64 {
+        self.get_bda_max_iterations()
+    }
+
+    fn get_arch_bits(&self) -> usize {
+        RzCoreWrapper::get_arch_bits(self)
+    }
+
+    fn get_reg_alias(&self) -> Vec<(RzRegisterId, String)> {
+        RzCoreWrapper::get_reg_alias(self)
+            .into_iter()
+            .map(|ralias| {
+                let ra = pderef!(ralias);
+                (ra.role, c_to_str(ra.reg_name))
+            })
+            .collect()
+    }
+
+    fn get_reg_bindings(&self) -> Option<Vec<(String, u32)>> {
+        let reg_bindings = RzCoreWrapper::get_reg_bindings(self)?;
+        let reg_count = pderef!(reg_bindings).regs_count;
+        let regs = pderef!(reg_bindings).regs;
+        let bindings = (0..reg_count)
+            .map(|i| {
+                let reg = unsafe { regs.offset(i as isize) };
+                (c_to_str(pderef!(reg).name), pderef!(reg).size)
+            })
+            .collect();
+        unsafe { rz_il_reg_binding_free(reg_bindings) };
+        Some(bindings)
+    }
+
+    fn has_iword_decoder(&self) -> bool {
+        self.get_iword_decoder().is_some()
+    }
+
+    fn get_iword(&self, addr: Address) -> *mut RzAnalysisInsnWord {
+        RzCoreWrapper::get_iword(self, addr)
+    }
+
+    fn get_analysis_op(&self, addr: Address) -> *mut RzAnalysisOp {
+        RzCoreWrapper::get_analysis_op(self, addr)
+    }
+
+    fn read_io_at(&self, addr: Address, len: usize) -> Vec<u8> {
+        RzCoreWrapper::read_io_at(self, addr, len)
+    }
+
+    fn read_io_mapped_at(&self, addr: Address, len: usize) -> Option<Vec<u8>> {
+        RzCoreWrapper::read_io_mapped_at(self, addr, len)
+    }
+
+    fn is_exec_addr(&self, addr: Address) -> bool {
+        RzCoreWrapper::is_exec_addr(self, addr)
+    }
+
+    fn is_read_only_addr(&self, addr: Address) -> bool {
+        RzCoreWrapper::is_read_only_addr(self, addr)
+    }
+
+    fn get_flag_name_at(&self, addr: Address) -> Option<String> {
+        RzCoreWrapper::get_flag_name_at(self, addr)
+    }
+
+    fn is_big_endian(&self) -> bool {
+        self.get_core_conf_val("cfg.bigendian") == "true"
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use log::{debug, warn};
+
+use crate::{
+    disassembly::Address,
+    elf::Patch,
+    rewriter::{Rewriter, Rewritten},
+};
+
+/// Finds the patch causing an error of the rewritten binary by bisection.
+/// [fails] builds the binary with the given patches, runs it and returns true,
+/// if the error still occurs.
+/// Returns None, if the error occurs without any patch. So it is not caused by one.
+pub fn find_faulty_patch<F>(patches: &[Patch], mut fails: F) -> Option<Address>
+where
+    F: FnMut(&[Patch]) -> bool,
+{
+    if patches.is_empty() || fails(&[]) {
+        return None;
+    }
+    // The error occurs with all enabled patches. The faulty one is among the suspects.
+    let mut enabled: Vec<Patch> = patches.to_vec();
+    let mut suspects: Vec<Patch> = patches.to_vec();
+    while suspects.len() > 1 {
+        let (first, second) = suspects.split_at(suspects.len() / 2);
+        let without_first: Vec<Patch> = enabled
+            .iter()
+            .filter(|p| !first.contains(p))
+            .cloned()
+            .collect();
+        if fails(&without_first) {
+            enabled = without_first;
+            suspects = second.to_vec();
+        } else {
+            suspects = first.to_vec();
+        }
+    }
+    Some(suspects[0].addr)
+}
+
+/// Rewrites the binary and corrects the rewriting errors, until [fails] observes none.
+/// [fails] runs the given rewritten binary and returns true, if it behaves
+/// differently than the original one (e.g. it crashes).
+/// The address of each faulty patch is marked as data, before the binary is rewritten again.
+/// At most [max_rounds] corrections are done. Returns the last rewriting result.
+pub fn rewrite_and_correct<F>(
+    rewriter: &mut Rewriter,
+    mut fails: F,
+    max_rounds: usize,
+) -> std::io::Result<Rewritten>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    for _ in 0..max_rounds {
+        let rewritten = rewriter.rewrite()?;
+        if !fails(&rewriter.build_elf(&rewritten, &rewritten.patches)?) {
+            return Ok(rewritten);
+        }
+        let faulty = find_faulty_patch(&rewritten.patches, |patches| {
+            rewriter
+                .build_elf(&rewritten, patches)
+                .map_or(true, |elf| fails(&elf))
+        });
+        let Some(faulty) = faulty else {
+            warn!(target: "StochFuzz", "The error is not caused by a patch. Stop correcting.");
+            return Ok(rewritten);
+        };
+        debug!(target: "StochFuzz", "Patch at {:#x} overwrites data. Rewrite again.", faulty);
+        rewriter.add_data_evidence(faulty);
+    }
+    rewriter.rewrite()
+}
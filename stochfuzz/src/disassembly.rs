@@ -0,0 +1,418 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use iced_x86::{
+    Code, Decoder, DecoderOptions, FlowControl, Instruction, InstructionInfoFactory, OpAccess,
+    Register,
+};
+
+pub type Address = u64;
+
+/// Prior probability of an offset being the start of a data byte sequence.
+const PRIOR_DP: f64 = 0.5;
+/// Probabilities of an offset being data, given the hint was observed.
+/// At least two control flows converge at the offset, one of them a branch.
+const CONVERGENCE_DP: f64 = 1.0 / 16.0;
+/// The instruction defines a register, which is used by its successor.
+const DEF_USE_DP: f64 = 1.0 / 16.0;
+/// The address is referenced as code pointer.
+const CODE_PTR_DP: f64 = 1.0 / 8.0;
+
+/// A range of executable bytes.
+#[derive(Clone, Debug)]
+pub struct CodeRegion {
+    pub addr: Address,
+    pub bytes: Vec<u8>,
+}
+
+impl CodeRegion {
+    pub fn new(addr: Address, bytes: Vec<u8>) -> CodeRegion {
+        CodeRegion { addr, bytes }
+    }
+
+    pub fn contains(&self, addr: Address) -> bool {
+        self.addr <= addr && addr < self.addr + self.bytes.len() as u64
+    }
+}
+
+/// Evidence about an offset. It overrides all hints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Evidence {
+    Code,
+    Data,
+}
+
+fn log_odds(p: f64) -> f64 {
+    (p / (1.0 - p)).ln()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Probabilistic disassembly (Miller et al., ICSE'19) as used by StochFuzz.
+/// Every offset of the code regions is disassembled (superset disassembly).
+/// Hints observed in the superset determine the probability of an offset being data.
+/// The probabilities are propagated along the control flow and between
+/// overlapping instructions.
+pub struct ProbDisassembly {
+    regions: Vec<CodeRegion>,
+    /// Index of the first offset of each region.
+    region_index: Vec<usize>,
+    /// The instruction decoded at each offset. None if it is invalid.
+    insns: Vec<Option<Instruction>>,
+    /// Sum of the log odds of all hints at each offset.
+    hints: Vec<f64>,
+    evidence: Vec<Option<Evidence>>,
+    /// Probability of each offset being data.
+    dp: Vec<f64>,
+}
+
+impl ProbDisassembly {
+    /// Disassembles every offset of the 64-bit x86 [regions].
+    /// Call [ProbDisassembly::analyse()] to calculate the probabilities.
+    pub fn new(regions: Vec<CodeRegion>) -> ProbDisassembly {
+        Self::with_bitness(regions, 64)
+    }
+
+    /// Disassembles every offset of the [regions] as x86 code of the given [bitness] (16, 32 or 64).
+    pub fn with_bitness(mut regions: Vec<CodeRegion>, bitness: u32) -> ProbDisassembly {
+        regions.sort_by_key(|r| r.addr);
+        let mut region_index = Vec::new();
+        let mut insns = Vec::new();
+        for r in regions.iter() {
+            region_index.push(insns.len());
+            let mut decoder = Decoder::with_ip(bitness, &r.bytes, r.addr, DecoderOptions::NONE);
+            for offset in 0..r.bytes.len() {
+                decoder
+                    .set_position(offset)
+                    .expect("Offset should be in range");
+                decoder.set_ip(r.addr + offset as u64);
+                let insn = decoder.decode();
+                insns.push(if insn.is_invalid() { None } else { Some(insn) });
+            }
+        }
+        let n = insns.len();
+        let mut disasm = ProbDisassembly {
+            regions,
+            region_index,
+            insns,
+            hints: vec![0.0; n],
+            evidence: vec![None; n],
+            dp: vec![PRIOR_DP; n],
+        };
+        disasm.add_structural_hints();
+        disasm
+    }
+
+    fn get_index(&self, addr: Address) -> Option<usize> {
+        let r = self.regions.partition_point(|r| r.addr <= addr);
+        if r == 0 || !self.regions[r - 1].contains(addr) {
+            return None;
+        }
+        Some(self.region_index[r - 1] + (addr - self.regions[r - 1].addr) as usize)
+    }
+
+    pub fn get_regions(&self) -> &Vec<CodeRegion> {
+        &self.regions
+    }
+
+    pub fn contains(&self, addr: Address) -> bool {
+        self.get_index(addr).is_some()
+    }
+
+    /// Returns the instruction decoded at [addr]. None if it is invalid
+    /// or [addr] is not in a code region.
+    pub fn get_insn(&self, addr: Address) -> Option<&Instruction> {
+        self.insns[self.get_index(addr)?].as_ref()
+    }
+
+    /// Returns the probability that the instruction at [addr] is data.
+    pub fn get_data_probability(&self, addr: Address) -> Option<f64> {
+        Some(self.dp[self.get_index(addr)?])
+    }
+
+    /// Returns the probability that the byte at [addr] belongs to an instruction.
+    pub fn get_code_probability(&self, addr: Address) -> Option<f64> {
+        self.get_index(addr)?;
+        // Instructions are at most 15 bytes long.
+        Some(
+            (addr.saturating_sub(14)..=addr)
+                .filter_map(|s| self.get_index(s))
+                .filter(|s| self.insns[*s].is_some_and(|i| i.next_ip() > addr))
+                .map(|s| 1.0 - self.dp[s])
+                .fold(0.0, f64::max),
+        )
+    }
+
+    /// Returns the probability that all [size] bytes starting at [addr] belong to instructions.
+    /// None if any byte is not in a code region.
+    pub fn get_range_code_probability(&self, addr: Address, size: u64) -> Option<f64> {
+        (addr..addr.checked_add(size.max(1))?)
+            .map(|a| self.get_code_probability(a))
+            .try_fold(1.0, |p: f64, c| Some(p.min(c?)))
+    }
+
+    /// Returns all instructions which are data with a probability below [threshold].
+    /// Together with their data probability and ordered by address.
+    pub fn get_code_candidates(&self, threshold: f64) -> Vec<(&Instruction, f64)> {
+        self.insns
+            .iter()
+            .zip(self.dp.iter())
+            .filter_map(|(i, dp)| match i {
+                Some(i) if *dp < threshold => Some((i, *dp)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Adds evidence about the offset at [addr]. It overrides all hints.
+    pub fn add_evidence(&mut self, addr: Address, evidence: Evidence) {
+        if let Some(idx) = self.get_index(addr) {
+            self.evidence[idx] = Some(evidence);
+        }
+    }
+
+    /// Adds the hint, that [addr] is referenced as code pointer.
+    pub fn add_code_pointer_hint(&mut self, addr: Address) {
+        if let Some(idx) = self.get_index(addr) {
+            self.hints[idx] += log_odds(CODE_PTR_DP);
+        }
+    }
+
+    /// Returns the address following the instruction at [idx],
+    /// if the instruction can continue execution there.
+    fn get_fall_through(&self, idx: usize) -> Option<Address> {
+        let insn = self.insns[idx].as_ref()?;
+        match insn.flow_control() {
+            FlowControl::Next
+            | FlowControl::ConditionalBranch
+            | FlowControl::XbeginXabortXend
+            | FlowControl::Call
+            | FlowControl::IndirectCall => Some(insn.next_ip()),
+            _ => None,
+        }
+    }
+
+    /// Returns the direct branch target of the instruction at [idx].
+    fn get_branch_target(&self, idx: usize) -> Option<Address> {
+        let insn = self.insns[idx].as_ref()?;
+        match insn.flow_control() {
+            FlowControl::UnconditionalBranch
+            | FlowControl::ConditionalBranch
+            | FlowControl::Call
+            | FlowControl::XbeginXabortXend
+                if insn.near_branch_target() != 0 =>
+            {
+                Some(insn.near_branch_target())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the addresses which are definitely executed after the instruction at [idx],
+    /// if it is code. The fall through of calls is excluded, because calls might not return.
+    fn get_must_successors(&self, idx: usize) -> [Option<Address>; 2] {
+        let is_call = self.insns[idx].is_some_and(|i| {
+            matches!(
+                i.flow_control(),
+                FlowControl::Call | FlowControl::IndirectCall
+            )
+        });
+        [
+            self.get_branch_target(idx),
+            self.get_fall_through(idx).filter(|_| !is_call),
+        ]
+    }
+
+    /// Returns the addresses which are likely executed after the instruction at [idx].
+    fn get_successors(&self, idx: usize) -> [Option<Address>; 2] {
+        [self.get_branch_target(idx), self.get_fall_through(idx)]
+    }
+
+    /// Adds the hints, which can be derived from the superset itself.
+    fn add_structural_hints(&mut self) {
+        let mut incoming = vec![0usize; self.insns.len()];
+        let mut is_target = vec![false; self.insns.len()];
+        for idx in 0..self.insns.len() {
+            if let Some(t) = self.get_branch_target(idx).and_then(|t| self.get_index(t)) {
+                incoming[t] += 1;
+                is_target[t] = true;
+            }
+            if let Some(f) = self.get_fall_through(idx).and_then(|f| self.get_index(f)) {
+                incoming[f] += 1;
+            }
+        }
+        let mut info_factory = InstructionInfoFactory::new();
+        for idx in 0..self.insns.len() {
+            if is_target[idx] && incoming[idx] >= 2 {
+                self.hints[idx] += log_odds(CONVERGENCE_DP);
+            }
+            let Some(insn) = self.insns[idx] else {
+                continue;
+            };
+            if insn.flow_control() != FlowControl::Next {
+                continue;
+            }
+            let Some(succ) = self
+                .get_index(insn.next_ip())
+                .and_then(|s| self.insns[s].as_ref())
+            else {
+                continue;
+            };
+            let defs: Vec<Register> = info_factory
+                .info(&insn)
+                .used_registers()
+                .iter()
+                .filter(|u| {
+                    matches!(
+                        u.access(),
+                        OpAccess::Write
+                            | OpAccess::CondWrite
+                            | OpAccess::ReadWrite
+                            | OpAccess::ReadCondWrite
+                    )
+                })
+                .map(|u| u.register().full_register())
+                .filter(|r| !matches!(*r, Register::RSP | Register::RIP | Register::None))
+                .collect();
+            let uses_def = info_factory.info(succ).used_registers().iter().any(|u| {
+                matches!(
+                    u.access(),
+                    OpAccess::Read
+                        | OpAccess::CondRead
+                        | OpAccess::ReadWrite
+                        | OpAccess::ReadCondWrite
+                ) && defs.contains(&u.register().full_register())
+            });
+            if uses_def {
+                self.hints[idx] += log_odds(DEF_USE_DP);
+            }
+        }
+    }
+
+    /// Returns the data probability of [idx] before any propagation.
+    fn get_initial_dp(&self, idx: usize) -> f64 {
+        match self.evidence[idx] {
+            Some(Evidence::Code) => 0.0,
+            Some(Evidence::Data) => 1.0,
+            None if self.insns[idx].is_none() => 1.0,
+            None if self.insns[idx].is_some_and(|i| i.code() == Code::Ud2) => 1.0,
+            None => sigmoid(log_odds(PRIOR_DP) + self.hints[idx]),
+        }
+    }
+
+    fn is_fixed(&self, idx: usize) -> bool {
+        self.evidence[idx].is_some() || self.insns[idx].is_none()
+    }
+
+    /// Instructions flowing into data are data as well.
+    /// Returns true if any probability changed.
+    fn propagate_data(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            let mut sweep_changed = false;
+            for idx in (0..self.insns.len()).rev() {
+                if self.is_fixed(idx) {
+                    continue;
+                }
+                let max_succ = self
+                    .get_must_successors(idx)
+                    .into_iter()
+                    .flatten()
+                    .map(|s| self.get_index(s).map_or(1.0, |s| self.dp[s]))
+                    .fold(0.0, f64::max);
+                if max_succ > PRIOR_DP && max_succ > self.dp[idx] {
+                    self.dp[idx] = max_succ;
+                    sweep_changed = true;
+                }
+            }
+            if !sweep_changed {
+                break;
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Successors of code are code as well.
+    /// Returns true if any probability changed.
+    fn propagate_code(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            let mut sweep_changed = false;
+            for idx in 0..self.insns.len() {
+                if self.dp[idx] >= PRIOR_DP {
+                    continue;
+                }
+                for s in self.get_successors(idx).into_iter().flatten() {
+                    let Some(s) = self.get_index(s) else {
+                        continue;
+                    };
+                    if self.is_fixed(s) || self.dp[s] <= self.dp[idx] {
+                        continue;
+                    }
+                    self.dp[s] = self.dp[idx];
+                    sweep_changed = true;
+                }
+            }
+            if !sweep_changed {
+                break;
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Instructions overlapping with more likely instructions are likely data.
+    /// Returns true if any probability changed.
+    fn resolve_occlusion(&mut self) -> bool {
+        let mut candidates: Vec<usize> = (0..self.insns.len())
+            .filter(|i| self.dp[*i] < PRIOR_DP && self.insns[*i].is_some())
+            .collect();
+        candidates.sort_by(|a, b| self.dp[*a].total_cmp(&self.dp[*b]).then(a.cmp(b)));
+        let mut owner: Vec<Option<usize>> = vec![None; self.insns.len()];
+        for idx in candidates {
+            let end = (idx + self.insns[idx].unwrap().len()).min(self.insns.len());
+            if owner[idx..end].iter().any(|o| o.is_some()) {
+                continue;
+            }
+            owner[idx..end].iter_mut().for_each(|o| *o = Some(idx));
+        }
+        let mut changed = false;
+        for idx in 0..self.insns.len() {
+            if self.is_fixed(idx) {
+                continue;
+            }
+            let end = (idx + self.insns[idx].unwrap().len()).min(self.insns.len());
+            let occluding = owner[idx..end]
+                .iter()
+                .flatten()
+                .filter(|o| **o != idx)
+                .map(|o| 1.0 - self.dp[*o])
+                .fold(0.0, f64::max);
+            if occluding > self.dp[idx] {
+                self.dp[idx] = occluding;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Calculates the probabilities from the hints and evidence.
+    /// The propagation is repeated at most [iterations] times.
+    pub fn analyse(&mut self, iterations: usize) {
+        self.dp = (0..self.insns.len())
+            .map(|i| self.get_initial_dp(i))
+            .collect();
+        self.propagate_data();
+        for _ in 0..iterations {
+            let code_changed = self.propagate_code();
+            let occl_changed = self.resolve_occlusion();
+            let data_changed = self.propagate_data();
+            if !(code_changed || occl_changed || data_changed) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    collections::BTreeSet,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use goblin::elf::{
+    header::EM_X86_64,
+    program_header::{PF_X, PT_GNU_PROPERTY, PT_LOAD, PT_NOTE, PT_NULL},
+    reloc::R_X86_64_RELATIVE,
+    section_header::{SHF_EXECINSTR, SHT_PROGBITS},
+    sym::STT_FUNC,
+    Elf,
+};
+
+use crate::disassembly::{Address, CodeRegion};
+
+pub const PAGE_SIZE: u64 = 0x1000;
+
+pub fn align_up(x: u64, align: u64) -> u64 {
+    x.div_ceil(align) * align
+}
+
+/// Bytes written over the original bytes at [addr].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub addr: Address,
+    pub bytes: Vec<u8>,
+}
+
+/// A loadable segment.
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub vaddr: Address,
+    pub memsz: u64,
+    pub offset: u64,
+    pub filesz: u64,
+    pub flags: u32,
+}
+
+/// An x86-64 ELF executable and the information about it relevant for rewriting.
+pub struct ElfBinary {
+    pub data: Vec<u8>,
+    pub entry: Address,
+    pub segments: Vec<Segment>,
+    /// The executable sections. Or segments, if there are no section headers.
+    pub code_regions: Vec<CodeRegion>,
+    /// Addresses of function symbols.
+    pub functions: BTreeSet<Address>,
+    /// Values in data segments and relocations, which point into the code regions.
+    pub code_pointers: BTreeSet<Address>,
+    phoff: u64,
+    phentsize: u64,
+    /// Index of the program header, which is reused for the new segment.
+    free_phdr: usize,
+    /// Indices of program headers with properties, which are dropped.
+    /// E.g. shadow stack support, which breaks with the rewritten returns.
+    property_phdrs: Vec<usize>,
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+impl ElfBinary {
+    /// Parses the ELF executable in [data].
+    pub fn parse(data: Vec<u8>) -> std::io::Result<ElfBinary> {
+        let elf = Elf::parse(&data).map_err(|e| invalid_data(e.to_string()))?;
+        if !elf.is_64 || !elf.little_endian || elf.header.e_machine != EM_X86_64 {
+            return Err(invalid_data(
+                "Only x86-64 ELF binaries are supported.".to_string(),
+            ));
+        }
+        let segments: Vec<Segment> = elf
+            .program_headers
+            .iter()
+            .filter(|ph| ph.p_type == PT_LOAD)
+            .map(|ph| Segment {
+                vaddr: ph.p_vaddr,
+                memsz: ph.p_memsz,
+                offset: ph.p_offset,
+                filesz: ph.p_filesz,
+                flags: ph.p_flags,
+            })
+            .collect();
+        let Some(free_phdr) = elf
+            .program_headers
+            .iter()
+            .position(|ph| ph.p_type == PT_NOTE)
+        else {
+            return Err(invalid_data(
+                "The binary has no PT_NOTE program header, which can be reused.".to_string(),
+            ));
+        };
+        let property_phdrs = elf
+            .program_headers
+            .iter()
+            .enumerate()
+            .filter(|(i, ph)| {
+                *i != free_phdr && (ph.p_type == PT_NOTE || ph.p_type == PT_GNU_PROPERTY)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let get_bytes = |offset: u64, size: u64| -> std::io::Result<Vec<u8>> {
+            data.get(offset as usize..(offset + size) as usize)
+                .map(|b| b.to_vec())
+                .ok_or_else(|| invalid_data(format!("Range {:#x} is out of the file.", offset)))
+        };
+        let mut code_regions = Vec::new();
+        for sh in elf.section_headers.iter() {
+            if sh.sh_type != SHT_PROGBITS
+                || sh.sh_flags & SHF_EXECINSTR as u64 == 0
+                || sh.sh_size == 0
+            {
+                continue;
+            }
+            code_regions.push(CodeRegion::new(
+                sh.sh_addr,
+                get_bytes(sh.sh_offset, sh.sh_size)?,
+            ));
+        }
+        if code_regions.is_empty() {
+            for seg in segments.iter().filter(|s| s.flags & PF_X != 0) {
+                code_regions.push(CodeRegion::new(
+                    seg.vaddr,
+                    get_bytes(seg.offset, seg.filesz)?,
+                ));
+            }
+        }
+        let in_code = |addr: Address| code_regions.iter().any(|r| r.contains(addr));
+
+        let functions = elf
+            .syms
+            .iter()
+            .chain(elf.dynsyms.iter())
+            .filter(|s| s.st_type() == STT_FUNC && in_code(s.st_value))
+            .map(|s| s.st_value)
+            .collect();
+
+        let mut code_pointers: BTreeSet<Address> = elf
+            .dynrelas
+            .iter()
+            .chain(elf.pltrelocs.iter())
+            .filter(|r| r.r_type == R_X86_64_RELATIVE)
+            .filter_map(|r| r.r_addend.map(|a| a as Address))
+            .filter(|a| in_code(*a))
+            .collect();
+        for seg in segments.iter().filter(|s| s.flags & PF_X == 0) {
+            let bytes = get_bytes(seg.offset, seg.filesz)?;
+            let first = (align_up(seg.vaddr, 8) - seg.vaddr) as usize;
+            for word in bytes.get(first..).unwrap_or_default().chunks_exact(8) {
+                let value = u64::from_le_bytes(word.try_into().unwrap());
+                if in_code(value) {
+                    code_pointers.insert(value);
+                }
+            }
+        }
+
+        Ok(ElfBinary {
+            entry: elf.entry,
+            segments,
+            code_regions,
+            functions,
+            code_pointers,
+            phoff: elf.header.e_phoff,
+            phentsize: elf.header.e_phentsize as u64,
+            free_phdr,
+            property_phdrs,
+            data,
+        })
+    }
+
+    /// Loads the ELF executable at [path].
+    pub fn load(path: &Path) -> std::io::Result<ElfBinary> {
+        ElfBinary::parse(std::fs::read(path)?)
+    }
+
+    /// Returns the first address after all loaded segments.
+    pub fn get_load_end(&self) -> Address {
+        self.segments
+            .iter()
+            .map(|s| s.vaddr + s.memsz)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the file offset of the byte loaded to [addr].
+    pub fn vaddr_to_offset(&self, addr: Address) -> Option<usize> {
+        self.segments
+            .iter()
+            .find(|s| s.vaddr <= addr && addr < s.vaddr + s.filesz)
+            .map(|s| (s.offset + addr - s.vaddr) as usize)
+    }
+
+    fn write_u32(out: &mut [u8], offset: u64, value: u32) {
+        out[offset as usize..offset as usize + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u64(out: &mut [u8], offset: u64, value: u64) {
+        out[offset as usize..offset as usize + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Returns the binary with the [patches] applied and [segment] added as loadable segment at [vaddr].
+    /// The segment is readable, writable and executable. Execution starts at [entry].
+    pub fn build(
+        &self,
+        patches: &[Patch],
+        segment: &[u8],
+        vaddr: Address,
+        entry: Address,
+    ) -> std::io::Result<Vec<u8>> {
+        if vaddr % PAGE_SIZE != 0 || vaddr < self.get_load_end() {
+            return Err(invalid_data(format!(
+                "Segment address {:#x} overlaps with the binary.",
+                vaddr
+            )));
+        }
+        let mut out = self.data.clone();
+        for p in patches.iter() {
+            let Some(offset) = self.vaddr_to_offset(p.addr) else {
+                return Err(invalid_data(format!(
+                    "Patch address {:#x} is not in the file.",
+                    p.addr
+                )));
+            };
+            out[offset..offset + p.bytes.len()].copy_from_slice(&p.bytes);
+        }
+        let seg_offset = align_up(out.len() as u64, PAGE_SIZE);
+        out.resize(seg_offset as usize, 0);
+        out.extend_from_slice(segment);
+
+        // Elf64_Phdr: p_type, p_flags, p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, p_align
+        let phdr = self.phoff + self.free_phdr as u64 * self.phentsize;
+        Self::write_u32(&mut out, phdr, PT_LOAD);
+        Self::write_u32(&mut out, phdr + 4, 0x7);
+        Self::write_u64(&mut out, phdr + 8, seg_offset);
+        Self::write_u64(&mut out, phdr + 16, vaddr);
+        Self::write_u64(&mut out, phdr + 24, vaddr);
+        Self::write_u64(&mut out, phdr + 32, segment.len() as u64);
+        Self::write_u64(&mut out, phdr + 40, segment.len() as u64);
+        Self::write_u64(&mut out, phdr + 48, PAGE_SIZE);
+        for i in self.property_phdrs.iter() {
+            Self::write_u32(&mut out, self.phoff + *i as u64 * self.phentsize, PT_NULL);
+        }
+        // e_entry
+        Self::write_u64(&mut out, 24, entry);
+        Ok(out)
+    }
+}
@@ -0,0 +1,335 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::process::{CommandExt, ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::instrumentation::{FORKSRV_FD, MAP_SIZE, SHM_ENV_VAR};
+
+/// Argument replaced with the path of the input file.
+pub const INPUT_FILE_ARG: &str = "@@";
+
+/// Counter to give the files of each executor a unique name.
+static EXECUTOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// How a run of the target ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitKind {
+    /// Exited with the given exit code.
+    Exited(i32),
+    /// Terminated by the given signal.
+    Signaled(i32),
+    /// Killed, because it ran longer than the timeout.
+    Timeout,
+}
+
+impl ExitKind {
+    fn from_wait_status(status: i32) -> ExitKind {
+        if libc::WIFSIGNALED(status) {
+            ExitKind::Signaled(libc::WTERMSIG(status))
+        } else {
+            ExitKind::Exited(libc::WEXITSTATUS(status))
+        }
+    }
+
+    pub fn is_crash(&self) -> bool {
+        matches!(self, ExitKind::Signaled(_))
+    }
+}
+
+/// A System V shared memory segment, as used by AFL for the coverage map.
+struct SharedMemory {
+    id: i32,
+    ptr: *mut u8,
+    size: usize,
+}
+
+impl SharedMemory {
+    fn new(size: usize) -> std::io::Result<SharedMemory> {
+        let id = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if id < 0 {
+            return Err(Error::last_os_error());
+        }
+        let ptr = unsafe { libc::shmat(id, std::ptr::null(), 0) };
+        if ptr as isize == -1 {
+            let err = Error::last_os_error();
+            unsafe { libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut()) };
+            return Err(err);
+        }
+        Ok(SharedMemory {
+            id,
+            ptr: ptr as *mut u8,
+            size,
+        })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.size) }
+    }
+
+    fn clear(&mut self) {
+        unsafe { std::ptr::write_bytes(self.ptr, 0, self.size) };
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::shmdt(self.ptr as *const libc::c_void);
+            libc::shmctl(self.id, libc::IPC_RMID, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Returns a pipe as (read end, write end). Both are closed on exec.
+fn get_pipe() -> std::io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    unsafe { Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))) }
+}
+
+/// Reads a 4 byte message from the [pipe].
+/// Returns None, if nothing was received before the [deadline].
+fn read_msg(pipe: &mut File, deadline: Option<Instant>) -> std::io::Result<Option<i32>> {
+    if let Some(deadline) = deadline {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let mut pfd = libc::pollfd {
+            fd: pipe.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let n = unsafe { libc::poll(&mut pfd, 1, ms) };
+        if n < 0 {
+            return Err(Error::last_os_error());
+        }
+        if n == 0 {
+            return Ok(None);
+        }
+    }
+    let mut buf = [0u8; 4];
+    pipe.read_exact(&mut buf)?;
+    Ok(Some(i32::from_ne_bytes(buf)))
+}
+
+/// The pipes to a running fork server.
+struct ForkServer {
+    process: Child,
+    /// Commands to the fork server.
+    ctl: File,
+    /// Pids and wait status of the children.
+    status: File,
+}
+
+/// Runs a target binary repeatedly with different inputs.
+///
+/// If the target is rewritten, its runtime acts as AFL fork server. The target is started
+/// only once and forks a child for every run. Otherwise each run starts a new process.
+/// The input is passed as file, if the [INPUT_FILE_ARG] is given as argument,
+/// and via stdin otherwise. The coverage map is shared with the target in both cases.
+pub struct Executor {
+    target: PathBuf,
+    args: Vec<String>,
+    timeout: Duration,
+    shm: SharedMemory,
+    input_path: PathBuf,
+    input: File,
+    output_path: PathBuf,
+    output: File,
+    server: Option<ForkServer>,
+}
+
+impl Executor {
+    /// Creates the executor for the [target] with the arguments [args].
+    /// Runs exceeding the [timeout] are killed.
+    pub fn new(target: &Path, args: &[&str], timeout: Duration) -> std::io::Result<Executor> {
+        let id = EXECUTOR_ID.fetch_add(1, Ordering::Relaxed);
+        let prefix = format!("stochfuzz_exec_{}_{}", std::process::id(), id);
+        let input_path = std::env::temp_dir().join(format!("{}_input", prefix));
+        let output_path = std::env::temp_dir().join(format!("{}_output", prefix));
+        let open = |p: &Path| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(p)
+        };
+        Ok(Executor {
+            target: target.to_path_buf(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            timeout,
+            shm: SharedMemory::new(MAP_SIZE as usize)?,
+            input: open(&input_path)?,
+            input_path,
+            output: open(&output_path)?,
+            output_path,
+            server: None,
+        })
+    }
+
+    /// Returns the command to start the target.
+    fn get_command(&self) -> std::io::Result<Command> {
+        let mut cmd = Command::new(&self.target);
+        let input_path = self.input_path.to_string_lossy();
+        cmd.args(
+            self.args
+                .iter()
+                .map(|a| a.replace(INPUT_FILE_ARG, &input_path)),
+        )
+        .env(SHM_ENV_VAR, self.shm.id.to_string())
+        .stdin(if self.args.iter().any(|a| a.contains(INPUT_FILE_ARG)) {
+            Stdio::null()
+        } else {
+            Stdio::from(self.input.try_clone()?)
+        })
+        .stdout(Stdio::from(self.output.try_clone()?))
+        .stderr(Stdio::null());
+        Ok(cmd)
+    }
+
+    /// Starts the fork server of the target.
+    /// Returns false if the target has none. Each run starts a new process then.
+    pub fn start(&mut self) -> std::io::Result<bool> {
+        self.stop();
+        let (ctl_read, ctl) = get_pipe()?;
+        let (mut status, status_write) = get_pipe()?;
+        let mut cmd = self.get_command()?;
+        let (ctl_fd, status_fd) = (ctl_read.as_raw_fd(), status_write.as_raw_fd());
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::dup2(ctl_fd, FORKSRV_FD) < 0 || libc::dup2(status_fd, FORKSRV_FD + 1) < 0 {
+                    return Err(Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        self.reset_files(&[])?;
+        let mut process = cmd.spawn()?;
+        // Only the target keeps these ends open. So we notice, if it exits.
+        drop(ctl_read);
+        drop(status_write);
+
+        let hello = read_msg(&mut status, Some(Instant::now() + self.timeout));
+        if !matches!(hello, Ok(Some(_))) {
+            let _ = process.kill();
+            let _ = process.wait();
+            return Ok(false);
+        }
+        self.server = Some(ForkServer {
+            process,
+            ctl,
+            status,
+        });
+        Ok(true)
+    }
+
+    /// Stops the fork server, if it runs.
+    pub fn stop(&mut self) {
+        if let Some(mut server) = self.server.take() {
+            let _ = server.process.kill();
+            let _ = server.process.wait();
+        }
+    }
+
+    pub fn has_fork_server(&self) -> bool {
+        self.server.is_some()
+    }
+
+    /// Writes the [input] and clears the output of the last run.
+    fn reset_files(&mut self, input: &[u8]) -> std::io::Result<()> {
+        self.input.set_len(0)?;
+        self.input.seek(SeekFrom::Start(0))?;
+        self.input.write_all(input)?;
+        self.input.seek(SeekFrom::Start(0))?;
+        self.output.set_len(0)?;
+        self.output.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+
+    /// Runs the target with the [input].
+    /// Afterwards the coverage map and output of the run can be queried.
+    pub fn run(&mut self, input: &[u8]) -> std::io::Result<ExitKind> {
+        self.shm.clear();
+        self.reset_files(input)?;
+        if self.server.is_some() {
+            self.run_forked()
+        } else {
+            self.run_process()
+        }
+    }
+
+    fn run_forked(&mut self) -> std::io::Result<ExitKind> {
+        let deadline = Instant::now() + self.timeout;
+        let server = self.server.as_mut().expect("Fork server should run");
+        server.ctl.write_all(&0i32.to_ne_bytes())?;
+        let Some(pid) = read_msg(&mut server.status, Some(deadline))? else {
+            self.stop();
+            return Err(Error::new(
+                ErrorKind::TimedOut,
+                "The fork server did not respond.",
+            ));
+        };
+        if let Some(status) = read_msg(&mut server.status, Some(deadline))? {
+            return Ok(ExitKind::from_wait_status(status));
+        }
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+        // The status of the killed child is still sent.
+        read_msg(&mut server.status, None)?;
+        Ok(ExitKind::Timeout)
+    }
+
+    fn run_process(&mut self) -> std::io::Result<ExitKind> {
+        let deadline = Instant::now() + self.timeout;
+        let mut child = self.get_command()?.spawn()?;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(match status.signal() {
+                    Some(sig) => ExitKind::Signaled(sig),
+                    None => ExitKind::Exited(status.code().unwrap_or(0)),
+                });
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                child.wait()?;
+                return Ok(ExitKind::Timeout);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Returns the coverage map of the last run.
+    pub fn get_coverage(&self) -> &[u8] {
+        self.shm.as_slice()
+    }
+
+    /// Returns the number of edges covered by the last run.
+    pub fn get_covered_edges(&self) -> usize {
+        self.get_coverage().iter().filter(|c| **c != 0).count()
+    }
+
+    /// Returns what the last run wrote to stdout.
+    pub fn get_output(&self) -> std::io::Result<Vec<u8>> {
+        std::fs::read(&self.output_path)
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = std::fs::remove_file(&self.input_path);
+        let _ = std::fs::remove_file(&self.output_path);
+    }
+}
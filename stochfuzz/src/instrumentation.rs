@@ -0,0 +1,374 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::io::Error;
+
+use iced_x86::{code_asm::*, Code, IcedError, Instruction, MemoryOperand, OpKind, Register};
+
+use crate::{
+    disassembly::Address,
+    elf::{align_up, PAGE_SIZE},
+};
+
+/// Size of the AFL coverage map.
+pub const MAP_SIZE: u64 = 1 << 16;
+/// Environment variable with the id of AFL's shared memory coverage map.
+pub const SHM_ENV_VAR: &str = "__AFL_SHM_ID";
+
+/// File descriptor the fork server reads its commands from.
+/// The status is written to the following one.
+pub const FORKSRV_FD: i32 = 198;
+
+/// Bytes below the stack pointer, which might be used by the instrumented function.
+const RED_ZONE_SIZE: i64 = 128;
+/// Numbers of the used syscalls.
+const SYS_READ: u32 = 0;
+const SYS_WRITE: u32 = 1;
+const SYS_CLOSE: u32 = 3;
+const SYS_SHMAT: u32 = 30;
+const SYS_FORK: u32 = 57;
+const SYS_WAIT4: u32 = 61;
+const SYS_EXIT_GROUP: u32 = 231;
+
+/// Returns the label of the translated instruction at [addr].
+/// Translated instructions are identified by labels instead of their original address.
+/// Otherwise the block encoder would also redirect data references to them into the shadow code.
+pub fn get_label(addr: Address) -> u64 {
+    addr | (1 << 63)
+}
+
+/// Returns the position of the block at [addr] in the coverage map.
+pub fn get_block_id(addr: Address) -> u64 {
+    let mut x = addr;
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    x ^= x >> 33;
+    x & (MAP_SIZE - 1)
+}
+
+fn rip_rel(addr: Address) -> MemoryOperand {
+    MemoryOperand::with_base_displ(Register::RIP, addr as i64)
+}
+
+fn expect_valid(insn: Result<Instruction, IcedError>) -> Instruction {
+    insn.expect("Instruction should be valid")
+}
+
+/// The layout of the segment with the instrumentation runtime and the shadow code.
+///
+/// ```text
+/// base + 0x0:           Pointer to the coverage map
+/// base + 0x8:           Previous block id
+/// base + 0x10:          Name of the shared memory environment variable
+/// base + PAGE_SIZE:     Coverage map used without AFL
+/// dummy map + MAP_SIZE: Shadow code, followed by the runtime
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RuntimeLayout {
+    pub base: Address,
+}
+
+impl RuntimeLayout {
+    pub fn new(base: Address) -> RuntimeLayout {
+        RuntimeLayout { base }
+    }
+
+    pub fn get_area_ptr(&self) -> Address {
+        self.base
+    }
+
+    pub fn get_prev_loc(&self) -> Address {
+        self.base + 8
+    }
+
+    pub fn get_env_name(&self) -> Address {
+        self.base + 0x10
+    }
+
+    pub fn get_dummy_map(&self) -> Address {
+        self.base + PAGE_SIZE
+    }
+
+    pub fn get_code_start(&self) -> Address {
+        self.get_dummy_map() + MAP_SIZE
+    }
+
+    /// Returns the initial content of the segment up to the code.
+    pub fn get_data(&self) -> Vec<u8> {
+        let mut data = vec![0; (self.get_code_start() - self.base) as usize];
+        let name = format!("{}=", SHM_ENV_VAR);
+        let offset = (self.get_env_name() - self.base) as usize;
+        data[offset..offset + name.len()].copy_from_slice(name.as_bytes());
+        data
+    }
+}
+
+/// Returns the instructions logging the edge to the block at [addr] in the coverage map.
+/// The first instruction gets the label of [addr], so branches to the block execute it.
+/// No register or flag is changed.
+pub fn get_coverage_stub(layout: &RuntimeLayout, addr: Address) -> Vec<Instruction> {
+    let id = get_block_id(addr);
+    let mut stub = vec![
+        expect_valid(Instruction::with2(
+            Code::Lea_r64_m,
+            Register::RSP,
+            MemoryOperand::with_base_displ(Register::RSP, -RED_ZONE_SIZE),
+        )),
+        expect_valid(Instruction::with1(Code::Push_r64, Register::RAX)),
+        expect_valid(Instruction::with1(Code::Push_r64, Register::RCX)),
+        Instruction::with(Code::Pushfq),
+        expect_valid(Instruction::with2(
+            Code::Mov_r64_rm64,
+            Register::RAX,
+            rip_rel(layout.get_area_ptr()),
+        )),
+        expect_valid(Instruction::with2(
+            Code::Mov_r64_rm64,
+            Register::RCX,
+            rip_rel(layout.get_prev_loc()),
+        )),
+        expect_valid(Instruction::with2(
+            Code::Xor_rm64_imm32,
+            Register::RCX,
+            id as i32,
+        )),
+        expect_valid(Instruction::with1(
+            Code::Inc_rm8,
+            MemoryOperand::with_base_index(Register::RAX, Register::RCX),
+        )),
+        expect_valid(Instruction::with2(
+            Code::Mov_rm64_imm32,
+            rip_rel(layout.get_prev_loc()),
+            (id >> 1) as i32,
+        )),
+        Instruction::with(Code::Popfq),
+        expect_valid(Instruction::with1(Code::Pop_r64, Register::RCX)),
+        expect_valid(Instruction::with1(Code::Pop_r64, Register::RAX)),
+        expect_valid(Instruction::with2(
+            Code::Lea_r64_m,
+            Register::RSP,
+            MemoryOperand::with_base_displ(Register::RSP, RED_ZONE_SIZE),
+        )),
+    ];
+    stub[0].set_ip(get_label(addr));
+    stub
+}
+
+/// Translates a near call into a push of its original return address and a jump to the target.
+/// So the return addresses on the stack are the same as in the original binary.
+/// Returning executes the original code at the return address, which jumps back to the shadow code.
+/// Other instructions are returned unchanged.
+pub fn translate_call(call: &Instruction) -> Vec<Instruction> {
+    let mut jmp = *call;
+    match call.code() {
+        Code::Call_rel32_64 => {
+            jmp = expect_valid(Instruction::with_branch(
+                Code::Jmp_rel32_64,
+                call.near_branch_target(),
+            ));
+        }
+        Code::Call_rm64 => {
+            jmp.set_code(Code::Jmp_rm64);
+            if jmp.op0_kind() == OpKind::Memory && jmp.memory_base() == Register::RSP {
+                // The return address is already pushed, when the target is read.
+                jmp.set_memory_displacement64(jmp.memory_displacement64().wrapping_add(8));
+            }
+        }
+        _ => return vec![*call],
+    }
+    jmp.set_ip(0);
+    vec![
+        expect_valid(Instruction::with2(
+            Code::Lea_r64_m,
+            Register::RSP,
+            MemoryOperand::with_base_displ(Register::RSP, -8),
+        )),
+        expect_valid(Instruction::with1(Code::Push_r64, Register::RAX)),
+        expect_valid(Instruction::with2(
+            Code::Lea_r64_m,
+            Register::RAX,
+            rip_rel(call.next_ip()),
+        )),
+        expect_valid(Instruction::with2(
+            Code::Mov_rm64_r64,
+            MemoryOperand::with_base_displ(Register::RSP, 8),
+            Register::RAX,
+        )),
+        expect_valid(Instruction::with1(Code::Pop_r64, Register::RAX)),
+        jmp,
+    ]
+}
+
+/// Assembles the runtime at [rip]. It is executed before the original entry.
+/// It attaches the shared memory coverage map of AFL, if its id is set in the environment.
+/// Otherwise the dummy map of the segment is used.
+/// If the fork server file descriptors are open, it runs the fork server
+/// (see [build_fork_server]). Afterwards it jumps to [entry].
+pub fn assemble_runtime(
+    layout: &RuntimeLayout,
+    rip: Address,
+    entry: Address,
+) -> std::io::Result<Vec<u8>> {
+    build_runtime(layout, entry)
+        .and_then(|mut a| a.assemble(rip))
+        .map_err(|e| Error::other(e.to_string()))
+}
+
+fn build_runtime(layout: &RuntimeLayout, entry: Address) -> Result<CodeAssembler, IcedError> {
+    let mut a = CodeAssembler::new(64)?;
+    let mut env_loop = a.create_label();
+    let mut cmp_loop = a.create_label();
+    let mut found = a.create_label();
+    let mut parse_loop = a.create_label();
+    let mut parse_done = a.create_label();
+    let mut done = a.create_label();
+
+    // rdx holds the finalizer of the dynamic linker.
+    a.push(rdx)?;
+    a.add_instruction(Instruction::with2(
+        Code::Lea_r64_m,
+        Register::RAX,
+        rip_rel(layout.get_dummy_map()),
+    )?)?;
+    a.add_instruction(Instruction::with2(
+        Code::Mov_rm64_r64,
+        rip_rel(layout.get_area_ptr()),
+        Register::RAX,
+    )?)?;
+    // Stack: rdx, argc, argv[argc], NULL, envp
+    a.mov(rcx, qword_ptr(rsp + 8))?;
+    a.lea(rsi, qword_ptr(rsp + rcx * 8 + 24))?;
+
+    a.set_label(&mut env_loop)?;
+    a.mov(rdi, qword_ptr(rsi))?;
+    a.test(rdi, rdi)?;
+    a.jz(done)?;
+    a.add(rsi, 8)?;
+    a.add_instruction(Instruction::with2(
+        Code::Lea_r64_m,
+        Register::RDX,
+        rip_rel(layout.get_env_name()),
+    )?)?;
+    a.set_label(&mut cmp_loop)?;
+    a.mov(al, byte_ptr(rdx))?;
+    a.test(al, al)?;
+    a.jz(found)?;
+    a.cmp(al, byte_ptr(rdi))?;
+    a.jne(env_loop)?;
+    a.inc(rdx)?;
+    a.inc(rdi)?;
+    a.jmp(cmp_loop)?;
+
+    a.set_label(&mut found)?;
+    a.xor(eax, eax)?;
+    a.set_label(&mut parse_loop)?;
+    a.movzx(ecx, byte_ptr(rdi))?;
+    a.sub(ecx, 0x30)?;
+    a.cmp(ecx, 9)?;
+    a.ja(parse_done)?;
+    a.imul_3(rax, rax, 10)?;
+    a.add(rax, rcx)?;
+    a.inc(rdi)?;
+    a.jmp(parse_loop)?;
+
+    a.set_label(&mut parse_done)?;
+    a.mov(rdi, rax)?;
+    a.xor(esi, esi)?;
+    a.xor(edx, edx)?;
+    a.mov(eax, SYS_SHMAT)?;
+    a.syscall()?;
+    // Errors are returned as -4095..-1
+    a.cmp(rax, -4095)?;
+    a.jae(done)?;
+    a.add_instruction(Instruction::with2(
+        Code::Mov_rm64_r64,
+        rip_rel(layout.get_area_ptr()),
+        Register::RAX,
+    )?)?;
+
+    a.set_label(&mut done)?;
+    build_fork_server(&mut a)?;
+    a.pop(rdx)?;
+    a.jmp(entry)?;
+    Ok(a)
+}
+
+/// Adds the AFL fork server to the runtime.
+/// It announces itself by writing 4 bytes to FORKSRV_FD + 1. If this fails, no one
+/// listens and the runtime continues. Otherwise, for every 4 bytes read from FORKSRV_FD
+/// it forks a child, writes the child's pid and afterwards its wait status.
+/// The children continue with the original entry.
+fn build_fork_server(a: &mut CodeAssembler) -> Result<(), IcedError> {
+    let mut server_loop = a.create_label();
+    let mut child = a.create_label();
+    let mut exit = a.create_label();
+    let mut done = a.create_label();
+
+    // 4 bytes buffer for the messages.
+    a.sub(rsp, 8)?;
+    a.mov(dword_ptr(rsp), 0)?;
+    a.mov(eax, SYS_WRITE)?;
+    a.mov(edi, FORKSRV_FD as u32 + 1)?;
+    a.mov(rsi, rsp)?;
+    a.mov(edx, 4)?;
+    a.syscall()?;
+    a.cmp(rax, 4)?;
+    a.jne(done)?;
+
+    a.set_label(&mut server_loop)?;
+    a.mov(eax, SYS_READ)?;
+    a.mov(edi, FORKSRV_FD as u32)?;
+    a.mov(rsi, rsp)?;
+    a.mov(edx, 4)?;
+    a.syscall()?;
+    a.cmp(rax, 4)?;
+    a.jne(exit)?;
+    a.mov(eax, SYS_FORK)?;
+    a.syscall()?;
+    a.test(rax, rax)?;
+    a.js(exit)?;
+    a.jz(child)?;
+    a.mov(dword_ptr(rsp), eax)?;
+    a.mov(eax, SYS_WRITE)?;
+    a.mov(edi, FORKSRV_FD as u32 + 1)?;
+    a.mov(rsi, rsp)?;
+    a.mov(edx, 4)?;
+    a.syscall()?;
+    // wait4(pid, &status, 0, NULL)
+    a.mov(edi, dword_ptr(rsp))?;
+    a.mov(eax, SYS_WAIT4)?;
+    a.mov(rsi, rsp)?;
+    a.xor(edx, edx)?;
+    a.xor(r10d, r10d)?;
+    a.syscall()?;
+    a.test(rax, rax)?;
+    a.jle(exit)?;
+    a.mov(eax, SYS_WRITE)?;
+    a.mov(edi, FORKSRV_FD as u32 + 1)?;
+    a.mov(rsi, rsp)?;
+    a.mov(edx, 4)?;
+    a.syscall()?;
+    a.jmp(server_loop)?;
+
+    a.set_label(&mut exit)?;
+    a.mov(eax, SYS_EXIT_GROUP)?;
+    a.xor(edi, edi)?;
+    a.syscall()?;
+
+    a.set_label(&mut child)?;
+    a.mov(eax, SYS_CLOSE)?;
+    a.mov(edi, FORKSRV_FD as u32)?;
+    a.syscall()?;
+    a.mov(eax, SYS_CLOSE)?;
+    a.mov(edi, FORKSRV_FD as u32 + 1)?;
+    a.syscall()?;
+
+    a.set_label(&mut done)?;
+    a.add(rsp, 8)?;
+    Ok(())
+}
+
+/// Returns the address of the runtime, placed after the shadow code of [code_size] bytes.
+pub fn get_runtime_addr(layout: &RuntimeLayout, code_size: u64) -> Address {
+    align_up(layout.get_code_start() + code_size, 16)
+}
@@ -1,17 +1,11 @@
 // SPDX-FileCopyrightText: 2023 Rot127 <unisono@quyllur.org>
 // SPDX-License-Identifier: LGPL-3.0-only
 
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub mod correction;
+pub mod disassembly;
+pub mod elf;
+pub mod executor;
+pub mod instrumentation;
+pub mod rewriter;
+mod test_correction;
+mod test_disassembly;
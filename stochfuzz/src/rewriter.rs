@@ -0,0 +1,397 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Error,
+    path::Path,
+};
+
+use iced_x86::{
+    BlockEncoder, BlockEncoderOptions, Code, FlowControl, Instruction, InstructionBlock, OpKind,
+};
+
+use crate::{
+    disassembly::{Address, Evidence, ProbDisassembly},
+    elf::{align_up, ElfBinary, Patch, PAGE_SIZE},
+    instrumentation::{
+        assemble_runtime, get_coverage_stub, get_label, get_runtime_addr, translate_call,
+        RuntimeLayout,
+    },
+};
+
+/// Size of the jump from the original code into the shadow code.
+const TRAMPOLINE_SIZE: u64 = 5;
+
+pub struct StochFuzzConfig {
+    /// Maximum number of rounds the probabilities are propagated.
+    pub iterations: usize,
+    /// Instructions with a lower data probability are translated into the shadow code.
+    pub translate_threshold: f64,
+    /// Instructions with a lower data probability can be overwritten
+    /// with a jump into the shadow code.
+    pub patch_threshold: f64,
+}
+
+impl StochFuzzConfig {
+    pub fn new() -> StochFuzzConfig {
+        StochFuzzConfig {
+            iterations: 16,
+            translate_threshold: 0.5,
+            patch_threshold: 0.2,
+        }
+    }
+}
+
+impl Default for StochFuzzConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An indirect call or jump with a resolved target, found by another analysis (e.g. BDA).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BranchHint {
+    /// Address of the branch instruction.
+    pub from: Address,
+    /// The branch target.
+    pub to: Address,
+    /// Set if the target was not observed, but is only plausible.
+    pub candidate: bool,
+}
+
+/// The result of a rewriting round.
+pub struct Rewritten {
+    /// Jumps from the original code into the shadow code.
+    pub patches: Vec<Patch>,
+    /// The added segment with the shadow code and the instrumentation runtime.
+    pub segment: Vec<u8>,
+    pub segment_addr: Address,
+    /// Entry point of the rewritten binary.
+    pub entry: Address,
+    /// Maps the address of each translated instruction to its address in the shadow code.
+    pub shadow: BTreeMap<Address, Address>,
+}
+
+/// Rewrites an executable, so it logs the covered edges in an AFL compatible coverage map.
+///
+/// The instructions, which are likely code, are translated with coverage instrumentation
+/// into shadow code. All original bytes are kept, so data mistaken for code is still intact.
+/// Only the addresses control flow can enter from outside the shadow code (entries, return
+/// addresses, code pointers) are patched with jumps into the shadow code.
+/// If such a patch overwrites data, the binary shows errors and the patch must be corrected
+/// (see [crate::correction]).
+pub struct Rewriter {
+    binary: ElfBinary,
+    disasm: ProbDisassembly,
+    config: StochFuzzConfig,
+    /// Addresses referenced as code pointers by likely code.
+    code_refs: BTreeSet<Address>,
+    /// Observed targets of indirect branches.
+    branch_targets: BTreeSet<Address>,
+}
+
+impl Rewriter {
+    pub fn new(binary: ElfBinary, config: StochFuzzConfig) -> Rewriter {
+        let mut disasm = ProbDisassembly::new(binary.code_regions.clone());
+        disasm.add_evidence(binary.entry, Evidence::Code);
+        for f in binary.functions.iter() {
+            disasm.add_evidence(*f, Evidence::Code);
+        }
+        for p in binary.code_pointers.iter() {
+            disasm.add_code_pointer_hint(*p);
+        }
+        disasm.analyse(config.iterations);
+        let mut rewriter = Rewriter {
+            binary,
+            disasm,
+            config,
+            code_refs: BTreeSet::new(),
+            branch_targets: BTreeSet::new(),
+        };
+        rewriter.code_refs = rewriter.get_code_refs();
+        for r in rewriter.code_refs.iter() {
+            rewriter.disasm.add_code_pointer_hint(*r);
+        }
+        rewriter.disasm.analyse(rewriter.config.iterations);
+        rewriter
+    }
+
+    pub fn get_binary(&self) -> &ElfBinary {
+        &self.binary
+    }
+
+    pub fn get_disassembly(&self) -> &ProbDisassembly {
+        &self.disasm
+    }
+
+    /// Returns the addresses in the code regions, which are loaded as address
+    /// or immediate value by likely code.
+    fn get_code_refs(&self) -> BTreeSet<Address> {
+        let mut refs = BTreeSet::new();
+        for (insn, _) in self
+            .disasm
+            .get_code_candidates(self.config.translate_threshold)
+        {
+            if insn.code() == Code::Lea_r64_m && insn.is_ip_rel_memory_operand() {
+                refs.insert(insn.ip_rel_memory_address());
+            }
+            for i in 0..insn.op_count() {
+                match insn.op_kind(i) {
+                    OpKind::Immediate32 | OpKind::Immediate32to64 | OpKind::Immediate64 => {
+                        refs.insert(insn.immediate(i));
+                    }
+                    _ => (),
+                }
+            }
+        }
+        refs.retain(|r| self.disasm.contains(*r));
+        refs
+    }
+
+    /// Adds the evidence, that the bytes at [addr] are data.
+    /// The next rewriting round doesn't translate or patch them.
+    pub fn add_data_evidence(&mut self, addr: Address) {
+        self.disasm.add_evidence(addr, Evidence::Data);
+        self.disasm.analyse(self.config.iterations);
+    }
+
+    /// Adds the indirect branches resolved by another analysis.
+    /// Observed branches and their targets are code. The targets are patched,
+    /// because the translated branches still jump to the original code.
+    /// Candidate targets are only hints.
+    pub fn add_branch_hints(&mut self, hints: &[BranchHint]) {
+        for hint in hints.iter() {
+            if hint.candidate {
+                self.disasm.add_code_pointer_hint(hint.to);
+                continue;
+            }
+            self.disasm.add_evidence(hint.from, Evidence::Code);
+            if self.disasm.contains(hint.to) {
+                self.disasm.add_evidence(hint.to, Evidence::Code);
+                self.branch_targets.insert(hint.to);
+            }
+        }
+        self.disasm.analyse(self.config.iterations);
+    }
+
+    /// Returns the translation of [insn] without its label.
+    /// Direct branches to translated instructions are redirected into the shadow code.
+    fn translate(
+        insn: &Instruction,
+        translated: &BTreeMap<Address, Instruction>,
+    ) -> Vec<Instruction> {
+        let mut insns = translate_call(insn);
+        for i in insns.iter_mut() {
+            i.set_ip(0);
+            if i.op_count() > 0
+                && i.op0_kind() == OpKind::NearBranch64
+                && translated.contains_key(&i.near_branch64())
+            {
+                i.set_near_branch64(get_label(i.near_branch64()));
+            }
+        }
+        insns
+    }
+
+    /// Returns the addresses control flow can enter the shadow code from the original code.
+    fn get_entry_sites(&self, translated: &BTreeMap<Address, Instruction>) -> BTreeSet<Address> {
+        let mut sites: BTreeSet<Address> = translated
+            .values()
+            .filter(|i| {
+                matches!(
+                    i.flow_control(),
+                    FlowControl::Call | FlowControl::IndirectCall
+                )
+            })
+            .map(|i| i.next_ip())
+            .collect();
+        sites.insert(self.binary.entry);
+        sites.extend(self.binary.functions.iter());
+        sites.extend(self.binary.code_pointers.iter());
+        sites.extend(self.code_refs.iter());
+        sites.extend(self.branch_targets.iter());
+        sites.retain(|s| translated.contains_key(s));
+        sites
+    }
+
+    /// Returns the addresses of the instructions starting a basic block.
+    fn get_leaders(
+        translated: &BTreeMap<Address, Instruction>,
+        sites: &BTreeSet<Address>,
+    ) -> BTreeSet<Address> {
+        let mut leaders = sites.clone();
+        for insn in translated.values() {
+            match insn.flow_control() {
+                FlowControl::ConditionalBranch => {
+                    leaders.insert(insn.near_branch_target());
+                    leaders.insert(insn.next_ip());
+                }
+                FlowControl::UnconditionalBranch | FlowControl::Call => {
+                    leaders.insert(insn.near_branch_target());
+                }
+                _ => (),
+            }
+        }
+        leaders.retain(|l| translated.contains_key(l));
+        leaders
+    }
+
+    /// Lays out the translated instructions. Sequences of fall through instructions are
+    /// kept together. A jump is added, if a sequence ends with a fall through.
+    /// Returns the instructions and the index of each translated instruction.
+    fn lay_out(
+        &self,
+        layout: &RuntimeLayout,
+        translated: &BTreeMap<Address, Instruction>,
+        leaders: &BTreeSet<Address>,
+    ) -> (Vec<Instruction>, BTreeMap<Address, usize>) {
+        let mut insns = Vec::new();
+        let mut index = BTreeMap::new();
+        for start in translated.keys() {
+            let mut addr = *start;
+            while !index.contains_key(&addr) {
+                let insn = &translated[&addr];
+                index.insert(addr, insns.len());
+                if leaders.contains(&addr) {
+                    insns.extend(get_coverage_stub(layout, addr));
+                    insns.extend(Self::translate(insn, translated));
+                } else {
+                    let mut t = Self::translate(insn, translated);
+                    t[0].set_ip(get_label(addr));
+                    insns.extend(t);
+                }
+                if !matches!(
+                    insn.flow_control(),
+                    FlowControl::Next
+                        | FlowControl::ConditionalBranch
+                        | FlowControl::Interrupt
+                        | FlowControl::XbeginXabortXend
+                ) {
+                    break;
+                }
+                let next = insn.next_ip();
+                if !translated.contains_key(&next) || index.contains_key(&next) {
+                    let target = if translated.contains_key(&next) {
+                        get_label(next)
+                    } else {
+                        next
+                    };
+                    insns.push(
+                        Instruction::with_branch(Code::Jmp_rel32_64, target)
+                            .expect("Jump should be valid"),
+                    );
+                    break;
+                }
+                addr = next;
+            }
+        }
+        (insns, index)
+    }
+
+    /// Returns the jumps from the [sites] in the original code into the shadow code.
+    /// Sites without enough space until the next site are skipped.
+    fn get_patches(
+        &self,
+        sites: &BTreeSet<Address>,
+        shadow: &BTreeMap<Address, Address>,
+    ) -> Vec<Patch> {
+        let mut patches = Vec::new();
+        let mut patched_end = 0;
+        let sites: Vec<Address> = sites
+            .iter()
+            .filter(|s| {
+                self.disasm
+                    .get_data_probability(**s)
+                    .is_some_and(|dp| dp < self.config.patch_threshold)
+            })
+            .cloned()
+            .collect();
+        for (i, site) in sites.iter().enumerate() {
+            let end = site + TRAMPOLINE_SIZE;
+            if *site < patched_end
+                || sites.get(i + 1).is_some_and(|next| *next < end)
+                || !self.disasm.contains(end - 1)
+            {
+                continue;
+            }
+            let rel = shadow[site].wrapping_sub(end) as i64;
+            let Ok(rel) = i32::try_from(rel) else {
+                continue;
+            };
+            let mut bytes = vec![0xe9];
+            bytes.extend_from_slice(&rel.to_le_bytes());
+            patches.push(Patch { addr: *site, bytes });
+            patched_end = end;
+        }
+        patches
+    }
+
+    /// Rewrites the binary with the current probabilities.
+    pub fn rewrite(&self) -> std::io::Result<Rewritten> {
+        let translated: BTreeMap<Address, Instruction> = self
+            .disasm
+            .get_code_candidates(self.config.translate_threshold)
+            .into_iter()
+            .map(|(i, _)| (i.ip(), *i))
+            .collect();
+        let sites = self.get_entry_sites(&translated);
+        let leaders = Self::get_leaders(&translated, &sites);
+        let layout = RuntimeLayout::new(align_up(self.binary.get_load_end(), PAGE_SIZE));
+        let (insns, index) = self.lay_out(&layout, &translated, &leaders);
+
+        let code_start = layout.get_code_start();
+        let encoded = BlockEncoder::encode(
+            64,
+            InstructionBlock::new(&insns, code_start),
+            BlockEncoderOptions::RETURN_NEW_INSTRUCTION_OFFSETS,
+        )
+        .map_err(|e| Error::other(e.to_string()))?;
+        let shadow: BTreeMap<Address, Address> = index
+            .iter()
+            .map(|(addr, i)| {
+                (
+                    *addr,
+                    code_start + encoded.new_instruction_offsets[*i] as u64,
+                )
+            })
+            .collect();
+
+        let entry = get_runtime_addr(&layout, encoded.code_buffer.len() as u64);
+        let entry_target = *shadow.get(&self.binary.entry).unwrap_or(&self.binary.entry);
+        let mut segment = layout.get_data();
+        segment.extend(encoded.code_buffer);
+        segment.resize((entry - layout.base) as usize, 0);
+        segment.extend(assemble_runtime(&layout, entry, entry_target)?);
+
+        Ok(Rewritten {
+            patches: self.get_patches(&sites, &shadow),
+            segment,
+            segment_addr: layout.base,
+            entry,
+            shadow,
+        })
+    }
+
+    /// Returns the rewritten binary with only the given [patches] applied.
+    pub fn build_elf(&self, rewritten: &Rewritten, patches: &[Patch]) -> std::io::Result<Vec<u8>> {
+        self.binary.build(
+            patches,
+            &rewritten.segment,
+            rewritten.segment_addr,
+            rewritten.entry,
+        )
+    }
+
+    /// Writes the [rewritten] binary as executable file to [path].
+    pub fn write(&self, rewritten: &Rewritten, path: &Path) -> std::io::Result<()> {
+        write_executable(path, &self.build_elf(rewritten, &rewritten.patches)?)
+    }
+}
+
+/// Writes [data] to the file at [path] and makes it executable.
+pub fn write_executable(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::write(path, data)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
@@ -0,0 +1,43 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use crate::{correction::find_faulty_patch, elf::Patch};
+
+    fn get_patches(n: u64) -> Vec<Patch> {
+        (0..n)
+            .map(|i| Patch {
+                addr: 0x1000 + i * 0x10,
+                bytes: vec![0xe9, 0, 0, 0, 0],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_faulty_patch() {
+        let patches = get_patches(13);
+        for faulty in patches.iter().map(|p| p.addr) {
+            let mut runs = 0;
+            let found = find_faulty_patch(&patches, |enabled| {
+                runs += 1;
+                enabled.iter().any(|p| p.addr == faulty)
+            });
+            assert_eq!(found, Some(faulty));
+            // One run without patches, the rest is bisection.
+            assert!(runs <= 1 + 4);
+        }
+        // Two faulty patches. One of them is found.
+        let found = find_faulty_patch(&patches, |enabled| {
+            enabled.iter().any(|p| p.addr == 0x1020 || p.addr == 0x10a0)
+        });
+        assert!(found == Some(0x1020) || found == Some(0x10a0));
+    }
+
+    #[test]
+    fn test_error_without_patches() {
+        let patches = get_patches(4);
+        assert_eq!(find_faulty_patch(&patches, |_| true), None);
+        assert_eq!(find_faulty_patch(&[], |_| true), None);
+    }
+}
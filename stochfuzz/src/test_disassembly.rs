@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembly::{CodeRegion, Evidence, ProbDisassembly};
+
+    const BASE: u64 = 0x1000;
+
+    fn get_disasm(bytes: &[u8]) -> ProbDisassembly {
+        ProbDisassembly::new(vec![CodeRegion::new(BASE, bytes.to_vec())])
+    }
+
+    #[test]
+    fn test_code_followed_by_data() {
+        // push rbp; mov rbp, rsp; xor eax, eax; pop rbp; ret
+        // followed by invalid bytes.
+        let code = [0x55, 0x48, 0x89, 0xe5, 0x31, 0xc0, 0x5d, 0xc3];
+        let mut bytes = code.to_vec();
+        bytes.extend([0xff, 0xff, 0xff, 0xff]);
+        let mut disasm = get_disasm(&bytes);
+        disasm.add_evidence(BASE, Evidence::Code);
+        disasm.analyse(8);
+
+        for insn in [0, 1, 4, 6, 7] {
+            assert_eq!(disasm.get_data_probability(BASE + insn), Some(0.0));
+        }
+        // Inside of instructions
+        for occluded in [2, 3, 5] {
+            assert_eq!(disasm.get_data_probability(BASE + occluded), Some(1.0));
+            assert_eq!(disasm.get_code_probability(BASE + occluded), Some(1.0));
+        }
+        for data in 8..12 {
+            assert_eq!(disasm.get_data_probability(BASE + data), Some(1.0));
+            assert_eq!(disasm.get_code_probability(BASE + data), Some(0.0));
+        }
+        let candidates: Vec<u64> = disasm
+            .get_code_candidates(0.5)
+            .iter()
+            .map(|(i, _)| i.ip())
+            .collect();
+        assert_eq!(
+            candidates,
+            vec![BASE, BASE + 1, BASE + 4, BASE + 6, BASE + 7]
+        );
+        assert_eq!(disasm.get_data_probability(BASE + 12), None);
+    }
+
+    #[test]
+    fn test_flow_into_invalid() {
+        // nop; nop; followed by invalid bytes.
+        let disasm = {
+            let mut d = get_disasm(&[0x90, 0x90, 0xff, 0xff]);
+            d.analyse(8);
+            d
+        };
+        for addr in BASE..BASE + 4 {
+            assert_eq!(disasm.get_data_probability(addr), Some(1.0));
+        }
+        // jmp 0x1003; invalid; nop; ret
+        let mut disasm = get_disasm(&[0xeb, 0x01, 0xff, 0x90, 0xc3]);
+        disasm.analyse(8);
+        assert!(disasm.get_data_probability(BASE).unwrap() < 1.0);
+        assert_eq!(disasm.get_data_probability(BASE + 2), Some(1.0));
+    }
+
+    #[test]
+    fn test_hints() {
+        // xor eax, eax; add ebx, eax; ret
+        let mut disasm = get_disasm(&[0x31, 0xc0, 0x01, 0xc3, 0xc3]);
+        disasm.analyse(8);
+        // The defined eax is used by its successor.
+        let dp_xor = disasm.get_data_probability(BASE).unwrap();
+        assert!(dp_xor < 0.1);
+        // Code evidence is propagated to the successors.
+        assert!(disasm.get_data_probability(BASE + 2).unwrap() <= dp_xor);
+        assert!(disasm.get_data_probability(BASE + 4).unwrap() <= dp_xor);
+        // Overlapping instructions are likely data.
+        assert!(disasm.get_data_probability(BASE + 1).unwrap() > 0.9);
+        assert!(disasm.get_data_probability(BASE + 3).unwrap() > 0.9);
+
+        // Code pointer hint: nop; ret
+        let mut disasm = get_disasm(&[0x90, 0xc3]);
+        disasm.analyse(8);
+        assert_eq!(disasm.get_data_probability(BASE), Some(0.5));
+        disasm.add_code_pointer_hint(BASE);
+        disasm.analyse(8);
+        assert!(disasm.get_data_probability(BASE).unwrap() < 0.5);
+    }
+
+    #[test]
+    fn test_data_evidence() {
+        // xor eax, eax; add ebx, eax; ret
+        let mut disasm = get_disasm(&[0x31, 0xc0, 0x01, 0xc3, 0xc3]);
+        disasm.add_evidence(BASE + 2, Evidence::Data);
+        disasm.analyse(8);
+        // The xor flows into data.
+        assert_eq!(disasm.get_data_probability(BASE), Some(1.0));
+        assert_eq!(disasm.get_data_probability(BASE + 2), Some(1.0));
+        assert!(disasm.get_data_probability(BASE + 4).unwrap() <= 0.5);
+    }
+
+    #[test]
+    fn test_range_code_probability() {
+        // push rbp; mov rbp, rsp; pop rbp; ret; followed by invalid bytes.
+        let mut disasm = get_disasm(&[0x55, 0x48, 0x89, 0xe5, 0x5d, 0xc3, 0xff, 0xff]);
+        disasm.add_evidence(BASE, Evidence::Code);
+        disasm.analyse(8);
+        assert_eq!(disasm.get_range_code_probability(BASE, 6), Some(1.0));
+        assert_eq!(disasm.get_range_code_probability(BASE + 1, 3), Some(1.0));
+        assert_eq!(disasm.get_range_code_probability(BASE + 4, 4), Some(0.0));
+        // Exceeds the region.
+        assert_eq!(disasm.get_range_code_probability(BASE + 6, 4), None);
+        assert_eq!(disasm.get_range_code_probability(BASE - 1, 2), None);
+    }
+
+    #[test]
+    fn test_bitness() {
+        // 32-bit: inc eax; ret
+        // 64-bit: the 0x40 is a REX prefix of ret.
+        let bytes = vec![0x40, 0xc3];
+        let mut disasm = ProbDisassembly::with_bitness(vec![CodeRegion::new(BASE, bytes)], 32);
+        disasm.add_evidence(BASE, Evidence::Code);
+        disasm.analyse(8);
+        assert_eq!(disasm.get_insn(BASE).unwrap().len(), 1);
+        assert_eq!(disasm.get_data_probability(BASE + 1), Some(0.0));
+
+        let mut disasm = get_disasm(&[0x40, 0xc3]);
+        disasm.add_evidence(BASE, Evidence::Code);
+        disasm.analyse(8);
+        assert_eq!(disasm.get_insn(BASE).unwrap().len(), 2);
+        assert_eq!(disasm.get_data_probability(BASE + 1), Some(1.0));
+    }
+}
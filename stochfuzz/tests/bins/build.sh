@@ -0,0 +1,8 @@
+# SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+# SPDX-License-Identifier: LGPL-3.0-only
+
+echo "Compile x86-64 binaries"
+gcc -O1 -o x86_64_switch_fptr switch_fptr.c
+gcc -O1 -s -o x86_64_switch_fptr_stripped switch_fptr.c
+gcc -O1 -no-pie -o x86_64_switch_fptr_no_pie switch_fptr.c
+gcc -O1 -o x86_64_data_in_code data_in_code.c
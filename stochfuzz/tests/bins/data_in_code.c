@@ -0,0 +1,23 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#include <stdio.h>
+
+// A table in the code section. Its bytes are valid instructions
+// (xor eax, eax; ret; nop...), but they are only read as data.
+extern const unsigned char code_table[];
+__asm__(".text\n"
+	".globl code_table\n"
+	".type code_table, @object\n"
+	"code_table:\n"
+	".byte 0x31, 0xc0, 0xc3, 0x90, 0x90, 0x90, 0x90, 0xc3\n"
+	".size code_table, 8\n");
+
+int main(void) {
+	unsigned int sum = 0;
+	for (int i = 0; i < 8; i++) {
+		sum = sum * 31 + code_table[i];
+	}
+	printf("%u\n", sum);
+	return 0;
+}
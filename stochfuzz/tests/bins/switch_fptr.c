@@ -0,0 +1,49 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static int add(int a, int b) { return a + b; }
+static int sub(int a, int b) { return a - b; }
+static int mul(int a, int b) { return a * b; }
+
+static int (*ops[])(int, int) = { add, sub, mul };
+
+static int cmp(const void *a, const void *b) {
+	return *(const char *)a - *(const char *)b;
+}
+
+static int fib(int n) {
+	return n < 2 ? n : fib(n - 1) + fib(n - 2);
+}
+
+static int classify(char c) {
+	switch (c) {
+	case 'a': return 3;
+	case 'b': return 7;
+	case 'c': return 11;
+	case 'd': return 13;
+	case 'e': return 17;
+	case 'f': return 19;
+	case 'g': return 23;
+	case 'h': return 29;
+	default: return 1;
+	}
+}
+
+int main(void) {
+	char buf[256] = { 0 };
+	size_t n = fread(buf, 1, sizeof(buf) - 1, stdin);
+	int acc = 0;
+	for (size_t i = 0; i < n; i++) {
+		acc = ops[i % 3](acc, classify(buf[i]));
+	}
+	qsort(buf, n, 1, cmp);
+	printf("%d %d %s\n", acc, fib(n % 20), buf);
+	if (n > 0 && buf[0] == '!') {
+		abort();
+	}
+	return acc & 0xff;
+}
@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: 2024 Rot127 <unisono@quyllur.org>
+// SPDX-License-Identifier: LGPL-3.0-only
+
+mod test {
+    use std::{
+        io::Write,
+        path::{Path, PathBuf},
+        process::{Command, Output, Stdio},
+        time::Duration,
+    };
+
+    use stochfuzz::{
+        correction::rewrite_and_correct,
+        elf::ElfBinary,
+        executor::{Executor, ExitKind},
+        rewriter::{write_executable, BranchHint, Rewriter, StochFuzzConfig},
+    };
+
+    fn get_test_bin(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("bins")
+            .join(name)
+    }
+
+    fn get_output_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("stochfuzz_{}_{}", std::process::id(), name))
+    }
+
+    fn run(bin: &Path, input: &[u8]) -> Output {
+        let mut child = Command::new(bin)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Binary should start");
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    /// Rewrites the binary [name] and checks it behaves like the original for all [inputs].
+    fn check_rewriting(name: &str, inputs: &[&[u8]]) {
+        let orig = get_test_bin(name);
+        let rewriter = Rewriter::new(ElfBinary::load(&orig).unwrap(), StochFuzzConfig::new());
+        let rewritten = rewriter.rewrite().unwrap();
+        assert!(!rewritten.shadow.is_empty());
+        assert!(!rewritten.patches.is_empty());
+        let out_path = get_output_path(name);
+        rewriter.write(&rewritten, &out_path).unwrap();
+        for input in inputs {
+            let expected = run(&orig, input);
+            let output = run(&out_path, input);
+            assert_eq!(expected.stdout, output.stdout);
+            assert_eq!(expected.status.code(), output.status.code());
+        }
+        std::fs::remove_file(&out_path).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_switch_fptr() {
+        let inputs: [&[u8]; 4] = [b"", b"abcdefgh", b"hello world", b"!crash"];
+        check_rewriting("x86_64_switch_fptr", &inputs);
+        check_rewriting("x86_64_switch_fptr_no_pie", &inputs);
+        check_rewriting("x86_64_switch_fptr_stripped", &inputs);
+    }
+
+    #[test]
+    fn test_correct_data_in_code() {
+        let orig = get_test_bin("x86_64_data_in_code");
+        let expected = run(&orig, b"");
+        let mut rewriter = Rewriter::new(ElfBinary::load(&orig).unwrap(), StochFuzzConfig::new());
+        let out_path = get_output_path("x86_64_data_in_code");
+
+        // The table is referenced like a function pointer. So it is patched.
+        let rewritten = rewriter.rewrite().unwrap();
+        rewriter.write(&rewritten, &out_path).unwrap();
+        assert_ne!(run(&out_path, b"").stdout, expected.stdout);
+
+        let mut fails = |elf: &[u8]| {
+            write_executable(&out_path, elf).unwrap();
+            run(&out_path, b"").stdout != expected.stdout
+        };
+        let rewritten = rewrite_and_correct(&mut rewriter, &mut fails, 4).unwrap();
+        rewriter.write(&rewritten, &out_path).unwrap();
+        assert_eq!(run(&out_path, b"").stdout, expected.stdout);
+        std::fs::remove_file(&out_path).unwrap();
+    }
+
+    #[test]
+    fn test_executor_fork_server() {
+        let orig = get_test_bin("x86_64_switch_fptr");
+        let rewriter = Rewriter::new(ElfBinary::load(&orig).unwrap(), StochFuzzConfig::new());
+        let out_path = get_output_path("x86_64_switch_fptr_exec");
+        rewriter
+            .write(&rewriter.rewrite().unwrap(), &out_path)
+            .unwrap();
+
+        let mut executor = Executor::new(&out_path, &[], Duration::from_secs(5)).unwrap();
+        assert!(executor.start().unwrap());
+        let mut edges = Vec::new();
+        for input in [b"".as_slice(), b"abcdefgh", b"hello world", b"abcdefgh"] {
+            let expected = run(&orig, input);
+            let exit = executor.run(input).unwrap();
+            assert_eq!(exit, ExitKind::Exited(expected.status.code().unwrap()));
+            assert_eq!(executor.get_output().unwrap(), expected.stdout);
+            edges.push(executor.get_covered_edges());
+        }
+        assert!(edges.iter().all(|e| *e > 0));
+        // Same input, same coverage.
+        assert_eq!(edges[1], edges[3]);
+        assert_ne!(edges[0], edges[1]);
+
+        let exit = executor.run(b"!crash").unwrap();
+        // SIGABRT
+        assert_eq!(exit, ExitKind::Signaled(6));
+        assert!(exit.is_crash());
+        // The fork server survives crashes of its children.
+        assert!(executor.has_fork_server());
+        assert!(matches!(executor.run(b"").unwrap(), ExitKind::Exited(_)));
+        drop(executor);
+        std::fs::remove_file(&out_path).unwrap();
+    }
+
+    #[test]
+    fn test_executor_without_fork_server() {
+        let orig = get_test_bin("x86_64_switch_fptr");
+        let mut executor = Executor::new(&orig, &[], Duration::from_secs(5)).unwrap();
+        assert!(!executor.start().unwrap());
+        let expected = run(&orig, b"hello world");
+        let exit = executor.run(b"hello world").unwrap();
+        assert_eq!(exit, ExitKind::Exited(expected.status.code().unwrap()));
+        assert_eq!(executor.get_output().unwrap(), expected.stdout);
+        // Not instrumented.
+        assert_eq!(executor.get_covered_edges(), 0);
+
+        // Input passed as file.
+        let mut executor =
+            Executor::new(Path::new("cat"), &["@@"], Duration::from_secs(5)).unwrap();
+        assert_eq!(executor.run(b"file input").unwrap(), ExitKind::Exited(0));
+        assert_eq!(executor.get_output().unwrap(), b"file input");
+
+        let mut executor =
+            Executor::new(Path::new("sleep"), &["10"], Duration::from_millis(100)).unwrap();
+        assert!(!executor.start().unwrap());
+        assert_eq!(executor.run(b"").unwrap(), ExitKind::Timeout);
+    }
+
+    #[test]
+    fn test_branch_hints() {
+        let orig = get_test_bin("x86_64_switch_fptr_stripped");
+        let mut rewriter = Rewriter::new(ElfBinary::load(&orig).unwrap(), StochFuzzConfig::new());
+        // call rdx, which calls add, sub and mul via the table.
+        let icall = 0x135f;
+        let targets = [0x1169, 0x116d, 0x1172];
+        let mut hints: Vec<BranchHint> = targets
+            .iter()
+            .map(|t| BranchHint {
+                from: icall,
+                to: *t,
+                candidate: false,
+            })
+            .collect();
+        // Targets outside of the binary are ignored.
+        hints.push(BranchHint {
+            from: icall,
+            to: 0xdead_0000,
+            candidate: false,
+        });
+        rewriter.add_branch_hints(&hints);
+        let disasm = rewriter.get_disassembly();
+        assert_eq!(disasm.get_data_probability(icall), Some(0.0));
+        for t in targets {
+            assert_eq!(disasm.get_data_probability(t), Some(0.0));
+        }
+
+        let rewritten = rewriter.rewrite().unwrap();
+        for t in targets {
+            assert!(rewritten.shadow.contains_key(&t));
+        }
+        // add and sub are too short for a jump into the shadow code.
+        assert!(rewritten.patches.iter().any(|p| p.addr == targets[2]));
+        let out_path = get_output_path("x86_64_switch_fptr_stripped_hints");
+        rewriter.write(&rewritten, &out_path).unwrap();
+        for input in [b"".as_slice(), b"abcdefgh", b"hello world"] {
+            let expected = run(&orig, input);
+            let output = run(&out_path, input);
+            assert_eq!(expected.stdout, output.stdout);
+            assert_eq!(expected.status.code(), output.status.code());
+        }
+        std::fs::remove_file(&out_path).unwrap();
+    }
+}